@@ -5,6 +5,12 @@ fn main() {
         .unwrap_or_else(|_| format!("{}/.local/include/pv_porcupine.h", env::var("HOME").unwrap()));
 
     println!("cargo:rerun-if-changed={}", header);
+    // Checked against pv_porcupine_version() at startup; empty when the header doesn't say.
+    let compiled_major = std::fs::read_to_string(&header).ok().and_then(|h| header_major_version(&h));
+    if compiled_major.is_none() {
+        println!("cargo:warning=no PV_PORCUPINE_VERSION in {}; the runtime version check is disabled", header);
+    }
+    println!("cargo:rustc-env=PORCUPINE_COMPILED_MAJOR={}", compiled_major.map(|m| m.to_string()).unwrap_or_default());
     println!("cargo:rustc-link-search=native={}/.local/lib", env::var("HOME").unwrap());
     println!("cargo:rustc-link-lib=dylib=pv_porcupine");
 
@@ -19,3 +25,14 @@ fn main() {
         .write_to_file(out.join("porcupine_bindings.rs"))
         .expect("Couldn't write bindings");
 }
+
+/// Major version from `#define PV_PORCUPINE_VERSION "4.0.1"` (or a
+/// `PV_PORCUPINE_VERSION_MAJOR 4` define).
+fn header_major_version(header: &str) -> Option<u32> {
+    header.lines().find_map(|line| {
+        let rest = line.trim().strip_prefix("#define")?.trim_start();
+        let value = rest.strip_prefix("PV_PORCUPINE_VERSION_MAJOR").or_else(|| rest.strip_prefix("PV_PORCUPINE_VERSION"))?;
+        let digits: String = value.trim().trim_start_matches('"').chars().take_while(|c| c.is_ascii_digit()).collect();
+        digits.parse().ok()
+    })
+}
@@ -3,15 +3,42 @@ import sys
 import os
 import json
 import io
+import struct
+import threading
 import wave
-from typing import Any, Dict
+from typing import Any, Dict, Optional
 
 import numpy as np
 from groq import Groq
 
+# Wire protocol version; must match PROTOCOL_VERSION in src/ml.rs.
+# v2: audio requests are a JSON header followed by `byte_len` bytes of
+# little-endian int16 PCM on stdin.
+# v3: every request carries an `id` that is echoed in its response (and in
+# stream partials) so the daemon can discard late replies to abandoned requests.
+# v4: JSON travels in frames, both ways: a 4-byte little-endian length, then
+# that many bytes of UTF-8 JSON. Audio payloads still follow their header
+# frame unframed.
+PROTOCOL_VERSION = 4
+
+# Must match MAX_FRAME_BYTES in src/ml.rs; a larger prefix means a corrupt stream.
+MAX_FRAME_BYTES = 1024 * 1024
+
 # Initialize Groq client once; reads key from GROQ_API_KEY or default env config
 _client = None
 
+# Streaming ASR: how much new audio (seconds) triggers a partial transcript
+PARTIAL_EVERY_SECS = float(os.environ.get("BTWD_ASR_PARTIAL_EVERY_SECS", "1.5"))
+# One-shot asr_stream requests: upper bound on prefix decodes per utterance
+MAX_STREAM_PARTIALS = int(os.environ.get("BTWD_ASR_STREAM_MAX_PARTIALS", "3"))
+
+# stdout is shared between the main loop and partial-transcript threads
+_out_lock = threading.Lock()
+
+# Sentence embedding model (optional; only if sentence-transformers is installed)
+EMBED_MODEL_NAME = os.environ.get("BTWD_EMBED_MODEL", "sentence-transformers/all-MiniLM-L6-v2")
+_embedder = None
+
 def get_client() -> Groq:
     global _client
     if _client is None:
@@ -25,6 +52,62 @@ def get_client() -> Groq:
     return _client
 
 
+def embeddings_available() -> bool:
+    try:
+        import sentence_transformers  # noqa: F401
+    except ImportError:
+        return False
+    return True
+
+
+def get_embedder():
+    global _embedder
+    if _embedder is None:
+        from sentence_transformers import SentenceTransformer
+        _embedder = SentenceTransformer(EMBED_MODEL_NAME)
+    return _embedder
+
+
+def emit(resp: Dict[str, Any]) -> None:
+    # One length-prefixed JSON frame per response
+    body = json.dumps(resp, ensure_ascii=False).encode("utf-8")
+    if len(body) > MAX_FRAME_BYTES:
+        # The daemon would drop the whole stream on this frame; answer the
+        # request with an error it can report instead.
+        print(f"response of {len(body)} bytes exceeds the {MAX_FRAME_BYTES} byte limit", file=sys.stderr)
+        error = {"type": resp.get("type"), "id": resp.get("id"), "text": "", "error": "response_too_large"}
+        body = json.dumps(error).encode("utf-8")
+    with _out_lock:
+        out = sys.stdout.buffer
+        out.write(struct.pack("<I", len(body)) + body)
+        out.flush()
+
+
+def handle_hello(req: Dict[str, Any]) -> Dict[str, Any]:
+    capabilities = ["asr", "asr_stream", "asr_stream_batch"]
+    if embeddings_available():
+        capabilities.append("embed")
+    return {
+        "type": "hello",
+        "protocol": PROTOCOL_VERSION,
+        "capabilities": capabilities,
+        "embed_model": EMBED_MODEL_NAME if "embed" in capabilities else None,
+    }
+
+
+def handle_embed(req: Dict[str, Any]) -> Dict[str, Any]:
+    texts = req.get("texts")
+    if not isinstance(texts, list) or not all(isinstance(t, str) for t in texts):
+        raise ValueError("texts must be a list of strings")
+    model = get_embedder()
+    vectors = model.encode(texts, normalize_embeddings=True)
+    return {
+        "type": "embed_result",
+        "vectors": [[float(x) for x in v] for v in vectors],
+        "error": None,
+    }
+
+
 def pcm16_to_wav_bytes(samples: np.ndarray, sample_rate: int) -> bytes:
     """Encode mono int16 PCM to WAV (in-memory)."""
     # Ensure dtype and little-endian order
@@ -39,31 +122,113 @@ def pcm16_to_wav_bytes(samples: np.ndarray, sample_rate: int) -> bytes:
     return buf.getvalue()
 
 
-def handle_asr(req: Dict[str, Any]) -> Dict[str, Any]:
-    # Validate request
+def validate_audio(req: Dict[str, Any]) -> int:
     if req.get("audio_format") != "pcm_s16le":
         raise ValueError("Unsupported audio_format; expected pcm_s16le")
     sr = int(req.get("sample_rate", 0))
     if sr != 16000:
         # Groq down-samples, but we standardize to 16k per protocol
         raise ValueError("Unsupported sample_rate; expected 16000")
-    samples = req.get("samples")
-    if not isinstance(samples, list):
-        raise ValueError("samples must be a list of int16")
-    # Convert to numpy int16
-    np_samples = np.array(samples, dtype=np.int16)
+    if req.get("protocol") != PROTOCOL_VERSION:
+        raise ValueError(f"protocol mismatch: got {req.get('protocol')}, worker speaks {PROTOCOL_VERSION}")
+    if not isinstance(req.get("pcm"), (bytes, bytearray)):
+        raise ValueError("missing binary pcm payload")
+    if len(req["pcm"]) % 2:
+        raise ValueError("pcm payload has odd length")
+    return sr
+
+
+def asr_settings(req: Dict[str, Any]) -> Dict[str, Any]:
+    """Optional [asr] settings from the daemon: language, translate, model, options."""
+    return {k: req[k] for k in ("language", "translate", "model", "options") if req.get(k) is not None}
+
+
+def transcribe_samples(pcm: bytes, sr: int, settings: Optional[Dict[str, Any]] = None) -> str:
+    # Little-endian int16, exactly as sent by the daemon
+    np_samples = np.frombuffer(pcm, dtype="<i2")
     wav_bytes = pcm16_to_wav_bytes(np_samples, sr)
+    settings = settings or {}
+
+    # Extra knobs first so the explicit fields below always win.
+    kwargs: Dict[str, Any] = dict(settings.get("options") or {})
+    translate = bool(settings.get("translate"))
+    # Translations always come back in English, so there is no language to pass.
+    if settings.get("language") and not translate:
+        kwargs["language"] = settings["language"]
 
     client = get_client()
+    # Use whisper-large-v3-turbo for lower latency; translation needs the full model.
+    # The SDK supports file-like or (filename, bytes)
+    kwargs.update(
+        file=("audio.wav", wav_bytes),
+        model=settings.get("model") or ("whisper-large-v3" if translate else "whisper-large-v3-turbo"),
+        response_format="json",
+    )
+    api = client.audio.translations if translate else client.audio.transcriptions
+    result = api.create(**kwargs)
+    return getattr(result, 'text', None) or ""
+
+
+class AsrStream:
+    """Accumulates asr_chunk audio and emits throttled partial transcripts.
+
+    At most one partial decode runs at a time (in a background thread, so stdin
+    keeps draining); finish() waits for it so no partial is ever written after
+    the final asr_result.
+    """
+
+    def __init__(self, sr: int, rid=None, settings=None):
+        self.sr = sr
+        self.rid = rid
+        self.settings = settings or {}
+        self.pcm = bytearray()
+        self.last_partial = 0
+        self.error = None
+        self.thread = None
+
+    def push(self, pcm: bytes) -> None:
+        self.pcm.extend(pcm)
+        busy = self.thread is not None and self.thread.is_alive()
+        if busy or (len(self.pcm) - self.last_partial) // 2 < int(self.sr * PARTIAL_EVERY_SECS):
+            return
+        snapshot = bytes(self.pcm)
+        self.last_partial = len(snapshot)
+        self.thread = threading.Thread(target=self._partial, args=(snapshot,), daemon=True)
+        self.thread.start()
+
+    def _partial(self, snapshot: bytes) -> None:
+        try:
+            text = transcribe_samples(snapshot, self.sr, self.settings)
+        except Exception as e:
+            print(f"ASR partial error: {type(e).__name__}: {e}", file=sys.stderr)
+            return
+        if text:
+            emit({"type": "partial", "id": self.rid, "text": text})
+
+    def finish(self) -> Dict[str, Any]:
+        if self.thread is not None:
+            self.thread.join()
+        if self.error:
+            return {
+                "type": "asr_result",
+                "text": "",
+                "confidence": None,
+                "error": self.error,
+            }
+        return handle_asr({
+            "audio_format": "pcm_s16le",
+            "protocol": PROTOCOL_VERSION,
+            "sample_rate": self.sr,
+            "pcm": bytes(self.pcm),
+            **self.settings,
+        })
+
+
+def handle_asr(req: Dict[str, Any]) -> Dict[str, Any]:
+    # Validate request
+    sr = validate_audio(req)
     try:
-        # Use whisper-large-v3-turbo for lower latency
-        # The SDK supports file-like or (filename, bytes)
-        result = client.audio.transcriptions.create(
-            file=("audio.wav", wav_bytes),
-            model="whisper-large-v3-turbo",
-            response_format="json"
-        )
-        text = getattr(result, 'text', None)
+        text = transcribe_samples(req["pcm"], sr, asr_settings(req))
     except Exception as e:
         # Report error to stderr and return structured error.
         # This keeps the Rust side from hanging and provides debuggable context.
@@ -83,18 +248,94 @@ def handle_asr(req: Dict[str, Any]) -> Dict[str, Any]:
     }
 
 
+def handle_asr_stream(req: Dict[str, Any]) -> Dict[str, Any]:
+    """Whole utterance in, partials out while the final decode runs.
+
+    Growing prefixes (every PARTIAL_EVERY_SECS) are transcribed in a background
+    thread alongside the full buffer, so partials cost no extra latency. Once
+    the final result is ready no further partial is written.
+    """
+    sr = validate_audio(req)
+    settings = asr_settings(req)
+    pcm = req["pcm"]
+    rid = req.get("id")
+    done = threading.Event()
+    gate = threading.Lock()
+
+    def partials() -> None:
+        step = max(int(sr * PARTIAL_EVERY_SECS), 1) * 2
+        for end in list(range(step, len(pcm), step))[:MAX_STREAM_PARTIALS]:
+            if done.is_set():
+                return
+            try:
+                text = transcribe_samples(pcm[:end], sr, settings)
+            except Exception as e:
+                print(f"ASR partial error: {type(e).__name__}: {e}", file=sys.stderr)
+                return
+            with gate:
+                if done.is_set():
+                    return
+                if text:
+                    emit({"type": "partial", "id": rid, "text": text, "is_final": False})
+
+    threading.Thread(target=partials, daemon=True).start()
+    resp = handle_asr(req)
+    with gate:
+        done.set()
+    resp["is_final"] = True
+    return resp
+
+
+def read_exact(inp, n: int):
+    buf = bytearray()
+    while len(buf) < n:
+        chunk = inp.read(n - len(buf))
+        if not chunk:
+            return None
+        buf.extend(chunk)
+    return bytes(buf)
+
+
+def read_frame(inp) -> Optional[bytes]:
+    # None on EOF between frames (or inside one, which is reported)
+    header = read_exact(inp, 4)
+    if header is None:
+        return None
+    (length,) = struct.unpack("<I", header)
+    if length > MAX_FRAME_BYTES:
+        print(f"frame of {length} bytes exceeds the {MAX_FRAME_BYTES} byte limit", file=sys.stderr)
+        return None
+    body = read_exact(inp, length)
+    if body is None:
+        print("EOF inside a frame", file=sys.stderr)
+    return body
+
+
 def main() -> None:
-    # Read line-delimited JSON from stdin; write line-delimited JSON to stdout
-    for line in sys.stdin:
-        line = line.strip()
-        if not line:
-            continue
+    # Read JSON frames (plus binary payloads) from stdin; write JSON frames to stdout
+    stream = None
+    inp = sys.stdin.buffer
+    while True:
+        body = read_frame(inp)
+        if body is None:
+            break
         try:
-            req = json.loads(line)
-        except json.JSONDecodeError as e:
+            req = json.loads(body.decode("utf-8"))
+        except (UnicodeDecodeError, json.JSONDecodeError) as e:
             print(f"Invalid JSON: {e}", file=sys.stderr)
             continue
+        if "byte_len" in req:
+            # Always consume the payload, even for a request we then reject,
+            # so the next header starts on a frame boundary.
+            pcm = read_exact(inp, int(req["byte_len"]))
+            if pcm is None:
+                print("EOF inside audio payload", file=sys.stderr)
+                break
+            req["pcm"] = pcm
         typ = req.get("type")
+        if typ == "shutdown":
+            # Daemon is stopping; exit promptly so the model is released.
+            break
         if typ == "asr":
             try:
                 resp = handle_asr(req)
@@ -106,6 +347,65 @@ def main() -> None:
                     "confidence": None,
                     "error": f"asr_handler_error: {type(e).__name__}: {e}",
                 }
+        elif typ == "asr_stream":
+            try:
+                resp = handle_asr_stream(req)
+            except Exception as e:
+                print(f"ASR stream handler error: {type(e).__name__}: {e}", file=sys.stderr)
+                resp = {
+                    "type": "asr_result",
+                    "text": "",
+                    "confidence": None,
+                    "error": f"asr_stream_error: {type(e).__name__}: {e}",
+                    "is_final": True,
+                }
+        elif typ == "asr_chunk":
+            # Chunks produce no direct response; partials are emitted asynchronously.
+            try:
+                if stream is None:
+                    stream = AsrStream(validate_audio(req), req.get("id"), asr_settings(req))
+                elif stream.error is None:
+                    validate_audio(req)
+                if stream.error is None:
+                    stream.push(req["pcm"])
+            except Exception as e:
+                print(f"ASR chunk error: {type(e).__name__}: {e}", file=sys.stderr)
+                if stream is None:
+                    stream = AsrStream(16000, req.get("id"))
+                stream.error = f"asr_chunk_error: {type(e).__name__}: {e}"
+            continue
+        elif typ == "asr_end":
+            if stream is None:
+                resp = {
+                    "type": "asr_result",
+                    "text": "",
+                    "confidence": None,
+                    "error": "asr_end_without_stream",
+                }
+            else:
+                try:
+                    resp = stream.finish()
+                except Exception as e:
+                    print(f"ASR stream error: {type(e).__name__}: {e}", file=sys.stderr)
+                    resp = {
+                        "type": "asr_result",
+                        "text": "",
+                        "confidence": None,
+                        "error": f"asr_stream_error: {type(e).__name__}: {e}",
+                    }
+                stream = None
+        elif typ == "hello":
+            resp = handle_hello(req)
+        elif typ == "embed":
+            try:
+                resp = handle_embed(req)
+            except Exception as e:
+                print(f"Embed handler error: {type(e).__name__}: {e}", file=sys.stderr)
+                resp = {
+                    "type": "embed_result",
+                    "vectors": [],
+                    "error": f"embed_handler_error: {type(e).__name__}: {e}",
+                }
         else:
             # Unknown request type; ignore
             print(f"Unknown request type: {typ}", file=sys.stderr)
@@ -115,9 +415,9 @@ def main() -> None:
                 "confidence": None,
                 "error": f"unknown_request_type: {typ}",
             }
-        # Write response JSON on a single line
-        sys.stdout.write(json.dumps(resp, ensure_ascii=False) + "\n")
-        sys.stdout.flush()
+        if "id" in req:
+            resp["id"] = req["id"]
+        emit(resp)
 
 if __name__ == "__main__":
     try:
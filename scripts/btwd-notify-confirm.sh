@@ -5,7 +5,8 @@ set -euo pipefail
 #   btwd-notify-confirm.sh <request_id> <title> <body>
 #
 # Shows a desktop notification with Yes/No actions.
-# When clicked, writes either "yes" or "no" to:
+# When clicked, answers through the control socket with btwctl when both are
+# available, and otherwise writes either "yes" or "no" to:
 #   ${XDG_RUNTIME_DIR}/btwd-confirm-<request_id>
 
 request_id="${1:-}"
@@ -19,6 +20,20 @@ fi
 
 runtime_dir="${XDG_RUNTIME_DIR:-/tmp}"
 out_path="${runtime_dir}/btwd-confirm-${request_id}"
+socket_path="${BTWD_CONTROL_SOCKET:-${runtime_dir}/btwd/control.sock}"
+
+# answer yes|no: the daemon rejects the answer if request_id is no longer pending.
+answer() {
+  if command -v btwctl >/dev/null 2>&1 && [[ -S "$socket_path" ]]; then
+    if [[ "$1" == "yes" ]]; then
+      btwctl confirm "$request_id" >/dev/null || true
+    else
+      btwctl cancel "$request_id" >/dev/null || true
+    fi
+    return 0
+  fi
+  printf '%s' "$1" >"$out_path"
+}
 
 # For swaync, we can still create action buttons via notify-send.
 # IMPORTANT: do NOT attempt to programmatically invoke actions here.
@@ -50,7 +65,7 @@ if command -v swaync-client >/dev/null 2>&1 && command -v notify-send >/dev/null
           }
         ' || true)"
     if [[ "$action" == "yes" || "$action" == "no" ]]; then
-      printf '%s' "$action" >"$out_path"
+      answer "$action"
     fi
   fi
 
@@ -64,7 +79,7 @@ if command -v dunstify >/dev/null 2>&1; then
     -A yes,Yes -A no,No \
     "$title" "$body" || true)"
   if [[ "$action" == "yes" || "$action" == "no" ]]; then
-    printf '%s' "$action" >"$out_path"
+    answer "$action"
     exit 0
   fi
   exit 0
@@ -0,0 +1,193 @@
+use crate::config::AsrCfg;
+use crate::error::{BtwError, Result};
+use crate::ml::{AsrResponse, MLWorker};
+
+/// A batch speech-to-text backend.
+///
+/// The Python `MLWorker` is the default; `whisper_rs` runs whisper.cpp
+/// in-process (cargo feature `whisper`) for setups where the venv is the
+/// weak link.
+pub trait AsrEngine {
+    fn transcribe(&mut self, samples: Vec<i16>, sample_rate: u32) -> Result<AsrResponse>;
+    fn name(&self) -> &'static str;
+}
+
+impl AsrEngine for MLWorker {
+    fn transcribe(&mut self, samples: Vec<i16>, sample_rate: u32) -> Result<AsrResponse> {
+        MLWorker::transcribe(self, samples, sample_rate)
+    }
+
+    fn name(&self) -> &'static str {
+        "python"
+    }
+}
+
+fn config_error(key: &'static str, message: String) -> BtwError {
+    BtwError::InvalidSetting { key, message }
+}
+
+/// Build the in-process engine selected by `[asr] engine`, or None when the
+/// Python worker handles ASR.
+pub fn local_engine(cfg: &AsrCfg) -> Result<Option<Box<dyn AsrEngine>>> {
+    match cfg.engine.as_str() {
+        "" | "python" => Ok(None),
+        "whisper_rs" | "whisper-native" => whisper_engine(cfg).map(Some),
+        other => Err(config_error("asr.engine", format!("unknown engine '{}' (expected \"python\" or \"whisper_rs\")", other))),
+    }
+}
+
+/// The engine to transcribe with: the local one if configured, else the worker.
+pub fn engine<'a>(local: &'a mut Option<Box<dyn AsrEngine>>, worker: &'a mut MLWorker) -> &'a mut dyn AsrEngine {
+    match local {
+        Some(e) => e.as_mut(),
+        None => worker,
+    }
+}
+
+#[cfg(feature = "whisper")]
+fn whisper_engine(cfg: &AsrCfg) -> Result<Box<dyn AsrEngine>> {
+    let path = cfg
+        .model_path
+        .as_deref()
+        .filter(|p| !p.trim().is_empty())
+        .ok_or_else(|| config_error("asr.model_path", "a ggml model is required with asr.engine = \"whisper_rs\"".into()))?;
+    Ok(Box::new(whisper::WhisperRsEngine::new(path, cfg.language.clone(), cfg.translate)?))
+}
+
+#[cfg(not(feature = "whisper"))]
+fn whisper_engine(_cfg: &AsrCfg) -> Result<Box<dyn AsrEngine>> {
+    Err(config_error("asr.engine", "\"whisper_rs\" needs btwd built with the `whisper` feature".into()))
+}
+
+#[cfg(feature = "whisper")]
+mod whisper {
+    use super::{config_error, AsrEngine};
+    use crate::error::{BtwError, Result};
+    use crate::ml::AsrResponse;
+    use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+
+    fn engine_error(message: String) -> BtwError {
+        BtwError::Asr { message }
+    }
+
+    /// whisper.cpp expects 16 kHz mono f32.
+    const WHISPER_RATE: u32 = 16000;
+
+    pub struct WhisperRsEngine {
+        ctx: WhisperContext,
+        language: Option<String>,
+        translate: bool,
+    }
+
+    impl WhisperRsEngine {
+        pub fn new(model_path: &str, language: Option<String>, translate: bool) -> Result<Self> {
+            let ctx = WhisperContext::new_with_params(model_path, WhisperContextParameters::default())
+                .map_err(|e| config_error("asr.model_path", format!("cannot load whisper model {}: {}", model_path, e)))?;
+            log::info!(
+                "asr: whisper_rs model={} language={} translate={}",
+                model_path,
+                language.as_deref().unwrap_or("auto"),
+                translate
+            );
+            Ok(Self { ctx, language, translate })
+        }
+    }
+
+    impl AsrEngine for WhisperRsEngine {
+        fn transcribe(&mut self, samples: Vec<i16>, sample_rate: u32) -> Result<AsrResponse> {
+            if sample_rate != WHISPER_RATE {
+                return Err(engine_error(format!("whisper_rs needs {} Hz audio, got {}", WHISPER_RATE, sample_rate)));
+            }
+            let audio: Vec<f32> = samples.iter().map(|&s| s as f32 / 32768.0).collect();
+            let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+            params.set_language(Some(self.language.as_deref().unwrap_or("auto")));
+            params.set_translate(self.translate);
+            params.set_print_progress(false);
+            params.set_print_realtime(false);
+            params.set_print_special(false);
+            params.set_print_timestamps(false);
+
+            let mut state = self.ctx.create_state().map_err(|e| engine_error(format!("whisper state: {}", e)))?;
+            if let Err(e) = state.full(params, &audio) {
+                return Ok(AsrResponse::failed(format!("whisper_rs_failed: {}", e)));
+            }
+            let n = state.full_n_segments().map_err(|e| engine_error(format!("whisper segments: {}", e)))?;
+            let mut text = String::new();
+            for i in 0..n {
+                if let Ok(seg) = state.full_get_segment_text(i) {
+                    text.push_str(&seg);
+                }
+            }
+            Ok(AsrResponse::from_text(text.trim().to_string()))
+        }
+
+        fn name(&self) -> &'static str {
+            "whisper_rs"
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        /// Runs only when a model is present, e.g.
+        /// `BTWD_TEST_WHISPER_MODEL=~/models/ggml-tiny.en.bin cargo test --features whisper`.
+        #[test]
+        fn smoke_transcribes_silence() {
+            let Some(path) = std::env::var("BTWD_TEST_WHISPER_MODEL").ok().filter(|p| std::path::Path::new(p).is_file()) else {
+                return;
+            };
+            let mut engine = WhisperRsEngine::new(&path, Some("en".into()), false).unwrap();
+            let resp = engine.transcribe(vec![0; WHISPER_RATE as usize], WHISPER_RATE).unwrap();
+            assert!(resp.error.is_none());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct MockEngine {
+        calls: Vec<usize>,
+    }
+
+    impl AsrEngine for MockEngine {
+        fn transcribe(&mut self, samples: Vec<i16>, _sample_rate: u32) -> Result<AsrResponse> {
+            self.calls.push(samples.len());
+            Ok(AsrResponse::from_text(format!("heard {} samples", samples.len())))
+        }
+
+        fn name(&self) -> &'static str {
+            "mock"
+        }
+    }
+
+    #[test]
+    fn trait_object_dispatches_to_engine() {
+        let mut engine: Box<dyn AsrEngine> = Box::new(MockEngine { calls: Vec::new() });
+        let resp = engine.transcribe(vec![0; 320], 16000).unwrap();
+        assert_eq!(resp.text, "heard 320 samples");
+        assert_eq!(engine.name(), "mock");
+    }
+
+    #[test]
+    fn python_engine_needs_no_local_backend() {
+        assert!(local_engine(&AsrCfg::default()).unwrap().is_none());
+        let cfg = AsrCfg { engine: "python".into(), ..AsrCfg::default() };
+        assert!(local_engine(&cfg).unwrap().is_none());
+        let cfg = AsrCfg { engine: "vosk".into(), ..AsrCfg::default() };
+        assert!(local_engine(&cfg).is_err());
+    }
+
+    #[cfg(not(feature = "whisper"))]
+    #[test]
+    fn whisper_without_feature_is_a_clear_error() {
+        for engine in ["whisper_rs", "whisper-native"] {
+            let cfg = AsrCfg { engine: engine.into(), ..AsrCfg::default() };
+            assert!(cfg.is_in_process());
+            let err = local_engine(&cfg).err().unwrap().to_string();
+            assert!(err.contains("`whisper` feature"), "{}", err);
+        }
+    }
+}
@@ -1,110 +1,662 @@
+use crate::config::AudioCfg;
 use crate::error::{BtwError, Result};
-use crate::porcupine::Porcupine;
+use crate::wake::WakeWordDetector;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use std::sync::mpsc::{Receiver, sync_channel};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, SyncSender, channel, sync_channel};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-/// Start microphone capture in a dedicated thread and feed frames into Porcupine.
-/// Logs "Wake word detected" on detection.
-pub fn start_listening(porcupine: &Porcupine) -> Result<(std::thread::JoinHandle<()>, Receiver<Vec<i16>>)> {
-    // Select default input device
-    let host = cpal::default_host();
-    let device = host.default_input_device().ok_or_else(|| BtwError::ParseError {
-        path: std::path::PathBuf::new(),
-        kind: "audio",
-        message: "no default input device".into(),
-    })?;
-
-    let required_rate = porcupine.sample_rate();
-    let frame_length = porcupine.frame_length();
-
-    // Choose a supported mono config matching Porcupine sample rate
-    let supported = device.supported_input_configs().map_err(|e| BtwError::ParseError {
-        path: std::path::PathBuf::new(),
-        kind: "audio",
-        message: format!("query input configs failed: {}", e),
-    })?;
-
-    // Prefer i16; fall back to f32
-    let mut selected: Option<(cpal::StreamConfig, bool)> = None;
-    for cfg in supported {
-        if cfg.channels() != 1 { continue; }
-        let range = cfg.min_sample_rate()..=cfg.max_sample_rate();
-        if !range.contains(&cpal::SampleRate(required_rate)) { continue; }
-        let with_sr = cfg.with_sample_rate(cpal::SampleRate(required_rate));
-        let is_i16 = cfg.sample_format() == cpal::SampleFormat::I16;
-        let sc = with_sr.config();
-        // Prefer i16; choose f32 only if i16 not yet selected
-        match (&selected, is_i16) {
-            (None, true) => { selected = Some((sc, true)); }
-            (None, false) => { selected = Some((sc, false)); }
-            (Some(_), true) => { selected = Some((sc, true)); break; }
-            _ => {}
-        }
-    }
-
-    let (config, is_i16) = selected.ok_or_else(|| BtwError::ParseError { path: std::path::PathBuf::new(), kind: "audio", message: format!("no mono input config at {} Hz", required_rate) })?;
+/// How often the capture thread checks whether it should stop.
+const STOP_POLL: Duration = Duration::from_millis(20);
+
+/// Capture state changes, for telling the user about a flaky microphone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AudioEvent {
+    /// A stream was opened and started.
+    StreamOk,
+    /// Capture was lost (or could not be restored); the message says why.
+    StreamError(String),
+    /// The input came back after a loss; carries the device name.
+    DeviceReconnected(String),
+}
+
+/// The running capture thread. `stop` (or dropping the handle) ends it and
+/// releases the input device; it also ends by itself once the frame
+/// receiver is dropped.
+pub struct AudioCapture {
+    stop: Arc<AtomicBool>,
+    join: Option<std::thread::JoinHandle<()>>,
+    failure: Arc<Mutex<Option<String>>>,
+}
+
+impl AudioCapture {
+    /// Run `body` on the capture thread. It owns the cpal stream and must
+    /// return once `stop` is set; returning drops the stream.
+    fn spawn(body: impl FnOnce(Arc<AtomicBool>) + Send + 'static) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = stop.clone();
+        let join = std::thread::spawn(move || body(flag));
+        Self { stop, join: Some(join), failure: Arc::new(Mutex::new(None)) }
+    }
+
+    /// Why capture ended on its own, once the frame channel has closed.
+    pub fn failure(&self) -> Option<String> {
+        self.failure.lock().unwrap_or_else(|p| p.into_inner()).clone()
+    }
+
+    /// Stop capturing and wait for the thread to finish.
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(join) = self.join.take() {
+            let _ = join.join();
+        }
+    }
+}
+
+impl Drop for AudioCapture {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
 
+/// Start microphone capture in a dedicated thread, chunked into the
+/// detector's frame length at its sample rate. Devices without a native
+/// mono config at that rate are downmixed and resampled.
+///
+/// If the device disappears or delivers nothing for `cfg.hotplug_timeout_ms`,
+/// the thread drops the stream, re-enumerates inputs (`cfg.input_device`,
+/// else the default) on a backoff schedule and keeps feeding the same
+/// channel. Each step is reported on the [`AudioEvent`] receiver. After
+/// `cfg.reconnect_attempts` failures the frame channel is closed and
+/// [`AudioCapture::failure`] says why.
+///
+/// A nonzero `pre_emphasis` applies [`PreEmphasis`] to every sample.
+pub fn start_listening(
+    detector: Arc<Mutex<dyn WakeWordDetector>>,
+    cfg: &AudioCfg,
+    pre_emphasis: f32,
+) -> Result<(AudioCapture, Receiver<Vec<i16>>, Receiver<AudioEvent>)> {
+    let (required_rate, frame_length) = {
+        let d = detector.lock().unwrap_or_else(|p| p.into_inner());
+        (d.sample_rate(), d.frame_length())
+    };
+    // Fail fast at startup; later losses go through the reconnect path.
+    let first = select_input(cfg.input_device.as_deref(), required_rate).map_err(|message| BtwError::AudioDevice { message })?;
+
+    let cfg = cfg.clone();
     let (tx, rx) = sync_channel::<Vec<i16>>(8);
-    let handle = std::thread::spawn(move || {
-
-        // Fixed-size frame buffer and index to avoid unbounded push and improve safety
-        let mut frame: Vec<i16> = vec![0i16; frame_length];
-        let mut idx: usize = 0;
-        let err_fn = |err| eprintln!("audio stream error: {}", err);
-
-        if is_i16 {
-            let stream = match device.build_input_stream(
-                &config,
-                move |data: &[i16], _| {
-                    for &sample in data {
-                        frame[idx] = sample;
-                        idx += 1;
-                        if idx == frame_length {
-                            let _ = tx.send(frame.clone());
-                            idx = 0;
-                        }
-                    }
-                },
-                err_fn,
-                None,
-            ) {
-                Ok(s) => s,
-                Err(err) => {
-                    eprintln!("audio: build input stream failed: {}", err);
-                    return;
+    let (events, events_rx) = channel::<AudioEvent>();
+    let failure = Arc::new(Mutex::new(None));
+    let failure_slot = failure.clone();
+    let mut handle = AudioCapture::spawn(move |stop| {
+        let stall = Duration::from_millis(cfg.hotplug_timeout_ms);
+        let mut next = Some(first);
+        loop {
+            let Some(input) = next.take() else { return };
+            let alive = Arc::new(AtomicU64::new(0));
+            let stream = match open_stream(input, required_rate, frame_length, pre_emphasis, &tx, &stop, &alive) {
+                Ok(s) => {
+                    let _ = events.send(AudioEvent::StreamOk);
+                    Some(s)
+                }
+                Err(e) => {
+                    log::error!("audio: {}", e);
+                    None
                 }
             };
-            if let Err(err) = stream.play() { eprintln!("audio: start stream failed: {}", err); return; }
-            loop { std::thread::sleep(std::time::Duration::from_secs(1)); }
-        } else {
-            let stream = match device.build_input_stream(
-                &config,
-                move |data: &[f32], _| {
-                    for &sample in data {
-                        // Convert normalized f32 samples (-1.0..1.0) to signed 16-bit PCM
-                        // as required by Porcupine. Values are clipped to avoid overflow.
-                        let s = (sample * i16::MAX as f32).round().clamp(i16::MIN as f32, i16::MAX as f32) as i16;
-                        frame[idx] = s;
-                        idx += 1;
-                        if idx == frame_length {
-                            let _ = tx.send(frame.clone());
-                            idx = 0;
-                        }
-                    }
-                },
-                err_fn,
-                None,
-            ) {
-                Ok(s) => s,
-                Err(err) => {
-                    eprintln!("audio: build input stream failed: {}", err);
+            if let Some(_stream) = &stream {
+                if wait_while_alive(&stop, &alive, stall) {
+                    log::debug!("audio: capture stopped");
                     return;
                 }
-            };
-            if let Err(err) = stream.play() { eprintln!("audio: start stream failed: {}", err); return; }
-            loop { std::thread::sleep(std::time::Duration::from_secs(1)); }
+            }
+            drop(stream);
+            log::warn!("audio: input lost; reconnecting");
+            let _ = events.send(AudioEvent::StreamError("input lost; reconnecting".into()));
+            let base = Duration::from_millis(cfg.reconnect_backoff_ms);
+            match reconnect(cfg.reconnect_attempts, base, &stop, || select_input(cfg.input_device.as_deref(), required_rate)) {
+                Some(input) => {
+                    log::info!("audio: input recovered ({})", input.name);
+                    let _ = events.send(AudioEvent::DeviceReconnected(input.name.clone()));
+                    next = Some(input);
+                }
+                None if stop.load(Ordering::SeqCst) => return,
+                None => {
+                    let msg = format!("audio input lost; gave up after {} reconnect attempt(s)", cfg.reconnect_attempts);
+                    log::error!("audio: {}", msg);
+                    let _ = events.send(AudioEvent::StreamError(msg.clone()));
+                    *failure_slot.lock().unwrap_or_else(|p| p.into_inner()) = Some(msg);
+                    // Dropping `tx` on return closes the frame channel.
+                    return;
+                }
+            }
         }
     });
+    handle.failure = failure;
+
+    Ok((handle, rx, events_rx))
+}
+
+/// A device plus the stream config to open it with.
+struct Input {
+    name: String,
+    device: cpal::Device,
+    config: cpal::StreamConfig,
+    is_i16: bool,
+}
+
+/// Find the named input device (substring match), or the default one, and
+/// pick a config for it.
+fn select_input(name: Option<&str>, required_rate: u32) -> std::result::Result<Input, String> {
+    let host = cpal::default_host();
+    let device = match name.map(str::trim).filter(|n| !n.is_empty()) {
+        Some(name) => host
+            .input_devices()
+            .map_err(|e| format!("enumerate input devices failed: {}", e))?
+            .find(|d| d.name().is_ok_and(|n| n.contains(name)))
+            .ok_or_else(|| format!("no input device matching '{}'", name))?,
+        None => host.default_input_device().ok_or("no default input device")?,
+    };
+
+    let supported: Vec<cpal::SupportedStreamConfigRange> = device
+        .supported_input_configs()
+        .map_err(|e| format!("query input configs failed: {}", e))?
+        .collect();
+    let candidates: Vec<Candidate> = supported
+        .iter()
+        .map(|c| Candidate {
+            channels: c.channels(),
+            min_rate: c.min_sample_rate().0,
+            max_rate: c.max_sample_rate().0,
+            format: match c.sample_format() {
+                cpal::SampleFormat::I16 => Some(true),
+                cpal::SampleFormat::F32 => Some(false),
+                _ => None,
+            },
+        })
+        .collect();
+    let (chosen, rate) = pick_config(&candidates, required_rate).ok_or("no i16 or f32 input config")?;
+    let config = supported[chosen].with_sample_rate(cpal::SampleRate(rate)).config();
+    let is_i16 = candidates[chosen].format == Some(true);
+    if config.channels != 1 || rate != required_rate {
+        log::info!(
+            "audio: device has no mono {} Hz input; capturing {} ch at {} Hz and converting",
+            required_rate,
+            config.channels,
+            rate
+        );
+    }
+    let name = device.name().unwrap_or_else(|_| "unknown device".into());
+    Ok(Input { name, device, config, is_i16 })
+}
+
+/// Build and start the stream. Every callback bumps `alive`; a device error
+/// sets it to `u64::MAX` so the capture thread reconnects.
+fn open_stream(
+    input: Input,
+    required_rate: u32,
+    frame_length: usize,
+    pre_emphasis: f32,
+    tx: &SyncSender<Vec<i16>>,
+    stop: &Arc<AtomicBool>,
+    alive: &Arc<AtomicU64>,
+) -> std::result::Result<cpal::Stream, String> {
+    let Input { device, config, is_i16, .. } = input;
+    let mut capture = Capture::new(config.channels as usize, config.sample_rate.0, required_rate, frame_length, stop.clone());
+    capture.pre_emphasis = PreEmphasis::new(pre_emphasis);
+    let tx = tx.clone();
+    let beat = alive.clone();
+    let dead = alive.clone();
+    let err_fn = move |err: cpal::StreamError| {
+        log::error!("audio stream error: {}", err);
+        if matches!(err, cpal::StreamError::DeviceNotAvailable) {
+            dead.store(u64::MAX, Ordering::SeqCst);
+        }
+    };
+    let built = if is_i16 {
+        device.build_input_stream(
+            &config,
+            move |data: &[i16], _| {
+                beat.fetch_add(1, Ordering::Relaxed);
+                capture.push_i16(data, &tx)
+            },
+            err_fn,
+            None,
+        )
+    } else {
+        device.build_input_stream(
+            &config,
+            move |data: &[f32], _| {
+                beat.fetch_add(1, Ordering::Relaxed);
+                capture.push_f32(data, &tx)
+            },
+            err_fn,
+            None,
+        )
+    };
+    let stream = built.map_err(|e| format!("build input stream failed: {}", e))?;
+    stream.play().map_err(|e| format!("start stream failed: {}", e))?;
+    Ok(stream)
+}
+
+/// Block until `stop` (returns true) or the stream is lost (false): a device
+/// error, or no callbacks for `stall` (the stream died without telling us).
+fn wait_while_alive(stop: &AtomicBool, alive: &AtomicU64, stall: Duration) -> bool {
+    let mut last = alive.load(Ordering::SeqCst);
+    let mut last_change = std::time::Instant::now();
+    while !stop.load(Ordering::SeqCst) {
+        std::thread::sleep(STOP_POLL);
+        let now = alive.load(Ordering::SeqCst);
+        if now == u64::MAX {
+            return false;
+        }
+        if now != last {
+            last = now;
+            last_change = std::time::Instant::now();
+        } else if last_change.elapsed() >= stall {
+            return false;
+        }
+    }
+    true
+}
+
+/// Delay before reconnect attempt `attempt` (1-based): `base` doubling, at most 10 s.
+fn backoff(attempt: u32, base: Duration) -> Duration {
+    base.saturating_mul(1u32 << attempt.saturating_sub(1).min(16)).min(Duration::from_secs(10))
+}
+
+/// Call `try_open` up to `attempts` times with [`backoff`] between tries.
+/// `None` when every attempt failed or `stop` was set while waiting.
+fn reconnect<T>(
+    attempts: u32,
+    base: Duration,
+    stop: &AtomicBool,
+    mut try_open: impl FnMut() -> std::result::Result<T, String>,
+) -> Option<T> {
+    for attempt in 1..=attempts {
+        let deadline = std::time::Instant::now() + backoff(attempt, base);
+        while std::time::Instant::now() < deadline {
+            if stop.load(Ordering::SeqCst) {
+                return None;
+            }
+            std::thread::sleep(STOP_POLL.min(deadline.saturating_duration_since(std::time::Instant::now())));
+        }
+        match try_open() {
+            Ok(v) => return Some(v),
+            Err(e) => log::warn!("audio: reconnect attempt {}/{} failed: {}", attempt, attempts, e),
+        }
+    }
+    None
+}
+
+/// What `pick_config` needs from a `SupportedStreamConfigRange`.
+/// `format` is `Some(true)` for i16, `Some(false)` for f32, `None` otherwise.
+struct Candidate {
+    channels: u16,
+    min_rate: u32,
+    max_rate: u32,
+    format: Option<bool>,
+}
+
+/// Rate the converting fallback prefers: an integer multiple of 16 kHz.
+const PREFERRED_FALLBACK_RATE: u32 = 48_000;
+
+/// Index of the config to open and the rate to open it at. A native mono
+/// config at `required` wins; otherwise the closest rate (48 kHz first),
+/// fewest channels, then i16 over f32.
+fn pick_config(candidates: &[Candidate], required: u32) -> Option<(usize, u32)> {
+    let usable = || candidates.iter().enumerate().filter(|(_, c)| c.format.is_some());
+    let native = usable()
+        .filter(|(_, c)| c.channels == 1 && (c.min_rate..=c.max_rate).contains(&required))
+        .min_by_key(|(_, c)| c.format != Some(true));
+    if let Some((i, _)) = native {
+        return Some((i, required));
+    }
+    usable()
+        .map(|(i, c)| {
+            let rate = if (c.min_rate..=c.max_rate).contains(&PREFERRED_FALLBACK_RATE) {
+                PREFERRED_FALLBACK_RATE
+            } else {
+                required.clamp(c.min_rate, c.max_rate)
+            };
+            let key = (rate != PREFERRED_FALLBACK_RATE, rate.abs_diff(required), c.channels, c.format != Some(true));
+            (key, i, rate)
+        })
+        .min_by_key(|(key, _, _)| *key)
+        .map(|(_, i, rate)| (i, rate))
+}
+
+/// Turns device callbacks into exact `frame_length` frames of mono i16 at
+/// the detector's rate, whatever the device delivers. State (partial frame,
+/// resampler phase) carries across callbacks.
+struct Capture {
+    channels: usize,
+    resampler: Option<Resampler>,
+    frame: Vec<i16>,
+    idx: usize,
+    mono: Vec<f32>,
+    resampled: Vec<f32>,
+    /// Applied to the output samples; its state spans frames and callbacks.
+    pre_emphasis: Option<PreEmphasis>,
+    /// Set when the frame receiver is gone, so the thread winds down.
+    stop: Arc<AtomicBool>,
+}
+
+impl Capture {
+    fn new(channels: usize, in_rate: u32, out_rate: u32, frame_length: usize, stop: Arc<AtomicBool>) -> Self {
+        Self {
+            channels: channels.max(1),
+            resampler: (in_rate != out_rate).then(|| Resampler::new(in_rate, out_rate)),
+            frame: vec![0i16; frame_length],
+            idx: 0,
+            mono: Vec::new(),
+            resampled: Vec::new(),
+            pre_emphasis: None,
+            stop,
+        }
+    }
+
+    fn push_i16(&mut self, data: &[i16], tx: &SyncSender<Vec<i16>>) {
+        if self.channels == 1 && self.resampler.is_none() {
+            for &s in data {
+                self.emit(s, tx);
+            }
+            return;
+        }
+        let normalized: Vec<f32> = data.iter().map(|&s| s as f32 / i16::MAX as f32).collect();
+        self.push_f32(&normalized, tx);
+    }
+
+    fn push_f32(&mut self, data: &[f32], tx: &SyncSender<Vec<i16>>) {
+        self.mono.clear();
+        downmix(data, self.channels, &mut self.mono);
+        let samples = match &mut self.resampler {
+            Some(r) => {
+                self.resampled.clear();
+                r.process(&self.mono, &mut self.resampled);
+                std::mem::take(&mut self.resampled)
+            }
+            None => std::mem::take(&mut self.mono),
+        };
+        for &s in &samples {
+            self.emit(to_i16(s), tx);
+        }
+        // Hand the buffers back so steady-state callbacks don't allocate.
+        if self.resampler.is_some() {
+            self.resampled = samples;
+        } else {
+            self.mono = samples;
+        }
+    }
+
+    fn emit(&mut self, sample: i16, tx: &SyncSender<Vec<i16>>) {
+        let sample = match &mut self.pre_emphasis {
+            Some(p) => p.apply(sample),
+            None => sample,
+        };
+        self.frame[self.idx] = sample;
+        self.idx += 1;
+        if self.idx == self.frame.len() {
+            if tx.send(self.frame.clone()).is_err() {
+                self.stop.store(true, Ordering::SeqCst);
+            }
+            self.idx = 0;
+        }
+    }
+}
+
+/// First-order pre-emphasis, `y[n] = x[n] - alpha * x[n-1]`: tilts energy
+/// away from the low end that cheap mics over-emphasize, toward the range
+/// where phonemes live.
+struct PreEmphasis {
+    alpha: f32,
+    last_sample: i16,
+}
+
+impl PreEmphasis {
+    /// `None` for a zero coefficient (filter disabled).
+    fn new(alpha: f32) -> Option<Self> {
+        (alpha != 0.0).then_some(Self { alpha, last_sample: 0 })
+    }
+
+    fn apply(&mut self, x: i16) -> i16 {
+        let y = x as f32 - self.alpha * self.last_sample as f32;
+        self.last_sample = x;
+        y.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16
+    }
+}
+
+/// Convert normalized f32 samples (-1.0..1.0) to signed 16-bit PCM as
+/// required by Porcupine. Values are clipped to avoid overflow.
+fn to_i16(sample: f32) -> i16 {
+    (sample * i16::MAX as f32).round().clamp(i16::MIN as f32, i16::MAX as f32) as i16
+}
+
+/// Average interleaved `channels` into one mono stream.
+fn downmix(interleaved: &[f32], channels: usize, out: &mut Vec<f32>) {
+    if channels <= 1 {
+        out.extend_from_slice(interleaved);
+        return;
+    }
+    out.extend(interleaved.chunks_exact(channels).map(|f| f.iter().sum::<f32>() / channels as f32));
+}
+
+/// Streaming linear-interpolation resampler. The fractional read position
+/// and the last input sample are kept between calls, so chunking the input
+/// differently yields the same output.
+struct Resampler {
+    /// Input samples advanced per output sample.
+    step: f64,
+    /// Read position relative to `last` (index 0) and the new chunk (1..).
+    pos: f64,
+    last: Option<f32>,
+}
+
+impl Resampler {
+    fn new(in_rate: u32, out_rate: u32) -> Self {
+        Self { step: in_rate as f64 / out_rate as f64, pos: 0.0, last: None }
+    }
+
+    fn process(&mut self, input: &[f32], out: &mut Vec<f32>) {
+        let (prev, input) = match self.last {
+            Some(prev) => (prev, input),
+            None => match input.split_first() {
+                Some((&first, rest)) => (first, rest),
+                None => return,
+            },
+        };
+        let at = |i: usize| if i == 0 { prev } else { input[i - 1] };
+        let n = input.len();
+        while self.pos <= n as f64 {
+            let i = self.pos as usize;
+            if i == n {
+                // Exactly on the newest sample; its neighbour hasn't arrived yet.
+                out.push(at(i));
+            } else {
+                let frac = (self.pos - i as f64) as f32;
+                out.push(at(i) + (at(i + 1) - at(i)) * frac);
+            }
+            self.pos += self.step;
+        }
+        self.pos -= n as f64;
+        self.last = Some(at(n));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    fn sine(freq: f32, rate: u32, len: usize) -> Vec<f32> {
+        (0..len).map(|i| (2.0 * PI * freq * i as f32 / rate as f32).sin() * 0.5).collect()
+    }
+
+    /// Rough dominant frequency from upward zero crossings.
+    fn zero_crossing_freq(samples: &[f32], rate: u32) -> f32 {
+        let ups = samples.windows(2).filter(|w| w[0] < 0.0 && w[1] >= 0.0).count();
+        ups as f32 * rate as f32 / samples.len() as f32
+    }
+
+    #[test]
+    fn resampling_48k_to_16k_keeps_length_and_frequency() {
+        let input = sine(440.0, 48_000, 48_000);
+        let mut out = Vec::new();
+        // Odd, uneven callback sizes exercise the carried phase.
+        let mut r = Resampler::new(48_000, 16_000);
+        for chunk in input.chunks(997) {
+            r.process(chunk, &mut out);
+        }
+        assert!((out.len() as i64 - 16_000).abs() <= 1, "len {}", out.len());
+        let f = zero_crossing_freq(&out, 16_000);
+        assert!((f - 440.0).abs() < 5.0, "freq {}", f);
+
+        let mut whole = Vec::new();
+        Resampler::new(48_000, 16_000).process(&input, &mut whole);
+        assert_eq!(whole.len(), out.len());
+        assert!(whole.iter().zip(&out).all(|(a, b)| (a - b).abs() < 1e-5));
+    }
+
+    #[test]
+    fn resampling_44k1_to_16k_keeps_length_and_frequency() {
+        let input = sine(1000.0, 44_100, 44_100);
+        let mut out = Vec::new();
+        let mut r = Resampler::new(44_100, 16_000);
+        for chunk in input.chunks(441) {
+            r.process(chunk, &mut out);
+        }
+        assert!((out.len() as i64 - 16_000).abs() <= 1, "len {}", out.len());
+        let f = zero_crossing_freq(&out, 16_000);
+        assert!((f - 1000.0).abs() < 10.0, "freq {}", f);
+    }
+
+    #[test]
+    fn stereo_48k_capture_yields_exact_frames() {
+        let (tx, rx) = sync_channel::<Vec<i16>>(64);
+        let mut cap = Capture::new(2, 48_000, 16_000, 512, Arc::new(AtomicBool::new(false)));
+        let mono = sine(300.0, 48_000, 48_000);
+        let stereo: Vec<f32> = mono.iter().flat_map(|&s| [s, s]).collect();
+        for chunk in stereo.chunks(2 * 480) {
+            cap.push_f32(chunk, &tx);
+        }
+        drop(tx);
+        let frames: Vec<Vec<i16>> = rx.iter().collect();
+        assert_eq!(frames.len(), 16_000 / 512);
+        assert!(frames.iter().all(|f| f.len() == 512));
+    }
+
+    /// Stand-in for the cpal stream: feeds the capture until told to stop.
+    fn mock_capture(tx: SyncSender<Vec<i16>>) -> AudioCapture {
+        AudioCapture::spawn(move |stop| {
+            let mut cap = Capture::new(1, 16_000, 16_000, 512, stop.clone());
+            while !stop.load(Ordering::SeqCst) {
+                cap.push_i16(&[0; 256], &tx);
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        })
+    }
+
+    #[test]
+    fn stop_ends_the_capture_thread_promptly() {
+        let (tx, rx) = sync_channel::<Vec<i16>>(1024);
+        let mut capture = mock_capture(tx);
+        assert_eq!(rx.recv_timeout(Duration::from_secs(2)).unwrap().len(), 512);
+        let started = std::time::Instant::now();
+        capture.stop();
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(capture.join.is_none());
+    }
+
+    #[test]
+    fn dropping_the_receiver_ends_the_capture_thread() {
+        let (tx, rx) = sync_channel::<Vec<i16>>(1);
+        let mut capture = mock_capture(tx);
+        drop(rx);
+        let started = std::time::Instant::now();
+        while !capture.stop.load(Ordering::SeqCst) {
+            assert!(started.elapsed() < Duration::from_secs(2), "capture ignored the closed receiver");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        capture.stop();
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_ten_seconds() {
+        let base = Duration::from_millis(500);
+        assert_eq!(backoff(1, base), Duration::from_millis(500));
+        assert_eq!(backoff(3, base), Duration::from_secs(2));
+        assert_eq!(backoff(6, base), Duration::from_secs(10));
+        assert_eq!(backoff(60, base), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn reconnect_retries_until_success_or_gives_up() {
+        let stop = AtomicBool::new(false);
+        let base = Duration::from_millis(1);
+        let mut tries = 0;
+        let got = reconnect(5, base, &stop, || {
+            tries += 1;
+            if tries < 3 { Err("gone".to_string()) } else { Ok(tries) }
+        });
+        assert_eq!(got, Some(3));
+
+        let mut tries = 0;
+        assert_eq!(reconnect(4, base, &stop, || -> std::result::Result<(), String> { tries += 1; Err("gone".into()) }), None);
+        assert_eq!(tries, 4);
+
+        stop.store(true, Ordering::SeqCst);
+        assert_eq!(reconnect(4, Duration::from_secs(5), &stop, || Ok(())), None);
+    }
 
-    Ok((handle, rx))
+    #[test]
+    fn lost_stream_is_detected() {
+        let stop = AtomicBool::new(false);
+        let alive = AtomicU64::new(u64::MAX);
+        assert!(!wait_while_alive(&stop, &alive, Duration::from_secs(3)));
+        // No callbacks at all: a stall.
+        let started = std::time::Instant::now();
+        assert!(!wait_while_alive(&stop, &AtomicU64::new(0), Duration::from_millis(100)));
+        assert!(started.elapsed() < Duration::from_secs(1));
+        stop.store(true, Ordering::SeqCst);
+        assert!(wait_while_alive(&stop, &AtomicU64::new(0), Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn pre_emphasis_favors_high_frequencies() {
+        fn power(samples: &[i16]) -> f64 {
+            samples.iter().map(|&s| (s as f64).powi(2)).sum::<f64>() / samples.len() as f64
+        }
+        let pcm = |freq: f32| sine(freq, 16_000, 16_000).iter().map(|&s| to_i16(s)).collect::<Vec<i16>>();
+        let filter = |input: &[i16]| {
+            let mut p = PreEmphasis::new(0.97).unwrap();
+            input.iter().map(|&s| p.apply(s)).collect::<Vec<i16>>()
+        };
+        let (low, high) = (pcm(100.0), pcm(3_000.0));
+        let before = power(&high) / power(&low);
+        let after = power(&filter(&high)) / power(&filter(&low));
+        assert!(after > before * 10.0, "ratio {} -> {}", before, after);
+        assert!(PreEmphasis::new(0.0).is_none());
+        // Full-scale steps saturate instead of wrapping.
+        let mut p = PreEmphasis::new(0.97).unwrap();
+        p.apply(i16::MAX);
+        assert_eq!(p.apply(i16::MIN), i16::MIN);
+    }
+
+    #[test]
+    fn downmix_averages_channels() {
+        let mut out = Vec::new();
+        downmix(&[1.0, 0.0, 0.5, 0.5, -1.0, 1.0], 2, &mut out);
+        assert_eq!(out, vec![0.5, 0.5, 0.0]);
+    }
+
+    #[test]
+    fn native_mono_config_is_preferred_then_48k() {
+        let c = |channels, min_rate, max_rate, format| Candidate { channels, min_rate, max_rate, format: Some(format) };
+        assert_eq!(pick_config(&[c(2, 8_000, 96_000, true), c(1, 16_000, 16_000, false)], 16_000), Some((1, 16_000)));
+        assert_eq!(pick_config(&[c(2, 44_100, 44_100, true), c(2, 48_000, 48_000, false)], 16_000), Some((1, 48_000)));
+        assert_eq!(pick_config(&[c(2, 44_100, 44_100, false), c(2, 22_050, 22_050, true)], 16_000), Some((1, 22_050)));
+        assert_eq!(pick_config(&[c(6, 48_000, 48_000, false), c(2, 48_000, 48_000, false)], 16_000), Some((1, 48_000)));
+        assert_eq!(pick_config(&[Candidate { channels: 1, min_rate: 16_000, max_rate: 16_000, format: None }], 16_000), None);
+    }
 }
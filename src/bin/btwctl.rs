@@ -0,0 +1,91 @@
+//! btwctl: talk to a running btwd over its control socket.
+//!
+//! ```text
+//! btwctl say <text...>   handle text as if it had been spoken
+//! btwctl listen          start listening, as if the wake word had fired
+//! btwctl confirm [id]    confirm the pending command (only if it is still `id`)
+//! btwctl cancel [id]     cancel the pending command (only if it is still `id`)
+//! btwctl status          print the daemon state as JSON (also: state)
+//! btwctl reload          re-read config.toml and commands.json (like SIGHUP)
+//! btwctl stop            stop speaking and drop any queued speech
+//! ```
+//!
+//! The socket is `$XDG_RUNTIME_DIR/btwd/control.sock` unless `BTWD_CONTROL_SOCKET` is set.
+
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: btwctl say <text...> | listen | confirm [request_id] | cancel [request_id] | status | reload | stop";
+
+fn socket_path() -> PathBuf {
+    if let Some(path) = std::env::var_os("BTWD_CONTROL_SOCKET").filter(|p| !p.is_empty()) {
+        return PathBuf::from(path);
+    }
+    let runtime_dir = std::env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".to_string());
+    PathBuf::from(runtime_dir).join("btwd/control.sock")
+}
+
+struct Client {
+    writer: UnixStream,
+    reader: BufReader<UnixStream>,
+}
+
+impl Client {
+    fn connect() -> Result<Self, String> {
+        let path = socket_path();
+        let writer = UnixStream::connect(&path).map_err(|e| format!("cannot connect to {} (is btwd running?): {}", path.display(), e))?;
+        let reader = BufReader::new(writer.try_clone().map_err(|e| e.to_string())?);
+        Ok(Self { writer, reader })
+    }
+
+    fn ask(&mut self, request: Value) -> Result<Value, String> {
+        writeln!(self.writer, "{}", request).map_err(|e| format!("send failed: {}", e))?;
+        let mut line = String::new();
+        self.reader.read_line(&mut line).map_err(|e| format!("read failed: {}", e))?;
+        if line.is_empty() {
+            return Err("btwd closed the connection".into());
+        }
+        serde_json::from_str(&line).map_err(|e| format!("bad response from btwd: {}", e))
+    }
+}
+
+fn run(args: &[String]) -> Result<Value, String> {
+    let mut client = Client::connect()?;
+    match args.first().map(String::as_str) {
+        Some("say") if args.len() > 1 => client.ask(json!({"op": "say", "text": args[1..].join(" ")})),
+        Some("status" | "state") if args.len() == 1 => client.ask(json!({"op": "state"})),
+        Some(op @ ("listen" | "reload" | "stop")) if args.len() == 1 => client.ask(json!({"op": op})),
+        Some(op @ ("confirm" | "cancel")) if args.len() == 2 => client.ask(json!({"op": op, "request_id": args[1]})),
+        // The daemon only accepts the live request id, so fetch it first;
+        // a confirmation that changes in between is rejected, not confirmed.
+        Some(op @ ("confirm" | "cancel")) if args.len() == 1 => {
+            let state = client.ask(json!({"op": "state"}))?;
+            let Some(request_id) = state["pending"]["request_id"].as_str() else {
+                return Err("no command is pending".into());
+            };
+            client.ask(json!({"op": op, "request_id": request_id}))
+        }
+        _ => Err(USAGE.into()),
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(response) => {
+            println!("{}", serde_json::to_string_pretty(&response).unwrap_or_else(|_| response.to_string()));
+            if response["ok"].as_bool() == Some(true) {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+        Err(e) => {
+            eprintln!("btwctl: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Cancellation flag for one wake → answer interaction.
+///
+/// A fresh token is minted on every wake; clones are handed to the listening
+/// notification and to any background answer thread, so an abort reaches
+/// whichever stage is currently running.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    flag: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.flag.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_canceled(&self) -> bool {
+        self.flag.load(Ordering::SeqCst)
+    }
+}
+
+/// Spool file an external tool can write to control the daemon
+/// (same convention as the confirmation spool).
+pub fn control_spool_path() -> PathBuf {
+    let runtime_dir = std::env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".to_string());
+    PathBuf::from(runtime_dir).join("btwd-control")
+}
+
+/// Requests accepted on the control spool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlRequest {
+    /// Abort the current interaction.
+    Abort,
+    /// Confirm the pending command (same as the notification's Yes).
+    Confirm,
+    /// Reject the pending command (same as the notification's No).
+    Deny,
+    /// Re-read config.toml and apply new wake word sensitivities (same as SIGUSR1).
+    ReloadWake,
+}
+
+/// Accepts either a bare word (`abort`) or `{"op":"abort"}`.
+pub fn parse_control_request(raw: &str) -> Option<ControlRequest> {
+    let raw = raw.trim();
+    let op = match serde_json::from_str::<serde_json::Value>(raw) {
+        Ok(v) => v.get("op").and_then(|op| op.as_str()).map(|s| s.to_string())?,
+        Err(_) => raw.to_string(),
+    };
+    match op.to_ascii_lowercase().as_str() {
+        "abort" => Some(ControlRequest::Abort),
+        "confirm" | "yes" => Some(ControlRequest::Confirm),
+        "deny" | "no" => Some(ControlRequest::Deny),
+        "reload_wake" => Some(ControlRequest::ReloadWake),
+        _ => None,
+    }
+}
+
+/// Consume a pending control request from the spool, if any.
+pub fn take_control_request() -> Option<ControlRequest> {
+    let path = control_spool_path();
+    let raw = std::fs::read_to_string(&path).ok()?;
+    let _ = std::fs::remove_file(&path);
+    let req = parse_control_request(&raw);
+    if req.is_none() {
+        log::warn!("control: ignoring unknown request '{}'", raw.trim());
+    }
+    req
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clones_share_the_flag() {
+        let token = CancelToken::new();
+        let clone = token.clone();
+        assert!(!clone.is_canceled());
+        token.cancel();
+        assert!(clone.is_canceled());
+        // A new interaction starts clean.
+        assert!(!CancelToken::new().is_canceled());
+    }
+
+    #[test]
+    fn parses_control_requests() {
+        assert_eq!(parse_control_request("abort\n"), Some(ControlRequest::Abort));
+        assert_eq!(parse_control_request(r#"{"op":"abort"}"#), Some(ControlRequest::Abort));
+        assert_eq!(parse_control_request(r#" { "op": "abort" } "#), Some(ControlRequest::Abort));
+        assert_eq!(parse_control_request(r#"{"op":"confirm"}"#), Some(ControlRequest::Confirm));
+        assert_eq!(parse_control_request("no"), Some(ControlRequest::Deny));
+        assert_eq!(parse_control_request(r#"{"op":"reload_wake"}"#), Some(ControlRequest::ReloadWake));
+        assert_eq!(parse_control_request(r#"{"op":"status"}"#), None);
+        assert_eq!(parse_control_request(r#"{"verb":"abort"}"#), None);
+        assert_eq!(parse_control_request(""), None);
+    }
+}
@@ -2,7 +2,10 @@ use serde::Deserialize;
 
 /// Minimal view of a command from the authoritative commands.json
 #[derive(Debug, Deserialize)]
-pub struct Command { pub id: String }
+pub struct Command {
+    #[allow(dead_code)]
+    pub id: String,
+}
 
 /// Parse commands.json for counting/logging purposes
 pub fn parse_commands_json(s: &str) -> Result<Vec<Command>, String> {
@@ -0,0 +1,374 @@
+use crate::error::{BtwError, Result};
+use crate::executor::{check_param_spec, validate_template, ParamDecl};
+use serde_json::Value;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+/// Every key a commands.json entry may carry, with its JSON type. The intent
+/// and executor loaders each read a subset of these, so neither can deny
+/// unknown fields on its own; anything else is rejected here instead.
+const FIELDS: &[(&str, Kind)] = &[
+    ("id", Kind::String),
+    ("description", Kind::String),
+    ("examples", Kind::Strings),
+    ("dangerous", Kind::Bool),
+    ("parameters", Kind::ParamMap),
+    ("shell_command_template", Kind::String),
+    ("category", Kind::String),
+    ("priority", Kind::Integer),
+    ("alias_of", Kind::String),
+    ("env_allowlist", Kind::Strings),
+    ("confirmation", Kind::String),
+];
+
+/// Optional fields that may also be `null`.
+const NULLABLE: &[&str] = &["alias_of", "env_allowlist", "confirmation"];
+
+/// Keys of a parameter declared as an object rather than a spec string.
+const PARAM_FIELDS: &[&str] = &["type", "min", "max", "values", "max_length", "default", "clamp", "pattern"];
+
+/// Values of a command's `confirmation`.
+const CONFIRMATION_POLICIES: &[&str] = &["always", "dangerous_only", "never"];
+
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    String,
+    Strings,
+    Bool,
+    Integer,
+    ParamMap,
+}
+
+impl Kind {
+    fn matches(self, v: &Value) -> bool {
+        match self {
+            Kind::String => v.is_string(),
+            Kind::Strings => v.as_array().is_some_and(|a| a.iter().all(Value::is_string)),
+            Kind::Bool => v.is_boolean(),
+            Kind::Integer => v.is_i64(),
+            Kind::ParamMap => v.as_object().is_some_and(|o| o.values().all(|p| p.is_string() || p.is_object())),
+        }
+    }
+
+    fn expected(self) -> &'static str {
+        match self {
+            Kind::String => "a string",
+            Kind::Strings => "an array of strings",
+            Kind::Bool => "true or false",
+            Kind::Integer => "a whole number",
+            Kind::ParamMap => "an object of name -> spec strings or objects",
+        }
+    }
+}
+
+fn valid_id(id: &str) -> bool {
+    !id.is_empty() && id.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_')
+}
+
+/// The known field `key` is most likely a typo of, if any.
+fn suggestion(key: &str) -> Option<&'static str> {
+    FIELDS.iter().map(|(name, _)| *name).find(|name| edit_distance(key, name) <= (name.len() / 3).min(2))
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diag = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let next = (diag + usize::from(ca != *cb)).min(row[j] + 1).min(row[j + 1] + 1);
+            diag = row[j + 1];
+            row[j + 1] = next;
+        }
+    }
+    row[b.len()]
+}
+
+/// `{name}` placeholders in a template, in order.
+fn placeholders(tpl: &str) -> std::result::Result<Vec<&str>, String> {
+    let mut out = Vec::new();
+    let mut rest = tpl;
+    while let Some(open) = rest.find('{') {
+        let Some(close) = rest[open..].find('}') else {
+            return Err("unterminated '{' in 'shell_command_template'".into());
+        };
+        out.push(&rest[open + 1..open + close]);
+        rest = &rest[open + close + 1..];
+    }
+    Ok(out)
+}
+
+/// A `parameters` value: a spec string, or an object with the same
+/// constraints spelled out and an optional slot `pattern`. Values of
+/// another type are reported by the field check.
+fn check_param(name: &str, spec: &Value) -> std::result::Result<(), String> {
+    let decl = match spec {
+        Value::String(spec) => return check_param_spec(name, spec),
+        Value::Object(fields) => {
+            if let Some(key) = fields.keys().find(|k| !PARAM_FIELDS.contains(&k.as_str())) {
+                return Err(format!("parameter '{}': unknown key '{}' (expected one of {})", name, key, PARAM_FIELDS.join(", ")));
+            }
+            serde_json::from_value::<ParamDecl>(spec.clone())
+                .map_err(|_| format!("parameter '{}': needs a string 'type', whole-number 'min'/'max', a string array 'values', a positive 'max_length', boolean 'clamp' and string 'pattern'", name))?
+        }
+        _ => return Ok(()),
+    };
+    check_param_spec(name, &decl.spec())?;
+    match decl.pattern() {
+        Some(pattern) => crate::intent::compile_slot_pattern(pattern).map(drop).map_err(|e| format!("parameter '{}': {}", name, e)),
+        None => Ok(()),
+    }
+}
+
+/// Check a parsed commands.json document.
+///
+/// Hard violations (malformed, duplicate or unknown keys, wrong types,
+/// missing templates, placeholders without a parameter) are all collected
+/// into the `Err`, one line each, naming the entry's index and id; they
+/// should stop the load. Soft ones (empty examples, unused parameters,
+/// unsafe templates, which the executor skips) come back as warnings.
+pub fn check(doc: &Value) -> std::result::Result<Vec<String>, String> {
+    let entries = doc.as_array().ok_or("expected a JSON array of commands")?;
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    for (i, entry) in entries.iter().enumerate() {
+        let Some(obj) = entry.as_object() else {
+            errors.push(format!("entry {}: expected an object", i));
+            continue;
+        };
+        let id = match obj.get("id") {
+            Some(Value::String(id)) => id.as_str(),
+            Some(_) => {
+                errors.push(format!("entry {}: 'id' must be a string", i));
+                continue;
+            }
+            None => {
+                errors.push(format!("entry {}: missing 'id'", i));
+                continue;
+            }
+        };
+        let at = format!("entry {} ('{}')", i, id);
+        if !valid_id(id) {
+            errors.push(format!("{}: id must match [a-z0-9_]+", at));
+        }
+        match seen.entry(id) {
+            Entry::Occupied(first) => errors.push(format!("entries {} and {}: duplicate command id '{}'", first.get(), i, id)),
+            Entry::Vacant(slot) => {
+                slot.insert(i);
+            }
+        }
+
+        for (key, value) in obj {
+            match FIELDS.iter().find(|(name, _)| *name == key.as_str()) {
+                None => match suggestion(key) {
+                    Some(known) => errors.push(format!("{}: unknown field '{}' (did you mean '{}'?)", at, key, known)),
+                    None => errors.push(format!("{}: unknown field '{}'", at, key)),
+                },
+                Some((_, kind)) if !kind.matches(value) && (!value.is_null() || !NULLABLE.contains(&key.as_str())) => {
+                    errors.push(format!("{}: '{}' must be {}", at, key, kind.expected()));
+                }
+                _ => {}
+            }
+        }
+
+        let params = obj.get("parameters").and_then(Value::as_object);
+        for (name, spec) in params.into_iter().flatten() {
+            if let Err(msg) = check_param(name, spec) {
+                errors.push(format!("{}: {}", at, msg));
+            }
+        }
+        match obj.get("shell_command_template") {
+            Some(Value::String(tpl)) => {
+                if let Err(msg) = validate_template(tpl) {
+                    warnings.push(format!("{}: {} (command will be skipped)", at, msg));
+                }
+                match placeholders(tpl) {
+                    Ok(used) => {
+                        for name in used.iter().filter(|name| !params.is_some_and(|p| p.contains_key(**name))) {
+                            errors.push(format!("{}: template placeholder '{{{}}}' is not declared in 'parameters'", at, name));
+                        }
+                        for name in params.into_iter().flat_map(|p| p.keys()).filter(|name| !used.contains(&name.as_str())) {
+                            warnings.push(format!("{}: parameter '{}' is not used by the template", at, name));
+                        }
+                    }
+                    Err(msg) => errors.push(format!("{}: {}", at, msg)),
+                }
+            }
+            // The wrong type is already reported above.
+            Some(_) => {}
+            None => errors.push(format!("{}: missing 'shell_command_template'", at)),
+        }
+        if let Some(Value::String(policy)) = obj.get("confirmation") {
+            if !CONFIRMATION_POLICIES.contains(&policy.as_str()) {
+                errors.push(format!("{}: 'confirmation' must be one of {}", at, CONFIRMATION_POLICIES.join("|")));
+            } else if policy == "never" && obj.get("dangerous") == Some(&Value::Bool(true)) {
+                warnings.push(format!("{}: dangerous commands always ask; 'confirmation: never' has no effect", at));
+            }
+        }
+        if let Some(Value::Array(examples)) = obj.get("examples") {
+            let empty = examples.iter().filter(|e| e.as_str().is_some_and(|s| s.trim().is_empty())).count();
+            if empty > 0 {
+                warnings.push(format!("{}: {} empty example(s)", at, empty));
+            }
+        }
+    }
+    if errors.is_empty() {
+        Ok(warnings)
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+/// Ids only one side has: commands the router can recognize but the
+/// executor won't run (e.g. its template was rejected), and runnable ones
+/// nothing routes to. Each mismatch is one message, sorted.
+pub fn cross_check<'a>(intent_ids: impl IntoIterator<Item = &'a str>, exec_ids: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let intent: BTreeSet<&str> = intent_ids.into_iter().collect();
+    let exec: BTreeSet<&str> = exec_ids.into_iter().collect();
+    let unrunnable = intent.difference(&exec).map(|id| format!("command '{}' can be recognized but is not in the executor's allow-list", id));
+    let unreachable = exec.difference(&intent).map(|id| format!("command '{}' can run but the intent router never emits it", id));
+    unrunnable.chain(unreachable).collect()
+}
+
+/// [`check`] the commands.json text read from `path`, mapping hard
+/// violations to [`BtwError::ParseError`].
+pub fn validate(path: &Path, s: &str) -> Result<Vec<String>> {
+    let doc: Value = serde_json::from_str(s)
+        .map_err(|e| BtwError::ParseError { path: path.to_path_buf(), kind: "json", message: e.to_string(), cause: Some(Box::new(e)) })?;
+    check(&doc).map_err(|message| BtwError::ParseError { path: path.to_path_buf(), kind: "json", message, cause: None })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn cmd(id: &str) -> Value {
+        json!({"id": id, "examples": ["do it"], "shell_command_template": "/usr/bin/true"})
+    }
+
+    #[test]
+    fn clean_file_has_no_warnings() {
+        let doc = Value::Array(vec![cmd("volume_up"), cmd("wifi_off2")]);
+        assert_eq!(check(&doc), Ok(vec![]));
+        let example = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/example.commands.json")).unwrap();
+        assert_eq!(validate(Path::new("example.commands.json"), &example).unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn soft_violations_are_warnings() {
+        let doc = json!([
+            {"id": "a", "examples": ["x"], "parameters": {"level": "int 0-10"}, "shell_command_template": "/usr/bin/true"},
+            {"id": "b", "examples": ["ok", " "], "shell_command_template": "/usr/bin/true; rm"},
+        ]);
+        let warnings = check(&doc).unwrap();
+        assert_eq!(
+            warnings,
+            [
+                "entry 0 ('a'): parameter 'level' is not used by the template",
+                "entry 1 ('b'): template contains unsafe shell constructs (command will be skipped)",
+                "entry 1 ('b'): 1 empty example(s)",
+            ]
+        );
+    }
+
+    #[test]
+    fn hard_violations_fail() {
+        assert!(check(&json!([cmd("Volume-Up")])).unwrap_err().contains("[a-z0-9_]+"));
+        assert!(check(&json!([cmd("")])).is_err());
+        assert!(check(&json!([{"id": "a"}])).unwrap_err().contains("missing 'shell_command_template'"));
+        assert!(check(&json!({"id": "a"})).is_err());
+        let err = validate(Path::new("c.json"), r#"[{"id": "a b", "shell_command_template": "x"}]"#).unwrap_err();
+        assert!(matches!(err, BtwError::ParseError { kind: "json", .. }));
+    }
+
+    #[test]
+    fn duplicate_ids_name_both_entries() {
+        let err = check(&json!([cmd("a"), cmd("b"), cmd("a"), cmd("a")])).unwrap_err();
+        assert_eq!(err, "entries 0 and 2: duplicate command id 'a'\nentries 0 and 3: duplicate command id 'a'");
+    }
+
+    #[test]
+    fn unknown_fields_and_wrong_types_are_errors() {
+        let doc = json!([
+            cmd("ok"),
+            {"id": "reboot", "dangerouse": true, "exapmles": ["reboot"], "colour": "red", "shell_command_template": "systemctl reboot"},
+            {"id": "mute", "dangerous": "yes", "priority": 1.5, "examples": ["mute", 3], "alias_of": null, "shell_command_template": "pamixer -t"},
+        ]);
+        let lines: Vec<String> = check(&doc).unwrap_err().lines().map(str::to_string).collect();
+        assert_eq!(
+            lines,
+            [
+                "entry 1 ('reboot'): unknown field 'colour'",
+                "entry 1 ('reboot'): unknown field 'dangerouse' (did you mean 'dangerous'?)",
+                "entry 1 ('reboot'): unknown field 'exapmles' (did you mean 'examples'?)",
+                "entry 2 ('mute'): 'dangerous' must be true or false",
+                "entry 2 ('mute'): 'examples' must be an array of strings",
+                "entry 2 ('mute'): 'priority' must be a whole number",
+            ]
+        );
+    }
+
+    #[test]
+    fn object_parameters_are_checked_like_specs() {
+        let doc = json!([
+            {"id": "fan_set", "parameters": {"level": {"type": "int", "min": 0, "max": 5, "pattern": "to (\\d+)"}, "mode": {"type": "enum", "values": ["auto", "quiet"]}}, "shell_command_template": "fanctl {mode} {level}"},
+            {"id": "fan_up", "parameters": {"delta": {"type": "percent"}}, "shell_command_template": "fanctl +{delta}"},
+            {"id": "fan_down", "parameters": {"delta": {"type": "int", "pattern": "by (\\d+"}}, "shell_command_template": "fanctl -{delta}"},
+            {"id": "fan_off", "parameters": {"delta": {"type": "int", "step": 2}, "n": 5}, "shell_command_template": "fanctl 0"},
+        ]);
+        let lines: Vec<String> = check(&doc).unwrap_err().lines().map(str::to_string).collect();
+        assert_eq!(lines.len(), 4, "{:?}", lines);
+        assert_eq!(lines[0], "entry 1 ('fan_up'): unsupported param spec for 'delta': 'percent'");
+        assert_eq!(lines[1], "entry 2 ('fan_down'): parameter 'delta': invalid pattern: unclosed group");
+        assert_eq!(lines[2], "entry 3 ('fan_off'): 'parameters' must be an object of name -> spec strings or objects");
+        assert!(lines[3].starts_with("entry 3 ('fan_off'): parameter 'delta': unknown key 'step'"));
+    }
+
+    #[test]
+    fn placeholders_must_be_declared_parameters() {
+        let doc = json!([
+            {"id": "volume_set", "parameters": {"value": "int 0-100"}, "shell_command_template": "pamixer --set-volume {value}"},
+            {"id": "volume_up", "parameters": {"delta": "int 1-100"}, "shell_command_template": "pamixer -i {step}"},
+            {"id": "brightness_set", "parameters": {"value": "percent"}, "shell_command_template": "brightnessctl set {value"},
+        ]);
+        let lines: Vec<String> = check(&doc).unwrap_err().lines().map(str::to_string).collect();
+        assert_eq!(
+            lines,
+            [
+                "entry 1 ('volume_up'): template placeholder '{step}' is not declared in 'parameters'",
+                "entry 2 ('brightness_set'): unsupported param spec for 'value': 'percent'",
+                "entry 2 ('brightness_set'): unterminated '{' in 'shell_command_template'",
+            ]
+        );
+        assert_eq!(placeholders("x {a} {b}"), Ok(vec!["a", "b"]));
+    }
+
+    #[test]
+    fn confirmation_must_be_a_known_policy() {
+        let doc = json!([
+            {"id": "volume_up", "confirmation": "never", "shell_command_template": "pamixer -i 5"},
+            {"id": "shutdown", "dangerous": true, "confirmation": "never", "shell_command_template": "systemctl poweroff"},
+        ]);
+        assert_eq!(check(&doc), Ok(vec!["entry 1 ('shutdown'): dangerous commands always ask; 'confirmation: never' has no effect".to_string()]));
+        let doc = json!([{"id": "mute", "confirmation": "sometimes", "shell_command_template": "pamixer -t"}]);
+        assert_eq!(check(&doc), Err("entry 0 ('mute'): 'confirmation' must be one of always|dangerous_only|never".to_string()));
+    }
+
+    #[test]
+    fn cross_check_lists_ids_on_one_side_only() {
+        let mismatches = cross_check(["lock_screen", "volume_up", "wifi_off"], ["volume_up", "system_reboot"]);
+        assert_eq!(
+            mismatches,
+            [
+                "command 'lock_screen' can be recognized but is not in the executor's allow-list",
+                "command 'wifi_off' can be recognized but is not in the executor's allow-list",
+                "command 'system_reboot' can run but the intent router never emits it",
+            ]
+        );
+        assert!(cross_check(["a"], ["a"]).is_empty());
+    }
+}
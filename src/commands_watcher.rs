@@ -0,0 +1,101 @@
+use notify::Watcher;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Bumped after every successful commands.json reload; shown on `/health`.
+pub static COMMANDS_VERSION: AtomicU64 = AtomicU64::new(0);
+
+pub fn version() -> u64 {
+    COMMANDS_VERSION.load(Ordering::SeqCst)
+}
+
+pub fn bump_version() -> u64 {
+    COMMANDS_VERSION.fetch_add(1, Ordering::SeqCst) + 1
+}
+
+/// Editors tend to write a file in several steps; wait for this much quiet
+/// before reloading so we don't parse a half-written file.
+const SETTLE: Duration = Duration::from_millis(300);
+
+/// Watches the commands file for changes. The main loop polls
+/// [`CommandsWatcher::take_changed`] and does the reload itself, so the
+/// router and executor are swapped between frames and never seen half-updated.
+pub struct CommandsWatcher {
+    _watcher: notify::RecommendedWatcher,
+    last_event: Arc<Mutex<Option<Instant>>>,
+}
+
+impl CommandsWatcher {
+    /// Watch `path`. Its directory is watched rather than the file, since
+    /// many editors save by writing a new file and renaming it over the old one.
+    pub fn start(path: &Path) -> notify::Result<Self> {
+        let file_name = path.file_name().map(|n| n.to_os_string());
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_else(|| PathBuf::from("."));
+        let last_event = Arc::new(Mutex::new(None));
+        let slot = last_event.clone();
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| match res {
+            Ok(ev) => {
+                let relevant = matches!(ev.kind, notify::EventKind::Create(_) | notify::EventKind::Modify(_))
+                    && ev.paths.iter().any(|p| p.file_name().map(|n| n.to_os_string()) == file_name);
+                if relevant {
+                    *slot.lock().unwrap_or_else(|p| p.into_inner()) = Some(Instant::now());
+                }
+            }
+            Err(e) => log::warn!("commands: watch error: {}", e),
+        })?;
+        watcher.watch(&dir, notify::RecursiveMode::NonRecursive)?;
+        Ok(Self { _watcher: watcher, last_event })
+    }
+
+    /// True once per burst of changes, after the file has settled.
+    pub fn take_changed(&self) -> bool {
+        let mut last = self.last_event.lock().unwrap_or_else(|p| p.into_inner());
+        if settled(*last, Instant::now()) {
+            *last = None;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+fn settled(last_event: Option<Instant>, now: Instant) -> bool {
+    last_event.is_some_and(|t| now.saturating_duration_since(t) >= SETTLE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waits_for_writes_to_settle() {
+        let now = Instant::now();
+        assert!(!settled(None, now));
+        assert!(!settled(Some(now), now + Duration::from_millis(100)));
+        assert!(settled(Some(now), now + SETTLE));
+    }
+
+    #[test]
+    fn detects_a_rewrite_of_the_watched_file() {
+        let dir = std::env::temp_dir().join(format!("btwd-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("commands.json");
+        std::fs::write(&path, "[]").unwrap();
+        let watcher = CommandsWatcher::start(&path).unwrap();
+
+        std::fs::write(dir.join("other.json"), "[]").unwrap();
+        std::thread::sleep(SETTLE * 2);
+        assert!(!watcher.take_changed());
+
+        std::fs::write(&path, "[ ]").unwrap();
+        let started = Instant::now();
+        while !watcher.take_changed() {
+            assert!(started.elapsed() < Duration::from_secs(5), "no change seen");
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert!(!watcher.take_changed());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,4 +1,5 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 
 /// Top-level configuration loaded from `config.toml`.
 ///
@@ -6,8 +7,10 @@ use serde::Deserialize;
 #[derive(Debug, Deserialize)]
 pub struct Config {
     /// Optional human-readable name for the daemon instance.
+    #[allow(dead_code)]
     pub name: Option<String>,
     /// Optional description for documentation purposes.
+    #[allow(dead_code)]
     pub description: Option<String>,
     /// Wake word configuration (required)
     pub wake_word: WakeWord,
@@ -32,6 +35,21 @@ pub struct Config {
     /// LLM provider configuration
     #[serde(default)]
     pub llm: LlmCfg,
+    /// Speech recognition options forwarded to the ML worker
+    #[serde(default)]
+    pub asr: AsrCfg,
+    /// Question / web-query keyword lists
+    #[serde(default)]
+    pub decision: DecisionCfg,
+    /// Log verbosity
+    #[serde(default)]
+    pub logging: LoggingCfg,
+    /// Microphone selection and reconnect behavior
+    #[serde(default)]
+    pub audio: AudioCfg,
+    /// Local JSON health endpoint
+    #[serde(default)]
+    pub health: HealthCfg,
 }
 
 impl Config {
@@ -39,26 +57,415 @@ impl Config {
     pub fn from_toml_str(s: &str) -> Result<Self, String> {
         toml::from_str::<Config>(s).map_err(|e| e.to_string())
     }
+
+    /// Every out-of-domain value, all at once. The daemon refuses to start
+    /// (and `--check-config` exits non-zero) when this is non-empty.
+    pub fn validate(&self) -> Vec<ConfigError> {
+        let mut v = Violations::default();
+
+        let wake = &self.wake_word;
+        v.unit("wake_word.sensitivity", wake.sensitivity);
+        // Push-to-talk never loads Porcupine, so its files may be missing.
+        let engine = !wake.push_to_talk;
+        if engine && wake.language_models.is_empty() {
+            v.file("wake_word.model_path", &wake.model_path);
+            if wake.keywords.is_empty() {
+                if wake.ppn_path.is_empty() {
+                    v.push("wake_word.ppn_path", "is empty; set it or add [[wake_word.keywords]]".into());
+                } else {
+                    v.file("wake_word.ppn_path", &wake.ppn_path);
+                }
+            }
+            for (i, k) in wake.keywords.iter().enumerate() {
+                v.file(&format!("wake_word.keywords[{}].ppn_path", i), &k.ppn_path);
+                if let Some(s) = k.sensitivity {
+                    v.unit(&format!("wake_word.keywords[{}].sensitivity", i), s);
+                }
+            }
+        }
+        for (i, m) in wake.language_models.iter().enumerate().filter(|_| engine) {
+            let key = |field: &str| format!("wake_word.language_models[{}].{}", i, field);
+            if m.language.trim().is_empty() {
+                v.push(&key("language"), "is empty".into());
+            }
+            v.file(&key("model_path"), &m.model_path);
+            v.file(&key("ppn_path"), &m.ppn_path);
+            v.unit(&key("sensitivity"), m.sensitivity);
+        }
+        for (i, p) in wake.sensitivity_profiles.iter().enumerate() {
+            let key = |field: &str| format!("wake_word.sensitivity_profiles[{}].{}", i, field);
+            if p.start_hour > 23 {
+                v.push(&key("start_hour"), format!("{} is out of range; expected an hour from 0 to 23", p.start_hour));
+            }
+            if p.end_hour > 24 {
+                v.push(&key("end_hour"), format!("{} is out of range; expected an hour from 0 to 24", p.end_hour));
+            }
+            v.unit(&key("sensitivity"), p.sensitivity);
+        }
+
+        let speech = &self.speech;
+        v.unit("speech.silence_threshold", speech.silence_threshold);
+        v.nonzero("speech.silence_duration_ms", speech.silence_duration_ms as u64);
+        v.nonzero("speech.max_utterance_seconds", speech.max_utterance_seconds as u64);
+        if let Some(ms) = speech.hangover_ms {
+            v.nonzero("speech.hangover_ms", ms as u64);
+        }
+        if !(0..=3).contains(&speech.vad_mode) {
+            v.push("speech.vad_mode", format!("{} is out of range; expected 0, 1, 2 or 3", speech.vad_mode));
+        }
+        if ![10, 20, 30].contains(&speech.vad_window_ms) {
+            v.push("speech.vad_window_ms", format!("{} is not supported; expected 10, 20 or 30", speech.vad_window_ms));
+        }
+        v.threshold("speech.vad_vote_ratio", speech.vad_vote_ratio);
+        v.unit("speech.noise_threshold", speech.noise_threshold);
+        if !(0.0..1.0).contains(&speech.pre_emphasis_coefficient) {
+            v.push("speech.pre_emphasis_coefficient", format!("{} is out of range; expected a value in [0, 1)", speech.pre_emphasis_coefficient));
+        }
+
+        v.threshold("intent.deterministic_threshold", self.intent.deterministic_threshold);
+        v.threshold("intent.llm_fallback_threshold", self.intent.llm_fallback_threshold);
+        v.threshold("intent.embedding_threshold", self.intent.embedding_threshold);
+        v.unit("intent.embedding_weight", self.intent.embedding_weight);
+        let mut overridden = std::collections::HashSet::new();
+        for (i, o) in self.intent.command_overrides.iter().enumerate() {
+            let key = |field: &str| format!("intent.command_overrides[{}].{}", i, field);
+            if o.id.trim().is_empty() {
+                v.push(&key("id"), "is empty".into());
+            } else if !overridden.insert(o.id.as_str()) {
+                v.push(&key("id"), format!("'{}' is overridden more than once", o.id));
+            }
+            v.threshold(&key("deterministic_threshold"), o.deterministic_threshold);
+        }
+        if !(0.0..1.0).contains(&self.decision.clarify_margin) {
+            v.push("decision.clarify_margin", format!("{} is out of range; expected a value in [0, 1)", self.decision.clarify_margin));
+        }
+
+        v.nonzero("execution.confirmation_timeout_seconds", self.execution.confirmation_timeout_seconds);
+        v.nonzero("ui.osd_timeout_ms", self.ui.osd_timeout_ms);
+        v.nonzero("search.timeout_ms", self.search.timeout_ms);
+        v.nonzero("audio.hotplug_timeout_ms", self.audio.hotplug_timeout_ms);
+
+        let out = &self.speech_output;
+        v.one_of("speech_output.provider", &out.provider, &["groq", "espeak", "piper"]);
+        v.one_of("speech_output.format", &out.format, &["wav", "mp3"]);
+        if out.rate.is_nan() || out.rate <= 0.0 {
+            v.push("speech_output.rate", format!("{} must be greater than 0", out.rate));
+        }
+        v.one_of("llm.provider", &self.llm.provider, &["groq", "mistral", "openai_compat"]);
+        if self.llm.provider == "openai_compat" {
+            let url = self.llm.base_url.trim();
+            if !(url.starts_with("http://") || url.starts_with("https://")) {
+                v.push("llm.base_url", format!("{:?} is not an http(s) URL; required with provider = \"openai_compat\"", self.llm.base_url));
+            }
+            if self.llm.model.trim().is_empty() {
+                v.push("llm.model", "is empty; required with provider = \"openai_compat\"".into());
+            }
+        }
+        if self.search.enabled && matches!(self.search.provider.trim().to_ascii_lowercase().as_str(), "searxng" | "searx") {
+            let url = self.search.base_url.trim();
+            if !(url.starts_with("http://") || url.starts_with("https://")) {
+                v.push("search.base_url", format!("{:?} is not an http(s) URL; required with provider = \"searxng\"", self.search.base_url));
+            }
+        }
+        v.nonzero("llm.classify_timeout_ms", self.llm.classify_timeout_ms);
+        v.nonzero("llm.answer_timeout_ms", self.llm.answer_timeout_ms);
+        v.0
+    }
+
+    /// Non-fatal sanity checks; returns human-readable warnings.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let out = &self.speech_output;
+        if out.enabled {
+            match out.provider.to_lowercase().as_str() {
+                "espeak" if !binary_in_path("espeak-ng") => {
+                    warnings.push("speech_output.provider = \"espeak\" but espeak-ng is not in PATH".into());
+                }
+                "piper" => {
+                    if !binary_in_path("piper") {
+                        warnings.push("speech_output.provider = \"piper\" but piper is not in PATH".into());
+                    }
+                    match out.local_model_path.as_deref().filter(|p| !p.trim().is_empty()) {
+                        None => warnings.push("speech_output.provider = \"piper\" requires speech_output.local_model_path".into()),
+                        Some(p) if !std::path::Path::new(p).is_file() => {
+                            warnings.push(format!("speech_output.local_model_path does not exist: {}", p))
+                        }
+                        Some(_) => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+        if !matches!(out.playback.trim().to_ascii_lowercase().as_str(), "native" | "external") {
+            warnings.push(format!("speech_output.playback = {:?} is not one of native|external; using native", out.playback));
+        }
+        if self.asr.is_in_process() {
+            match self.asr.model_path.as_deref().filter(|p| !p.trim().is_empty()) {
+                None => warnings.push(format!("asr.engine = {:?} requires asr.model_path", self.asr.engine)),
+                Some(p) if !std::path::Path::new(p).is_file() => warnings.push(format!("asr.model_path does not exist: {}", p)),
+                Some(_) => {}
+            }
+        }
+        if !matches!(self.ui.search_engine.trim().to_ascii_lowercase().as_str(), "google" | "duckduckgo" | "ddg" | "brave") {
+            warnings.push(format!("ui.search_engine = {:?} is not one of google|duckduckgo|brave; using google", self.ui.search_engine));
+        }
+        if !matches!(self.execution.pending_policy.trim().to_ascii_lowercase().as_str(), "reject" | "replace" | "queue") {
+            warnings.push(format!("execution.pending_policy = {:?} is not one of reject|replace|queue; using reject", self.execution.pending_policy));
+        }
+        if !matches!(self.execution.confirmation.trim().to_ascii_lowercase().as_str(), "always" | "dangerous_only" | "never") {
+            warnings.push(format!("execution.confirmation = {:?} is not one of always|dangerous_only|never; using dangerous_only", self.execution.confirmation));
+        }
+        if !matches!(self.ui.notifier.trim().to_ascii_lowercase().as_str(), "dbus" | "subprocess") {
+            warnings.push(format!("ui.notifier = {:?} is not one of dbus|subprocess; using dbus", self.ui.notifier));
+        }
+        for (i, k) in self.wake_word.keywords.iter().enumerate() {
+            if !matches!(k.action.trim().to_ascii_lowercase().as_str(), "listen" | "cancel") {
+                warnings.push(format!("wake_word.keywords[{}].action = {:?} is not one of listen|cancel; using listen", i, k.action));
+            }
+        }
+        let keywords = self.wake_word.keyword_list();
+        if !keywords.is_empty() && keywords.iter().all(|k| k.action.trim().eq_ignore_ascii_case("cancel")) {
+            warnings.push("every wake word keyword has action = \"cancel\"; nothing can start listening".into());
+        }
+        if !matches!(self.intent.self_check.as_str(), "" | "off" | "warn" | "error") {
+            warnings.push(format!("intent.self_check = {:?} is not one of off|warn|error; using warn", self.intent.self_check));
+        }
+        if !matches!(self.intent.consistency_check.as_str(), "" | "off" | "warn" | "error") {
+            warnings.push(format!("intent.consistency_check = {:?} is not one of off|warn|error; using warn", self.intent.consistency_check));
+        }
+        if self.search.enabled && !matches!(self.search.provider.trim().to_ascii_lowercase().as_str(), "" | "tavily" | "searxng" | "searx" | "duckduckgo" | "ddg") {
+            warnings.push(format!("search.provider = {:?} is not one of tavily|searxng|duckduckgo", self.search.provider));
+        }
+        if crate::logging::parse_level(&self.logging.level).is_none() {
+            warnings.push(format!("logging.level = {:?} is not a log level; using info", self.logging.level));
+        }
+        if crate::logging::LogFormat::parse(&self.logging.format).is_none() {
+            warnings.push(format!("logging.format = {:?} is not one of text|json; using text", self.logging.format));
+        }
+        warnings
+    }
+}
+
+/// One invalid config value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError {
+    /// TOML key path, e.g. `wake_word.keywords[1].sensitivity`.
+    pub key: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.key, self.message)
+    }
+}
+
+/// Collects [`ConfigError`]s for [`Config::validate`].
+#[derive(Default)]
+struct Violations(Vec<ConfigError>);
+
+impl Violations {
+    fn push(&mut self, key: &str, message: String) {
+        self.0.push(ConfigError { key: key.to_string(), message });
+    }
+
+    /// [0, 1]
+    fn unit(&mut self, key: &str, value: f32) {
+        if !(0.0..=1.0).contains(&value) {
+            self.push(key, format!("{} is out of range; expected a value in [0, 1]", value));
+        }
+    }
+
+    /// (0, 1]
+    fn threshold(&mut self, key: &str, value: f32) {
+        if value.is_nan() || value <= 0.0 || value > 1.0 {
+            self.push(key, format!("{} is out of range; expected a value in (0, 1]", value));
+        }
+    }
+
+    fn nonzero(&mut self, key: &str, value: u64) {
+        if value == 0 {
+            self.push(key, "must be greater than 0".into());
+        }
+    }
+
+    fn one_of(&mut self, key: &str, value: &str, allowed: &[&str]) {
+        if !allowed.contains(&value.trim().to_ascii_lowercase().as_str()) {
+            self.push(key, format!("{:?} is not one of {}", value, allowed.join("|")));
+        }
+    }
+
+    /// An absolute path to an existing file.
+    fn file(&mut self, key: &str, path: &str) {
+        let p = std::path::Path::new(path);
+        if path.trim().is_empty() {
+            self.push(key, "is empty".into());
+        } else if !p.is_absolute() {
+            self.push(key, format!("{:?} is not an absolute path", path));
+        } else if !p.is_file() {
+            self.push(key, format!("{} does not exist", path));
+        }
+    }
+}
+
+/// True if `name` resolves to an executable file somewhere on `$PATH`.
+pub(crate) fn binary_in_path(name: &str) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    let Some(path) = std::env::var_os("PATH") else { return false };
+    std::env::split_paths(&path).any(|dir| {
+        std::fs::metadata(dir.join(name))
+            .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
+    })
 }
 
 /// Wake word configuration loaded from `config.toml`.
 #[derive(Debug, Deserialize)]
 pub struct WakeWord {
-    /// Absolute path to the .ppn keyword file.
+    /// Absolute path to the .ppn keyword file (single-keyword form).
+    #[serde(default)]
     pub ppn_path: String,
-    /// Absolute path to `porcupine_params.pv` (required for Porcupine 4.0).
+    /// Absolute path to `porcupine_params.pv` (required for Porcupine 4.0
+    /// unless `language_models` is used).
+    #[serde(default)]
     pub model_path: String,
     /// Porcupine device string: "cpu", "cpu:N", "gpu", or "best".
     #[serde(default = "default_porcupine_device")]
     pub device: String,
-    /// Detection sensitivity in [0.0, 1.0].
+    /// Detection sensitivity in [0.0, 1.0]; also the default for `keywords`.
+    #[serde(default = "default_wake_sensitivity")]
+    pub sensitivity: f32,
+    /// Several wake words at once (`[[wake_word.keywords]]`). Takes precedence
+    /// over `ppn_path` when non-empty.
+    #[serde(default)]
+    pub keywords: Vec<KeywordEntry>,
+    /// Sensitivities for parts of the day (`[[wake_word.sensitivity_profiles]]`);
+    /// the first one covering the current local hour applies to every keyword.
+    #[serde(default)]
+    pub sensitivity_profiles: Vec<SensitivityProfile>,
+    /// Wake words in several languages (`[[wake_word.language_models]]`), one
+    /// Porcupine instance each, all fed the same frames. Replaces `model_path`,
+    /// `ppn_path` and `keywords` when non-empty.
+    #[serde(default)]
+    pub language_models: Vec<LanguageModel>,
+    /// Don't load Porcupine at all: listening starts only on SIGUSR1,
+    /// `btwctl listen` or the D-Bus `Listen` method (e.g. a headset button).
+    #[serde(default)]
+    pub push_to_talk: bool,
+}
+
+/// A keyword together with the Porcupine model for its language; `.ppn`
+/// files only load with the model of the language they were trained for.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct LanguageModel {
+    /// Shown in logs, e.g. "en" or "de".
+    pub language: String,
+    /// Absolute path to the language's `porcupine_params*.pv`.
+    pub model_path: String,
+    /// Absolute path to the .ppn keyword file.
+    pub ppn_path: String,
+    /// Detection sensitivity in [0.0, 1.0]; defaults to 0.5.
+    #[serde(default = "default_wake_sensitivity")]
     pub sensitivity: f32,
 }
 
+/// `sensitivity` from `start_hour` up to (not including) `end_hour`, local
+/// time. Wraps past midnight when `end_hour < start_hour` (22 to 7); equal
+/// hours cover the whole day.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct SensitivityProfile {
+    pub start_hour: u8,
+    pub end_hour: u8,
+    pub sensitivity: f32,
+}
+
+impl SensitivityProfile {
+    pub fn covers(&self, hour: u8) -> bool {
+        match self.start_hour.cmp(&self.end_hour) {
+            std::cmp::Ordering::Less => (self.start_hour..self.end_hour).contains(&hour),
+            std::cmp::Ordering::Greater => hour >= self.start_hour || hour < self.end_hour,
+            std::cmp::Ordering::Equal => true,
+        }
+    }
+}
+
+/// One wake word keyword.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct KeywordEntry {
+    /// Absolute path to the .ppn keyword file.
+    pub ppn_path: String,
+    /// Detection sensitivity in [0.0, 1.0]; defaults to `wake_word.sensitivity`.
+    #[serde(default)]
+    pub sensitivity: Option<f32>,
+    /// Name used in logs; defaults to the file stem.
+    #[serde(default)]
+    pub label: Option<String>,
+    /// What the keyword does: "listen" (default) or "cancel" (abort the
+    /// current interaction, drop a pending confirmation and stop speaking).
+    #[serde(default = "default_keyword_action")]
+    pub action: String,
+}
+
+fn default_keyword_action() -> String { "listen".into() }
+
+impl WakeWord {
+    /// Index of the profile in effect at `hour` (0..=23), if any.
+    pub fn active_profile(&self, hour: u8) -> Option<usize> {
+        self.sensitivity_profiles.iter().position(|p| p.covers(hour))
+    }
+
+    /// The sensitivity in effect at `hour`: the active profile's, else `sensitivity`.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn sensitivity_at(&self, hour: u8) -> f32 {
+        self.active_profile(hour).map(|i| self.sensitivity_profiles[i].sensitivity).unwrap_or(self.sensitivity)
+    }
+
+    /// Effective keywords in Porcupine index order, with sensitivity and label
+    /// filled in. The legacy single `ppn_path` becomes a one-entry list; with
+    /// `language_models` there is one entry per language, labelled
+    /// `<stem> [<language>]`.
+    pub fn keyword_list(&self) -> Vec<KeywordEntry> {
+        if !self.language_models.is_empty() {
+            return self
+                .language_models
+                .iter()
+                .map(|m| KeywordEntry {
+                    ppn_path: m.ppn_path.clone(),
+                    sensitivity: Some(m.sensitivity),
+                    label: Some(format!("{} [{}]", file_stem(&m.ppn_path), m.language)),
+                    action: default_keyword_action(),
+                })
+                .collect();
+        }
+        let entries = if self.keywords.is_empty() {
+            if self.ppn_path.is_empty() {
+                return Vec::new();
+            }
+            vec![KeywordEntry { ppn_path: self.ppn_path.clone(), sensitivity: None, label: None, action: default_keyword_action() }]
+        } else {
+            self.keywords.clone()
+        };
+        entries
+            .into_iter()
+            .map(|k| KeywordEntry {
+                sensitivity: Some(k.sensitivity.unwrap_or(self.sensitivity)),
+                label: Some(k.label.clone().unwrap_or_else(|| file_stem(&k.ppn_path))),
+                ppn_path: k.ppn_path,
+                action: k.action,
+            })
+            .collect()
+    }
+}
+
+fn file_stem(path: &str) -> String {
+    std::path::Path::new(path).file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_else(|| path.to_string())
+}
+
 fn default_porcupine_device() -> String { "cpu".into() }
+fn default_wake_sensitivity() -> f32 { 0.5 }
 
 /// Speech recording parameters for end-of-speech detection.
-#[derive(Debug, Deserialize, Default)]
+#[derive(Debug, Deserialize)]
 pub struct Speech {
     /// RMS threshold (0.0..1.0) below which audio is considered silence.
     #[serde(default = "default_silence_threshold")]
@@ -74,24 +481,256 @@ pub struct Speech {
     /// Defaults to 2 (VeryAggressive) to preserve prior behavior.
     #[serde(default = "default_vad_mode")]
     pub vad_mode: i32,
+    /// VAD analysis window: 10, 20 or 30 ms. Frames are re-chunked to fit.
+    #[serde(default = "default_vad_window_ms")]
+    pub vad_window_ms: u32,
+    /// Share of a frame's windows that must be speech for the frame to count as speech.
+    #[serde(default = "default_vad_vote_ratio")]
+    pub vad_vote_ratio: f32,
+    /// Raise the VAD to its most aggressive mode while background noise is high.
+    #[serde(default)]
+    pub adaptive_vad: bool,
+    /// Background RMS (0.0..1.0) above which adaptive VAD kicks in; it backs
+    /// off again below half of this.
+    #[serde(default = "default_noise_threshold")]
+    pub noise_threshold: f32,
+    /// Silence needed to end an utterance; defaults to `silence_duration_ms`.
+    #[serde(default)]
+    pub hangover_ms: Option<u32>,
+    /// Speech required before silence ends the recording; shorter captures
+    /// are dropped without ASR. 0 keeps every capture.
+    #[serde(default)]
+    pub min_speech_ms: u32,
+    /// Pre-emphasis filter coefficient applied to captured audio (about 0.97
+    /// helps bass-heavy mics); 0.0 disables it.
+    #[serde(default)]
+    pub pre_emphasis_coefficient: f32,
+}
+
+impl Speech {
+    pub fn hangover_ms(&self) -> u32 {
+        self.hangover_ms.unwrap_or(self.silence_duration_ms)
+    }
+}
+
+impl Default for Speech {
+    fn default() -> Self {
+        Self {
+            silence_threshold: default_silence_threshold(),
+            silence_duration_ms: default_silence_duration_ms(),
+            max_utterance_seconds: default_max_utterance_seconds(),
+            vad_mode: default_vad_mode(),
+            vad_window_ms: default_vad_window_ms(),
+            vad_vote_ratio: default_vad_vote_ratio(),
+            adaptive_vad: false,
+            noise_threshold: default_noise_threshold(),
+            hangover_ms: None,
+            min_speech_ms: 0,
+            pre_emphasis_coefficient: 0.0,
+        }
+    }
 }
 
 fn default_silence_threshold() -> f32 { 0.01 }
 fn default_silence_duration_ms() -> u32 { 700 }
 fn default_max_utterance_seconds() -> u32 { 30 }
 fn default_vad_mode() -> i32 { 2 }
+fn default_vad_window_ms() -> u32 { 30 }
+fn default_vad_vote_ratio() -> f32 { 0.5 }
+fn default_noise_threshold() -> f32 { 0.02 }
+
+/// How a decision keyword is matched against the normalized transcript.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchMode {
+    StartsWith,
+    Contains,
+}
+
+/// A keyword entry: either `"translate"` (list's default mode) or
+/// `{ text = "today", mode = "contains" }`.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum KeywordSpec {
+    Text(String),
+    Entry {
+        text: String,
+        #[serde(default)]
+        mode: Option<MatchMode>,
+    },
+}
+
+impl KeywordSpec {
+    pub fn text(&self) -> &str {
+        match self {
+            KeywordSpec::Text(t) => t,
+            KeywordSpec::Entry { text, .. } => text,
+        }
+    }
+
+    pub fn mode(&self) -> Option<MatchMode> {
+        match self {
+            KeywordSpec::Text(_) => None,
+            KeywordSpec::Entry { mode, .. } => *mode,
+        }
+    }
+}
+
+/// Extra question starters (default mode `starts_with`) and web-query
+/// keywords (default mode `contains`), added to the built-in lists.
+#[derive(Debug, Deserialize, Clone)]
+pub struct DecisionCfg {
+    #[serde(default)]
+    pub question_starters: Vec<KeywordSpec>,
+    #[serde(default)]
+    pub web_keywords: Vec<KeywordSpec>,
+    /// Use only the configured lists instead of extending the built-in ones.
+    #[serde(default)]
+    pub replace_defaults: bool,
+    /// Ask which command was meant when the top two scores are within this
+    /// margin (0 disables).
+    #[serde(default = "default_clarify_margin")]
+    pub clarify_margin: f32,
+    /// Question/answer pairs remembered for follow-up questions (0 disables).
+    #[serde(default)]
+    pub context_window: usize,
+    /// Seconds after the last answer that the conversation is kept.
+    #[serde(default = "default_context_ttl_secs")]
+    pub context_ttl_secs: u64,
+}
+
+impl Default for DecisionCfg {
+    fn default() -> Self {
+        Self { question_starters: Vec::new(), web_keywords: Vec::new(), replace_defaults: false, clarify_margin: default_clarify_margin(), context_window: 0, context_ttl_secs: default_context_ttl_secs() }
+    }
+}
+
+fn default_clarify_margin() -> f32 { 0.08 }
+fn default_context_ttl_secs() -> u64 { 120 }
+
+/// ASR options sent with every transcription request. Unset fields are
+/// omitted, so the worker keeps its own defaults.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct AsrCfg {
+    /// "python" (the ML worker, default) or "whisper_rs" (in-process,
+    /// needs the `whisper` build feature; "whisper-native" is an alias).
+    /// Also accepted as `backend`.
+    #[serde(default = "default_asr_engine", alias = "backend", skip_serializing)]
+    pub engine: String,
+    /// ggml model file for `engine = "whisper_rs"`.
+    #[serde(default, skip_serializing)]
+    pub model_path: Option<String>,
+    /// Language hint, e.g. "en" or "hi".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Return an English translation instead of a transcript in the spoken language.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub translate: bool,
+    /// Override the worker's ASR model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Extra decoder knobs passed through as-is (temperature, prompt, ...).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub options: BTreeMap<String, serde_json::Value>,
+}
+
+impl Default for AsrCfg {
+    fn default() -> Self { Self { engine: default_asr_engine(), model_path: None, language: None, translate: false, model: None, options: BTreeMap::new() } }
+}
+
+fn default_asr_engine() -> String { "python".into() }
+
+impl AsrCfg {
+    /// Transcription runs in-process (whisper.cpp) rather than in the worker.
+    pub fn is_in_process(&self) -> bool {
+        matches!(self.engine.as_str(), "whisper_rs" | "whisper-native")
+    }
+}
 
 /// Intent routing configuration thresholds
-#[derive(Debug, Deserialize, Default)]
+#[derive(Debug, Deserialize)]
 pub struct IntentCfg {
     #[serde(default = "default_deterministic_threshold")] 
     pub deterministic_threshold: f32,
     #[serde(default = "default_llm_fallback_threshold")] 
     pub llm_fallback_threshold: f32,
+    /// Enable the embedding tier (requires an ML worker with the `embed` capability).
+    #[serde(default)]
+    pub embeddings: bool,
+    /// Minimum cosine similarity for an embedding match to be accepted.
+    #[serde(default = "default_embedding_threshold")]
+    pub embedding_threshold: f32,
+    /// Weight of the cosine similarity when blended with the token score for
+    /// near misses of both tiers; 0 turns blending off.
+    #[serde(default = "default_embedding_weight")]
+    pub embedding_weight: f32,
+    /// At load, check that every example routes to its own command:
+    /// "off", "warn" (default) or "error" (refuse to start).
+    #[serde(default = "default_self_check")]
+    pub self_check: String,
+    /// At load, compare the commands intent routing can emit with the ones
+    /// the executor will run: "off", "warn" (default) or "error" (refuse to load).
+    #[serde(default = "default_self_check")]
+    pub consistency_check: String,
+    /// How long LLM intent classifications are reused for an identical
+    /// transcript, in seconds (0 disables the cache).
+    #[serde(default = "default_llm_cache_ttl_secs")]
+    pub llm_cache_ttl_secs: u64,
+    /// How long "a bit more" / "again" / a bare number may refer back to the
+    /// last executed command, in seconds (0 disables follow-ups).
+    #[serde(default = "default_follow_up_ttl_secs")]
+    pub follow_up_ttl_secs: u64,
+    /// LLM intent classifications allowed per minute; past that the
+    /// transcript is treated as unknown without a network call (0 = unlimited).
+    #[serde(default = "default_llm_rate_limit_per_min")]
+    pub llm_rate_limit_per_min: u32,
+    /// Per-command `deterministic_threshold` (`[[intent.command_overrides]]`),
+    /// e.g. stricter for reboot than for volume.
+    #[serde(default)]
+    pub command_overrides: Vec<CommandOverride>,
+}
+
+/// One `[[intent.command_overrides]]` entry.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct CommandOverride {
+    /// A command id from commands.json.
+    pub id: String,
+    pub deterministic_threshold: f32,
+}
+
+impl IntentCfg {
+    /// `command_overrides` by command id.
+    pub fn threshold_overrides(&self) -> HashMap<String, f32> {
+        self.command_overrides.iter().map(|o| (o.id.clone(), o.deterministic_threshold)).collect()
+    }
+}
+
+impl Default for IntentCfg {
+    fn default() -> Self {
+        Self {
+            deterministic_threshold: default_deterministic_threshold(),
+            llm_fallback_threshold: default_llm_fallback_threshold(),
+            embeddings: false,
+            embedding_threshold: default_embedding_threshold(),
+            embedding_weight: default_embedding_weight(),
+            self_check: default_self_check(),
+            consistency_check: default_self_check(),
+            llm_cache_ttl_secs: default_llm_cache_ttl_secs(),
+            follow_up_ttl_secs: default_follow_up_ttl_secs(),
+            llm_rate_limit_per_min: default_llm_rate_limit_per_min(),
+            command_overrides: Vec::new(),
+        }
+    }
 }
 
 fn default_deterministic_threshold() -> f32 { 0.75 }
 fn default_llm_fallback_threshold() -> f32 { 0.8 }
+fn default_embedding_threshold() -> f32 { 0.82 }
+fn default_embedding_weight() -> f32 { 0.4 }
+fn default_self_check() -> String { "warn".into() }
+fn default_llm_cache_ttl_secs() -> u64 { 300 }
+fn default_follow_up_ttl_secs() -> u64 { 30 }
+fn default_llm_rate_limit_per_min() -> u32 { 20 }
 
 /// Execution configuration
 #[derive(Debug, Deserialize)]
@@ -100,10 +739,34 @@ pub struct ExecutionCfg {
     pub confirmation_timeout_seconds: u64,
     #[serde(default)]
     pub dry_run: bool,
+    /// Accept a spoken "yes"/"no" for pending confirmations.
+    #[serde(default)]
+    pub voice_confirmation: bool,
+    /// A command spoken while another awaits confirmation: "reject", "replace" or "queue".
+    #[serde(default = "default_pending_policy")]
+    pub pending_policy: String,
+    /// Which commands ask first: "always", "dangerous_only" (default) or
+    /// "never" (dangerous commands still ask). Commands can set their own.
+    #[serde(default = "default_confirmation")]
+    pub confirmation: String,
+    /// Environment variables passed to commands without their own `env_allowlist`.
+    #[serde(default = "default_env_allowlist")]
+    pub default_env_allowlist: Vec<String>,
 }
 
 impl Default for ExecutionCfg {
-    fn default() -> Self { Self { confirmation_timeout_seconds: 10, dry_run: false } }
+    fn default() -> Self {
+        Self { confirmation_timeout_seconds: 10, dry_run: false, voice_confirmation: false, pending_policy: default_pending_policy(), confirmation: default_confirmation(), default_env_allowlist: default_env_allowlist() }
+    }
+}
+
+fn default_pending_policy() -> String { "reject".into() }
+fn default_confirmation() -> String { "dangerous_only".into() }
+
+fn default_env_allowlist() -> Vec<String> {
+    ["PATH", "HOME", "USER", "LANG", "DISPLAY", "WAYLAND_DISPLAY", "XDG_RUNTIME_DIR", "DBUS_SESSION_BUS_ADDRESS", "PULSE_SERVER"]
+        .map(String::from)
+        .to_vec()
 }
 
 fn default_confirmation_timeout_seconds() -> u64 { 10 }
@@ -117,15 +780,94 @@ pub struct UiCfg {
     pub osd: bool,
     #[serde(default = "default_osd_timeout_ms")] 
     pub osd_timeout_ms: u64,
+    /// Keep notifying (at critical urgency) while Do-Not-Disturb is on,
+    /// instead of falling back to speech.
+    #[serde(default)]
+    pub ignore_dnd: bool,
+    /// "dbus" talks to org.freedesktop.Notifications directly; "subprocess"
+    /// shells out to notify-send/dunstify as before.
+    #[serde(default = "default_notifier")]
+    pub notifier: String,
+    /// Keep `$XDG_RUNTIME_DIR/btwd/status.json` up to date for status bars.
+    #[serde(default)]
+    pub status_file: bool,
+    /// Also write each status update as a line on `$XDG_RUNTIME_DIR/btwd/status.fifo`.
+    #[serde(default)]
+    pub status_fifo: bool,
+    /// Where "open in browser" on a web answer goes: "google", "duckduckgo" or "brave".
+    #[serde(default = "default_search_engine")]
+    pub search_engine: String,
 }
 
 impl Default for UiCfg {
-    fn default() -> Self { Self { listening_notification: true, osd: true, osd_timeout_ms: 1500 } }
+    fn default() -> Self { Self { listening_notification: true, osd: true, osd_timeout_ms: 1500, ignore_dnd: false, notifier: default_notifier(), status_file: false, status_fifo: false, search_engine: default_search_engine() } }
 }
 
 fn default_listening_notification() -> bool { true }
 fn default_osd() -> bool { true }
 fn default_osd_timeout_ms() -> u64 { 1500 }
+fn default_notifier() -> String { "dbus".into() }
+fn default_search_engine() -> String { "google".into() }
+
+/// Logging configuration
+#[derive(Debug, Deserialize, Clone)]
+pub struct LoggingCfg {
+    /// error | warn | info | debug | trace | off (`RUST_LOG` overrides it on a TTY)
+    #[serde(default = "default_log_level")]
+    pub level: String,
+    /// text | json (one JSON object per line on stderr, tagged with the interaction id)
+    #[serde(default = "default_log_format")]
+    pub format: String,
+}
+
+impl Default for LoggingCfg {
+    fn default() -> Self { Self { level: default_log_level(), format: default_log_format() } }
+}
+
+fn default_log_format() -> String { "text".into() }
+
+fn default_log_level() -> String { "info".into() }
+
+/// Microphone capture configuration
+#[derive(Debug, Deserialize, Clone)]
+pub struct AudioCfg {
+    /// Input device name (substring match); the system default when unset.
+    #[serde(default)]
+    pub input_device: Option<String>,
+    /// Reopen attempts after the device is lost before btwd exits (0 exits at once).
+    #[serde(default = "default_reconnect_attempts")]
+    pub reconnect_attempts: u32,
+    /// Delay before the first reopen attempt; doubles per attempt, capped at 10 s.
+    #[serde(default = "default_reconnect_backoff_ms")]
+    pub reconnect_backoff_ms: u64,
+    /// No audio delivered for this long counts as a lost device (e.g. an unplugged USB mic).
+    #[serde(default = "default_hotplug_timeout_ms")]
+    pub hotplug_timeout_ms: u64,
+}
+
+impl Default for AudioCfg {
+    fn default() -> Self {
+        Self { input_device: None, reconnect_attempts: default_reconnect_attempts(), reconnect_backoff_ms: default_reconnect_backoff_ms(), hotplug_timeout_ms: default_hotplug_timeout_ms() }
+    }
+}
+
+/// Health check endpoint configuration
+#[derive(Debug, Deserialize, Clone)]
+pub struct HealthCfg {
+    /// TCP port on 127.0.0.1 serving the daemon state as JSON; 0 disables.
+    #[serde(default = "default_health_port")]
+    pub port: u16,
+}
+
+impl Default for HealthCfg {
+    fn default() -> Self { Self { port: default_health_port() } }
+}
+
+fn default_health_port() -> u16 { 9874 }
+
+fn default_reconnect_attempts() -> u32 { 10 }
+fn default_reconnect_backoff_ms() -> u64 { 500 }
+fn default_hotplug_timeout_ms() -> u64 { 3000 }
 
 /// Speech output (TTS) configuration
 #[derive(Debug, Deserialize, Clone)]
@@ -140,10 +882,22 @@ pub struct SpeechOutputCfg {
     pub format: String, // "wav" or "mp3"
     #[serde(default = "default_tts_rate")] 
     pub rate: f32,
+    /// Voice model for local providers (piper: path to the .onnx file).
+    /// `model_path` is accepted as an alias.
+    #[serde(default, alias = "model_path")]
+    pub local_model_path: Option<String>,
+    /// Size cap for the synthesized-audio cache in MiB (0 disables caching).
+    #[serde(default = "default_tts_cache_max_mb")]
+    pub cache_max_mb: u64,
+    /// "native" decodes and plays in-process on the default output device
+    /// (falling back to external players if it can't be opened); "external"
+    /// always pipes to pw-play/aplay/ffplay.
+    #[serde(default = "default_tts_playback")]
+    pub playback: String,
 }
 
 impl Default for SpeechOutputCfg {
-    fn default() -> Self { Self { enabled: true, provider: "groq".into(), voice: "default".into(), format: "wav".into(), rate: 1.0 } }
+    fn default() -> Self { Self { enabled: true, provider: "groq".into(), voice: "default".into(), format: "wav".into(), rate: 1.0, local_model_path: None, cache_max_mb: 50, playback: default_tts_playback() } }
 }
 
 fn default_tts_enabled() -> bool { true }
@@ -151,6 +905,8 @@ fn default_tts_provider() -> String { "groq".into() }
 fn default_tts_voice() -> String { "default".into() }
 fn default_tts_format() -> String { "wav".into() }
 fn default_tts_rate() -> f32 { 1.0 }
+fn default_tts_cache_max_mb() -> u64 { 50 }
+fn default_tts_playback() -> String { "native".into() }
 
 /// Search configuration
 #[derive(Debug, Deserialize, Clone)]
@@ -163,6 +919,19 @@ pub struct SearchCfg {
     /// Optional Tavily "country" parameter (e.g. "india", "us").
     #[serde(default)]
     pub country: Option<String>,
+
+    /// Web search backend: "tavily" (needs TAVILY_API_KEY), "searxng"
+    /// (self-hosted, at `base_url`) or "duckduckgo" (no key).
+    #[serde(default = "default_search_provider")]
+    pub provider: String,
+
+    /// searxng: the instance's root URL, e.g. `http://localhost:8888`.
+    #[serde(default)]
+    pub base_url: String,
+
+    /// Seconds to reuse results for a repeated query; 0 disables the cache.
+    #[serde(default)]
+    pub cache_ttl_secs: u64,
 }
 
 impl Default for SearchCfg {
@@ -171,22 +940,330 @@ impl Default for SearchCfg {
             enabled: true,
             timeout_ms: 4000,
             country: None,
+            provider: default_search_provider(),
+            base_url: String::new(),
+            cache_ttl_secs: 0,
         }
     }
 }
 
 fn default_search_enabled() -> bool { true }
 fn default_search_timeout_ms() -> u64 { 4000 }
+fn default_search_provider() -> String { "tavily".into() }
 
 /// LLM provider configuration
 #[derive(Debug, Deserialize, Clone)]
 pub struct LlmCfg {
     #[serde(default = "default_llm_provider")] 
-    pub provider: String, // "groq" | "mistral"
+    pub provider: String, // "groq" | "mistral" | "openai_compat"
+    /// openai_compat: the server's API root, e.g. `http://localhost:11434/v1` for Ollama.
+    #[serde(default)]
+    pub base_url: String,
+    /// openai_compat: model name as the server knows it, e.g. `llama3.2`.
+    #[serde(default)]
+    pub model: String,
+    /// openai_compat: name of the environment variable holding the API key;
+    /// unset for servers without auth.
+    #[serde(default)]
+    pub api_key_env: Option<String>,
+    /// Budget for one intent classification, retries included. Keep it tight:
+    /// it delays every utterance the deterministic matcher could not place.
+    #[serde(default = "default_classify_timeout_ms")]
+    pub classify_timeout_ms: u64,
+    /// Budget for one answer or search summary, retries included. Local
+    /// models can be slow to answer.
+    #[serde(default = "default_answer_timeout_ms", alias = "timeout_ms")]
+    pub answer_timeout_ms: u64,
+    /// Extra attempts after connect errors, timeouts, 429 and 5xx.
+    #[serde(default = "default_llm_max_retries")]
+    pub max_retries: u32,
+    /// Wait before the first retry, doubled for each further one (with jitter).
+    #[serde(default = "default_llm_retry_backoff_ms")]
+    pub retry_backoff_ms: u64,
+    /// Consecutive failures before LLM calls are skipped for `breaker_cooldown_secs`; 0 never skips.
+    #[serde(default = "default_llm_breaker_failures")]
+    pub breaker_failures: u32,
+    #[serde(default = "default_llm_breaker_cooldown_secs")]
+    pub breaker_cooldown_secs: u64,
+    /// Stream spoken answers and start speaking at the first full sentence.
+    #[serde(default = "default_llm_stream_answers")]
+    pub stream_answers: bool,
 }
 
 impl Default for LlmCfg {
-    fn default() -> Self { Self { provider: "groq".into() } }
+    fn default() -> Self {
+        Self {
+            provider: "groq".into(),
+            base_url: String::new(),
+            model: String::new(),
+            api_key_env: None,
+            classify_timeout_ms: default_classify_timeout_ms(),
+            answer_timeout_ms: default_answer_timeout_ms(),
+            max_retries: default_llm_max_retries(),
+            retry_backoff_ms: default_llm_retry_backoff_ms(),
+            breaker_failures: default_llm_breaker_failures(),
+            breaker_cooldown_secs: default_llm_breaker_cooldown_secs(),
+            stream_answers: default_llm_stream_answers(),
+        }
+    }
 }
 
 fn default_llm_provider() -> String { "groq".into() }
+fn default_classify_timeout_ms() -> u64 { 2000 }
+fn default_answer_timeout_ms() -> u64 { 15_000 }
+fn default_llm_max_retries() -> u32 { 2 }
+fn default_llm_retry_backoff_ms() -> u64 { 250 }
+fn default_llm_breaker_failures() -> u32 { 3 }
+fn default_llm_breaker_cooldown_secs() -> u64 { 30 }
+fn default_llm_stream_answers() -> bool { true }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = r#"
+[wake_word]
+ppn_path = "/tmp/btw.ppn"
+model_path = "/tmp/porcupine_params.pv"
+sensitivity = 0.6
+"#;
+
+    #[test]
+    fn piper_without_model_path_warns() {
+        let cfg = Config::from_toml_str(&format!("{}\n[speech_output]\nprovider = \"piper\"\n", BASE)).unwrap();
+        let warnings = cfg.warnings();
+        assert!(warnings.iter().any(|w| w.contains("local_model_path")), "{:?}", warnings);
+    }
+
+    #[test]
+    fn single_keyword_form_still_works() {
+        let cfg = Config::from_toml_str(BASE).unwrap();
+        let kws = cfg.wake_word.keyword_list();
+        assert_eq!(kws.len(), 1);
+        assert_eq!(kws[0].ppn_path, "/tmp/btw.ppn");
+        assert_eq!(kws[0].sensitivity, Some(0.6));
+        assert_eq!(kws[0].label.as_deref(), Some("btw"));
+    }
+
+    #[test]
+    fn multiple_keywords_with_per_keyword_sensitivity() {
+        let cfg = Config::from_toml_str(
+            r#"
+[wake_word]
+model_path = "/tmp/porcupine_params.pv"
+sensitivity = 0.4
+
+[[wake_word.keywords]]
+ppn_path = "/tmp/hey-btw.ppn"
+sensitivity = 0.7
+label = "hey btw"
+
+[[wake_word.keywords]]
+ppn_path = "/tmp/computer.ppn"
+"#,
+        )
+        .unwrap();
+        let kws = cfg.wake_word.keyword_list();
+        assert_eq!(kws.len(), 2);
+        assert_eq!((kws[0].sensitivity, kws[0].label.as_deref()), (Some(0.7), Some("hey btw")));
+        assert_eq!((kws[1].sensitivity, kws[1].label.as_deref()), (Some(0.4), Some("computer")));
+        assert!(kws.iter().all(|k| k.action == "listen"));
+    }
+
+    #[test]
+    fn keywords_can_cancel_instead_of_listen() {
+        let toml = |second: &str| {
+            format!(
+                "[wake_word]\nmodel_path = \"/tmp/porcupine_params.pv\"\n\n[[wake_word.keywords]]\nppn_path = \"/tmp/hey-btw.ppn\"\naction = \"cancel\"\n\n[[wake_word.keywords]]\nppn_path = \"/tmp/btw-stop.ppn\"\naction = \"{}\"\n",
+                second
+            )
+        };
+        let cfg = Config::from_toml_str(&toml("listen")).unwrap();
+        let actions: Vec<String> = cfg.wake_word.keyword_list().into_iter().map(|k| k.action).collect();
+        assert_eq!(actions, ["cancel", "listen"]);
+        assert!(!cfg.warnings().iter().any(|w| w.contains("action")));
+
+        let warnings = Config::from_toml_str(&toml("stop")).unwrap().warnings();
+        assert!(warnings.iter().any(|w| w.starts_with("wake_word.keywords[1].action = \"stop\"")), "{:?}", warnings);
+        let warnings = Config::from_toml_str(&toml("cancel")).unwrap().warnings();
+        assert!(warnings.iter().any(|w| w.contains("nothing can start listening")), "{:?}", warnings);
+    }
+
+    #[test]
+    fn language_models_replace_the_single_model_form() {
+        let cfg = Config::from_toml_str(
+            r#"
+[wake_word]
+sensitivity = 0.4
+
+[[wake_word.language_models]]
+language = "en"
+model_path = "/tmp/porcupine_params.pv"
+ppn_path = "/tmp/hey-btw_en.ppn"
+sensitivity = 0.7
+
+[[wake_word.language_models]]
+language = "de"
+model_path = "/tmp/porcupine_params_de.pv"
+ppn_path = "relative/hallo-btw_de.ppn"
+"#,
+        )
+        .unwrap();
+        let kws = cfg.wake_word.keyword_list();
+        assert_eq!(kws.len(), 2);
+        assert_eq!((kws[0].sensitivity, kws[0].label.as_deref()), (Some(0.7), Some("hey-btw_en [en]")));
+        assert_eq!((kws[1].sensitivity, kws[1].label.as_deref()), (Some(0.5), Some("hallo-btw_de [de]")));
+
+        let keys: Vec<String> = cfg.validate().into_iter().map(|e| e.key).filter(|k| k.starts_with("wake_word.")).collect();
+        // No complaint about the unused top-level model_path/ppn_path.
+        assert_eq!(
+            keys,
+            [
+                "wake_word.language_models[0].model_path",
+                "wake_word.language_models[0].ppn_path",
+                "wake_word.language_models[1].model_path",
+                "wake_word.language_models[1].ppn_path",
+            ]
+        );
+    }
+
+    #[test]
+    fn push_to_talk_needs_no_porcupine_files() {
+        let cfg = Config::from_toml_str("[wake_word]\npush_to_talk = true\n").unwrap();
+        assert!(cfg.wake_word.keyword_list().is_empty());
+        assert!(!cfg.validate().iter().any(|e| e.key.starts_with("wake_word.")));
+        let cfg = Config::from_toml_str("[wake_word]\n").unwrap();
+        assert!(cfg.validate().iter().any(|e| e.key == "wake_word.model_path"));
+    }
+
+    #[test]
+    fn openai_compat_needs_base_url_and_model() {
+        let cfg = Config::from_toml_str(&format!("{}\n[llm]\nprovider = \"openai_compat\"\nbase_url = \"localhost:11434\"\n", BASE)).unwrap();
+        let keys: Vec<String> = cfg.validate().into_iter().map(|e| e.key).filter(|k| k.starts_with("llm.")).collect();
+        assert_eq!(keys, ["llm.base_url", "llm.model"]);
+
+        let cfg = Config::from_toml_str(&format!(
+            "{}\n[llm]\nprovider = \"openai_compat\"\nbase_url = \"http://localhost:11434/v1\"\nmodel = \"llama3.2\"\n",
+            BASE
+        ))
+        .unwrap();
+        assert!(!cfg.validate().iter().any(|e| e.key.starts_with("llm.")));
+        assert_eq!((cfg.llm.api_key_env, cfg.llm.classify_timeout_ms, cfg.llm.answer_timeout_ms), (None, 2000, 15_000));
+
+        // `timeout_ms` from before the split still sets the answer budget.
+        let cfg = Config::from_toml_str(&format!("{}\n[llm]\ntimeout_ms = 60000\nclassify_timeout_ms = 0\n", BASE)).unwrap();
+        assert_eq!(cfg.llm.answer_timeout_ms, 60_000);
+        assert!(cfg.validate().iter().any(|e| e.key == "llm.classify_timeout_ms"));
+    }
+
+    #[test]
+    fn sensitivity_profiles_by_hour() {
+        let cfg = Config::from_toml_str(&format!(
+            "{}\n[[wake_word.sensitivity_profiles]]\nstart_hour = 22\nend_hour = 7\nsensitivity = 0.3\n\n[[wake_word.sensitivity_profiles]]\nstart_hour = 9\nend_hour = 18\nsensitivity = 0.7\n",
+            BASE
+        ))
+        .unwrap();
+        let wake = &cfg.wake_word;
+        assert_eq!((wake.sensitivity_at(23), wake.sensitivity_at(0), wake.sensitivity_at(6)), (0.3, 0.3, 0.3));
+        assert_eq!((wake.sensitivity_at(9), wake.sensitivity_at(17)), (0.7, 0.7));
+        // Uncovered hours fall back to wake_word.sensitivity.
+        assert_eq!((wake.sensitivity_at(7), wake.sensitivity_at(18)), (0.6, 0.6));
+        assert_eq!((wake.active_profile(3), wake.active_profile(12), wake.active_profile(20)), (Some(0), Some(1), None));
+        assert!(SensitivityProfile { start_hour: 5, end_hour: 5, sensitivity: 0.5 }.covers(13));
+
+        let cfg = Config::from_toml_str(&format!("{}\n[[wake_word.sensitivity_profiles]]\nstart_hour = 25\nend_hour = 7\nsensitivity = 0.3\n", BASE)).unwrap();
+        assert!(cfg.validate().iter().any(|e| e.key == "wake_word.sensitivity_profiles[0].start_hour"));
+    }
+
+    #[test]
+    fn command_overrides_by_id() {
+        let cfg = Config::from_toml_str(&format!(
+            "{}\n[[intent.command_overrides]]\nid = \"system_reboot\"\ndeterministic_threshold = 0.95\n\n[[intent.command_overrides]]\nid = \"volume_up\"\ndeterministic_threshold = 0.6\n",
+            BASE
+        ))
+        .unwrap();
+        let overrides = cfg.intent.threshold_overrides();
+        assert_eq!((overrides["system_reboot"], overrides["volume_up"], overrides.len()), (0.95, 0.6, 2));
+        assert!(!cfg.validate().iter().any(|e| e.key.starts_with("intent.")));
+
+        let cfg = Config::from_toml_str(&format!(
+            "{}\n[[intent.command_overrides]]\nid = \"volume_up\"\ndeterministic_threshold = 1.5\n\n[[intent.command_overrides]]\nid = \"volume_up\"\ndeterministic_threshold = 0.6\n",
+            BASE
+        ))
+        .unwrap();
+        let keys: Vec<String> = cfg.validate().into_iter().map(|e| e.key).filter(|k| k.starts_with("intent.")).collect();
+        assert_eq!(keys, ["intent.command_overrides[0].deterministic_threshold", "intent.command_overrides[1].id"]);
+    }
+
+    #[test]
+    fn decision_keywords_accept_plain_and_table_entries() {
+        let cfg = Config::from_toml_str(&format!(
+            "{}\n[decision]\nquestion_starters = [\"translate\", {{ text = \"define\" }}]\nweb_keywords = [{{ text = \"tonight\", mode = \"contains\" }}]\n",
+            BASE
+        ))
+        .unwrap();
+        assert_eq!(cfg.decision.question_starters[0], KeywordSpec::Text("translate".into()));
+        assert_eq!(cfg.decision.question_starters[1].mode(), None);
+        assert_eq!(cfg.decision.web_keywords[0].mode(), Some(MatchMode::Contains));
+        assert!(!cfg.decision.replace_defaults);
+    }
+
+    #[test]
+    fn unknown_log_level_warns() {
+        let cfg = Config::from_toml_str(BASE).unwrap();
+        assert_eq!(cfg.logging.level, "info");
+        let cfg = Config::from_toml_str(&format!("{}\n[logging]\nlevel = \"chatty\"\n", BASE)).unwrap();
+        assert!(cfg.warnings().iter().any(|w| w.contains("logging.level")));
+        let cfg = Config::from_toml_str(&format!("{}\n[logging]\nformat = \"yaml\"\n", BASE)).unwrap();
+        assert!(cfg.warnings().iter().any(|w| w.contains("logging.format")));
+    }
+
+    #[test]
+    fn groq_provider_has_no_local_warnings() {
+        let cfg = Config::from_toml_str(BASE).unwrap();
+        assert!(cfg.warnings().is_empty());
+    }
+
+    /// BASE with its two paths pointing at real files under a temp dir.
+    fn valid_config(dir: &std::path::Path) -> String {
+        std::fs::create_dir_all(dir).unwrap();
+        for f in ["btw.ppn", "porcupine_params.pv"] {
+            std::fs::write(dir.join(f), b"").unwrap();
+        }
+        BASE.replace("/tmp/", &format!("{}/", dir.display()))
+    }
+
+    #[test]
+    fn valid_config_has_no_errors() {
+        let dir = std::env::temp_dir().join(format!("btwd-config-ok-{}", std::process::id()));
+        let cfg = Config::from_toml_str(&valid_config(&dir)).unwrap();
+        assert_eq!(cfg.validate(), Vec::new());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn validate_reports_every_violation_with_its_key() {
+        let dir = std::env::temp_dir().join(format!("btwd-config-bad-{}", std::process::id()));
+        let toml = valid_config(&dir).replace("sensitivity = 0.6", "sensitivity = 7.0")
+            + "\n[speech]\nvad_mode = 9\n[intent]\ndeterministic_threshold = 0.0\n[execution]\nconfirmation_timeout_seconds = 0\n[speech_output]\nformat = \"ogg\"\nprovider = \"say\"\n";
+        let cfg = Config::from_toml_str(&toml).unwrap();
+        let keys: Vec<String> = cfg.validate().into_iter().map(|e| e.key).collect();
+        assert_eq!(
+            keys,
+            [
+                "wake_word.sensitivity",
+                "speech.vad_mode",
+                "intent.deterministic_threshold",
+                "execution.confirmation_timeout_seconds",
+                "speech_output.provider",
+                "speech_output.format",
+            ]
+        );
+
+        let cfg = Config::from_toml_str("[wake_word]\nppn_path = \"\"\nmodel_path = \"porcupine_params.pv\"\n").unwrap();
+        let errors = cfg.validate();
+        assert_eq!(errors[0].to_string(), "wake_word.model_path: \"porcupine_params.pv\" is not an absolute path");
+        assert_eq!(errors[1].key, "wake_word.ppn_path");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,216 @@
+use crate::config::{Config, ConfigError};
+
+/// A config value replaced from the environment, for the startup log.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Override {
+    pub var: String,
+    /// TOML key path, e.g. `execution.dry_run`.
+    pub key: &'static str,
+    /// The new value, or `<redacted>` for secret-looking keys.
+    pub shown: String,
+}
+
+impl std::fmt::Display for Override {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} = {} (from {})", self.key, self.shown, self.var)
+    }
+}
+
+/// A value that can be read from an environment variable.
+pub trait EnvValue: Sized {
+    /// What a well-formed value looks like, for error messages.
+    const EXPECTED: &'static str;
+    fn parse_env(raw: &str) -> Option<Self>;
+}
+
+impl EnvValue for bool {
+    const EXPECTED: &'static str = "one of 1|0|true|false|yes|no|on|off";
+    fn parse_env(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => Some(true),
+            "0" | "false" | "no" | "off" => Some(false),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! number_env_value {
+    ($($t:ty => $expected:literal),* $(,)?) => {
+        $(impl EnvValue for $t {
+            const EXPECTED: &'static str = $expected;
+            fn parse_env(raw: &str) -> Option<Self> {
+                raw.trim().parse().ok()
+            }
+        })*
+    };
+}
+
+number_env_value! {
+    f32 => "a number",
+    i32 => "a whole number",
+    u16 => "a whole number from 0 to 65535",
+    u32 => "a non-negative whole number",
+    u64 => "a non-negative whole number",
+}
+
+impl EnvValue for String {
+    const EXPECTED: &'static str = "a string";
+    fn parse_env(raw: &str) -> Option<Self> {
+        Some(raw.to_string())
+    }
+}
+
+/// An empty value unsets the field.
+impl EnvValue for Option<String> {
+    const EXPECTED: &'static str = "a string";
+    fn parse_env(raw: &str) -> Option<Self> {
+        Some(Some(raw.to_string()).filter(|s| !s.trim().is_empty()))
+    }
+}
+
+/// `BTWD_<KEY>` with dots turned into underscores, e.g. `BTWD_WAKE_WORD_SENSITIVITY`.
+pub fn var_name(key: &str) -> String {
+    format!("BTWD_{}", key.replace('.', "_").to_ascii_uppercase())
+}
+
+fn is_secret(key: &str) -> bool {
+    ["key", "token", "secret", "password"].iter().any(|s| key.contains(s))
+}
+
+/// Read a `BTWD_*` tunable that has no config.toml field. Malformed values
+/// are logged and ignored, so the caller's default applies.
+pub fn var<T: EnvValue>(name: &str) -> Option<T> {
+    let raw = std::env::var(name).ok()?;
+    let parsed = T::parse_env(&raw);
+    if parsed.is_none() {
+        log::warn!("config: ignoring {}={:?}: expected {}", name, raw, T::EXPECTED);
+    }
+    parsed
+}
+
+fn set<T: EnvValue>(
+    field: &mut T,
+    key: &'static str,
+    get: &impl Fn(&str) -> Option<String>,
+    overrides: &mut Vec<Override>,
+    errors: &mut Vec<ConfigError>,
+) {
+    let var = var_name(key);
+    let Some(raw) = get(&var) else { return };
+    match T::parse_env(&raw) {
+        Some(value) => {
+            *field = value;
+            let shown = if is_secret(key) { "<redacted>".to_string() } else { format!("{:?}", raw) };
+            overrides.push(Override { var, key, shown });
+        }
+        None => errors.push(ConfigError { key: key.to_string(), message: format!("{}={:?} is not {}", var, raw, T::EXPECTED) }),
+    }
+}
+
+macro_rules! overridable {
+    ($cfg:ident, $get:ident, $overrides:ident, $errors:ident; $($section:ident . $field:ident),* $(,)?) => {
+        $(set(&mut $cfg.$section.$field, concat!(stringify!($section), ".", stringify!($field)), &$get, &mut $overrides, &mut $errors);)*
+    };
+}
+
+/// Apply `BTWD_<SECTION>_<FIELD>` overrides on top of the parsed file
+/// (env > file > defaults). `get` looks up a variable; see [`apply_process_env`].
+/// All malformed values are reported together.
+pub fn apply(cfg: &mut Config, get: impl Fn(&str) -> Option<String>) -> Result<Vec<Override>, Vec<ConfigError>> {
+    let mut overrides = Vec::new();
+    let mut errors = Vec::new();
+    overridable!(cfg, get, overrides, errors;
+        wake_word.sensitivity, wake_word.device, wake_word.push_to_talk,
+        speech.silence_threshold, speech.silence_duration_ms, speech.max_utterance_seconds, speech.vad_mode,
+        speech.adaptive_vad, speech.min_speech_ms, speech.pre_emphasis_coefficient,
+        intent.deterministic_threshold, intent.llm_fallback_threshold, intent.embeddings, intent.embedding_threshold, intent.embedding_weight,
+        intent.self_check, intent.consistency_check, intent.llm_rate_limit_per_min,
+        execution.confirmation_timeout_seconds, execution.dry_run, execution.voice_confirmation, execution.pending_policy, execution.confirmation,
+        ui.listening_notification, ui.osd, ui.osd_timeout_ms, ui.ignore_dnd, ui.notifier, ui.status_file, ui.status_fifo, ui.search_engine,
+        speech_output.enabled, speech_output.provider, speech_output.voice, speech_output.format, speech_output.rate,
+        speech_output.local_model_path, speech_output.cache_max_mb, speech_output.playback,
+        search.enabled, search.timeout_ms, search.country, search.provider, search.base_url, search.cache_ttl_secs,
+        llm.provider, llm.base_url, llm.model, llm.classify_timeout_ms, llm.answer_timeout_ms, llm.max_retries, llm.stream_answers,
+        asr.engine, asr.model_path, asr.language, asr.translate, asr.model,
+        logging.level, logging.format,
+        audio.input_device,
+        health.port,
+    );
+    if errors.is_empty() {
+        Ok(overrides)
+    } else {
+        Err(errors)
+    }
+}
+
+/// [`apply`] with the process environment.
+pub fn apply_process_env(cfg: &mut Config) -> Result<Vec<Override>, Vec<ConfigError>> {
+    apply(cfg, |name| std::env::var(name).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const BASE: &str = "[wake_word]\nppn_path = \"/tmp/btw.ppn\"\nmodel_path = \"/tmp/porcupine_params.pv\"\nsensitivity = 0.5\n[search]\nprovider = \"duckduckgo\"\n";
+
+    fn apply_vars(vars: &[(&str, &str)]) -> (Config, Result<Vec<Override>, Vec<ConfigError>>) {
+        let env: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        let mut cfg = Config::from_toml_str(BASE).unwrap();
+        let result = apply(&mut cfg, |name| env.get(name).cloned());
+        (cfg, result)
+    }
+
+    #[test]
+    fn overrides_each_supported_type() {
+        let (cfg, result) = apply_vars(&[
+            ("BTWD_EXECUTION_DRY_RUN", "1"),
+            ("BTWD_SPEECH_OUTPUT_ENABLED", "off"),
+            ("BTWD_WAKE_WORD_SENSITIVITY", "0.6"),
+            ("BTWD_SPEECH_VAD_MODE", "3"),
+            ("BTWD_SPEECH_MIN_SPEECH_MS", "250"),
+            ("BTWD_EXECUTION_CONFIRMATION_TIMEOUT_SECONDS", "30"),
+            ("BTWD_HEALTH_PORT", "0"),
+            ("BTWD_LLM_PROVIDER", "mistral"),
+            ("BTWD_AUDIO_INPUT_DEVICE", "USB"),
+            ("BTWD_SEARCH_COUNTRY", ""),
+        ]);
+        let overrides = result.unwrap();
+        assert!(cfg.execution.dry_run);
+        assert!(!cfg.speech_output.enabled);
+        assert_eq!(cfg.wake_word.sensitivity, 0.6);
+        assert_eq!(cfg.speech.vad_mode, 3);
+        assert_eq!(cfg.speech.min_speech_ms, 250);
+        assert_eq!(cfg.execution.confirmation_timeout_seconds, 30);
+        assert_eq!(cfg.health.port, 0);
+        assert_eq!(cfg.llm.provider, "mistral");
+        assert_eq!(cfg.audio.input_device.as_deref(), Some("USB"));
+        assert_eq!(cfg.search.country, None);
+        // Fields without a variable keep the file's value.
+        assert_eq!(cfg.search.provider, "duckduckgo");
+        assert_eq!(overrides.len(), 10);
+        assert!(overrides.iter().any(|o| o.to_string() == "execution.dry_run = \"1\" (from BTWD_EXECUTION_DRY_RUN)"));
+    }
+
+    #[test]
+    fn malformed_values_are_all_reported() {
+        let (cfg, result) = apply_vars(&[
+            ("BTWD_EXECUTION_DRY_RUN", "maybe"),
+            ("BTWD_WAKE_WORD_SENSITIVITY", "high"),
+            ("BTWD_HEALTH_PORT", "70000"),
+        ]);
+        let errors = result.unwrap_err();
+        let keys: Vec<&str> = errors.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, ["wake_word.sensitivity", "execution.dry_run", "health.port"]);
+        assert_eq!(errors[0].to_string(), "wake_word.sensitivity: BTWD_WAKE_WORD_SENSITIVITY=\"high\" is not a number");
+        assert_eq!(cfg.wake_word.sensitivity, 0.5);
+    }
+
+    #[test]
+    fn secret_looking_keys_are_redacted() {
+        assert_eq!(var_name("speech_output.local_model_path"), "BTWD_SPEECH_OUTPUT_LOCAL_MODEL_PATH");
+        assert!(is_secret("llm.api_key"));
+        assert!(!is_secret("llm.provider"));
+    }
+}
@@ -0,0 +1,280 @@
+use crate::intent::{is_sensitive_command_id, IntentCommand, IntentResult};
+use crate::params::{Params, Provenance};
+use std::time::{Duration, Instant};
+
+/// Step used by "more"/"less" on an absolute (`value`) command.
+const RELATIVE_STEP: i64 = 10;
+
+/// Words a follow-up may consist of besides the cue itself and a number;
+/// anything else ("tell me more about rust") is not a follow-up.
+const FILLER: &[&str] = &[
+    "a", "bit", "little", "make", "it", "set", "to", "the", "please", "%", "do", "that", "go", "turn", "now", "just",
+];
+const MORE: &[&str] = &["more", "higher", "louder", "brighter", "up", "increase"];
+const LESS: &[&str] = &["less", "lower", "quieter", "dimmer", "down", "decrease"];
+const AGAIN: &[&str] = &["again", "repeat", "once"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cue {
+    More,
+    Less,
+    Again,
+    Number(i64),
+}
+
+struct LastCommand {
+    command_id: String,
+    params: Params,
+    score: f32,
+    at: Instant,
+}
+
+/// Remembers the last executed command for a short while so "a bit more",
+/// "again" or a bare "60" can refer back to it.
+///
+/// Dangerous and session/security commands are never remembered.
+pub struct FollowUpContext {
+    ttl: Duration,
+    last: Option<LastCommand>,
+}
+
+impl FollowUpContext {
+    /// A zero `ttl` disables follow-ups.
+    pub fn new(ttl: Duration) -> Self {
+        Self { ttl, last: None }
+    }
+
+    /// Remember a successfully executed command; `params` are the resolved
+    /// parameters the executor actually used.
+    pub fn record(&mut self, intent: &IntentResult, params: &Params) {
+        let Some(id) = intent.command_id.as_deref() else { return };
+        if self.ttl.is_zero() || intent.dangerous || intent.requires_confirmation || is_sensitive_command_id(id) {
+            self.last = None;
+            return;
+        }
+        let score = intent.deterministic_score.or(intent.embedding_score).unwrap_or(0.0);
+        self.last = Some(LastCommand { command_id: id.to_string(), params: params.clone(), score, at: Instant::now() });
+    }
+
+    pub fn clear(&mut self) {
+        self.last = None;
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.last.as_ref().is_some_and(|l| l.at.elapsed() < self.ttl)
+    }
+
+    /// Turn `text` into a command relative to the remembered one, if it is a
+    /// follow-up. `commands` is the allow-list, used to find the `_set`/`_up`/
+    /// `_down` sibling a cue maps to.
+    pub fn resolve(&mut self, text: &str, commands: &[IntentCommand]) -> Option<IntentResult> {
+        if !self.is_active() {
+            self.last = None;
+            return None;
+        }
+        let cue = parse_cue(&crate::decision::normalize_input(text))?;
+        let last = self.last.as_ref()?;
+        let (command_id, params) = adjust(&last.command_id, &last.params, cue, commands)?;
+        let cmd = commands.iter().find(|c| c.id == command_id)?;
+        if cmd.dangerous || is_sensitive_command_id(&cmd.id) {
+            return None;
+        }
+        Some(IntentResult {
+            intent_type: "follow_up".into(),
+            command_id: Some(command_id),
+            parameters: params,
+            // Inherits the confidence of the command it refers back to.
+            deterministic_score: Some(last.score),
+            embedding_score: None,
+            dangerous: false,
+            requires_confirmation: false,
+        })
+    }
+}
+
+fn parse_cue(norm: &str) -> Option<Cue> {
+    let mut cue = None;
+    let mut number = None;
+    for tok in norm.split_whitespace() {
+        let tok = tok.trim_end_matches('%');
+        if let Ok(n) = tok.parse::<i64>() {
+            if number.replace(n).is_some() {
+                return None;
+            }
+        } else if AGAIN.contains(&tok) {
+            cue = Some(Cue::Again);
+        } else if MORE.contains(&tok) {
+            // "once more" repeats rather than increases.
+            if cue != Some(Cue::Again) {
+                cue = Some(Cue::More);
+            }
+        } else if LESS.contains(&tok) {
+            cue = Some(Cue::Less);
+        } else if !tok.is_empty() && !FILLER.contains(&tok) {
+            return None;
+        }
+    }
+    match (cue, number) {
+        (None, Some(n)) => Some(Cue::Number(n)),
+        // "10 more" / "5 less": a relative amount.
+        (Some(Cue::More | Cue::Less), Some(_)) => None,
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+/// `brightness_up` -> ("brightness", "up").
+fn split_id(id: &str) -> (&str, &str) {
+    match id.rsplit_once('_') {
+        Some((base, suffix)) if matches!(suffix, "set" | "up" | "down") => (base, suffix),
+        _ => (id, ""),
+    }
+}
+
+fn adjust(id: &str, params: &Params, cue: Cue, commands: &[IntentCommand]) -> Option<(String, Params)> {
+    let exists = |id: &str| commands.iter().any(|c| c.id == id);
+    let (base, kind) = split_id(id);
+    let mut out = Params::new();
+    match (cue, kind) {
+        (Cue::Again, _) => {
+            for (name, p) in params.iter() {
+                out.insert(name, p.value.clone(), Provenance::FollowUp);
+            }
+            Some((id.to_string(), out))
+        }
+        (Cue::Number(n), _) => {
+            let set_id = format!("{}_set", base);
+            if exists(&set_id) {
+                out.insert("value", serde_json::json!(n), Provenance::FollowUp);
+                Some((set_id, out))
+            } else if kind == "up" || kind == "down" {
+                out.insert("delta", serde_json::json!(n), Provenance::FollowUp);
+                Some((id.to_string(), out))
+            } else {
+                None
+            }
+        }
+        (Cue::More | Cue::Less, "set") => {
+            let value = params.get_int("value")?;
+            let value = if cue == Cue::More { value + RELATIVE_STEP } else { (value - RELATIVE_STEP).max(0) };
+            out.insert("value", serde_json::json!(value), Provenance::FollowUp);
+            Some((id.to_string(), out))
+        }
+        (Cue::More | Cue::Less, "up" | "down") => {
+            // "more" keeps going the same way; "less" turns around.
+            let same = cue == Cue::More;
+            let target = if same { id.to_string() } else { format!("{}_{}", base, if kind == "up" { "down" } else { "up" }) };
+            if !exists(&target) {
+                return None;
+            }
+            if let Some(delta) = params.get("delta") {
+                out.insert("delta", delta.value.clone(), Provenance::FollowUp);
+            }
+            Some((target, out))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn cmd(id: &str, dangerous: bool) -> IntentCommand {
+        IntentCommand { id: id.into(), description: String::new(), examples: Vec::new(), dangerous, priority: 0, alias_of: None, slots: Vec::new() }
+    }
+
+    fn allow_list() -> Vec<IntentCommand> {
+        ["brightness_set", "brightness_up", "brightness_down", "volume_set", "volume_up", "volume_down", "lock_screen"]
+            .iter()
+            .map(|id| cmd(id, false))
+            .chain(std::iter::once(cmd("wipe_disk", true)))
+            .collect()
+    }
+
+    fn executed(id: &str, params: &[(&str, i64)]) -> (IntentResult, Params) {
+        let mut p = Params::new();
+        for (k, v) in params {
+            p.insert(k, json!(v), Provenance::Deterministic);
+        }
+        let intent = IntentResult {
+            intent_type: "command".into(),
+            command_id: Some(id.into()),
+            parameters: p.clone(),
+            deterministic_score: Some(0.9),
+            embedding_score: None,
+            dangerous: false,
+            requires_confirmation: false,
+        };
+        (intent, p)
+    }
+
+    #[test]
+    fn more_after_brightness_set_raises_the_value() {
+        let mut ctx = FollowUpContext::new(Duration::from_secs(30));
+        let (intent, params) = executed("brightness_set", &[("value", 40)]);
+        ctx.record(&intent, &params);
+
+        let r = ctx.resolve("a bit more", &allow_list()).unwrap();
+        assert_eq!(r.intent_type, "follow_up");
+        assert_eq!(r.command_id.as_deref(), Some("brightness_set"));
+        assert_eq!(r.parameters.get_int("value"), Some(50));
+        assert_eq!(r.parameters.provenance("value"), Some(Provenance::FollowUp));
+        assert_eq!(r.deterministic_score, Some(0.9));
+
+        let r = ctx.resolve("less", &allow_list()).unwrap();
+        assert_eq!(r.parameters.get_int("value"), Some(30));
+    }
+
+    #[test]
+    fn bare_number_after_volume_sets_it() {
+        let mut ctx = FollowUpContext::new(Duration::from_secs(30));
+        let (intent, params) = executed("volume_up", &[("delta", 5)]);
+        ctx.record(&intent, &params);
+
+        let r = ctx.resolve("60", &allow_list()).unwrap();
+        assert_eq!(r.command_id.as_deref(), Some("volume_set"));
+        assert_eq!(r.parameters.get_int("value"), Some(60));
+
+        let r = ctx.resolve("make it 60 percent", &allow_list()).unwrap();
+        assert_eq!(r.parameters.get_int("value"), Some(60));
+
+        let r = ctx.resolve("less", &allow_list()).unwrap();
+        assert_eq!(r.command_id.as_deref(), Some("volume_down"));
+        assert_eq!(r.parameters.get_int("delta"), Some(5));
+
+        let r = ctx.resolve("once more", &allow_list()).unwrap();
+        assert_eq!(r.command_id.as_deref(), Some("volume_up"));
+    }
+
+    #[test]
+    fn context_expires_and_clears() {
+        let mut ctx = FollowUpContext::new(Duration::from_millis(20));
+        let (intent, params) = executed("brightness_set", &[("value", 40)]);
+        ctx.record(&intent, &params);
+        assert!(ctx.is_active());
+        std::thread::sleep(Duration::from_millis(40));
+        assert!(ctx.resolve("more", &allow_list()).is_none());
+
+        let mut ctx = FollowUpContext::new(Duration::from_secs(30));
+        ctx.record(&intent, &params);
+        ctx.clear();
+        assert!(ctx.resolve("more", &allow_list()).is_none());
+    }
+
+    #[test]
+    fn unrelated_speech_and_sensitive_commands_are_not_follow_ups() {
+        let mut ctx = FollowUpContext::new(Duration::from_secs(30));
+        let (intent, params) = executed("brightness_set", &[("value", 40)]);
+        ctx.record(&intent, &params);
+        assert!(ctx.resolve("tell me more about rust", &allow_list()).is_none());
+        assert!(ctx.resolve("what is 2 plus 2", &allow_list()).is_none());
+
+        let (mut lock, params) = executed("lock_screen", &[]);
+        lock.requires_confirmation = true;
+        ctx.record(&lock, &params);
+        assert!(!ctx.is_active());
+        assert!(ctx.resolve("again", &allow_list()).is_none());
+    }
+}
@@ -0,0 +1,359 @@
+use crate::executor::{ExecStatus, Executor};
+use crate::intent::IntentResult;
+use crate::manager::{Manager, TranscriptEntry};
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
+
+/// How long a client waits for the main loop; `say` may sit behind an LLM call.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// `$XDG_RUNTIME_DIR/btwd/control.sock`, or `BTWD_CONTROL_SOCKET` (which btwctl reads too).
+pub fn socket_path() -> PathBuf {
+    crate::config_env::var::<Option<String>>("BTWD_CONTROL_SOCKET")
+        .flatten()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| crate::status::status_dir().join("control.sock"))
+}
+
+/// One request line from `btwctl` (or any client speaking the protocol):
+///
+/// `{"op":"say","text":"set brightness to 40"}`, `{"op":"listen"}`,
+/// `{"op":"confirm","request_id":"..."}`, `{"op":"cancel"}`, `{"op":"state"}`,
+/// `{"op":"reload"}`, `{"op":"stop"}`
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlCommand {
+    /// Handle `text` exactly like an ASR transcript.
+    Say(String),
+    /// Start listening, as if a listen keyword had fired. The main loop does
+    /// the transition; the reply only acknowledges it.
+    Listen,
+    /// Confirm the pending command; `request_id` must be the live one.
+    Confirm { request_id: String },
+    /// Cancel the pending command (when `request_id` is given, only if it is still live).
+    Cancel { request_id: Option<String> },
+    State,
+    /// Re-read commands.json and the wake word sensitivities. The main loop
+    /// does this itself, since it owns the router and the detector.
+    Reload,
+    /// Stop speaking now and drop any queued speech.
+    StopSpeaking,
+}
+
+pub fn parse_command(line: &str) -> Result<ControlCommand, String> {
+    let v: Value = serde_json::from_str(line.trim()).map_err(|e| format!("invalid JSON: {}", e))?;
+    let field = |k: &str| v.get(k).and_then(Value::as_str).map(str::to_string);
+    match field("op").as_deref() {
+        Some("say") => match field("text") {
+            Some(text) if !text.trim().is_empty() => Ok(ControlCommand::Say(text)),
+            _ => Err("say needs a non-empty \"text\"".into()),
+        },
+        Some("confirm") => field("request_id").map(|request_id| ControlCommand::Confirm { request_id }).ok_or_else(|| "confirm needs the pending \"request_id\" (see state)".into()),
+        Some("cancel") => Ok(ControlCommand::Cancel { request_id: field("request_id") }),
+        Some("listen") => Ok(ControlCommand::Listen),
+        Some("state" | "status") => Ok(ControlCommand::State),
+        Some("reload") => Ok(ControlCommand::Reload),
+        Some("stop") => Ok(ControlCommand::StopSpeaking),
+        Some(op) => Err(format!("unknown op '{}'", op)),
+        None => Err("missing \"op\"".into()),
+    }
+}
+
+/// A request handed to the main loop, answered through [`Request::respond`].
+pub struct Request {
+    pub command: Result<ControlCommand, String>,
+    reply: Sender<Value>,
+}
+
+impl Request {
+    pub fn respond(self, response: Value) {
+        // The client may have hung up; nothing to do then.
+        let _ = self.reply.send(response);
+    }
+}
+
+/// Accepts connections on a background thread and queues their requests for
+/// the main loop, which answers them between audio frames.
+pub struct ControlServer {
+    requests: Receiver<Request>,
+}
+
+impl ControlServer {
+    /// Listen at `path` (mode 0600), replacing a stale socket left by a crashed
+    /// daemon. Fails if another daemon is still answering there.
+    pub fn bind(path: &Path) -> std::io::Result<Self> {
+        use std::os::unix::fs::PermissionsExt;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        if path.exists() {
+            if UnixStream::connect(path).is_ok() {
+                return Err(std::io::Error::new(std::io::ErrorKind::AddrInUse, format!("{} is in use by another btwd", path.display())));
+            }
+            std::fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        let (tx, requests) = mpsc::channel();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let tx = tx.clone();
+                        std::thread::spawn(move || serve_client(stream, tx));
+                    }
+                    Err(e) => log::warn!("control: accept failed: {}", e),
+                }
+            }
+        });
+        Ok(Self { requests })
+    }
+
+    pub fn try_recv(&self) -> Option<Request> {
+        self.requests.try_recv().ok()
+    }
+}
+
+/// One JSON request per line, one JSON response per line, until EOF.
+fn serve_client(stream: UnixStream, requests: Sender<Request>) {
+    let Ok(mut writer) = stream.try_clone() else { return };
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else { return };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some(response) = submit(&requests, parse_command(&line)) else { return };
+        if writeln!(writer, "{}", response).is_err() {
+            return;
+        }
+    }
+}
+
+/// Queue `command` for the main loop and wait for its answer. `None` once the
+/// main loop has gone away.
+pub fn submit(requests: &Sender<Request>, command: Result<ControlCommand, String>) -> Option<Value> {
+    let (reply, response) = mpsc::channel();
+    requests.send(Request { command, reply }).ok()?;
+    Some(response.recv_timeout(REPLY_TIMEOUT).unwrap_or_else(|_| error("daemon did not answer in time")))
+}
+
+fn error(message: &str) -> Value {
+    json!({"ok": false, "error": message})
+}
+
+/// Handles one transcript against the live Executor and Manager, returning
+/// the decision type and the routed intent, like `handle_transcript`.
+pub type SayFn<'a> = dyn FnMut(&str, &mut Executor, &mut Manager) -> (&'static str, Option<IntentResult>) + 'a;
+
+/// What a control request acts on. The main loop passes its live Executor
+/// and Manager, and `say` runs the same transcript handling as ASR output.
+pub struct Pipeline<'a> {
+    pub exec: &'a mut Executor,
+    pub mgr: &'a mut Manager,
+    pub say: &'a mut SayFn<'a>,
+}
+
+impl Pipeline<'_> {
+    pub fn handle(&mut self, command: &ControlCommand) -> Value {
+        match command {
+            ControlCommand::Say(text) => {
+                let (kind, routed) = (self.say)(text.trim(), self.exec, self.mgr);
+                let entry = TranscriptEntry::new(text, kind, routed.as_ref(), None);
+                let response = json!({"ok": true, "decision": entry.to_json(), "pending": self.pending()});
+                self.mgr.record_transcript(entry);
+                match self.exec.pending_request_id() {
+                    Some(id) => self.mgr.mirror_confirmation(id, self.exec.pending_description().unwrap_or("a command")),
+                    None if self.exec.awaiting_parameter().is_some() => self.mgr.mirror_parameter_request(),
+                    None => self.mgr.end_turn(),
+                }
+                response
+            }
+            ControlCommand::Listen => json!({"ok": true, "state": "listening"}),
+            ControlCommand::Confirm { request_id } => match self.exec.pending_request_id() {
+                Some(live) if live == request_id => {
+                    log::info!("exec: confirm via control socket");
+                    let status = self.exec.confirm_pending();
+                    log::info!("exec: {:?}", status);
+                    self.mgr.reset_to_idle();
+                    json!({"ok": true, "status": status_name(&status)})
+                }
+                Some(_) => error("stale request_id; the pending confirmation has changed"),
+                None => error("no command is pending"),
+            },
+            ControlCommand::Cancel { request_id } => match self.exec.pending_request_id() {
+                Some(live) if request_id.as_deref().is_some_and(|id| id != live) => error("stale request_id; the pending confirmation has changed"),
+                Some(_) => {
+                    log::info!("exec: cancel via control socket");
+                    let status = self.exec.cancel_pending("user canceled");
+                    log::info!("exec: {:?}", status);
+                    self.mgr.reset_to_idle();
+                    json!({"ok": true, "status": status_name(&status)})
+                }
+                None if self.exec.awaiting_parameter().is_some() => {
+                    let status = self.exec.cancel_parameter_request("user canceled");
+                    log::info!("exec: {:?}", status);
+                    self.mgr.reset_to_idle();
+                    json!({"ok": true, "status": status_name(&status)})
+                }
+                None => error("no command is pending"),
+            },
+            ControlCommand::Reload => error("reload is only available in the daemon's main loop"),
+            ControlCommand::StopSpeaking => {
+                let was_speaking = crate::tts::is_speaking();
+                crate::tts::stop_speaking();
+                json!({"ok": true, "was_speaking": was_speaking})
+            }
+            ControlCommand::State => json!({
+                "ok": true,
+                "state": format!("{:?}", self.mgr.state()).to_ascii_lowercase(),
+                "pending": self.pending(),
+                "last_decision": self.mgr.transcript_history().back().map(TranscriptEntry::to_json),
+            }),
+        }
+    }
+
+    fn pending(&self) -> Value {
+        match self.exec.pending_request_id() {
+            Some(id) => json!({"request_id": id, "preview": self.exec.pending_description().unwrap_or("")}),
+            None => Value::Null,
+        }
+    }
+}
+
+fn status_name(status: &ExecStatus) -> &'static str {
+    match status {
+        ExecStatus::Executed { .. } => "executed",
+        ExecStatus::DryRun { .. } => "dry_run",
+        ExecStatus::PendingConfirmation { .. } => "pending_confirmation",
+        ExecStatus::Canceled { .. } => "canceled",
+        ExecStatus::Queued { .. } => "queued",
+        ExecStatus::NeedsParameter { .. } => "needs_parameter",
+        ExecStatus::Rejected { .. } => "rejected",
+        ExecStatus::Ignored => "ignored",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decision::{DecisionConfig, DecisionManager};
+    use crate::executor::{ExecutionCfg, PendingPolicy};
+    use crate::intent::{IntentCommand, IntentConfig, IntentRouter};
+
+    struct NoLlm;
+
+    impl crate::llm::LlmClient for NoLlm {
+        fn classify_intent(&self, _text: &str, _commands: &[IntentCommand]) -> Result<crate::llm::LlmIntent, String> {
+            Err("offline".into())
+        }
+        fn answer_short(&self, _prompt: &str) -> Result<String, String> {
+            Err("offline".into())
+        }
+        fn tts(&self, _text: &str) -> Result<Vec<u8>, String> {
+            Err("offline".into())
+        }
+    }
+
+    #[test]
+    fn parses_requests() {
+        assert_eq!(parse_command(r#"{"op":"say","text":"volume up"}"#), Ok(ControlCommand::Say("volume up".into())));
+        assert_eq!(parse_command(r#"{"op":"cancel"}"#), Ok(ControlCommand::Cancel { request_id: None }));
+        assert_eq!(parse_command(r#"{"op":"listen"}"#), Ok(ControlCommand::Listen));
+        assert_eq!(parse_command(r#"{"op":"status"}"#), Ok(ControlCommand::State));
+        assert_eq!(parse_command(r#"{"op":"reload"}"#), Ok(ControlCommand::Reload));
+        assert_eq!(parse_command(r#"{"op":"stop"}"#), Ok(ControlCommand::StopSpeaking));
+        assert!(parse_command(r#"{"op":"confirm"}"#).unwrap_err().contains("request_id"));
+        assert!(parse_command(r#"{"op":"say","text":"  "}"#).is_err());
+        assert!(parse_command(r#"{"op":"reboot"}"#).unwrap_err().contains("unknown op"));
+        assert!(parse_command("state").unwrap_err().contains("invalid JSON"));
+    }
+
+    /// Drives a real socket the way `btwctl` does, with the test thread
+    /// standing in for the main loop.
+    #[test]
+    fn say_confirm_and_state_over_the_socket() {
+        let dir = std::env::temp_dir().join(format!("btwd-control-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let socket = dir.join("control.sock");
+        let commands = dir.join("commands.json");
+        std::fs::write(
+            &commands,
+            r#"[{"id": "system_shutdown", "description": "Shut down the system", "examples": ["shut down the computer"], "dangerous": true, "shell_command_template": "systemctl poweroff"}]"#,
+        )
+        .unwrap();
+
+        let cfg = ExecutionCfg { confirmation_timeout_seconds: 30, dry_run: true, voice_confirmation: false, pending_policy: PendingPolicy::Reject, default_env_allowlist: Vec::new(), confirmation: Default::default() };
+        let mut exec = Executor::new_from_path(&commands, cfg.clone()).unwrap();
+        let mut mgr = Manager::with_execution_cfg(DecisionManager::new(DecisionConfig::with_threshold(0.75)).unwrap(), &cfg);
+        let intent_cfg = IntentConfig { deterministic_threshold: 0.6, llm_fallback_threshold: 0.9, embedding_threshold: 0.8, embedding_weight: 0.0, llm_rate_limit_per_min: 0 };
+        let router = IntentRouter::from_file(&commands, intent_cfg, std::sync::Arc::new(NoLlm)).unwrap();
+        let mut say = |text: &str, exec: &mut Executor, _mgr: &mut Manager| {
+            let routed = router.route(text);
+            if routed.command_id.is_none() {
+                return ("ignored", Some(routed));
+            }
+            let kind = match exec.handle_intent(&routed) {
+                ExecStatus::PendingConfirmation { .. } => "confirmation",
+                _ => "command",
+            };
+            (kind, Some(routed))
+        };
+
+        let server = ControlServer::bind(&socket).unwrap();
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(std::fs::metadata(&socket).unwrap().permissions().mode() & 0o777, 0o600);
+        assert!(ControlServer::bind(&socket).is_err(), "a live socket must not be taken over");
+
+        let client = std::thread::spawn({
+            let socket = socket.clone();
+            move || {
+                let stream = UnixStream::connect(&socket).unwrap();
+                let mut writer = stream.try_clone().unwrap();
+                let mut lines = BufReader::new(stream).lines();
+                let mut ask = |req: Value| -> Value {
+                    writeln!(writer, "{}", req).unwrap();
+                    serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap()
+                };
+                let said = ask(json!({"op": "say", "text": "shut down the computer"}));
+                let state = ask(json!({"op": "state"}));
+                let stale = ask(json!({"op": "confirm", "request_id": "system_shutdown-1"}));
+                let request_id = state["pending"]["request_id"].as_str().unwrap().to_string();
+                let confirmed = ask(json!({"op": "confirm", "request_id": request_id}));
+                let after = ask(json!({"op": "state"}));
+                let bad = ask(json!({"op": "dance"}));
+                (said, state, stale, confirmed, after, bad)
+            }
+        });
+
+        let mut answered = 0;
+        while answered < 6 {
+            let Some(req) = server.try_recv() else {
+                std::thread::sleep(Duration::from_millis(5));
+                continue;
+            };
+            let response = match &req.command {
+                Ok(command) => Pipeline { exec: &mut exec, mgr: &mut mgr, say: &mut say }.handle(command),
+                Err(e) => error(e),
+            };
+            req.respond(response);
+            answered += 1;
+        }
+        let (said, state, stale, confirmed, after, bad) = client.join().unwrap();
+
+        assert_eq!(said["decision"]["decision_type"], "confirmation");
+        assert_eq!(said["decision"]["command_id"], "system_shutdown");
+        assert_eq!(state["state"], "confirming");
+        assert_eq!(state["pending"]["preview"], "Shut down the system");
+        assert_eq!(state["last_decision"]["raw_text"], "shut down the computer");
+        assert_eq!((stale["ok"].as_bool(), stale["error"].as_str().map(|e| e.contains("stale"))), (Some(false), Some(true)));
+        assert_eq!(confirmed, json!({"ok": true, "status": "dry_run"}));
+        assert_eq!((after["state"].as_str(), after["pending"].is_null()), (Some("idle"), true));
+        assert_eq!(bad["ok"], false);
+        assert!(!exec.has_pending());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,182 @@
+use crate::control_socket::{self, ControlCommand, Request};
+use crate::executor::ExecObserver;
+use serde_json::Value;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Instant;
+use zbus::fdo;
+
+pub const BUS_NAME: &str = "org.btw.Daemon";
+pub const OBJECT_PATH: &str = "/org/btw/Daemon";
+
+/// The `org.btw.Daemon` object. Method calls are queued for the main loop
+/// exactly like control socket requests, so they are served between
+/// utterances and never mid-capture.
+struct Daemon {
+    requests: Sender<Request>,
+}
+
+impl Daemon {
+    fn call(&self, command: ControlCommand) -> fdo::Result<Value> {
+        reply(control_socket::submit(&self.requests, Ok(command)))
+    }
+}
+
+/// A control response as a method result: `{"ok": false}` becomes a D-Bus error.
+fn reply(response: Option<Value>) -> fdo::Result<Value> {
+    match response {
+        None => Err(fdo::Error::Failed("btwd is shutting down".into())),
+        Some(v) if v["ok"].as_bool() == Some(true) => Ok(v),
+        Some(v) => Err(fdo::Error::Failed(v["error"].as_str().unwrap_or("request failed").to_string())),
+    }
+}
+
+fn status(response: &Value) -> String {
+    response["status"].as_str().unwrap_or_default().to_string()
+}
+
+#[zbus::interface(name = "org.btw.Daemon")]
+impl Daemon {
+    /// Start listening, as if the wake word had fired.
+    fn listen(&self) -> fdo::Result<()> {
+        self.call(ControlCommand::Listen).map(drop)
+    }
+
+    /// Confirm the pending command; `request_id` must be the live one (see
+    /// `GetState`). Returns the execution status, e.g. `executed`.
+    fn confirm(&self, request_id: String) -> fdo::Result<String> {
+        self.call(ControlCommand::Confirm { request_id }).map(|v| status(&v))
+    }
+
+    /// Cancel whatever command is pending.
+    fn cancel(&self) -> fdo::Result<String> {
+        self.call(ControlCommand::Cancel { request_id: None }).map(|v| status(&v))
+    }
+
+    /// Stop speaking now (barge in over a long answer) and drop queued speech.
+    fn stop_speaking(&self) -> fdo::Result<()> {
+        self.call(ControlCommand::StopSpeaking).map(drop)
+    }
+
+    /// The same JSON document as `btwctl status`.
+    fn get_state(&self) -> fdo::Result<String> {
+        self.call(ControlCommand::State).map(|v| v.to_string())
+    }
+
+    /// `keyword` is the wake word's label from config.toml (may be empty).
+    #[zbus(signal)]
+    async fn wake_detected(ctxt: &zbus::SignalContext<'_>, keyword: &str) -> zbus::Result<()>;
+
+    /// `decision` is the transcript's decision type, as in the status file.
+    #[zbus(signal)]
+    async fn transcript_ready(ctxt: &zbus::SignalContext<'_>, text: &str, decision: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn command_executed(ctxt: &zbus::SignalContext<'_>, command_id: &str, output: &str) -> zbus::Result<()>;
+}
+
+/// Owns `org.btw.Daemon` on the session bus. zbus dispatches calls on its own
+/// thread; the main loop drains them with [`DbusService::try_recv`].
+pub struct DbusService {
+    connection: zbus::blocking::Connection,
+    requests: Receiver<Request>,
+}
+
+impl DbusService {
+    /// Fails without a session bus, or if another btwd already owns the name.
+    pub fn start() -> zbus::Result<Self> {
+        let (tx, requests) = mpsc::channel();
+        let connection = zbus::blocking::connection::Builder::session()?
+            .name(BUS_NAME)?
+            .serve_at(OBJECT_PATH, Daemon { requests: tx })?
+            .build()?;
+        Ok(Self { connection, requests })
+    }
+
+    pub fn try_recv(&self) -> Option<Request> {
+        self.requests.try_recv().ok()
+    }
+
+    pub fn signals(&self) -> Signals {
+        Signals { connection: Some(self.connection.clone()) }
+    }
+}
+
+/// Emits the `org.btw.Daemon` signals. A handle without a connection (no
+/// session bus) does nothing, so callers never have to check.
+#[derive(Clone, Default)]
+pub struct Signals {
+    connection: Option<zbus::blocking::Connection>,
+}
+
+impl Signals {
+    fn emit<B>(&self, name: &str, body: &B)
+    where
+        B: serde::Serialize + zbus::zvariant::DynamicType,
+    {
+        let Some(connection) = &self.connection else { return };
+        // Best-effort: a listener-less bus or a full queue never blocks the pipeline.
+        if let Err(e) = connection.emit_signal(None::<&str>, OBJECT_PATH, BUS_NAME, name, body) {
+            log::debug!("dbus: {} not emitted: {}", name, e);
+        }
+    }
+
+    pub fn wake_detected(&self, keyword: &str) {
+        self.emit("WakeDetected", &keyword);
+    }
+
+    pub fn transcript_ready(&self, text: &str, decision: &str) {
+        self.emit("TranscriptReady", &(text, decision));
+    }
+}
+
+impl ExecObserver for Signals {
+    fn on_executed(&self, id: &str, stdout: &str) {
+        self.emit("CommandExecuted", &(id, stdout));
+    }
+
+    fn on_pending(&self, _id: &str, _preview: &str, _deadline: Instant) {}
+
+    fn on_canceled(&self, _id: &str, _reason: &str) {}
+
+    fn on_rejected(&self, _reason: &str) {}
+
+    // A dry run executes nothing.
+    fn on_dry_run(&self, _id: &str, _command: &str) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn control_responses_map_to_method_results() {
+        let ok = reply(Some(json!({"ok": true, "status": "executed"}))).unwrap();
+        assert_eq!(status(&ok), "executed");
+        match reply(Some(json!({"ok": false, "error": "no command is pending"}))) {
+            Err(fdo::Error::Failed(msg)) => assert_eq!(msg, "no command is pending"),
+            other => panic!("expected Failed, got {:?}", other),
+        }
+        assert!(matches!(reply(None), Err(fdo::Error::Failed(_))));
+    }
+
+    #[test]
+    fn methods_are_queued_for_the_main_loop() {
+        let (tx, rx) = mpsc::channel();
+        let daemon = Daemon { requests: tx };
+        let loop_thread = std::thread::spawn(move || {
+            let req: Request = rx.recv().unwrap();
+            assert_eq!(req.command, Ok(ControlCommand::Cancel { request_id: None }));
+            req.respond(json!({"ok": true, "status": "canceled"}));
+        });
+        assert_eq!(daemon.cancel().unwrap(), "canceled");
+        loop_thread.join().unwrap();
+    }
+
+    #[test]
+    fn signals_without_a_bus_are_no_ops() {
+        let signals = Signals::default();
+        signals.wake_detected("btw");
+        signals.on_executed("volume_up", "");
+    }
+}
@@ -1,33 +1,199 @@
+use crate::config::{KeywordSpec, MatchMode};
 use crate::intent::IntentResult;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone)]
 pub enum Decision {
+    #[cfg_attr(not(test), allow(dead_code))]
     Command {
         intent: IntentResult,
         preview: String,
         requires_confirmation: bool,
     },
-    Question {
-        text: String,
-    },
-    WebQuery {
-        text: String,
+    Question,
+    WebQuery,
+    /// Two commands scored too close to call; ask which one was meant.
+    /// `options` are `(command_id, preview)`, best first.
+    Clarify {
+        options: Vec<(String, String)>,
     },
     Ignored,
 }
 
+/// A question starter or web-query keyword.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keyword {
+    pub text: String,
+    pub mode: MatchMode,
+}
+
+impl Keyword {
+    pub fn new(text: &str, mode: MatchMode) -> Self {
+        Self { text: text.to_string(), mode }
+    }
+
+    fn matches(&self, norm: &str) -> bool {
+        match self.mode {
+            MatchMode::StartsWith => norm.starts_with(&self.text),
+            MatchMode::Contains => norm.contains(&self.text),
+        }
+    }
+}
+
+pub fn default_question_starters() -> Vec<Keyword> {
+    ["what is", "whats", "who is", "why", "how", "when", "where", "explain", "tell me", "calculate", "solve"]
+        .iter()
+        .map(|k| Keyword::new(k, MatchMode::StartsWith))
+        .collect()
+}
+
+pub fn default_web_keywords() -> Vec<Keyword> {
+    ["weather", "news", "current time", "time is", "date is", "today", "stock", "price of"]
+        .iter()
+        .map(|k| Keyword::new(k, MatchMode::Contains))
+        .collect()
+}
+
+/// Add configured keywords to `defaults` (or replace them), using
+/// `default_mode` for entries that don't name one.
+pub fn merge_keywords(defaults: Vec<Keyword>, custom: &[KeywordSpec], default_mode: MatchMode, replace: bool) -> Vec<Keyword> {
+    let mut out = if replace { Vec::new() } else { defaults };
+    for spec in custom {
+        out.push(Keyword::new(spec.text(), spec.mode().unwrap_or(default_mode)));
+    }
+    out
+}
+
 #[derive(Debug, Clone)]
 pub struct DecisionConfig {
     pub deterministic_threshold: f32,
+    /// Per-command replacements for `deterministic_threshold`, by command id.
+    pub threshold_overrides: HashMap<String, f32>,
+    pub question_starters: Vec<Keyword>,
+    pub web_keywords: Vec<Keyword>,
+    /// Ask instead of picking when the top two deterministic scores are
+    /// within this margin and both pass the threshold (0 disables).
+    pub clarify_margin: f32,
+    /// Recent question/answer pairs prepended to the next question's
+    /// prompt so follow-ups like "what about tomorrow?" make sense (0 disables).
+    pub context_window: usize,
+    /// The conversation is forgotten this long after the last answer.
+    pub context_ttl: Duration,
+}
+
+impl DecisionConfig {
+    /// The deterministic threshold `command_id` has to reach.
+    pub fn threshold_for(&self, command_id: &str) -> f32 {
+        self.threshold_overrides.get(command_id).copied().unwrap_or(self.deterministic_threshold)
+    }
+
+    /// Built-in keyword lists with the given threshold.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn with_threshold(deterministic_threshold: f32) -> Self {
+        Self {
+            deterministic_threshold,
+            threshold_overrides: HashMap::new(),
+            question_starters: default_question_starters(),
+            web_keywords: default_web_keywords(),
+            clarify_margin: 0.08,
+            context_window: 0,
+            context_ttl: Duration::from_secs(120),
+        }
+    }
 }
 
 pub struct DecisionManager {
     cfg: DecisionConfig,
+    /// `(raw_text, answer)` for the last `context_window` answered questions.
+    context: VecDeque<(String, String)>,
+    /// When the newest `context` entry was recorded.
+    last_answer: Option<Instant>,
+}
+
+/// Trim, lowercase and collapse whitespace so entries compare against
+/// `normalize_input` output; empty entries and empty lists are rejected.
+fn validate_keywords(list: &mut Vec<Keyword>, name: &str) -> Result<(), String> {
+    for k in list.iter_mut() {
+        let text = k.text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+        if text.is_empty() {
+            return Err(format!("decision.{} contains an empty entry", name));
+        }
+        k.text = text;
+    }
+    let mut seen = Vec::new();
+    list.retain(|k| {
+        let dup = seen.contains(k);
+        if !dup {
+            seen.push(k.clone());
+        }
+        !dup
+    });
+    if list.is_empty() {
+        return Err(format!("decision.{} must not be empty", name));
+    }
+    Ok(())
 }
 
 impl DecisionManager {
-    pub fn new(cfg: DecisionConfig) -> Self {
-        Self { cfg }
+    pub fn new(mut cfg: DecisionConfig) -> Result<Self, String> {
+        validate_keywords(&mut cfg.question_starters, "question_starters")?;
+        validate_keywords(&mut cfg.web_keywords, "web_keywords")?;
+        Ok(Self { cfg, context: VecDeque::new(), last_answer: None })
+    }
+
+    /// Update the context window after acting on `decision`: an ignored
+    /// utterance ends the conversation.
+    pub fn observe(&mut self, decision: &Decision) {
+        if matches!(decision, Decision::Ignored) {
+            self.clear_context();
+        }
+    }
+
+    pub fn clear_context(&mut self) {
+        self.context.clear();
+        self.last_answer = None;
+    }
+
+    /// Whether a follow-up question would still get the context window.
+    pub fn has_context(&self) -> bool {
+        !self.context.is_empty()
+    }
+
+    /// Forget the conversation once `context_ttl` has passed since the last
+    /// answer; true if it was forgotten just now.
+    pub fn expire_context(&mut self, now: Instant) -> bool {
+        match self.last_answer {
+            Some(at) if now.saturating_duration_since(at) >= self.cfg.context_ttl => {
+                self.clear_context();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Remember an answered [`Decision::Question`]. Web queries and commands
+    /// are never recorded.
+    pub fn record_answer(&mut self, raw_text: &str, answer: &str) {
+        if self.cfg.context_window == 0 {
+            return;
+        }
+        self.context.push_back((raw_text.trim().to_string(), answer.trim().to_string()));
+        while self.context.len() > self.cfg.context_window {
+            self.context.pop_front();
+        }
+        self.last_answer = Some(Instant::now());
+    }
+
+    /// The prompt for `answer_short`: the context window as
+    /// `User: ...\nAssistant: ...\n` lines, then the question.
+    pub fn question_prompt(&self, question: &str) -> String {
+        let mut prompt = String::new();
+        for (user, assistant) in &self.context {
+            prompt.push_str(&format!("User: {}\nAssistant: {}\n", user, assistant));
+        }
+        prompt.push_str(question);
+        prompt
     }
 
     pub fn decide(&self, raw_text: &str, deterministic: IntentResult) -> Decision {
@@ -42,16 +208,12 @@ impl DecisionManager {
             if deterministic.intent_type == "command" || deterministic.intent_type == "dangerous_command" {
                 // Safety: the router must provide a score that meets threshold before treating this as a command.
                 // If unavailable, default to NOT executing.
+                // An embedding_score is only ever set by the router after it passed embedding_threshold.
                 let score = deterministic.deterministic_score.unwrap_or(0.0);
 
-                if score >= self.cfg.deterministic_threshold {
-                    let dangerous = deterministic.dangerous;
-                    let requires_confirmation = dangerous;
-                    let preview = if deterministic.parameters.is_object() && deterministic.parameters.as_object().map(|o| !o.is_empty()).unwrap_or(false) {
-                        format!("About to run: {} {}", command_id, deterministic.parameters)
-                    } else {
-                        format!("About to run: {}", command_id)
-                    };
+                if score >= self.cfg.threshold_for(command_id) || deterministic.embedding_score.is_some() {
+                    let requires_confirmation = deterministic.dangerous;
+                    let preview = preview_for(&deterministic);
                     return Decision::Command {
                         intent: deterministic,
                         preview,
@@ -66,18 +228,68 @@ impl DecisionManager {
         }
 
         // Step 4: Non-command handling.
-        if is_web_query(&normalized) {
-            return Decision::WebQuery { text: raw_text.trim().to_string() };
+        if self.is_web_query(&normalized) {
+            return Decision::WebQuery;
+        }
+        if self.is_question(&normalized) {
+            return Decision::Question;
         }
-        if is_question(&normalized) {
-            return Decision::Question { text: raw_text.trim().to_string() };
+
+        Decision::Question
+    }
+
+    /// [`decide`](Self::decide), but emits [`Decision::Clarify`] when
+    /// `runner_up` is a distinct command scoring within `clarify_margin` of a
+    /// deterministic best match. Never executes anything by itself.
+    pub fn decide_ranked(&self, raw_text: &str, best: IntentResult, runner_up: Option<IntentResult>) -> Decision {
+        let best_score = best.deterministic_score;
+        let decision = self.decide(raw_text, best);
+        let Some(second) = runner_up else { return decision };
+        let intent = match &decision {
+            Decision::Command { intent, .. } => intent.clone(),
+            _ => return decision,
+        };
+        let (Some(best_score), Some(second_score)) = (best_score, second.deterministic_score) else {
+            return decision;
+        };
+        let is_command = second.intent_type == "command" || second.intent_type == "dangerous_command";
+        if self.cfg.clarify_margin <= 0.0
+            || !is_command
+            || second.command_id.is_none()
+            || second.command_id == intent.command_id
+            || second.command_id.as_deref().is_some_and(|id| second_score < self.cfg.threshold_for(id))
+            || best_score - second_score > self.cfg.clarify_margin
+        {
+            return decision;
         }
+        let options = [&intent, &second]
+            .iter()
+            .filter_map(|i| Some((i.command_id.clone()?, preview_for(i))))
+            .collect();
+        Decision::Clarify { options }
+    }
+
+    fn is_question(&self, norm: &str) -> bool {
+        let t = norm.trim();
+        !t.is_empty() && self.cfg.question_starters.iter().any(|k| k.matches(t))
+    }
 
-        Decision::Question { text: raw_text.trim().to_string() }
+    fn is_web_query(&self, norm: &str) -> bool {
+        let t = norm.trim();
+        !t.is_empty() && self.cfg.web_keywords.iter().any(|k| k.matches(t))
     }
 }
 
-fn normalize_input(s: &str) -> String {
+fn preview_for(intent: &IntentResult) -> String {
+    let command_id = intent.command_id.as_deref().unwrap_or("unknown");
+    if !intent.parameters.is_empty() {
+        format!("About to run: {} ({})", command_id, intent.parameters)
+    } else {
+        format!("About to run: {}", command_id)
+    }
+}
+
+pub fn normalize_input(s: &str) -> String {
     // Lowercase + trim + normalize punctuation and basic number words.
     let mut cleaned = String::with_capacity(s.len());
     for ch in s.chars() {
@@ -89,74 +301,87 @@ fn normalize_input(s: &str) -> String {
     normalize_number_words(&cleaned)
 }
 
-fn normalize_number_words(s: &str) -> String {
-    // Minimal word->digit normalization for common speech.
-    // This is intentionally conservative and only handles 0-10.
-    let map = [
-        ("zero", "0"),
-        ("one", "1"),
-        ("two", "2"),
-        ("three", "3"),
-        ("four", "4"),
-        ("five", "5"),
-        ("six", "6"),
-        ("seven", "7"),
-        ("eight", "8"),
-        ("nine", "9"),
-        ("ten", "10"),
-        ("percent", "%"),
-    ];
-    let mut out: Vec<String> = Vec::new();
-    for tok in s.split_whitespace() {
-        let mut replaced = tok.to_string();
-        for (w, d) in map {
-            if tok == w {
-                replaced = d.to_string();
-                break;
+const UNITS: [&str; 20] = [
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten",
+    "eleven", "twelve", "thirteen", "fourteen", "fifteen", "sixteen", "seventeen", "eighteen", "nineteen",
+];
+const TENS: [&str; 8] = ["twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety"];
+
+/// A token after the first pass of [`normalize_number_words`].
+#[derive(Clone, Copy, PartialEq)]
+enum NumTok<'a> {
+    Word(&'a str),
+    /// zero through nineteen
+    Unit(u32),
+    /// twenty, thirty, ... ninety
+    Tens(u32),
+    Hundred,
+}
+
+fn number_token(tok: &str) -> NumTok<'_> {
+    if let Some(n) = UNITS.iter().position(|w| *w == tok) {
+        return NumTok::Unit(n as u32);
+    }
+    if let Some(n) = TENS.iter().position(|w| *w == tok) {
+        return NumTok::Tens(20 + 10 * n as u32);
+    }
+    if tok == "hundred" {
+        return NumTok::Hundred;
+    }
+    NumTok::Word(tok)
+}
+
+/// Fold the longest number phrase starting at `toks[0]`: `[unit] hundred
+/// [and]`, then `tens [unit]` or a single unit. Returns (value, tokens used).
+fn fold_number(toks: &[NumTok]) -> Option<(u32, usize)> {
+    let at = |i: usize| toks.get(i).copied().unwrap_or(NumTok::Word(""));
+    let (mut value, mut i) = match (at(0), at(1)) {
+        (NumTok::Unit(u @ 1..=9), NumTok::Hundred) => (u * 100, 2),
+        (NumTok::Hundred, _) => (100, 1),
+        _ => (0, 0),
+    };
+    let hundreds = i > 0;
+    if hundreds && at(i) == NumTok::Word("and") && matches!(at(i + 1), NumTok::Unit(1..=19) | NumTok::Tens(_)) {
+        i += 1;
+    }
+    match at(i) {
+        NumTok::Tens(t) => {
+            value += t;
+            i += 1;
+            if let NumTok::Unit(u @ 1..=9) = at(i) {
+                value += u;
+                i += 1;
             }
         }
-        out.push(replaced);
+        // "one hundred zero" is not a number; leave the zero on its own.
+        NumTok::Unit(u) if !(hundreds && u == 0) => {
+            value += u;
+            i += 1;
+        }
+        _ => {}
     }
-    out.join(" ")
+    (i > 0).then_some((value, i))
 }
 
-fn is_question(norm: &str) -> bool {
-    let t = norm.trim();
-    if t.is_empty() {
-        return false;
-    }
-    let starters = [
-        "what is",
-        "whats",
-        "who is",
-        "why",
-        "how",
-        "when",
-        "where",
-        "explain",
-        "tell me",
-        "calculate",
-        "solve",
-    ];
-    starters.iter().any(|s| t.starts_with(s))
-}
-
-fn is_web_query(norm: &str) -> bool {
-    let t = norm.trim();
-    if t.is_empty() {
-        return false;
-    }
-    let keywords = [
-        "weather",
-        "news",
-        "current time",
-        "time is",
-        "date is",
-        "today",
-        "stock",
-        "price of",
-    ];
-    keywords.iter().any(|k| t.contains(k))
+pub fn normalize_number_words(s: &str) -> String {
+    // Word->digit normalization for common speech, 0-999.
+    // Pass 1 classifies number words; pass 2 combines adjacent ones
+    // ("twenty five" -> 25, "one hundred" -> 100). Other tokens pass through.
+    let toks: Vec<NumTok> = s.split_whitespace().map(number_token).collect();
+    let mut out: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < toks.len() {
+        if let Some((value, used)) = fold_number(&toks[i..]) {
+            out.push(value.to_string());
+            i += used;
+            continue;
+        }
+        if let NumTok::Word(w) = toks[i] {
+            out.push(if w == "percent" { "%".to_string() } else { w.to_string() });
+        }
+        i += 1;
+    }
+    out.join(" ")
 }
 
 #[cfg(test)]
@@ -167,8 +392,9 @@ mod tests {
         IntentResult {
             intent_type: "unknown_intent".into(),
             command_id: None,
-            parameters: serde_json::json!({}),
+            parameters: crate::params::Params::new(),
             deterministic_score: score,
+            embedding_score: None,
             dangerous: false,
             requires_confirmation: false,
         }
@@ -178,8 +404,9 @@ mod tests {
         IntentResult {
             intent_type: if dangerous { "dangerous_command".into() } else { "command".into() },
             command_id: Some(id.to_string()),
-            parameters: serde_json::json!({}),
+            parameters: crate::params::Params::new(),
             deterministic_score: Some(score),
+            embedding_score: None,
             dangerous,
             requires_confirmation: dangerous,
         }
@@ -187,33 +414,41 @@ mod tests {
 
     #[test]
     fn non_command_question_never_becomes_command() {
-        let dm = DecisionManager::new(DecisionConfig { deterministic_threshold: 0.75 });
+        let dm = DecisionManager::new(DecisionConfig::with_threshold(0.75)).unwrap();
         let det = IntentResult {
             intent_type: "unknown_intent".into(),
             command_id: None,
-            parameters: serde_json::json!({}),
+            parameters: crate::params::Params::new(),
             deterministic_score: None,
+            embedding_score: None,
             dangerous: false,
             requires_confirmation: false,
         };
         let d = dm.decide("what is two plus two", det);
-        assert!(matches!(d, Decision::Question { .. }));
+        assert!(matches!(d, Decision::Question));
     }
 
     #[test]
     fn deterministic_below_threshold_is_not_command() {
-        let dm = DecisionManager::new(DecisionConfig { deterministic_threshold: 0.75 });
+        let dm = DecisionManager::new(DecisionConfig::with_threshold(0.75)).unwrap();
         let det = intent_command("brightness_set", 0.50, false);
         let d = dm.decide("set brightness to 40 percent", det);
-        match d {
-            Decision::Command { .. } => panic!("should not accept below threshold"),
-            _ => {}
-        }
+        assert!(!matches!(d, Decision::Command { .. }), "should not accept below threshold");
+    }
+
+    #[test]
+    fn threshold_overrides_apply_per_command() {
+        let mut cfg = DecisionConfig::with_threshold(0.75);
+        cfg.threshold_overrides = HashMap::from([("system_reboot".to_string(), 0.95), ("volume_up".to_string(), 0.6)]);
+        let dm = DecisionManager::new(cfg).unwrap();
+        assert!(!matches!(dm.decide("reboot the computer", intent_command("system_reboot", 0.9, true)), Decision::Command { .. }));
+        assert!(matches!(dm.decide("volume up a bit", intent_command("volume_up", 0.65, false)), Decision::Command { .. }));
+        assert!(!matches!(dm.decide("brightness", intent_command("brightness_set", 0.65, false)), Decision::Command { .. }));
     }
 
     #[test]
     fn deterministic_above_threshold_becomes_command() {
-        let dm = DecisionManager::new(DecisionConfig { deterministic_threshold: 0.75 });
+        let dm = DecisionManager::new(DecisionConfig::with_threshold(0.75)).unwrap();
         let det = intent_command("brightness_set", 0.90, false);
         let d = dm.decide("set brightness to 40 percent", det);
         match d {
@@ -224,8 +459,186 @@ mod tests {
     
     #[test]
     fn news_question_routes_to_web_query() {
-        let mgr = DecisionManager::new(DecisionConfig { deterministic_threshold: 0.75 });
+        let mgr = DecisionManager::new(DecisionConfig::with_threshold(0.75)).unwrap();
         let d = mgr.decide("What's in news today?", dummy_intent(None));
-        assert!(matches!(d, Decision::WebQuery { .. }));
+        assert!(matches!(d, Decision::WebQuery));
+    }
+
+    #[test]
+    fn custom_web_keyword_changes_the_decision() {
+        let text = "translate good morning to hindi";
+        let stock = DecisionManager::new(DecisionConfig::with_threshold(0.75)).unwrap();
+        assert!(matches!(stock.decide(text, dummy_intent(None)), Decision::Question));
+
+        let mut cfg = DecisionConfig::with_threshold(0.75);
+        cfg.web_keywords = merge_keywords(
+            cfg.web_keywords,
+            &[KeywordSpec::Entry { text: "  Translate ".into(), mode: Some(MatchMode::StartsWith) }],
+            MatchMode::Contains,
+            false,
+        );
+        let custom = DecisionManager::new(cfg).unwrap();
+        assert!(matches!(custom.decide(text, dummy_intent(None)), Decision::WebQuery));
+        // Defaults were merged, not replaced.
+        assert!(matches!(custom.decide("weather in pune", dummy_intent(None)), Decision::WebQuery));
+        // starts_with: no match mid-sentence.
+        assert!(matches!(custom.decide("please translate this", dummy_intent(None)), Decision::Question));
+    }
+
+    #[test]
+    fn replacing_defaults_drops_builtin_keywords() {
+        let mut cfg = DecisionConfig::with_threshold(0.75);
+        cfg.web_keywords = merge_keywords(cfg.web_keywords, &[KeywordSpec::Text("tonight".into())], MatchMode::Contains, true);
+        let dm = DecisionManager::new(cfg).unwrap();
+        assert!(matches!(dm.decide("any gigs tonight", dummy_intent(None)), Decision::WebQuery));
+        assert!(matches!(dm.decide("weather in pune", dummy_intent(None)), Decision::Question));
+    }
+
+    #[test]
+    fn invalid_keyword_lists_are_rejected() {
+        let mut cfg = DecisionConfig::with_threshold(0.75);
+        cfg.question_starters.push(Keyword::new("   ", MatchMode::StartsWith));
+        assert!(DecisionManager::new(cfg).is_err());
+
+        let mut cfg = DecisionConfig::with_threshold(0.75);
+        cfg.web_keywords.clear();
+        assert!(DecisionManager::new(cfg).err().unwrap().contains("web_keywords must not be empty"));
+    }
+
+    fn ranked(best: f32, second: f32, margin: f32) -> Decision {
+        let mut cfg = DecisionConfig::with_threshold(0.5);
+        cfg.clarify_margin = margin;
+        let dm = DecisionManager::new(cfg).unwrap();
+        dm.decide_ranked(
+            "turn it up",
+            intent_command("volume_up", best, false),
+            Some(intent_command("brightness_up", second, false)),
+        )
+    }
+
+    #[test]
+    fn close_scores_ask_for_clarification() {
+        match ranked(0.62, 0.60, 0.08) {
+            Decision::Clarify { options } => {
+                let ids: Vec<&str> = options.iter().map(|(id, _)| id.as_str()).collect();
+                assert_eq!(ids, vec!["volume_up", "brightness_up"]);
+                assert!(options[0].1.contains("volume_up"));
+            }
+            other => panic!("expected Clarify, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn clarify_margin_boundaries() {
+        // Exactly representable values: 0.75 - 0.625 == 0.125.
+        assert!(matches!(ranked(0.75, 0.625, 0.125), Decision::Clarify { .. }));
+        assert!(matches!(ranked(0.75, 0.5625, 0.125), Decision::Command { .. }));
+        // Runner-up below the threshold is never offered.
+        assert!(matches!(ranked(0.52, 0.49, 0.08), Decision::Command { .. }));
+        // Margin 0 disables clarification, even for a tie.
+        assert!(matches!(ranked(0.7, 0.7, 0.0), Decision::Command { .. }));
+    }
+
+    #[test]
+    fn same_command_runner_up_is_not_a_choice() {
+        let dm = DecisionManager::new(DecisionConfig::with_threshold(0.5)).unwrap();
+        let d = dm.decide_ranked(
+            "turn it up",
+            intent_command("volume_up", 0.7, false),
+            Some(intent_command("volume_up", 0.7, false)),
+        );
+        assert!(matches!(d, Decision::Command { .. }));
+    }
+
+    /// 1..=999 spelled the way speech-to-text writes it ("nine hundred ninety nine").
+    fn spell(n: u32) -> String {
+        let mut words = Vec::new();
+        if n >= 100 {
+            words.push(UNITS[(n / 100) as usize].to_string());
+            words.push("hundred".to_string());
+        }
+        match n % 100 {
+            0 => {}
+            r @ 1..=19 => words.push(UNITS[r as usize].to_string()),
+            r => {
+                words.push(TENS[(r / 10 - 2) as usize].to_string());
+                if r % 10 != 0 {
+                    words.push(UNITS[(r % 10) as usize].to_string());
+                }
+            }
+        }
+        words.join(" ")
+    }
+
+    #[test]
+    fn context_window_keeps_recent_answers_and_resets_on_ignored() {
+        let mut cfg = DecisionConfig::with_threshold(0.75);
+        assert_eq!(DecisionManager::new(cfg.clone()).unwrap().question_prompt("hi"), "hi");
+        cfg.context_window = 2;
+        let mut dm = DecisionManager::new(cfg).unwrap();
+        dm.record_answer("what's the weather in paris?", "Sunny, 21 degrees.");
+        assert_eq!(
+            dm.question_prompt("what about tomorrow?"),
+            "User: what's the weather in paris?\nAssistant: Sunny, 21 degrees.\nwhat about tomorrow?"
+        );
+        dm.record_answer("what about tomorrow?", "Rain.");
+        dm.record_answer("and sunday?", "Clear.");
+        let prompt = dm.question_prompt("thanks");
+        assert!(!prompt.contains("paris") && prompt.starts_with("User: what about tomorrow?"), "{}", prompt);
+
+        let d = dm.decide("hmm", dummy_intent(None));
+        dm.observe(&d);
+        assert!(dm.question_prompt("thanks").starts_with("User:"));
+        dm.observe(&dm.decide("   ", dummy_intent(None)));
+        assert_eq!(dm.question_prompt("thanks"), "thanks");
+
+        let mut off = DecisionManager::new(DecisionConfig::with_threshold(0.75)).unwrap();
+        off.record_answer("q", "a");
+        assert_eq!(off.question_prompt("next"), "next");
+        assert!(!off.has_context());
+    }
+
+    #[test]
+    fn context_expires_after_the_ttl() {
+        let cfg = DecisionConfig { context_window: 2, context_ttl: Duration::from_secs(60), ..DecisionConfig::with_threshold(0.75) };
+        let mut dm = DecisionManager::new(cfg).unwrap();
+        dm.record_answer("what's the weather in paris?", "Sunny.");
+        let now = Instant::now();
+        assert!(!dm.expire_context(now + Duration::from_secs(59)));
+        assert!(dm.has_context());
+        assert!(dm.expire_context(now + Duration::from_secs(61)));
+        assert_eq!(dm.question_prompt("what about tomorrow?"), "what about tomorrow?");
+        assert!(!dm.expire_context(now + Duration::from_secs(120)));
+    }
+
+    #[test]
+    fn number_words_cover_eleven_to_nine_hundred_ninety_nine() {
+        for n in 11..=999 {
+            let spoken = spell(n);
+            assert_eq!(normalize_number_words(&spoken), n.to_string(), "{}", spoken);
+        }
+    }
+
+    #[test]
+    fn number_word_boundaries_and_context() {
+        let cases = [
+            ("zero", "0"),
+            ("ten", "10"),
+            ("twenty", "20"),
+            ("one hundred", "100"),
+            ("hundred", "100"),
+            ("nine hundred ninety nine", "999"),
+            ("one hundred and five", "105"),
+            ("set brightness to fifty percent", "set brightness to 50 %"),
+            ("twenty five thirty", "25 30"),
+            ("one two three", "1 2 3"),
+            ("one hundred zero", "100 0"),
+            ("hundred and then", "100 and then"),
+        ];
+        for (input, want) in cases {
+            assert_eq!(normalize_number_words(input), want, "{}", input);
+        }
+        assert_eq!(normalize_input("Set volume to Sixty-Five!"), "set volume to sixtyfive");
+        assert_eq!(normalize_input("set volume to sixty five"), "set volume to 65");
     }
 }
@@ -0,0 +1,260 @@
+use crate::error::{BtwError, Result};
+use crate::intent::IntentCommand;
+use crate::ml::MLWorker;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Embedding of a single command example.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExampleVector {
+    pub command_id: String,
+    pub example: String,
+    pub vector: Vec<f32>,
+}
+
+/// On-disk cache layout. `hash` covers the model name and every example, so
+/// editing commands.json (or switching models) invalidates it.
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    hash: String,
+    entries: Vec<ExampleVector>,
+}
+
+/// Precomputed example embeddings used as the middle intent tier
+/// (between token overlap and the LLM fallback).
+pub struct EmbeddingIndex {
+    entries: Vec<ExampleVector>,
+}
+
+impl EmbeddingIndex {
+    pub fn new(entries: Vec<ExampleVector>) -> Self {
+        Self { entries }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Best cosine similarity per command, sorted descending.
+    pub fn rank(&self, query: &[f32]) -> Vec<(String, f32)> {
+        let mut best: Vec<(String, f32)> = Vec::new();
+        for e in &self.entries {
+            let score = cosine(query, &e.vector);
+            match best.iter_mut().find(|slot| slot.0 == e.command_id) {
+                Some(slot) => {
+                    if score > slot.1 {
+                        slot.1 = score;
+                    }
+                }
+                None => best.push((e.command_id.clone(), score)),
+            }
+        }
+        best.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        best
+    }
+
+    pub fn best(&self, query: &[f32]) -> Option<(String, f32)> {
+        self.rank(query).into_iter().next()
+    }
+}
+
+/// Cosine similarity; 0.0 for mismatched lengths or zero vectors.
+pub fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let mut dot = 0.0f32;
+    let mut na = 0.0f32;
+    let mut nb = 0.0f32;
+    for (x, y) in a.iter().zip(b) {
+        dot += x * y;
+        na += x * x;
+        nb += y * y;
+    }
+    if na == 0.0 || nb == 0.0 {
+        return 0.0;
+    }
+    dot / (na.sqrt() * nb.sqrt())
+}
+
+fn fnv1a(h: &mut u64, bytes: &[u8]) {
+    for &b in bytes {
+        *h ^= b as u64;
+        *h = h.wrapping_mul(0x100000001b3);
+    }
+}
+
+/// Stable hash of the embedding model and all command examples.
+///
+/// FNV-1a rather than `DefaultHasher` so the cache key survives toolchain upgrades.
+pub fn examples_hash(model: &str, commands: &[IntentCommand]) -> String {
+    let mut h: u64 = 0xcbf29ce484222325;
+    fnv1a(&mut h, model.as_bytes());
+    fnv1a(&mut h, &[0]);
+    for c in commands {
+        fnv1a(&mut h, c.id.as_bytes());
+        fnv1a(&mut h, &[0]);
+        for ex in &c.examples {
+            fnv1a(&mut h, ex.as_bytes());
+            fnv1a(&mut h, &[0]);
+        }
+        fnv1a(&mut h, &[1]);
+    }
+    format!("{:016x}", h)
+}
+
+fn load_cached(path: &Path, hash: &str) -> Option<EmbeddingIndex> {
+    let s = std::fs::read_to_string(path).ok()?;
+    let cache: CacheFile = serde_json::from_str(&s).ok()?;
+    if cache.hash != hash {
+        return None;
+    }
+    Some(EmbeddingIndex::new(cache.entries))
+}
+
+fn save_cached(path: &Path, hash: &str, entries: &[ExampleVector]) {
+    // Best-effort: a failed write only costs a recompute on next startup.
+    let cache = CacheFile { hash: hash.to_string(), entries: entries.to_vec() };
+    match serde_json::to_string(&cache) {
+        Ok(s) => {
+            if let Err(e) = std::fs::write(path, s) {
+                log::warn!("intent: failed to write embedding cache {}: {}", path.display(), e);
+            }
+        }
+        Err(e) => log::warn!("intent: failed to serialize embedding cache: {}", e),
+    }
+}
+
+/// One entry per example; a worker that answers with a different number of
+/// vectors than it was sent texts is an error, not a shorter index.
+fn pair_vectors(ids: Vec<String>, texts: Vec<String>, vectors: Vec<Vec<f32>>) -> Result<Vec<ExampleVector>> {
+    if vectors.len() != texts.len() {
+        return Err(BtwError::ParseError {
+            path: PathBuf::new(),
+            kind: "ml",
+            message: format!("embed returned {} vectors for {} examples", vectors.len(), texts.len()),
+            cause: None,
+        });
+    }
+    Ok(ids
+        .into_iter()
+        .zip(texts)
+        .zip(vectors)
+        .map(|((command_id, example), vector)| ExampleVector { command_id, example, vector })
+        .collect())
+}
+
+/// Build the example index, reusing `cache_path` when its hash still matches.
+pub fn build_index(worker: &mut MLWorker, commands: &[IntentCommand], cache_path: Option<&Path>) -> Result<EmbeddingIndex> {
+    let model = worker.embed_model().unwrap_or("unknown").to_string();
+    let hash = examples_hash(&model, commands);
+
+    if let Some(path) = cache_path {
+        if let Some(index) = load_cached(path, &hash) {
+            log::info!("intent: loaded {} example embeddings from cache", index.len());
+            return Ok(index);
+        }
+    }
+
+    let mut ids: Vec<String> = Vec::new();
+    let mut texts: Vec<String> = Vec::new();
+    for c in commands {
+        for ex in &c.examples {
+            if ex.trim().is_empty() {
+                continue;
+            }
+            ids.push(c.id.clone());
+            texts.push(ex.clone());
+        }
+    }
+
+    let vectors = if texts.is_empty() { Vec::new() } else { worker.embed(&texts)? };
+    let entries = pair_vectors(ids, texts, vectors)?;
+
+    if let Some(path) = cache_path {
+        save_cached(path, &hash, &entries);
+    }
+    log::info!("intent: computed {} example embeddings (model={})", entries.len(), model);
+    Ok(EmbeddingIndex::new(entries))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ev(id: &str, example: &str, vector: &[f32]) -> ExampleVector {
+        ExampleVector { command_id: id.into(), example: example.into(), vector: vector.to_vec() }
+    }
+
+    fn fixture_index() -> EmbeddingIndex {
+        EmbeddingIndex::new(vec![
+            ev("brightness_down", "dim the screen", &[0.9, 0.1, 0.0]),
+            ev("brightness_down", "decrease brightness", &[0.8, 0.3, 0.1]),
+            ev("volume_down", "turn it down", &[0.1, 0.9, 0.2]),
+            ev("wifi_off", "disable wifi", &[0.0, 0.1, 0.95]),
+        ])
+    }
+
+    #[test]
+    fn cosine_basics() {
+        assert!((cosine(&[1.0, 0.0], &[1.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert!(cosine(&[1.0, 0.0], &[0.0, 1.0]).abs() < 1e-6);
+        assert_eq!(cosine(&[1.0, 0.0], &[1.0]), 0.0);
+        assert_eq!(cosine(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn rank_keeps_best_example_per_command() {
+        let index = fixture_index();
+        // "make the screen less bright"
+        let ranked = index.rank(&[0.85, 0.15, 0.05]);
+        assert_eq!(ranked.len(), 3);
+        assert_eq!(ranked[0].0, "brightness_down");
+        assert_eq!(ranked[1].0, "volume_down");
+        assert_eq!(ranked[2].0, "wifi_off");
+        assert!(ranked[0].1 > 0.99);
+        assert!(ranked.windows(2).all(|w| w[0].1 >= w[1].1));
+    }
+
+    #[test]
+    fn hash_changes_with_examples_and_model() {
+        let mut cmds = vec![IntentCommand {
+            id: "a".into(),
+            description: String::new(),
+            examples: vec!["one".into()],
+            dangerous: false,
+            priority: 0,
+            alias_of: None,
+            slots: Vec::new(),
+        }];
+        let h1 = examples_hash("m", &cmds);
+        assert_eq!(h1, examples_hash("m", &cmds));
+        assert_ne!(h1, examples_hash("other", &cmds));
+        cmds[0].examples.push("two".into());
+        assert_ne!(h1, examples_hash("m", &cmds));
+    }
+
+    #[test]
+    fn vector_count_must_match_the_examples() {
+        let ids = vec!["a".to_string(), "b".to_string()];
+        let texts = vec!["one".to_string(), "two".to_string()];
+        let entries = pair_vectors(ids.clone(), texts.clone(), vec![vec![1.0], vec![0.5]]).unwrap();
+        assert_eq!((entries[1].command_id.as_str(), entries[1].example.as_str()), ("b", "two"));
+        let err = pair_vectors(ids, texts, vec![vec![1.0]]).unwrap_err();
+        assert!(err.to_string().contains("1 vectors for 2 examples"), "{}", err);
+    }
+
+    #[test]
+    fn cache_roundtrip_respects_hash() {
+        let path = std::env::temp_dir().join(format!("btwd-embed-cache-{}.json", std::process::id()));
+        let entries = vec![ev("a", "one", &[1.0, 0.0])];
+        save_cached(&path, "abc", &entries);
+        assert_eq!(load_cached(&path, "abc").map(|i| i.len()), Some(1));
+        assert!(load_cached(&path, "def").is_none());
+        let _ = std::fs::remove_file(&path);
+    }
+}
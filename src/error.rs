@@ -1,84 +1,200 @@
-use std::{fmt, io, path::PathBuf};
+use std::{io, path::PathBuf, time::Duration};
 
 /// Unified error type for btwd startup failures
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
 pub enum BtwError {
     /// Required file is missing
+    #[error("Missing required {kind} file: {}", path.display())]
     MissingFile { path: PathBuf, kind: &'static str },
     /// I/O error while reading a file
-    ReadError { path: PathBuf, source: io::Error },
+    #[error("Failed to read file {}: {source}", path.display())]
+    ReadError {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
     /// Parse error for config or commands
-    ParseError { path: PathBuf, kind: &'static str, message: String },
+    #[error("Failed to parse {kind} file {}: {message}", path.display())]
+    ParseError {
+        path: PathBuf,
+        kind: &'static str,
+        message: String,
+        /// Underlying error, when there is one (serde, io, ...)
+        #[source]
+        cause: Option<Box<dyn std::error::Error + Send + Sync>>,
+    },
     /// .env loading error
-    EnvLoadError { path: PathBuf, source: dotenvy::Error },
+    #[error("Failed to load environment from {}: {source}", path.display())]
+    EnvLoadError {
+        path: PathBuf,
+        #[source]
+        source: dotenvy::Error,
+    },
     /// XDG path resolution errors
+    #[error("XDG path resolution error: {message}")]
     XdgError { message: String },
 
     /// Porcupine initialization failed (often due to incorrect arguments or missing files)
+    #[error(
+        "Porcupine init failed (status={status}){}",
+        if messages.is_empty() { String::new() } else { format!(": {}", messages.join(" | ")) }
+    )]
     PorcupineInitFailed { status: i32, messages: Vec<String> },
-}
 
-impl fmt::Display for BtwError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            BtwError::MissingFile { path, kind } => {
-                write!(
-                    f,
-                    "Missing required {} file: {}",
-                    kind,
-                    path.display()
-                )
-            }
-            BtwError::ReadError { path, source } => {
-                write!(
-                    f,
-                    "Failed to read file {}: {}",
-                    path.display(),
-                    source
-                )
-            }
-            BtwError::ParseError { path, kind, message } => {
-                write!(
-                    f,
-                    "Failed to parse {} file {}: {}",
-                    kind,
-                    path.display(),
-                    message
-                )
-            }
-            BtwError::EnvLoadError { path, source } => {
-                write!(
-                    f,
-                    "Failed to load environment from {}: {}",
-                    path.display(),
-                    source
-                )
-            }
-            BtwError::XdgError { message } => {
-                write!(f, "XDG path resolution error: {}", message)
-            }
-
-            BtwError::PorcupineInitFailed { status, messages } => {
-                if messages.is_empty() {
-                    write!(f, "Porcupine init failed (status={})", status)
-                } else {
-                    write!(f, "Porcupine init failed (status={}): {}", status, messages.join(" | "))
-                }
-            }
+    /// ML worker keeps failing; requests are refused until `retry_in` elapses
+    #[error(
+        "ML worker unavailable after {failures} consecutive failures; next retry in {:.1}s (last error: {last_error})",
+        retry_in.as_secs_f32()
+    )]
+    WorkerUnavailable { failures: u32, retry_in: Duration, last_error: String },
 
-        }
-    }
+    /// The ML worker process could not be started
+    #[error("Failed to start ML worker: {source}")]
+    WorkerSpawn {
+        #[source]
+        source: io::Error,
+    },
+    /// The ML worker broke the protocol or reported a failure
+    #[error("ML worker error: {message}")]
+    Worker { message: String },
+    /// No transcription arrived in time (the worker was respawned)
+    #[error("ASR timed out after {:.1}s", elapsed.as_secs_f32())]
+    AsrTimeout { elapsed: Duration },
+    /// The in-process ASR engine failed
+    #[cfg_attr(not(feature = "whisper"), allow(dead_code))]
+    #[error("ASR error: {message}")]
+    Asr { message: String },
+
+    /// A command could not be started or exited unsuccessfully; `status` is
+    /// None when it never ran or was killed by a signal
+    #[error(
+        "Command '{id}' failed ({}){}",
+        status.map(|c| format!("exit code {}", c)).unwrap_or_else(|| "no exit code".into()),
+        if stderr.trim().is_empty() { String::new() } else { format!(": {}", stderr.trim()) }
+    )]
+    ExecFailed { id: String, status: Option<i32>, stderr: String },
+
+    /// Audio input could not be opened or was lost
+    #[error("Audio device error: {message}")]
+    AudioDevice { message: String },
+
+    /// An LLM provider is unusable (missing key) or a request to it failed
+    #[error("LLM error ({provider}): {message}")]
+    LlmError { provider: &'static str, message: String },
+
+    /// Porcupine failed outside of `pv_porcupine_init` (access key, processing)
+    #[error("Porcupine error: {message}")]
+    Porcupine { message: String },
+
+    /// A config value is unusable for a reason only found while starting up
+    #[error("Invalid {key}: {message}")]
+    InvalidSetting { key: &'static str, message: String },
+
+    /// A signal handler could not be installed
+    #[error("Failed to install handler for {signal}: {source}")]
+    SignalHandler {
+        signal: &'static str,
+        #[source]
+        source: io::Error,
+    },
+}
+
+/// Exit codes for startup failures, so a systemd unit (`RestartPreventExitStatus=`)
+/// or a monitor can tell the classes apart. Values follow sysexits.h.
+pub mod exit_code {
+    /// Anything not listed below
+    pub const OTHER: i32 = 1;
+    /// Worker or LLM provider unavailable (EX_UNAVAILABLE)
+    pub const UNAVAILABLE: i32 = 69;
+    /// Audio device missing or lost (EX_IOERR)
+    pub const AUDIO: i32 = 74;
+    /// Porcupine rejected the access key, model or keywords (EX_NOPERM)
+    pub const PORCUPINE: i32 = 77;
+    /// Config, commands or .env missing or invalid (EX_CONFIG)
+    pub const CONFIG: i32 = 78;
 }
 
-impl std::error::Error for BtwError {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+impl BtwError {
+    /// The process exit code for this error; see [`exit_code`].
+    pub fn exit_code(&self) -> i32 {
         match self {
-            BtwError::ReadError { source, .. } => Some(source),
-            BtwError::EnvLoadError { source, .. } => Some(source),
-            _ => None,
+            BtwError::MissingFile { .. }
+            | BtwError::ReadError { .. }
+            | BtwError::ParseError { .. }
+            | BtwError::EnvLoadError { .. }
+            | BtwError::XdgError { .. }
+            | BtwError::InvalidSetting { .. } => exit_code::CONFIG,
+            BtwError::PorcupineInitFailed { .. } | BtwError::Porcupine { .. } => exit_code::PORCUPINE,
+            BtwError::AudioDevice { .. } => exit_code::AUDIO,
+            BtwError::WorkerUnavailable { .. }
+            | BtwError::WorkerSpawn { .. }
+            | BtwError::Worker { .. }
+            | BtwError::AsrTimeout { .. }
+            | BtwError::Asr { .. }
+            | BtwError::LlmError { .. } => exit_code::UNAVAILABLE,
+            BtwError::ExecFailed { .. } | BtwError::SignalHandler { .. } => exit_code::OTHER,
         }
     }
 }
 
 /// Convenient result alias for btwd
 pub type Result<T> = std::result::Result<T, BtwError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+
+    #[test]
+    fn parse_error_exposes_its_cause() {
+        let json_err = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+        let e = BtwError::ParseError {
+            path: PathBuf::from("/tmp/commands.json"),
+            kind: "json",
+            message: json_err.to_string(),
+            cause: Some(Box::new(json_err)),
+        };
+        assert!(e.to_string().starts_with("Failed to parse json file /tmp/commands.json: "));
+        assert!(e.source().unwrap().is::<serde_json::Error>());
+
+        let bare = BtwError::ParseError { path: PathBuf::new(), kind: "ml", message: "x".into(), cause: None };
+        assert!(bare.source().is_none());
+    }
+
+    #[test]
+    fn display_matches_previous_wording() {
+        let e = BtwError::PorcupineInitFailed { status: 3, messages: vec![] };
+        assert_eq!(e.to_string(), "Porcupine init failed (status=3)");
+        let e = BtwError::PorcupineInitFailed { status: 3, messages: vec!["a".into(), "b".into()] };
+        assert_eq!(e.to_string(), "Porcupine init failed (status=3): a | b");
+        let e = BtwError::ReadError { path: PathBuf::from("/x"), source: io::Error::new(io::ErrorKind::NotFound, "gone") };
+        assert_eq!(e.to_string(), "Failed to read file /x: gone");
+        assert!(e.source().is_some());
+    }
+
+    #[test]
+    fn variants_describe_their_failure_and_map_to_exit_codes() {
+        let e = BtwError::ExecFailed { id: "lock_screen".into(), status: Some(2), stderr: "no session\n".into() };
+        assert_eq!(e.to_string(), "Command 'lock_screen' failed (exit code 2): no session");
+        assert_eq!(e.exit_code(), exit_code::OTHER);
+        let e = BtwError::ExecFailed { id: "x".into(), status: None, stderr: String::new() };
+        assert_eq!(e.to_string(), "Command 'x' failed (no exit code)");
+
+        let e = BtwError::AsrTimeout { elapsed: Duration::from_millis(30_000) };
+        assert_eq!(e.to_string(), "ASR timed out after 30.0s");
+        let e = BtwError::WorkerSpawn { source: io::Error::new(io::ErrorKind::NotFound, "no python") };
+        assert_eq!(e.to_string(), "Failed to start ML worker: no python");
+        assert!(e.source().unwrap().is::<io::Error>());
+        assert_eq!(e.exit_code(), exit_code::UNAVAILABLE);
+
+        let e = BtwError::LlmError { provider: "groq", message: "missing GROQ_API_KEY".into() };
+        assert_eq!(e.to_string(), "LLM error (groq): missing GROQ_API_KEY");
+        let config = BtwError::ParseError { path: PathBuf::from("/c.toml"), kind: "toml", message: "x".into(), cause: None };
+        let license = BtwError::PorcupineInitFailed { status: 5, messages: vec![] };
+        let audio = BtwError::AudioDevice { message: "no input".into() };
+        assert_eq!(
+            [config.exit_code(), license.exit_code(), audio.exit_code()],
+            [exit_code::CONFIG, exit_code::PORCUPINE, exit_code::AUDIO]
+        );
+    }
+}
@@ -1,6 +1,10 @@
 use crate::error::{BtwError, Result};
 use crate::intent::IntentResult;
+use crate::decision::normalize_input;
+use crate::manager::{classify_confirmation, VoiceAnswer};
+use crate::params::{Params, Provenance, Validation};
 use serde::Deserialize;
+use serde_json::Value;
 use std::collections::HashMap;
 use std::path::Path;
 use std::process::Command;
@@ -14,26 +18,118 @@ pub struct ExecCommand {
     pub description: String,
     #[serde(default)]
     pub dangerous: bool,
-    #[serde(default)]
+    /// Name → spec string; the object form is reduced to its spec.
+    #[serde(default, deserialize_with = "param_specs")]
     pub parameters: HashMap<String, String>,
     pub shell_command_template: String,
+    /// Environment variables passed to the program; replaces
+    /// [`ExecutionCfg::default_env_allowlist`] for this command.
+    #[serde(default)]
+    pub env_allowlist: Option<Vec<String>>,
+    /// When this command asks first; replaces [`ExecutionCfg::confirmation`].
+    #[serde(default)]
+    pub confirmation: Option<String>,
 }
 
 #[derive(Debug, Clone)]
 pub struct ExecutionCfg {
     pub confirmation_timeout_seconds: u64,
     pub dry_run: bool,
+    /// Allow a spoken yes/no to answer a pending confirmation.
+    pub voice_confirmation: bool,
+    pub pending_policy: PendingPolicy,
+    /// Environment variables commands receive unless they set their own
+    /// `env_allowlist`; everything else (API keys included) is withheld.
+    pub default_env_allowlist: Vec<String>,
+    /// For commands that don't set their own `confirmation`.
+    pub confirmation: ConfirmationPolicy,
+}
+
+/// Which commands ask before running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConfirmationPolicy {
+    /// Every command.
+    Always,
+    /// Commands marked `dangerous` and the session/security ones the router
+    /// flags (lock, logout, suspend, ...).
+    #[default]
+    DangerousOnly,
+    /// Only commands marked `dangerous`; those always ask.
+    Never,
+}
+
+impl ConfirmationPolicy {
+    /// `execution.confirmation` or a command's `confirmation`; anything
+    /// unrecognized means dangerous_only.
+    pub fn from_config(s: &str) -> Self {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "always" => Self::Always,
+            "never" => Self::Never,
+            _ => Self::DangerousOnly,
+        }
+    }
+
+    /// `flagged` is the router's `requires_confirmation`.
+    pub fn asks(self, dangerous: bool, flagged: bool) -> bool {
+        match self {
+            Self::Always => true,
+            Self::DangerousOnly => dangerous || flagged,
+            Self::Never => dangerous,
+        }
+    }
+}
+
+/// What happens to a command that arrives while another awaits confirmation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PendingPolicy {
+    /// Refuse it; the pending command stays.
+    #[default]
+    Reject,
+    /// Cancel the pending command and handle the new one instead.
+    Replace,
+    /// Hold one command; it asks for its own confirmation once the pending
+    /// one is confirmed, canceled or times out.
+    Queue,
+}
+
+impl PendingPolicy {
+    /// `execution.pending_policy`; anything unrecognized means reject.
+    pub fn from_config(s: &str) -> Self {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "replace" => Self::Replace,
+            "queue" => Self::Queue,
+            _ => Self::Reject,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum ExecStatus {
-    Executed { id: String },
-    PendingConfirmation { id: String, description: String, deadline: Instant },
+    /// `stdout` is the command's trimmed output.
+    Executed { id: String, params: Params, stdout: String },
+    /// `dry_run = true`: what would have run, for review before going live.
+    DryRun { id: String, program: String, args: Vec<String>, params: Params },
+    #[cfg_attr(not(test), allow(dead_code))]
+    PendingConfirmation { id: String, description: String, deadline: Instant, params: Params },
     Canceled { id: String, reason: String },
+    /// Held until the pending confirmation resolves (`PendingPolicy::Queue`).
+    Queued { id: String },
+    /// `name` has no value and no default; `prompt` asks for it, and the
+    /// answer goes to [`Executor::fill_parameter`].
+    #[cfg_attr(not(test), allow(dead_code))]
+    NeedsParameter { id: String, name: String, prompt: String },
     Rejected { reason: String },
     Ignored,
 }
 
+/// A command held back by [`ExecStatus::NeedsParameter`].
+struct ParameterRequest {
+    intent: IntentResult,
+    name: String,
+    request_id: String,
+    deadline: Instant,
+}
+
 struct Pending {
     program: String,
     args: Vec<String>,
@@ -41,30 +137,115 @@ struct Pending {
     description: String,
     deadline: Instant,
     request_id: String,
+    params: Params,
+    reprompted: bool,
+    /// The rest of a chained utterance, run in order once this one is confirmed.
+    then: Vec<Step>,
+}
+
+/// One rendered command, ready to run.
+struct Step {
+    id: String,
+    program: String,
+    args: Vec<String>,
+    params: Params,
+}
+
+/// Reacts to execution events (notifications, audit log, ...) so callers
+/// don't have to match on every [`ExecStatus`] themselves.
+pub trait ExecObserver: Send {
+    fn on_executed(&self, id: &str, stdout: &str);
+    fn on_pending(&self, id: &str, preview: &str, deadline: Instant);
+    fn on_canceled(&self, id: &str, reason: &str);
+    fn on_rejected(&self, reason: &str);
+    /// `command` is the rendered invocation (see [`display_command`]).
+    /// Defaults to reporting it like an execution.
+    fn on_dry_run(&self, id: &str, command: &str) {
+        self.on_executed(id, command);
+    }
+}
+
+/// `program` and `args` as a shell-style line for display, quoting any
+/// argument that would otherwise read ambiguously. Never passed to a shell.
+pub fn display_command(program: &str, args: &[String]) -> String {
+    let quote = |s: &str| {
+        if !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || "-_./=:,+%@".contains(c)) {
+            s.to_string()
+        } else {
+            format!("'{}'", s.replace('\'', r"'\''"))
+        }
+    };
+    std::iter::once(quote(program)).chain(args.iter().map(|a| quote(a))).collect::<Vec<_>>().join(" ")
+}
+
+/// Read, validate and index the allow-listed commands in `path`. Commands
+/// with unsafe templates are skipped with a warning.
+pub fn load_commands(path: &Path) -> Result<HashMap<String, ExecCommand>> {
+    let s = std::fs::read_to_string(path)
+        .map_err(|e| BtwError::ReadError { path: path.to_path_buf(), source: e })?;
+    // Soft warnings are already reported by intent::load_commands; only
+    // the hard checks matter here.
+    crate::commands_schema::validate(path, &s)?;
+    let cmds: Vec<ExecCommand> = serde_json::from_str(&s)
+        .map_err(|e| BtwError::ParseError { path: path.to_path_buf(), kind: "json", message: e.to_string(), cause: Some(Box::new(e)) })?;
+    // Validate templates and index by id
+    let mut by_id = HashMap::new();
+    for c in cmds {
+        if let Err(msg) = validate_template(&c.shell_command_template) {
+            log::warn!("Skipping command '{}' due to unsafe template: {}", c.id, msg);
+            continue;
+        }
+        by_id.insert(c.id.clone(), c);
+    }
+    Ok(by_id)
 }
 
 pub struct Executor {
     by_id: HashMap<String, ExecCommand>,
     cfg: ExecutionCfg,
     pending: Option<Pending>,
+    queued: Option<IntentResult>,
+    awaiting: Option<ParameterRequest>,
+    observers: Vec<Box<dyn ExecObserver>>,
 }
 
 impl Executor {
     pub fn new_from_path(path: &Path, cfg: ExecutionCfg) -> Result<Self> {
-        let s = std::fs::read_to_string(path)
-            .map_err(|e| BtwError::ReadError { path: path.to_path_buf(), source: e })?;
-        let cmds: Vec<ExecCommand> = serde_json::from_str(&s)
-            .map_err(|e| BtwError::ParseError { path: path.to_path_buf(), kind: "json", message: e.to_string() })?;
-        // Validate templates and index by id
-        let mut by_id = HashMap::new();
-        for c in cmds {
-            if let Err(msg) = validate_template(&c.shell_command_template) {
-                eprintln!("Skipping command '{}' due to unsafe template: {}", c.id, msg);
-                continue;
+        let by_id = load_commands(path)?;
+        Ok(Self { by_id, cfg, pending: None, queued: None, awaiting: None, observers: Vec::new() })
+    }
+
+    /// Swap in a freshly loaded command set. A pending confirmation keeps
+    /// the command it was created from.
+    pub fn replace_commands(&mut self, by_id: HashMap<String, ExecCommand>) {
+        self.by_id = by_id;
+    }
+
+    fn confirmation_for(&self, cmd: &ExecCommand) -> ConfirmationPolicy {
+        cmd.confirmation.as_deref().map(ConfirmationPolicy::from_config).unwrap_or(self.cfg.confirmation)
+    }
+
+    /// The ids this executor will run.
+    pub fn command_ids(&self) -> impl Iterator<Item = &str> {
+        self.by_id.keys().map(String::as_str)
+    }
+
+    /// Observers are told about every status in registration order.
+    pub fn add_observer(&mut self, observer: Box<dyn ExecObserver>) {
+        self.observers.push(observer);
+    }
+
+    fn observe(&self, status: &ExecStatus) {
+        for o in &self.observers {
+            match status {
+                ExecStatus::Executed { id, stdout, .. } => o.on_executed(id, stdout),
+                ExecStatus::DryRun { id, program, args, .. } => o.on_dry_run(id, &display_command(program, args)),
+                ExecStatus::PendingConfirmation { id, description, deadline, .. } => o.on_pending(id, description, *deadline),
+                ExecStatus::Canceled { id, reason } => o.on_canceled(id, reason),
+                ExecStatus::Rejected { reason } => o.on_rejected(reason),
+                ExecStatus::Queued { .. } | ExecStatus::NeedsParameter { .. } | ExecStatus::Ignored => {}
             }
-            by_id.insert(c.id.clone(), c);
         }
-        Ok(Self { by_id, cfg, pending: None })
     }
 
     pub fn has_pending(&self) -> bool { self.pending.is_some() }
@@ -73,15 +254,28 @@ impl Executor {
         self.pending.as_ref().map(|p| p.request_id.as_str())
     }
 
+    pub fn pending_description(&self) -> Option<&str> {
+        self.pending.as_ref().map(|p| p.description.as_str())
+    }
+
+    /// When the pending confirmation expires (see [`Executor::handle_tick`]).
+    pub fn pending_deadline(&self) -> Option<Instant> {
+        self.pending.as_ref().map(|p| p.deadline)
+    }
+
     pub fn confirm_pending(&mut self) -> ExecStatus {
         let pending = match self.pending.take() {
             Some(p) => p,
             None => return ExecStatus::Ignored,
         };
-        match self.exec_program_args(&pending.id, &pending.program, &pending.args) {
-            Ok(_) => ExecStatus::Executed { id: pending.id },
-            Err(e) => ExecStatus::Rejected { reason: format!("execution failed: {}", e) },
+        let first = Step { id: pending.id, program: pending.program, args: pending.args, params: pending.params };
+        let mut statuses = self.run_steps(std::iter::once(first).chain(pending.then).collect());
+        for status in &statuses {
+            self.observe(status);
         }
+        self.promote_queued();
+        // A chain reports its last step; observers saw every one.
+        statuses.pop().unwrap_or(ExecStatus::Ignored)
     }
 
     pub fn cancel_pending(&mut self, reason: &str) -> ExecStatus {
@@ -89,107 +283,311 @@ impl Executor {
             Some(p) => p,
             None => return ExecStatus::Ignored,
         };
-        ExecStatus::Canceled { id: pending.id, reason: reason.to_string() }
+        let status = ExecStatus::Canceled { id: pending.id, reason: reason.to_string() };
+        self.observe(&status);
+        self.promote_queued();
+        status
     }
 
-    pub fn handle_tick(&mut self, now: Instant) {
-        if let Some(p) = &self.pending {
-            if now >= p.deadline {
-                eprintln!("Confirmation timed out for '{}', canceling", p.id);
-                self.pending = None;
-            }
+    pub fn pending_policy(&self) -> PendingPolicy {
+        self.cfg.pending_policy
+    }
+
+    pub fn queued_id(&self) -> Option<&str> {
+        self.queued.as_ref().and_then(|q| q.command_id.as_deref())
+    }
+
+    /// Forget the queued command (abort path); returns its id.
+    pub fn clear_queue(&mut self) -> Option<String> {
+        self.queued.take().and_then(|q| q.command_id)
+    }
+
+    /// Once nothing is pending, the queued command asks for confirmation of
+    /// its own; it was spoken during a confirmation, so it never runs unasked.
+    fn promote_queued(&mut self) {
+        let Some(next) = self.queued.take() else { return };
+        let status = self.route_intent(&next, true);
+        self.observe(&status);
+        log::info!("exec: queued command -> {:?}", status);
+    }
+
+    /// `(request_id, deadline)` of the command waiting for a parameter.
+    pub fn awaiting_parameter(&self) -> Option<(&str, Instant)> {
+        self.awaiting.as_ref().map(|r| (r.request_id.as_str(), r.deadline))
+    }
+
+    /// The request a spoken answer would go to: the pending confirmation,
+    /// else the parameter question.
+    pub fn answer_request_id(&self) -> Option<&str> {
+        self.pending_request_id().or(self.awaiting.as_ref().map(|r| r.request_id.as_str()))
+    }
+
+    /// The answer to a [`ExecStatus::NeedsParameter`] question ("sixty"),
+    /// merged into the held intent, which then runs (or asks to confirm, or
+    /// asks for the next missing parameter) as if it had been spoken whole.
+    /// Returns that intent with its status; "no", "cancel" or "never mind"
+    /// cancels. `None` when `text` holds no usable value: the request is
+    /// dropped and `text` should be routed as usual.
+    pub fn fill_parameter(&mut self, text: &str) -> Option<(IntentResult, ExecStatus)> {
+        let req = self.awaiting.take()?;
+        let id = req.intent.command_id.clone().unwrap_or_default();
+        let expired = Instant::now() >= req.deadline;
+        let declined = classify_confirmation(text) == VoiceAnswer::Deny || matches!(normalize_input(text).as_str(), "never mind" | "forget it");
+        if expired || declined {
+            let reason = if expired { "parameter request timed out" } else { "user canceled" };
+            let status = ExecStatus::Canceled { id, reason: reason.into() };
+            self.observe(&status);
+            return Some((req.intent, status));
+        }
+        let spec = self.by_id.get(&id).and_then(|c| c.parameters.get(&req.name))?;
+        let values = parameter_answer(&req.name, spec, text);
+        if values.is_empty() {
+            log::info!("exec: no value for '{}' in {:?}; dropping the request for '{}'", req.name, text, id);
+            return None;
+        }
+        let mut intent = req.intent;
+        for (name, value) in values {
+            intent.parameters.insert(&name, value, Provenance::Deterministic);
         }
+        let status = self.handle_intent(&intent);
+        Some((intent, status))
     }
 
+    /// Forget the command waiting for a parameter (abort, cancel).
+    pub fn cancel_parameter_request(&mut self, reason: &str) -> ExecStatus {
+        let Some(req) = self.awaiting.take() else { return ExecStatus::Ignored };
+        let status = ExecStatus::Canceled { id: req.intent.command_id.unwrap_or_default(), reason: reason.to_string() };
+        self.observe(&status);
+        status
+    }
+
+    /// Drop an unconfirmed command, or one still missing a parameter, once
+    /// its deadline passes; returns the cancellation so the caller can tell
+    /// the user.
+    pub fn handle_tick(&mut self, now: Instant) -> Option<ExecStatus> {
+        if self.awaiting.as_ref().is_some_and(|r| now >= r.deadline) {
+            log::info!("exec: no answer to the parameter question, canceling");
+            return Some(self.cancel_parameter_request("parameter request timed out"));
+        }
+        let p = self.pending.as_ref()?;
+        if now < p.deadline {
+            return None;
+        }
+        log::info!("Confirmation timed out for '{}', canceling", p.id);
+        let p = self.pending.take()?;
+        let status = ExecStatus::Canceled { id: p.id, reason: "confirmation timed out".into() };
+        self.observe(&status);
+        self.promote_queued();
+        Some(status)
+    }
+
+    /// Spoken answer to the pending confirmation. Off unless
+    /// `voice_confirmation` is set; confirmation then normally comes from the
+    /// UI action path (confirm_pending/cancel_pending). An unclear answer
+    /// re-prompts once (returned as `PendingConfirmation`), then cancels.
     pub fn handle_confirmation_text(&mut self, text: &str) -> ExecStatus {
-        // Safety: voice confirmations are disabled for execution.
-        // Confirmation must come from the UI action path (confirm_pending/cancel_pending).
-        let _ = text;
-        ExecStatus::Ignored
+        if !self.cfg.voice_confirmation {
+            return ExecStatus::Ignored;
+        }
+        let now = Instant::now();
+        let Some(p) = self.pending.as_mut() else { return ExecStatus::Ignored };
+        if now >= p.deadline {
+            return self.handle_tick(now).unwrap_or(ExecStatus::Ignored);
+        }
+        match classify_confirmation(text) {
+            VoiceAnswer::Affirm => self.confirm_pending(),
+            VoiceAnswer::Deny => self.cancel_pending("user canceled"),
+            VoiceAnswer::Other if !p.reprompted => {
+                p.reprompted = true;
+                ExecStatus::PendingConfirmation { id: p.id.clone(), description: p.description.clone(), deadline: p.deadline, params: p.params.clone() }
+            }
+            VoiceAnswer::Other => self.cancel_pending("no clear answer"),
+        }
     }
 
     pub fn handle_intent(&mut self, intent: &IntentResult) -> ExecStatus {
+        let status = self.route_intent(intent, false);
+        self.observe(&status);
+        status
+    }
+
+    /// `always_ask` overrides the confirmation policy.
+    fn route_intent(&mut self, intent: &IntentResult, always_ask: bool) -> ExecStatus {
+        let id = match &intent.command_id { Some(s) => s.clone(), None => return ExecStatus::Ignored };
         if self.pending.is_some() {
-            return ExecStatus::Rejected { reason: "confirmation pending; ignoring new commands".into() };
+            match self.cfg.pending_policy {
+                PendingPolicy::Reject => {
+                    return ExecStatus::Rejected { reason: "confirmation pending; ignoring new commands".into() };
+                }
+                PendingPolicy::Replace => {
+                    let old = self.cancel_pending("replaced by a new command");
+                    log::info!("exec: {:?}", old);
+                }
+                PendingPolicy::Queue => {
+                    if let Some(queued) = self.queued_id() {
+                        return ExecStatus::Rejected { reason: format!("confirmation pending and '{}' already queued", queued) };
+                    }
+                    if !self.by_id.contains_key(&id) {
+                        return ExecStatus::Rejected { reason: format!("unknown command id '{}': not in allow-list", id) };
+                    }
+                    self.queued = Some(intent.clone());
+                    return ExecStatus::Queued { id };
+                }
+            }
         }
-        let id = match &intent.command_id { Some(s) => s.clone(), None => return ExecStatus::Ignored };
 
         // Strict mode: only allow deterministic decisions to reach execution.
         // If deterministic_score is missing, or below threshold, reject.
-        let score = intent.deterministic_score.unwrap_or(0.0);
+        let score = intent.deterministic_score.or(intent.embedding_score).unwrap_or(0.0);
         if score <= 0.0 {
             return ExecStatus::Rejected { reason: "non-deterministic or low-confidence command blocked".into() };
         }
 
         let cmd = match self.by_id.get(&id) { Some(c) => c.clone(), None => return ExecStatus::Rejected { reason: format!("unknown command id '{}': not in allow-list", id) } };
-        // Validate parameters against spec
-        if let Err(msg) = validate_parameters(&cmd.parameters, &intent.parameters) {
-            return ExecStatus::Rejected { reason: msg };
+        // "Set the brightness": ask for the number rather than reject.
+        if let Some((name, prompt)) = missing_parameter(&cmd.parameters, &intent.parameters) {
+            let nonce = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_nanos();
+            self.awaiting = Some(ParameterRequest {
+                intent: intent.clone(),
+                name: name.clone(),
+                request_id: format!("{}-{}-{}", id, name, nonce),
+                deadline: Instant::now() + Duration::from_secs(self.cfg.confirmation_timeout_seconds),
+            });
+            log::info!("exec: '{}' needs '{}'; asking", id, name);
+            return ExecStatus::NeedsParameter { id, name, prompt };
         }
-        // Render template
-        let rendered = match render_template(&cmd.shell_command_template, &intent.parameters, &cmd.parameters) {
+        let Step { id, program, args, params } = match render_step(id, &cmd, &intent.parameters) {
             Ok(s) => s,
-            Err(msg) => return ExecStatus::Rejected { reason: msg },
+            Err(reason) => return ExecStatus::Rejected { reason },
         };
-        let tokens: Vec<String> = split_tokens(&rendered);
-        if tokens.is_empty() {
-            return ExecStatus::Rejected { reason: "empty command".into() };
-        }
-        if let Err(msg) = validate_tokens(&tokens) {
-            return ExecStatus::Rejected { reason: msg };
-        }
-        let program = tokens[0].clone();
-        let args = tokens[1..].to_vec();
-        if cmd.dangerous || intent.requires_confirmation {
+        if always_ask || self.confirmation_for(&cmd).asks(cmd.dangerous, intent.requires_confirmation) {
             let deadline = Instant::now() + Duration::from_secs(self.cfg.confirmation_timeout_seconds);
-            eprintln!("Confirmation required: {}. Say 'yes' to confirm or 'no' to cancel.", cmd.description);
+            log::info!("Confirmation required: {}. Say 'yes' to confirm or 'no' to cancel.", cmd.description);
             let nonce = SystemTime::now()
                 .duration_since(SystemTime::UNIX_EPOCH)
                 .unwrap_or_default()
                 .as_nanos();
             let request_id = format!("{}-{}", id, nonce);
-            self.pending = Some(Pending { program, args, id: id.clone(), description: cmd.description.clone(), deadline, request_id });
-            return ExecStatus::PendingConfirmation { id, description: cmd.description, deadline };
+            self.pending = Some(Pending { program, args, id: id.clone(), description: cmd.description.clone(), deadline, request_id, params: params.clone(), reprompted: false, then: Vec::new() });
+            return ExecStatus::PendingConfirmation { id, description: cmd.description, deadline, params };
         }
-        match self.exec_program_args(&id, &program, &args) {
-            Ok(_) => ExecStatus::Executed { id },
-            Err(e) => ExecStatus::Rejected { reason: format!("execution failed: {}", e) },
+        self.exec_program_args(id, program, args, params)
+    }
+
+    /// Run "lower the volume and lock the screen" part by part, in spoken
+    /// order. Every part is checked before anything runs, and a part that
+    /// would ask on its own makes the whole chain wait for one confirmation
+    /// previewing all of it. Running stops at the first failure. A chain
+    /// never queues behind or replaces a pending confirmation, and never
+    /// asks for a missing parameter.
+    pub fn handle_chain(&mut self, intents: &[IntentResult]) -> Vec<ExecStatus> {
+        let statuses = self.route_chain(intents);
+        for status in &statuses {
+            self.observe(status);
+        }
+        statuses
+    }
+
+    fn route_chain(&mut self, intents: &[IntentResult]) -> Vec<ExecStatus> {
+        let reject = |reason: String| vec![ExecStatus::Rejected { reason }];
+        if self.pending.is_some() {
+            return reject("confirmation pending; ignoring new commands".into());
+        }
+        let mut steps = Vec::new();
+        let mut previews = Vec::new();
+        let mut asks = false;
+        for intent in intents {
+            let Some(id) = intent.command_id.clone() else { return vec![ExecStatus::Ignored] };
+            if intent.deterministic_score.or(intent.embedding_score).unwrap_or(0.0) <= 0.0 {
+                return reject("non-deterministic or low-confidence command blocked".into());
+            }
+            let Some(cmd) = self.by_id.get(&id).cloned() else {
+                return reject(format!("unknown command id '{}': not in allow-list", id));
+            };
+            if let Some((name, _)) = missing_parameter(&cmd.parameters, &intent.parameters) {
+                return reject(format!("'{}' needs '{}'; ask for it on its own", id, name));
+            }
+            match render_step(id.clone(), &cmd, &intent.parameters) {
+                Ok(step) => steps.push(step),
+                Err(msg) => return reject(format!("'{}': {}", id, msg)),
+            }
+            asks |= self.confirmation_for(&cmd).asks(cmd.dangerous, intent.requires_confirmation);
+            previews.push(cmd.description);
+        }
+        if !asks || steps.is_empty() {
+            return self.run_steps(steps);
         }
+        let first = steps.remove(0);
+        let description = previews.join(", then ");
+        let deadline = Instant::now() + Duration::from_secs(self.cfg.confirmation_timeout_seconds);
+        log::info!("Confirmation required: {}. Say 'yes' to confirm or 'no' to cancel.", description);
+        let nonce = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_nanos();
+        let request_id = format!("{}-{}", first.id, nonce);
+        let status = ExecStatus::PendingConfirmation { id: first.id.clone(), description: description.clone(), deadline, params: first.params.clone() };
+        self.pending = Some(Pending { program: first.program, args: first.args, id: first.id, description, deadline, request_id, params: first.params, reprompted: false, then: steps });
+        vec![status]
     }
 
-    fn exec_program_args(&self, id: &str, program: &str, args: &[String]) -> Result<()> {
+    /// Run `steps` in order, stopping after the first one that fails.
+    fn run_steps(&self, steps: Vec<Step>) -> Vec<ExecStatus> {
+        let mut statuses = Vec::new();
+        for step in steps {
+            let status = self.exec_program_args(step.id, step.program, step.args, step.params);
+            let failed = matches!(status, ExecStatus::Rejected { .. });
+            statuses.push(status);
+            if failed {
+                break;
+            }
+        }
+        statuses
+    }
+
+    /// Run the command, or in dry-run mode just report what would run.
+    fn exec_program_args(&self, id: String, program: String, args: Vec<String>, params: Params) -> ExecStatus {
+        let _span = tracing::info_span!("exec", command = %id).entered();
         if self.cfg.dry_run {
-            eprintln!("[dry-run] Would execute command: {}", id);
-            return Ok(());
+            log::info!("[dry-run] Would execute command '{}': {}", id, display_command(&program, &args));
+            return ExecStatus::DryRun { id, program, args, params };
+        }
+        match self.run(&id, &program, &args) {
+            Ok(stdout) => ExecStatus::Executed { id, params, stdout },
+            Err(e) => ExecStatus::Rejected { reason: format!("execution failed: {}", e) },
         }
-        eprintln!("exec: running id='{}' program='{}' args={:?}", id, program, args);
+    }
+
+    /// Run the program; returns its trimmed stdout.
+    fn run(&self, id: &str, program: &str, args: &[String]) -> Result<String> {
+        log::info!("exec: running id='{}' program='{}' args={:?}", id, program, args);
         let mut cmd = Command::new(program);
         for a in args { cmd.arg(a); }
-        // Inherit minimal env by default; do not invoke shell
+        // Only allow-listed variables reach the child; do not invoke shell
+        let allowlist = self.by_id.get(id).and_then(|c| c.env_allowlist.as_deref()).unwrap_or(self.cfg.default_env_allowlist.as_slice());
+        cmd.env_clear().envs(child_env(allowlist, |k| std::env::var_os(k)));
         let output = cmd
             .output()
-            .map_err(|e| BtwError::ParseError { path: std::path::PathBuf::new(), kind: "exec", message: e.to_string() })?;
+            .map_err(|e| BtwError::ExecFailed { id: id.to_string(), status: None, stderr: e.to_string() })?;
         if !output.status.success() {
             let stdout = String::from_utf8_lossy(&output.stdout);
             let stderr = String::from_utf8_lossy(&output.stderr);
-            eprintln!("exec: non-zero exit for id='{}': status={}", id, output.status);
+            log::warn!("exec: non-zero exit for id='{}': status={}", id, output.status);
             if !stdout.trim().is_empty() {
-                eprintln!("exec: stdout: {}", stdout.trim());
+                log::warn!("exec: stdout: {}", stdout.trim());
             }
             if !stderr.trim().is_empty() {
-                eprintln!("exec: stderr: {}", stderr.trim());
+                log::warn!("exec: stderr: {}", stderr.trim());
             }
-            return Err(BtwError::ParseError {
-                path: std::path::PathBuf::new(),
-                kind: "exec",
-                message: format!("non-zero exit: {}", output.status),
-            });
+            return Err(BtwError::ExecFailed { id: id.to_string(), status: output.status.code(), stderr: stderr.into_owned() });
         }
-        Ok(())
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
     }
 }
 
-fn validate_template(tpl: &str) -> std::result::Result<(), String> {
+/// The allow-listed variables that are set in the parent, per `get`.
+fn child_env(allowlist: &[String], get: impl Fn(&str) -> Option<std::ffi::OsString>) -> Vec<(String, std::ffi::OsString)> {
+    allowlist.iter().filter_map(|k| get(k).map(|v| (k.clone(), v))).collect()
+}
+
+pub fn validate_template(tpl: &str) -> std::result::Result<(), String> {
     // Block known unsafe shell constructs while allowing %, @, +, -
     let forbidden_substrings = ["|", "&", ";", ">", "<", "`", "$(", "${", "\\", "\"", "'"];
     if forbidden_substrings.iter().any(|s| tpl.contains(s)) {
@@ -202,7 +600,13 @@ fn validate_template(tpl: &str) -> std::result::Result<(), String> {
     Ok(())
 }
 
-fn render_template(tpl: &str, params: &serde_json::Value, spec: &HashMap<String, String>) -> std::result::Result<String, String> {
+/// The program and its arguments. Placeholders are filled per
+/// whitespace-separated token, so a text value with spaces stays one argument.
+fn render_template(tpl: &str, params: &Params, spec: &HashMap<String, String>) -> std::result::Result<Vec<String>, String> {
+    split_tokens(tpl).iter().enumerate().map(|(i, token)| render_token(token, i == 0, params, spec)).collect()
+}
+
+fn render_token(tpl: &str, program: bool, params: &Params, spec: &HashMap<String, String>) -> std::result::Result<String, String> {
     let mut out = String::with_capacity(tpl.len());
     let mut i = 0;
     while i < tpl.len() {
@@ -214,8 +618,13 @@ fn render_template(tpl: &str, params: &serde_json::Value, spec: &HashMap<String,
                 if !spec.contains_key(key) {
                     return Err(format!("unknown placeholder '{{{}}}'", key));
                 }
-                let v = params.get(key).and_then(|v| v.as_i64()).ok_or_else(|| format!("missing or non-integer parameter '{}'", key))?;
-                out.push_str(&v.to_string());
+                match params.get(key).map(|p| &p.value) {
+                    Some(Value::Number(n)) if n.is_i64() => out.push_str(&n.to_string()),
+                    // Text never picks what runs, only what it is given.
+                    Some(Value::String(_)) if program => return Err(format!("parameter '{}' cannot name the program", key)),
+                    Some(Value::String(v)) => out.push_str(v),
+                    _ => return Err(format!("missing or invalid parameter '{}'", key)),
+                }
                 i = i + 1 + j + 1;
                 continue;
             } else {
@@ -228,6 +637,19 @@ fn render_template(tpl: &str, params: &serde_json::Value, spec: &HashMap<String,
     Ok(out)
 }
 
+/// Check `params` against the command's spec (filling defaults, clamping
+/// where allowed) and render its template into a program and arguments.
+fn render_step(id: String, cmd: &ExecCommand, params: &Params) -> std::result::Result<Step, String> {
+    let params = resolve_parameters(&cmd.parameters, params)?;
+    let mut tokens = render_template(&cmd.shell_command_template, &params, &cmd.parameters)?;
+    if tokens.is_empty() {
+        return Err("empty command".into());
+    }
+    validate_tokens(&tokens)?;
+    let program = tokens.remove(0);
+    Ok(Step { id, program, args: tokens, params })
+}
+
 fn split_tokens(s: &str) -> Vec<String> {
     s.split_whitespace().map(|t| t.to_string()).collect()
 }
@@ -241,29 +663,757 @@ fn validate_tokens(tokens: &[String]) -> std::result::Result<(), String> {
     Ok(())
 }
 
-fn validate_parameters(spec: &HashMap<String, String>, params: &serde_json::Value) -> std::result::Result<(), String> {
-    for (k, v) in spec.iter() {
-        let vtrim = v.trim();
-        if !vtrim.starts_with("int") {
-            return Err(format!("unsupported param spec for '{}': '{}'", k, v));
-        }
-        // default: any int if no range
-        let (min, max) = if let Some(space) = vtrim.find(' ') {
-            let range = &vtrim[space+1..];
-            if let Some(dash) = range.find('-') {
-                let (a,b) = (&range[..dash], &range[dash+1..]);
-                let min = a.trim().parse::<i64>().map_err(|_| format!("invalid min for '{}': '{}'", k, a))?;
-                let max = b.trim().parse::<i64>().map_err(|_| format!("invalid max for '{}': '{}'", k, b))?;
-                (Some(min), Some(max))
-            } else { (None, None) }
-        } else { (None, None) };
-        let val = params.get(k).and_then(|x| x.as_i64()).ok_or_else(|| format!("missing integer parameter '{}'", k))?;
-        if let Some(lo) = min { if val < lo { return Err(format!("parameter '{}' below min {}", k, lo)); } }
-        if let Some(hi) = max { if val > hi { return Err(format!("parameter '{}' above max {}", k, hi)); } }
+/// A `parameters` entry in commands.json: a spec string such as
+/// `"int 0-100 default=50 clamp"`, or the same as an object plus an
+/// extraction `pattern`, e.g. `{"type": "int", "min": 0, "max": 100,
+/// "pattern": "to (\\d+)"}`. Only the intent router reads the pattern.
+/// `values` and `max_length` are the object spelling of an `enum` list and
+/// a `string` `max=`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum ParamDecl {
+    Spec(String),
+    Detailed {
+        #[serde(rename = "type")]
+        kind: String,
+        #[serde(default)]
+        min: Option<i64>,
+        #[serde(default)]
+        max: Option<i64>,
+        #[serde(default)]
+        values: Option<Vec<String>>,
+        #[serde(default)]
+        max_length: Option<usize>,
+        #[serde(default)]
+        default: Option<Value>,
+        #[serde(default)]
+        clamp: bool,
+        #[serde(default)]
+        pattern: Option<String>,
+    },
+}
+
+impl ParamDecl {
+    /// The equivalent spec string; an object with only one bound leaves the
+    /// other open.
+    pub fn spec(&self) -> String {
+        match self {
+            ParamDecl::Spec(spec) => spec.clone(),
+            ParamDecl::Detailed { kind, min, max, values, max_length, default, clamp, .. } => {
+                let mut spec = kind.clone();
+                if let Some(values) = values {
+                    spec.push_str(&format!(" {}", values.join("|")));
+                }
+                if let Some(n) = max_length {
+                    spec.push_str(&format!(" max={}", n));
+                }
+                if min.is_some() || max.is_some() {
+                    spec.push_str(&format!(" {}-{}", min.unwrap_or(i64::MIN), max.unwrap_or(i64::MAX)));
+                }
+                match default {
+                    Some(Value::String(d)) => spec.push_str(&format!(" default={}", d)),
+                    Some(d) => spec.push_str(&format!(" default={}", d)),
+                    None => {}
+                }
+                if *clamp {
+                    spec.push_str(" clamp");
+                }
+                spec
+            }
+        }
+    }
+
+    pub fn pattern(&self) -> Option<&str> {
+        match self {
+            ParamDecl::Detailed { pattern, .. } => pattern.as_deref(),
+            ParamDecl::Spec(_) => None,
+        }
     }
-    Ok(())
 }
 
-fn normalize(s: &str) -> String {
-    s.trim().to_ascii_lowercase()
+fn param_specs<'de, D: serde::Deserializer<'de>>(d: D) -> std::result::Result<HashMap<String, String>, D::Error> {
+    let decls = HashMap::<String, ParamDecl>::deserialize(d)?;
+    Ok(decls.into_iter().map(|(name, decl)| (name, decl.spec())).collect())
+}
+
+/// Parsed form of a parameter spec such as `"int 0-100 default=50 clamp"`.
+#[derive(Debug, PartialEq)]
+struct ParamSpec {
+    min: Option<i64>,
+    max: Option<i64>,
+    default: Option<i64>,
+    /// Clamp out-of-range values to the bounds instead of rejecting them.
+    clamp: bool,
+}
+
+/// Any parameter spec: `int ...`, `enum a|b|c` or `string max=N`, the
+/// latter two optionally with `default=`.
+#[derive(Debug, PartialEq)]
+enum ParamType {
+    Int(ParamSpec),
+    /// One of `values`, matched case-insensitively and passed on as listed.
+    Enum { values: Vec<String>, default: Option<String> },
+    /// Free text, cleaned up by [`sanitize_text`].
+    Text { max_len: usize, default: Option<String> },
+}
+
+/// Whether `spec` is a parameter spec the executor understands.
+pub fn check_param_spec(name: &str, spec: &str) -> std::result::Result<(), String> {
+    parse_param_type(name, spec).map(|_| ())
+}
+
+fn parse_param_type(name: &str, spec: &str) -> std::result::Result<ParamType, String> {
+    let unsupported = || format!("unsupported param spec for '{}': '{}'", name, spec);
+    let mut parts = spec.split_whitespace();
+    match parts.next() {
+        Some("int") => parse_param_spec(name, spec).map(ParamType::Int),
+        Some("enum") => {
+            let values: Vec<String> = parts.next().ok_or_else(unsupported)?.split('|').map(str::to_string).collect();
+            if let Some(bad) = values.iter().find(|v| sanitize_text(v, usize::MAX).as_deref() != Ok(v.as_str()) || v.contains(' ')) {
+                return Err(format!("invalid enum value for '{}': '{}'", name, bad));
+            }
+            let mut default = None;
+            for part in parts {
+                let d = part.strip_prefix("default=").ok_or_else(unsupported)?;
+                let known = values.iter().find(|v| v.eq_ignore_ascii_case(d));
+                default = Some(known.ok_or_else(|| format!("invalid default for '{}': '{}'", name, d))?.clone());
+            }
+            Ok(ParamType::Enum { values, default })
+        }
+        Some("string") => {
+            let (mut max_len, mut default) = (None, None);
+            for part in parts {
+                if let Some(n) = part.strip_prefix("max=") {
+                    max_len = Some(n.parse::<usize>().ok().filter(|n| *n > 0).ok_or_else(|| format!("invalid max for '{}': '{}'", name, n))?);
+                } else if let Some(d) = part.strip_prefix("default=") {
+                    default = Some(d.to_string());
+                } else {
+                    return Err(unsupported());
+                }
+            }
+            let max_len = max_len.ok_or_else(|| format!("string parameter '{}' needs a max=N length", name))?;
+            let default = default.map(|d: String| sanitize_text(&d, max_len).map_err(|e| format!("invalid default for '{}': {}", name, e))).transpose()?;
+            Ok(ParamType::Text { max_len, default })
+        }
+        _ => Err(unsupported()),
+    }
+}
+
+fn parse_param_spec(name: &str, spec: &str) -> std::result::Result<ParamSpec, String> {
+    let mut parts = spec.split_whitespace();
+    if parts.next() != Some("int") {
+        return Err(format!("unsupported param spec for '{}': '{}'", name, spec));
+    }
+    let mut out = ParamSpec { min: None, max: None, default: None, clamp: false };
+    for part in parts {
+        if part == "clamp" {
+            out.clamp = true;
+        } else if let Some(d) = part.strip_prefix("default=") {
+            out.default = Some(d.parse::<i64>().map_err(|_| format!("invalid default for '{}': '{}'", name, d))?);
+        } else if let Some(dash) = part.char_indices().skip(1).find(|&(_, c)| c == '-').map(|(i, _)| i) {
+            // Skip index 0 so a leading minus sign ("-10-10") is part of the min.
+            let (a, b) = (&part[..dash], &part[dash + 1..]);
+            out.min = Some(a.trim().parse::<i64>().map_err(|_| format!("invalid min for '{}': '{}'", name, a))?);
+            out.max = Some(b.trim().parse::<i64>().map_err(|_| format!("invalid max for '{}': '{}'", name, b))?);
+        } else {
+            return Err(format!("unsupported param spec for '{}': '{}'", name, spec));
+        }
+    }
+    Ok(out)
+}
+
+/// Check `params` against `spec`, returning the params actually used for execution:
+/// missing values take the spec default (`Provenance::Default`) and, where the spec
+/// says `clamp`, out-of-range values are clamped (`Provenance::Clamped`). Enum
+/// values are replaced by the listed spelling and text by its sanitized form.
+fn resolve_parameters(spec: &HashMap<String, String>, params: &Params) -> std::result::Result<Params, String> {
+    let mut out = params.clone();
+    // Deterministic order so error messages are stable.
+    let mut names: Vec<&String> = spec.keys().collect();
+    names.sort();
+    for k in names {
+        let ps = match parse_param_type(k, &spec[k])? {
+            ParamType::Int(ps) => ps,
+            ParamType::Enum { values, default } => {
+                let value = match params.get(k).and_then(|p| as_text(&p.value)) {
+                    Some(v) => values.iter().find(|x| x.eq_ignore_ascii_case(v.trim())).cloned().ok_or_else(|| format!("parameter '{}' must be one of {}", k, values.join("|")))?,
+                    None => text_default(k, default, &mut out)?,
+                };
+                keep_provenance(k, value, params, &mut out);
+                continue;
+            }
+            ParamType::Text { max_len, default } => {
+                let value = match params.get(k).and_then(|p| as_text(&p.value)) {
+                    Some(v) => sanitize_text(&v, max_len).map_err(|e| format!("parameter '{}' {}", k, e))?,
+                    None => text_default(k, default, &mut out)?,
+                };
+                keep_provenance(k, value, params, &mut out);
+                continue;
+            }
+        };
+        let val = match (params.get_int(k), ps.default) {
+            (Some(v), _) => v,
+            (None, Some(d)) => {
+                out.insert(k, serde_json::json!(d), Provenance::Default);
+                d
+            }
+            (None, None) => return Err(format!("missing integer parameter '{}'", k)),
+        };
+        let bounded = val.max(ps.min.unwrap_or(i64::MIN)).min(ps.max.unwrap_or(i64::MAX));
+        if bounded != val {
+            if !ps.clamp {
+                return Err(if val < bounded {
+                    format!("parameter '{}' below min {}", k, bounded)
+                } else {
+                    format!("parameter '{}' above max {}", k, bounded)
+                });
+            }
+            out.insert(k, serde_json::json!(bounded), Provenance::Clamped);
+        }
+    }
+    out.set_validation(Validation::Valid);
+    Ok(out)
+}
+
+/// The first parameter (by name) with neither a value nor a default, and
+/// the question that asks for it. Specs that don't parse are left for
+/// [`resolve_parameters`] to report.
+fn missing_parameter(spec: &HashMap<String, String>, params: &Params) -> Option<(String, String)> {
+    let mut names: Vec<&String> = spec.keys().filter(|k| params.get(k).is_none()).collect();
+    names.sort();
+    names.into_iter().find_map(|k| {
+        let prompt = match parse_param_type(k, &spec[k]).ok()? {
+            ParamType::Int(ps) if ps.default.is_some() => return None,
+            ParamType::Enum { default: Some(_), .. } | ParamType::Text { default: Some(_), .. } => return None,
+            ParamType::Enum { values, .. } => format!("Which one: {}?", values.join(", ")),
+            ParamType::Int(ps) => match k.as_str() {
+                "duration_secs" => "For how long?".to_string(),
+                "hour" | "minute" => "At what time?".to_string(),
+                "delta" => "By how much?".to_string(),
+                _ if (ps.min, ps.max) == (Some(0), Some(100)) => "To what percent?".to_string(),
+                _ => format!("What {}?", k.replace('_', " ")),
+            },
+            ParamType::Text { .. } => format!("What {}?", k.replace('_', " ")),
+        };
+        Some((k.clone(), prompt))
+    })
+}
+
+/// Values for `name` (and, for a time of day, its partner) in a short
+/// answer such as "sixty", "at 7 30", "ten minutes" or "the hdmi one".
+fn parameter_answer(name: &str, spec: &str, text: &str) -> Vec<(String, Value)> {
+    let Ok(ptype) = parse_param_type(name, spec) else { return Vec::new() };
+    match ptype {
+        ParamType::Int(_) if name == "duration_secs" => {
+            crate::intent::extract_duration_secs(text).map(|secs| vec![(name.to_string(), serde_json::json!(secs))]).unwrap_or_default()
+        }
+        ParamType::Int(_) if name == "hour" || name == "minute" => crate::intent::extract_time_of_day(text)
+            .map(|(h, m)| vec![("hour".to_string(), serde_json::json!(h)), ("minute".to_string(), serde_json::json!(m))])
+            .unwrap_or_default(),
+        ParamType::Int(_) => {
+            let digits = crate::decision::normalize_number_words(&text.to_lowercase());
+            let number = digits.split(|c: char| !c.is_ascii_digit()).find(|t| !t.is_empty()).and_then(|t| t.parse::<i64>().ok());
+            number.map(|n| vec![(name.to_string(), serde_json::json!(n))]).unwrap_or_default()
+        }
+        ParamType::Enum { values, .. } => {
+            let words: Vec<&str> = text.split(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')).collect();
+            let known = values.iter().find(|v| words.iter().any(|w| w.trim_matches('.').eq_ignore_ascii_case(v)));
+            known.map(|v| vec![(name.to_string(), serde_json::json!(v))]).unwrap_or_default()
+        }
+        ParamType::Text { .. } if text.trim().is_empty() => Vec::new(),
+        ParamType::Text { .. } => vec![(name.to_string(), serde_json::json!(text.trim()))],
+    }
+}
+
+/// A string or whole-number value as text; "workspace 2" arrives as `2`.
+fn as_text(v: &Value) -> Option<String> {
+    v.as_str().map(str::to_string).or_else(|| v.as_i64().map(|n| n.to_string()))
+}
+
+/// The spec default for a missing enum or text parameter, recorded in `out`.
+fn text_default(name: &str, default: Option<String>, out: &mut Params) -> std::result::Result<String, String> {
+    let d = default.ok_or_else(|| format!("missing parameter '{}'", name))?;
+    out.insert(name, serde_json::json!(d), Provenance::Default);
+    Ok(d)
+}
+
+/// Store the checked form of a provided value under its original provenance.
+fn keep_provenance(name: &str, value: String, params: &Params, out: &mut Params) {
+    if let Some(prov) = params.provenance(name) {
+        out.insert(name, serde_json::json!(value), prov);
+    }
+}
+
+/// Characters a text parameter may keep: no shell syntax, quotes or path
+/// separators, so a value can only ever be one plain argument.
+fn text_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.')
+}
+
+/// Collapse whitespace and trim punctuation off the ends ("Chill  vibes." is
+/// "Chill vibes", "-rf" is "rf"), then reject what is left if it is empty,
+/// longer than `max_len` characters or holds anything else.
+fn sanitize_text(raw: &str, max_len: usize) -> std::result::Result<String, String> {
+    let text = raw.split_whitespace().collect::<Vec<_>>().join(" ");
+    let text = text.trim_matches(|c: char| c.is_ascii_punctuation() || c == ' ');
+    if text.is_empty() {
+        return Err("is empty".into());
+    }
+    if let Some(c) = text.chars().find(|c| !text_char(*c)) {
+        return Err(format!("contains {:?}", c));
+    }
+    if text.chars().count() > max_len {
+        return Err(format!("is longer than {} characters", max_len));
+    }
+    Ok(text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn spec(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    fn dry_run_executor(s: &HashMap<String, String>) -> Executor {
+        let cmd = ExecCommand {
+            id: "volume_set".into(),
+            description: "Set volume".into(),
+            dangerous: false,
+            parameters: s.clone(),
+            shell_command_template: "pamixer --set-volume {value}".into(),
+            env_allowlist: None,
+            confirmation: None,
+        };
+        let mut by_id = HashMap::new();
+        by_id.insert(cmd.id.clone(), cmd);
+        Executor { by_id, cfg: ExecutionCfg { confirmation_timeout_seconds: 10, dry_run: true, voice_confirmation: true, pending_policy: PendingPolicy::Reject, default_env_allowlist: Vec::new(), confirmation: ConfirmationPolicy::DangerousOnly }, pending: None, queued: None, awaiting: None, observers: Vec::new() }
+    }
+
+    fn intent_with(params: Params) -> IntentResult {
+        IntentResult {
+            intent_type: "command".into(),
+            command_id: Some("volume_set".into()),
+            parameters: params,
+            deterministic_score: Some(0.9),
+            embedding_score: None,
+            dangerous: false,
+            requires_confirmation: false,
+        }
+    }
+
+    #[test]
+    fn object_parameters_reduce_to_spec_strings() {
+        let cmd: ExecCommand = serde_json::from_value(json!({
+            "id": "volume_set",
+            "parameters": {
+                "value": {"type": "int", "min": 0, "max": 100, "clamp": true, "pattern": "to (\\d+)"},
+                "step": "int 1-10 default=5",
+                "level": {"type": "int", "max": 10},
+                "output": {"type": "enum", "values": ["laptop", "hdmi"], "default": "laptop"}
+            },
+            "shell_command_template": "pamixer --set-volume {value}"
+        }))
+        .unwrap();
+        assert_eq!(cmd.parameters["value"], "int 0-100 clamp");
+        assert_eq!(cmd.parameters["step"], "int 1-10 default=5");
+        assert_eq!(cmd.parameters["output"], "enum laptop|hdmi default=laptop");
+        let level = parse_param_spec("level", &cmd.parameters["level"]).unwrap();
+        assert_eq!((level.min, level.max), (Some(i64::MIN), Some(10)));
+    }
+
+    #[test]
+    fn child_env_keeps_only_allowlisted_variables_that_are_set() {
+        let parent: HashMap<&str, &str> = [("PATH", "/usr/bin"), ("HOME", "/home/u"), ("GROQ_API_KEY", "secret")].into();
+        let get = |k: &str| parent.get(k).map(std::ffi::OsString::from);
+        let allow: Vec<String> = ["PATH", "HOME", "DISPLAY"].map(String::from).to_vec();
+        let env = child_env(&allow, get);
+        let names: Vec<&str> = env.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(names, ["PATH", "HOME"]);
+        assert_eq!(env[0].1, "/usr/bin");
+        assert!(child_env(&[], get).is_empty());
+    }
+
+    #[test]
+    fn parses_param_specs() {
+        assert_eq!(parse_param_spec("v", "int").unwrap(), ParamSpec { min: None, max: None, default: None, clamp: false });
+        assert_eq!(
+            parse_param_spec("v", "int 0-100 default=50 clamp").unwrap(),
+            ParamSpec { min: Some(0), max: Some(100), default: Some(50), clamp: true }
+        );
+        assert_eq!(parse_param_spec("v", "int -10-10").unwrap().min, Some(-10));
+        assert!(parse_param_spec("v", "string").is_err());
+        assert!(parse_param_spec("v", "int 0-100 loud").is_err());
+    }
+
+    #[test]
+    fn parses_enum_and_string_specs() {
+        assert_eq!(
+            parse_param_type("v", "enum laptop|HDMI-1 default=hdmi-1").unwrap(),
+            ParamType::Enum { values: vec!["laptop".into(), "HDMI-1".into()], default: Some("HDMI-1".into()) }
+        );
+        assert_eq!(parse_param_type("v", "string max=32").unwrap(), ParamType::Text { max_len: 32, default: None });
+        assert!(matches!(parse_param_type("v", "int 0-10").unwrap(), ParamType::Int(_)));
+        assert!(parse_param_type("v", "enum").is_err());
+        assert!(parse_param_type("v", "enum a|b default=c").is_err());
+        assert!(parse_param_type("v", "enum a|$(rm)").unwrap_err().contains("invalid enum value"));
+        assert!(parse_param_type("v", "string").unwrap_err().contains("needs a max=N"));
+        assert!(parse_param_type("v", "string max=0").is_err());
+    }
+
+    #[test]
+    fn enum_and_text_values_are_checked() {
+        let s = spec(&[("output", "enum laptop|hdmi default=laptop"), ("name", "string max=16")]);
+        let mut p = Params::new();
+        p.insert("output", json!("HDMI"), Provenance::Llm);
+        p.insert("name", json!("  Chill   vibes. "), Provenance::Deterministic);
+        let out = resolve_parameters(&s, &p).unwrap();
+        assert_eq!(out.get_str("output"), Some("hdmi"));
+        assert_eq!(out.provenance("output"), Some(Provenance::Llm));
+        assert_eq!(out.get_str("name"), Some("Chill vibes"));
+
+        p.insert("output", json!("dp"), Provenance::Llm);
+        assert_eq!(resolve_parameters(&s, &p).unwrap_err(), "parameter 'output' must be one of laptop|hdmi");
+        p.insert("output", json!("laptop"), Provenance::Llm);
+        for (bad, why) in [("a; rm -rf ~", "contains ';'"), ("music/../etc", "contains '/'"), ("a very long playlist name", "is longer than 16"), ("...", "is empty")] {
+            p.insert("name", json!(bad), Provenance::Llm);
+            let err = resolve_parameters(&s, &p).unwrap_err();
+            assert!(err.starts_with("parameter 'name' ") && err.contains(why), "{}: {}", bad, err);
+        }
+
+        let out = resolve_parameters(&s, &{ let mut p = Params::new(); p.insert("name", json!(2), Provenance::Deterministic); p }).unwrap();
+        assert_eq!((out.get_str("output"), out.provenance("output")), (Some("laptop"), Some(Provenance::Default)));
+        assert_eq!(out.get_str("name"), Some("2"));
+        assert_eq!(resolve_parameters(&s, &Params::new()).unwrap_err(), "missing parameter 'name'");
+    }
+
+    #[test]
+    fn text_values_stay_one_argument() {
+        let mut exec = dry_run_executor(&spec(&[("playlist", "string max=32")]));
+        exec.by_id.get_mut("volume_set").unwrap().shell_command_template = "mpc --playlist={playlist} play {playlist}".into();
+        let mut p = Params::new();
+        p.insert("playlist", json!("road trip"), Provenance::Llm);
+        match exec.handle_intent(&intent_with(p.clone())) {
+            ExecStatus::DryRun { program, args, .. } => {
+                assert_eq!(program, "mpc");
+                assert_eq!(args, ["--playlist=road trip", "play", "road trip"]);
+            }
+            other => panic!("expected DryRun, got {:?}", other),
+        }
+
+        exec.by_id.get_mut("volume_set").unwrap().shell_command_template = "{playlist} --now".into();
+        match exec.handle_intent(&intent_with(p)) {
+            ExecStatus::Rejected { reason } => assert_eq!(reason, "parameter 'playlist' cannot name the program"),
+            other => panic!("expected Rejected, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn provided_values_keep_their_provenance() {
+        let s = spec(&[("value", "int 0-100")]);
+        for prov in [Provenance::Deterministic, Provenance::Llm] {
+            let mut p = Params::new();
+            p.insert("value", json!(40), prov);
+            let out = resolve_parameters(&s, &p).unwrap();
+            assert_eq!(out.get_int("value"), Some(40));
+            assert_eq!(out.provenance("value"), Some(prov));
+            assert_eq!(out.validation(), &Validation::Valid);
+        }
+    }
+
+    #[test]
+    fn missing_value_uses_default_or_is_rejected() {
+        let out = resolve_parameters(&spec(&[("value", "int 0-100 default=50")]), &Params::new()).unwrap();
+        assert_eq!(out.get_int("value"), Some(50));
+        assert_eq!(out.provenance("value"), Some(Provenance::Default));
+
+        let err = resolve_parameters(&spec(&[("value", "int 0-100")]), &Params::new()).unwrap_err();
+        assert!(err.contains("missing integer parameter"));
+    }
+
+    #[test]
+    fn out_of_range_is_clamped_only_when_allowed() {
+        let mut p = Params::new();
+        p.insert("value", json!(150), Provenance::Llm);
+        let out = resolve_parameters(&spec(&[("value", "int 0-100 clamp")]), &p).unwrap();
+        assert_eq!(out.get_int("value"), Some(100));
+        assert_eq!(out.provenance("value"), Some(Provenance::Clamped));
+
+        let err = resolve_parameters(&spec(&[("value", "int 0-100")]), &p).unwrap_err();
+        assert!(err.contains("above max 100"));
+    }
+
+    #[test]
+    fn exec_status_carries_resolved_params() {
+        let mut exec = dry_run_executor(&spec(&[("value", "int 0-100 default=30 clamp")]));
+        match exec.handle_intent(&intent_with(Params::new())) {
+            ExecStatus::DryRun { id, program, args, params } => {
+                assert_eq!(id, "volume_set");
+                assert_eq!((program.as_str(), args), ("pamixer", vec!["--set-volume".to_string(), "30".to_string()]));
+                assert_eq!(params.get_int("value"), Some(30));
+                assert_eq!(params.provenance("value"), Some(Provenance::Default));
+            }
+            other => panic!("expected DryRun, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn missing_parameters_are_asked_for() {
+        let mut exec = dry_run_executor(&spec(&[("value", "int 0-100")]));
+        match exec.handle_intent(&intent_with(Params::new())) {
+            ExecStatus::NeedsParameter { id, name, prompt } => assert_eq!((id.as_str(), name.as_str(), prompt.as_str()), ("volume_set", "value", "To what percent?")),
+            other => panic!("expected NeedsParameter, got {:?}", other),
+        }
+        let (request_id, _) = exec.awaiting_parameter().unwrap();
+        assert_eq!(exec.answer_request_id(), Some(request_id));
+        match exec.fill_parameter("sixty percent") {
+            Some((intent, ExecStatus::DryRun { args, params, .. })) => {
+                assert_eq!(args, ["--set-volume", "60"]);
+                assert_eq!(params.provenance("value"), Some(Provenance::Deterministic));
+                assert_eq!(intent.parameters.get_int("value"), Some(60));
+            }
+            other => panic!("expected DryRun, got {:?}", other.map(|(_, s)| s)),
+        }
+        assert!(exec.awaiting_parameter().is_none());
+
+        // An answer without a number gives the utterance back to the router.
+        exec.handle_intent(&intent_with(Params::new()));
+        assert!(exec.fill_parameter("what time is it").is_none());
+        assert!(exec.awaiting_parameter().is_none());
+
+        exec.handle_intent(&intent_with(Params::new()));
+        assert!(matches!(exec.fill_parameter("never mind"), Some((_, ExecStatus::Canceled { .. }))));
+
+        exec.handle_intent(&intent_with(Params::new()));
+        assert!(exec.handle_tick(Instant::now() + Duration::from_secs(5)).is_none());
+        assert!(matches!(exec.handle_tick(Instant::now() + Duration::from_secs(11)), Some(ExecStatus::Canceled { .. })));
+        assert!(exec.answer_request_id().is_none());
+    }
+
+    #[test]
+    fn parameter_answers_by_type() {
+        assert_eq!(missing_parameter(&spec(&[("output", "enum laptop|hdmi"), ("level", "int 0-5 default=2")]), &Params::new()).unwrap().1, "Which one: laptop, hdmi?");
+        assert_eq!(parameter_answer("output", "enum laptop|hdmi", "the HDMI one."), [("output".to_string(), json!("hdmi"))]);
+        assert!(parameter_answer("output", "enum laptop|hdmi", "the projector").is_empty());
+        assert_eq!(parameter_answer("duration_secs", "int", "ten minutes"), [("duration_secs".to_string(), json!(600))]);
+        assert_eq!(parameter_answer("hour", "int 0-23", "at 7 30 pm").len(), 2);
+        assert_eq!(parameter_answer("name", "string max=20", " road trip "), [("name".to_string(), json!("road trip"))]);
+    }
+
+    #[test]
+    fn chains_run_in_order_or_confirm_as_one() {
+        let mut exec = dry_run_executor(&spec(&[("value", "int 0-100")]));
+        let lock = ExecCommand {
+            id: "lock_screen".into(),
+            description: "Lock the screen".into(),
+            dangerous: true,
+            parameters: HashMap::new(),
+            shell_command_template: "loginctl lock-session".into(),
+            env_allowlist: None,
+            confirmation: None,
+        };
+        exec.by_id.insert(lock.id.clone(), lock);
+        let volume = |v: i64| {
+            let mut p = Params::new();
+            p.insert("value", json!(v), Provenance::Deterministic);
+            intent_with(p)
+        };
+        let lock_intent = IntentResult { command_id: Some("lock_screen".into()), ..intent_with(Params::new()) };
+
+        let args: Vec<Vec<String>> = exec
+            .handle_chain(&[volume(20), volume(40)])
+            .into_iter()
+            .map(|s| match s {
+                ExecStatus::DryRun { args, .. } => args,
+                other => panic!("expected DryRun, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(args, [["--set-volume", "20"], ["--set-volume", "40"]]);
+
+        // One dangerous part holds the whole chain behind a single preview.
+        match exec.handle_chain(&[volume(20), lock_intent.clone()]).as_slice() {
+            [ExecStatus::PendingConfirmation { id, description, .. }] => {
+                assert_eq!((id.as_str(), description.as_str()), ("volume_set", "Set volume, then Lock the screen"));
+            }
+            other => panic!("expected one PendingConfirmation, got {:?}", other),
+        }
+        assert!(matches!(exec.handle_chain(&[volume(10)]).as_slice(), [ExecStatus::Rejected { .. }]));
+        match exec.confirm_pending() {
+            ExecStatus::DryRun { id, args, .. } => assert_eq!((id.as_str(), args), ("lock_screen", vec!["lock-session".to_string()])),
+            other => panic!("expected DryRun, got {:?}", other),
+        }
+
+        // Nothing runs when any part is incomplete.
+        match exec.handle_chain(&[lock_intent, intent_with(Params::new())]).as_slice() {
+            [ExecStatus::Rejected { reason }] => assert_eq!(reason, "'volume_set' needs 'value'; ask for it on its own"),
+            other => panic!("expected Rejected, got {:?}", other),
+        }
+        assert!(!exec.has_pending() && exec.awaiting_parameter().is_none());
+    }
+
+    #[test]
+    fn voice_confirmation_reprompts_once_then_cancels() {
+        let confirm = IntentResult { requires_confirmation: true, ..intent_with(Params::new()) };
+        let mut exec = dry_run_executor(&spec(&[("value", "int 0-100 default=30")]));
+        assert!(matches!(exec.handle_intent(&confirm), ExecStatus::PendingConfirmation { .. }));
+        assert!(matches!(exec.handle_confirmation_text("yes but change it to 50"), ExecStatus::PendingConfirmation { .. }));
+        assert!(matches!(exec.handle_confirmation_text("hmm"), ExecStatus::Canceled { .. }));
+        assert!(!exec.has_pending());
+
+        assert!(matches!(exec.handle_intent(&confirm), ExecStatus::PendingConfirmation { .. }));
+        assert!(matches!(exec.handle_confirmation_text("Yes"), ExecStatus::DryRun { .. }));
+
+        exec.cfg.voice_confirmation = false;
+        assert!(matches!(exec.handle_intent(&confirm), ExecStatus::PendingConfirmation { .. }));
+        assert!(matches!(exec.handle_confirmation_text("yes"), ExecStatus::Ignored));
+        assert!(exec.has_pending());
+    }
+
+    #[test]
+    fn confirmation_policy_global_and_per_command() {
+        let plain = intent_with(Params::new());
+        let flagged = IntentResult { requires_confirmation: true, ..intent_with(Params::new()) };
+        let mut exec = dry_run_executor(&spec(&[("value", "int 0-100 default=30")]));
+        assert!(matches!(exec.handle_intent(&plain), ExecStatus::DryRun { .. }));
+
+        exec.cfg.confirmation = ConfirmationPolicy::Always;
+        assert!(matches!(exec.handle_intent(&plain), ExecStatus::PendingConfirmation { .. }));
+        exec.cancel_pending("test");
+
+        // The command's own setting wins over the global one.
+        exec.by_id.get_mut("volume_set").unwrap().confirmation = Some("never".into());
+        assert!(matches!(exec.handle_intent(&flagged), ExecStatus::DryRun { .. }));
+        assert_eq!(exec.confirmation_for(&exec.by_id["volume_set"]), ConfirmationPolicy::Never);
+
+        // ...except that dangerous commands always ask.
+        exec.by_id.get_mut("volume_set").unwrap().dangerous = true;
+        assert!(matches!(exec.handle_intent(&plain), ExecStatus::PendingConfirmation { .. }));
+        assert_eq!(ConfirmationPolicy::from_config("sometimes"), ConfirmationPolicy::DangerousOnly);
+    }
+
+    struct Recorder(std::sync::Arc<std::sync::Mutex<Vec<String>>>);
+
+    impl ExecObserver for Recorder {
+        fn on_executed(&self, id: &str, _stdout: &str) {
+            self.0.lock().unwrap().push(format!("executed {}", id));
+        }
+        fn on_pending(&self, id: &str, _preview: &str, _deadline: Instant) {
+            self.0.lock().unwrap().push(format!("pending {}", id));
+        }
+        fn on_canceled(&self, id: &str, reason: &str) {
+            self.0.lock().unwrap().push(format!("canceled {} ({})", id, reason));
+        }
+        fn on_rejected(&self, _reason: &str) {
+            self.0.lock().unwrap().push("rejected".into());
+        }
+    }
+
+    #[test]
+    fn display_command_quotes_only_when_needed() {
+        let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(display_command("pamixer", &args(&["--set-volume", "30"])), "pamixer --set-volume 30");
+        assert_eq!(display_command("notify-send", &args(&["hello world", "it's", ""])), r"notify-send 'hello world' 'it'\''s' ''");
+    }
+
+    #[test]
+    fn observers_see_every_status() {
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut exec = dry_run_executor(&spec(&[("value", "int 0-100 default=30")]));
+        exec.add_observer(Box::new(Recorder(seen.clone())));
+        let confirm = IntentResult { requires_confirmation: true, ..intent_with(Params::new()) };
+
+        exec.handle_intent(&intent_with(Params::new()));
+        exec.handle_intent(&confirm);
+        exec.handle_intent(&confirm);
+        exec.confirm_pending();
+        exec.handle_intent(&confirm);
+        exec.handle_tick(Instant::now() + Duration::from_secs(60));
+        exec.handle_intent(&IntentResult { command_id: Some("nope".into()), ..intent_with(Params::new()) });
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                "executed volume_set",
+                "pending volume_set",
+                "rejected",
+                "executed volume_set",
+                "pending volume_set",
+                "canceled volume_set (confirmation timed out)",
+                "rejected",
+            ]
+        );
+    }
+
+    fn pending_executor(policy: PendingPolicy) -> (Executor, IntentResult) {
+        let mut exec = dry_run_executor(&spec(&[("value", "int 0-100 default=30")]));
+        exec.cfg.pending_policy = policy;
+        let confirm = IntentResult { requires_confirmation: true, ..intent_with(Params::new()) };
+        assert!(matches!(exec.handle_intent(&confirm), ExecStatus::PendingConfirmation { .. }));
+        (exec, confirm)
+    }
+
+    #[test]
+    fn reject_policy_keeps_the_pending_command() {
+        let (mut exec, confirm) = pending_executor(PendingPolicy::Reject);
+        let first = exec.pending_request_id().unwrap().to_string();
+        assert!(matches!(exec.handle_intent(&confirm), ExecStatus::Rejected { .. }));
+        assert_eq!(exec.pending_request_id(), Some(first.as_str()));
+    }
+
+    #[test]
+    fn replace_policy_swaps_in_a_fresh_confirmation() {
+        let (mut exec, _) = pending_executor(PendingPolicy::Replace);
+        let first = exec.pending_request_id().unwrap().to_string();
+        let mut p = Params::new();
+        p.insert("value", serde_json::json!(70), Provenance::Deterministic);
+        let next = IntentResult { requires_confirmation: true, ..intent_with(p) };
+        match exec.handle_intent(&next) {
+            ExecStatus::PendingConfirmation { params, .. } => assert_eq!(params.get_int("value"), Some(70)),
+            other => panic!("expected PendingConfirmation, got {:?}", other),
+        }
+        assert_ne!(exec.pending_request_id(), Some(first.as_str()));
+
+        // A command that needs no confirmation just runs, dropping the stale one.
+        assert!(matches!(exec.handle_intent(&intent_with(Params::new())), ExecStatus::DryRun { .. }));
+        assert!(!exec.has_pending());
+    }
+
+    #[test]
+    fn queue_policy_holds_one_command_until_the_pending_one_resolves() {
+        let (mut exec, _) = pending_executor(PendingPolicy::Queue);
+        let first = exec.pending_request_id().unwrap().to_string();
+        // Even a command that would normally run at once waits, then asks.
+        assert!(matches!(exec.handle_intent(&intent_with(Params::new())), ExecStatus::Queued { .. }));
+        assert_eq!(exec.queued_id(), Some("volume_set"));
+        assert!(matches!(exec.confirm_pending(), ExecStatus::DryRun { .. }));
+        assert!(exec.queued_id().is_none());
+        let second = exec.pending_request_id().unwrap().to_string();
+        assert_ne!(second, first);
+
+        // Cancel and timeout promote too.
+        assert!(matches!(exec.handle_intent(&intent_with(Params::new())), ExecStatus::Queued { .. }));
+        assert!(matches!(exec.cancel_pending("user canceled"), ExecStatus::Canceled { .. }));
+        assert!(exec.has_pending());
+        assert!(matches!(exec.handle_intent(&intent_with(Params::new())), ExecStatus::Queued { .. }));
+        assert!(exec.handle_tick(Instant::now() + Duration::from_secs(60)).is_some());
+        assert!(exec.has_pending());
+        assert!(exec.queued_id().is_none());
+    }
+
+    #[test]
+    fn queue_policy_overflow_is_rejected() {
+        let (mut exec, confirm) = pending_executor(PendingPolicy::Queue);
+        assert!(matches!(exec.handle_intent(&confirm), ExecStatus::Queued { .. }));
+        match exec.handle_intent(&confirm) {
+            ExecStatus::Rejected { reason } => assert!(reason.contains("already queued")),
+            other => panic!("expected Rejected, got {:?}", other),
+        }
+        let unknown = IntentResult { command_id: Some("nope".into()), ..confirm.clone() };
+        assert_eq!(exec.clear_queue().as_deref(), Some("volume_set"));
+        assert!(matches!(exec.handle_intent(&unknown), ExecStatus::Rejected { .. }));
+        let _ = exec.cancel_pending("aborted");
+        assert!(!exec.has_pending());
+    }
 }
@@ -0,0 +1,140 @@
+use crate::manager::TranscriptEntry;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// What the main loop publishes for `/health`. Updated once per audio frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManagerState {
+    pub state: String,
+    pub pending_request_id: Option<String>,
+    pub asr_worker_alive: bool,
+    /// Rolling RMS of recent non-speech audio, see `vad::AdaptiveVad`.
+    pub background_rms: f32,
+    /// Number of successful commands.json reloads since startup.
+    pub commands_version: u64,
+    /// The last few transcripts, oldest first.
+    pub recent_transcripts: Vec<TranscriptEntry>,
+}
+
+impl Default for ManagerState {
+    fn default() -> Self {
+        Self { state: "Idle".into(), pending_request_id: None, asr_worker_alive: false, background_rms: 0.0, commands_version: 0, recent_transcripts: Vec::new() }
+    }
+}
+
+fn body(status: &ManagerState, uptime: Duration) -> String {
+    serde_json::json!({
+        "state": status.state,
+        "pending_request_id": status.pending_request_id,
+        "uptime_secs": uptime.as_secs(),
+        "asr_worker_alive": status.asr_worker_alive,
+        "background_rms": status.background_rms,
+        "commands_version": status.commands_version,
+        "recent_transcripts": status.recent_transcripts.iter().map(TranscriptEntry::to_json).collect::<Vec<_>>(),
+    })
+    .to_string()
+}
+
+/// Answer one connection: any GET gets the JSON status, anything else 405.
+fn respond(stream: TcpStream, status: &Mutex<ManagerState>, started: Instant) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut stream = reader.into_inner();
+    let (code, payload) = if request_line.starts_with("GET ") {
+        let snapshot = status.lock().unwrap_or_else(|p| p.into_inner()).clone();
+        ("200 OK", body(&snapshot, started.elapsed()))
+    } else {
+        ("405 Method Not Allowed", r#"{"error":"method not allowed"}"#.to_string())
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        code,
+        payload.len(),
+        payload
+    )?;
+    stream.flush()
+}
+
+fn serve(listener: TcpListener, status: Arc<Mutex<ManagerState>>) {
+    let started = Instant::now();
+    for conn in listener.incoming() {
+        match conn {
+            Ok(stream) => {
+                if let Err(e) = respond(stream, &status, started) {
+                    log::debug!("health: request failed: {}", e);
+                }
+            }
+            Err(e) => log::warn!("health: accept failed: {}", e),
+        }
+    }
+}
+
+/// Serve the status on `127.0.0.1:port` from a background thread.
+/// Port 0 disables the endpoint.
+pub fn start(port: u16, status: Arc<Mutex<ManagerState>>) -> std::io::Result<()> {
+    if port == 0 {
+        return Ok(());
+    }
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    log::info!("health: serving on http://127.0.0.1:{}/", port);
+    std::thread::spawn(move || serve(listener, status));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn request(port: u16, raw: &str) -> String {
+        let mut conn = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        conn.write_all(raw.as_bytes()).unwrap();
+        let mut out = String::new();
+        conn.read_to_string(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn get_returns_the_shared_state_as_json() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let status = Arc::new(Mutex::new(ManagerState::default()));
+        let shared = status.clone();
+        std::thread::spawn(move || serve(listener, shared));
+
+        let idle = request(port, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        assert!(idle.starts_with("HTTP/1.1 200 OK\r\n"), "{}", idle);
+        assert!(idle.contains("Content-Type: application/json\r\n"));
+        let json: serde_json::Value = serde_json::from_str(idle.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+        assert_eq!(json["state"], "Idle");
+        assert_eq!(json["pending_request_id"], serde_json::Value::Null);
+        assert!(json["uptime_secs"].is_u64());
+
+        *status.lock().unwrap() = ManagerState {
+            state: "Recording".into(),
+            pending_request_id: Some("lock_screen-1".into()),
+            asr_worker_alive: true,
+            background_rms: 0.25,
+            commands_version: 2,
+            recent_transcripts: vec![TranscriptEntry::new("what time is it", "question", None, Some(0.9))],
+        };
+        let busy = request(port, "GET /health HTTP/1.0\r\n\r\n");
+        assert!(busy.contains(r#""state":"Recording""#) && busy.contains(r#""asr_worker_alive":true"#), "{}", busy);
+        assert!(busy.contains(r#""pending_request_id":"lock_screen-1""#));
+        assert!(busy.contains(r#""background_rms":0.25"#), "{}", busy);
+        assert!(busy.contains(r#""commands_version":2"#), "{}", busy);
+        assert!(busy.contains(r#""raw_text":"what time is it""#) && busy.contains(r#""decision_type":"question""#), "{}", busy);
+
+        assert!(request(port, "POST / HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 405"));
+    }
+
+    #[test]
+    fn port_zero_is_disabled() {
+        assert!(start(0, Arc::new(Mutex::new(ManagerState::default()))).is_ok());
+    }
+}
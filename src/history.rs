@@ -0,0 +1,25 @@
+use std::io::Write;
+
+/// Append one interaction entry to `$XDG_STATE_HOME/btw/history.jsonl`.
+///
+/// Best-effort: history is a convenience for reviewing what was said while
+/// notifications were unavailable, never a reason to fail an interaction.
+pub fn record(kind: &str, text: &str, delivered_via: &str) {
+    let path = match xdg::BaseDirectories::with_prefix("btw").ok().and_then(|x| x.place_state_file("history.jsonl").ok()) {
+        Some(p) => p,
+        None => return,
+    };
+    let ts = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let line = serde_json::json!({"ts": ts, "kind": kind, "text": text, "delivered_via": delivered_via}).to_string();
+    let res = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut f| writeln!(f, "{}", line));
+    if let Err(e) = res {
+        log::warn!("history: failed to append to {}: {}", path.display(), e);
+    }
+}
@@ -1,8 +1,14 @@
+use crate::embedding::EmbeddingIndex;
 use crate::error::{BtwError, Result};
 use crate::llm::{LlmClient, LlmIntent};
+use crate::params::{Params, Provenance};
+use crate::rate_limiter::TokenBucket;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::fs;
 use std::path::PathBuf;
+use std::sync::Mutex;
 
 #[derive(Debug, Deserialize)]
 pub struct IntentConfig {
@@ -10,9 +16,21 @@ pub struct IntentConfig {
     pub deterministic_threshold: f32,
     #[serde(default = "default_llm_fallback_threshold")] 
     pub llm_fallback_threshold: f32,
+    /// Minimum cosine similarity for the (optional) embedding tier.
+    #[serde(default = "default_embedding_threshold")]
+    pub embedding_threshold: f32,
+    /// Share of the cosine similarity in the blended score that backs up a
+    /// partial token match (0 = no blending).
+    #[serde(default)]
+    pub embedding_weight: f32,
+    /// LLM classifications allowed per minute (0 = unlimited).
+    #[serde(default = "default_llm_rate_limit_per_min")]
+    pub llm_rate_limit_per_min: u32,
 }
 fn default_deterministic_threshold() -> f32 { 0.75 }
 fn default_llm_fallback_threshold() -> f32 { 0.8 }
+fn default_embedding_threshold() -> f32 { 0.82 }
+fn default_llm_rate_limit_per_min() -> u32 { 20 }
 
 #[derive(Debug, Deserialize)]
 pub struct IntentCommand {
@@ -23,6 +41,54 @@ pub struct IntentCommand {
     pub examples: Vec<String>,
     #[serde(default)]
     pub dangerous: bool,
+    /// Breaks near-ties with other commands: higher wins. Default 0.
+    #[serde(default)]
+    pub priority: i32,
+    /// Id of a command whose examples (and description, if this one has
+    /// none) are inherited at load; see [`resolve_aliases`].
+    #[serde(default)]
+    pub alias_of: Option<String>,
+    /// The `parameters` that declare a `pattern`, compiled at load.
+    #[serde(default, rename = "parameters", deserialize_with = "slot_patterns")]
+    pub slots: Vec<Slot>,
+}
+
+/// A parameter filled from the transcript by its own regex instead of the
+/// id-based guess in [`extract_parameters`].
+#[derive(Debug, Clone)]
+pub struct Slot {
+    pub name: String,
+    /// Case-insensitive. The value is the group named after the slot, else
+    /// group 1, else the whole match; other named groups fill the
+    /// parameters they are named after.
+    pub pattern: regex::Regex,
+    /// An `int` slot is matched with number words already turned into
+    /// digits ("forty" is 40); an `enum` or `string` one sees the words.
+    pub numeric: bool,
+}
+
+fn slot_patterns<'de, D: serde::Deserializer<'de>>(d: D) -> std::result::Result<Vec<Slot>, D::Error> {
+    let decls = HashMap::<String, crate::executor::ParamDecl>::deserialize(d)?;
+    let mut slots = Vec::new();
+    for (name, decl) in decls {
+        let Some(pattern) = decl.pattern() else { continue };
+        let pattern = compile_slot_pattern(pattern).map_err(|e| serde::de::Error::custom(format!("parameter '{}': {}", name, e)))?;
+        let numeric = decl.spec().split_whitespace().next() == Some("int");
+        slots.push(Slot { name, pattern, numeric });
+    }
+    slots.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(slots)
+}
+
+/// A slot `pattern` as it is matched, or why it can't be. The reason is one
+/// line: syntax errors keep only their last line ("unclosed group"), not
+/// the pattern excerpt and caret above it.
+pub fn compile_slot_pattern(pattern: &str) -> std::result::Result<regex::Regex, String> {
+    regex::RegexBuilder::new(pattern).case_insensitive(true).build().map_err(|e| {
+        let text = e.to_string();
+        let reason = text.lines().map(str::trim).rfind(|l| !l.is_empty()).unwrap_or_default();
+        format!("invalid pattern: {}", reason.strip_prefix("error: ").unwrap_or(reason))
+    })
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -30,37 +96,411 @@ pub struct IntentResult {
     pub intent_type: String,
     pub command_id: Option<String>,
     #[serde(default)]
-    pub parameters: serde_json::Value,
+    pub parameters: Params,
     #[serde(default)]
     pub deterministic_score: Option<f32>,
+    /// Cosine similarity when the embedding tier or the blended score
+    /// produced the match.
+    #[serde(default)]
+    pub embedding_score: Option<f32>,
     #[serde(default)]
     pub dangerous: bool,
     #[serde(default)]
     pub requires_confirmation: bool,
 }
 
+/// A routing result plus the second-best deterministic candidate, so callers
+/// can ask for clarification when the two are too close to call.
+#[derive(Debug, Clone)]
+pub struct RankedIntent {
+    pub best: IntentResult,
+    /// Only set when `best` came from the deterministic tier and another
+    /// command scored above zero.
+    pub runner_up: Option<IntentResult>,
+}
+
+/// Normalized text and its token set, computed once at load.
+struct PreparedText {
+    norm: String,
+    tokens: HashSet<String>,
+}
+
+impl PreparedText {
+    fn new(s: &str) -> Self {
+        let norm = normalize(s);
+        let tokens = norm.split_whitespace().map(|t| t.to_string()).collect();
+        Self { norm, tokens }
+    }
+}
+
+struct PreparedCommand {
+    examples: Vec<PreparedText>,
+    description: PreparedText,
+}
+
+/// Per-command normalized examples and token sets, so scoring an utterance
+/// doesn't re-normalize every example of every command.
+pub struct PreparedIndex {
+    commands: Vec<PreparedCommand>,
+}
+
+impl PreparedIndex {
+    pub fn new(commands: &[IntentCommand]) -> Self {
+        let commands = commands
+            .iter()
+            .map(|c| PreparedCommand {
+                examples: c.examples.iter().map(|e| PreparedText::new(e)).collect(),
+                description: PreparedText::new(&c.description),
+            })
+            .collect();
+        Self { commands }
+    }
+}
+
 pub struct IntentRouter {
     pub cfg: IntentConfig,
+    /// Must stay in sync with `index`; replace both via `IntentRouter::new`.
     pub commands: Vec<IntentCommand>,
     pub llm: std::sync::Arc<dyn LlmClient>,
+    pub embeddings: Option<EmbeddingIndex>,
+    index: PreparedIndex,
+    /// Caps LLM classifications so stray wakes can't burn the API quota.
+    llm_budget: Option<Mutex<TokenBucket>>,
+    /// `intent.command_overrides`: deterministic thresholds by command id.
+    threshold_overrides: HashMap<String, f32>,
+}
+
+/// Read and parse commands.json into intent commands.
+pub fn load_commands(commands_path: &PathBuf) -> Result<Vec<IntentCommand>> {
+    let s = fs::read_to_string(commands_path).map_err(|e| BtwError::ReadError { path: commands_path.clone(), source: e })?;
+    for w in crate::commands_schema::validate(commands_path, &s)? {
+        log::warn!("intent: {}: {}", commands_path.display(), w);
+    }
+    let mut commands: Vec<IntentCommand> = serde_json::from_str(&s)
+        .map_err(|e| BtwError::ParseError { path: commands_path.clone(), kind: "json", message: e.to_string(), cause: Some(Box::new(e)) })?;
+    resolve_aliases(&mut commands)
+        .map_err(|message| BtwError::ParseError { path: commands_path.clone(), kind: "json", message, cause: None })?;
+    Ok(commands)
+}
+
+/// Give every `alias_of` command the examples of the command it names
+/// (following chains of aliases), plus its description when the alias has
+/// none. The alias keeps its own id, `dangerous` flag and priority. Unknown
+/// targets and cycles are errors.
+pub fn resolve_aliases(commands: &mut [IntentCommand]) -> std::result::Result<(), String> {
+    let index: std::collections::HashMap<&str, usize> = commands.iter().enumerate().map(|(i, c)| (c.id.as_str(), i)).collect();
+    let mut inherited = Vec::new();
+    for (i, cmd) in commands.iter().enumerate() {
+        if cmd.alias_of.is_none() {
+            continue;
+        }
+        let mut chain = vec![cmd.id.as_str()];
+        let mut cur = i;
+        let (mut examples, mut description) = (Vec::new(), String::new());
+        while let Some(target) = commands[cur].alias_of.as_deref() {
+            if chain.contains(&target) {
+                chain.push(target);
+                return Err(format!("alias cycle: {}", chain.join(" -> ")));
+            }
+            let &next = index.get(target).ok_or_else(|| format!("command '{}': alias_of '{}' is not a known command", commands[cur].id, target))?;
+            chain.push(target);
+            examples.extend(commands[next].examples.iter().cloned());
+            if description.is_empty() {
+                description = commands[next].description.clone();
+            }
+            cur = next;
+        }
+        inherited.push((i, examples, description));
+    }
+    for (i, examples, description) in inherited {
+        let cmd = &mut commands[i];
+        cmd.examples.extend(examples);
+        if cmd.description.is_empty() {
+            cmd.description = description;
+        }
+    }
+    Ok(())
+}
+
+/// Which command tokens an utterance shared with a command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explanation {
+    pub command_id: String,
+    pub score: f32,
+    /// The example (or description) with the largest token overlap.
+    pub closest: String,
+    pub shared_tokens: Vec<String>,
+}
+
+/// An example that routes to a command other than the one it belongs to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrossMatch {
+    pub example: String,
+    pub owner: String,
+    pub owner_score: f32,
+    pub winner: String,
+    pub winner_score: f32,
+    /// Tokens the example shares with the winner's closest phrase.
+    pub shared_tokens: Vec<String>,
+}
+
+impl fmt::Display for CrossMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "example {:?} of '{}' (score {:.3}) routes to '{}' (score {:.3})",
+            self.example, self.owner, self.owner_score, self.winner, self.winner_score
+        )?;
+        if !self.shared_tokens.is_empty() {
+            write!(f, "; shared tokens: {}", self.shared_tokens.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// What produced a command's deterministic score.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchType {
+    /// The utterance is one of the examples (score 1.0).
+    ExactExample,
+    /// An example appears inside the utterance (0.85).
+    SubstringExample,
+    /// The description appears inside the utterance (0.8).
+    SubstringDescription,
+    /// Shared tokens with an example or the description (at most 0.55).
+    TokenOverlap,
+    /// A lock/logout-style command without an explicit keyword is never scored.
+    SensitiveKeywordBlocked,
+    Zero,
+}
+
+impl fmt::Display for MatchType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            MatchType::ExactExample => "exact_example",
+            MatchType::SubstringExample => "substring_example",
+            MatchType::SubstringDescription => "substring_description",
+            MatchType::TokenOverlap => "token_overlap",
+            MatchType::SensitiveKeywordBlocked => "sensitive_keyword_blocked",
+            MatchType::Zero => "zero",
+        })
+    }
+}
+
+/// One command's deterministic score for an utterance, for tuning commands.json.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoreEntry {
+    pub command_id: String,
+    pub score: f32,
+    /// The example behind the score; None for description and zero matches.
+    pub matched_example: Option<String>,
+    pub match_type: MatchType,
+    /// The deterministic threshold this command has to reach (its override, if any).
+    pub threshold: f32,
+}
+
+/// Every command's deterministic score for `text`, best first, with the
+/// threshold `threshold_for` gives each command id. Exact ties keep
+/// document order.
+pub fn score_all(commands: &[IntentCommand], index: &PreparedIndex, text: &str, threshold_for: impl Fn(&str) -> f32) -> Vec<ScoreEntry> {
+    let mut entries = score_entries(commands, index, &normalize(text), threshold_for);
+    entries.sort_by(|a, b| b.score.total_cmp(&a.score));
+    entries
+}
+
+/// Like [`score_all`] for already-normalized text, in document order.
+fn score_entries(commands: &[IntentCommand], index: &PreparedIndex, norm_text: &str, threshold_for: impl Fn(&str) -> f32) -> Vec<ScoreEntry> {
+    commands
+        .iter()
+        .zip(&index.commands)
+        .map(|(cmd, prep)| {
+            let (score, match_type, matched_example) = score_detailed(norm_text, cmd, prep);
+            ScoreEntry {
+                command_id: cmd.id.clone(),
+                score,
+                matched_example: matched_example.map(str::to_string),
+                match_type,
+                threshold: threshold_for(&cmd.id),
+            }
+        })
+        .collect()
+}
+
+/// Route every example through the deterministic scorer and report those
+/// whose best match is not their own command (ties go to the earlier
+/// command, as in `route`).
+/// Scores this close count as a tie, decided by [`IntentCommand::priority`].
+const PRIORITY_TIE_EPSILON: f32 = 0.001;
+
+/// Whether candidate `a` beats `b`. Near-ties between commands of different
+/// priority go to the higher priority; otherwise the strictly higher score
+/// wins, so an exact tie keeps the earlier command (document order).
+fn outranks(a: (f32, &IntentCommand), b: (f32, &IntentCommand)) -> bool {
+    if (a.0 - b.0).abs() <= PRIORITY_TIE_EPSILON && a.1.priority != b.1.priority {
+        return a.1.priority > b.1.priority;
+    }
+    a.0 > b.0
+}
+
+pub fn self_check(commands: &[IntentCommand], index: &PreparedIndex) -> Vec<CrossMatch> {
+    let mut out = Vec::new();
+    for (owner_idx, owner) in commands.iter().enumerate() {
+        for (ex_idx, ex) in owner.examples.iter().enumerate() {
+            let norm = &index.commands[owner_idx].examples[ex_idx].norm;
+            if norm.is_empty() {
+                continue;
+            }
+            let mut best: Option<(f32, usize)> = None;
+            let mut owner_score = 0.0;
+            for (i, cmd) in commands.iter().enumerate() {
+                let score = score_prepared(norm, cmd, &index.commands[i]);
+                if i == owner_idx {
+                    owner_score = score;
+                }
+                best = match best {
+                    Some((b, j)) if outranks((score, cmd), (b, &commands[j])) => Some((score, i)),
+                    None => Some((score, i)),
+                    _ => best,
+                };
+            }
+            let Some((winner_score, winner_idx)) = best else { continue };
+            if winner_idx == owner_idx || winner_score <= 0.0 {
+                continue;
+            }
+            let winner = &commands[winner_idx];
+            out.push(CrossMatch {
+                example: ex.clone(),
+                owner: owner.id.clone(),
+                owner_score,
+                winner: winner.id.clone(),
+                winner_score,
+                shared_tokens: explain_prepared(norm, winner, &index.commands[winner_idx]).shared_tokens,
+            });
+        }
+    }
+    out
+}
+
+fn explain_prepared(norm_text: &str, cmd: &IntentCommand, prep: &PreparedCommand) -> Explanation {
+    let tset: HashSet<&str> = norm_text.split_whitespace().collect();
+    let mut closest: Option<(usize, &str, Vec<String>)> = None;
+    let phrases = cmd.examples.iter().zip(&prep.examples).chain(std::iter::once((&cmd.description, &prep.description)));
+    for (raw, p) in phrases {
+        let mut shared: Vec<String> = p.tokens.iter().filter(|t| tset.contains(t.as_str())).cloned().collect();
+        shared.sort();
+        if closest.as_ref().map(|c| shared.len() > c.0).unwrap_or(true) {
+            closest = Some((shared.len(), raw.as_str(), shared));
+        }
+    }
+    let (_, closest, shared_tokens) = closest.unwrap_or((0, "", Vec::new()));
+    Explanation {
+        command_id: cmd.id.clone(),
+        score: score_prepared(norm_text, cmd, prep),
+        closest: closest.to_string(),
+        shared_tokens,
+    }
 }
 
 impl IntentRouter {
+    pub fn new(cfg: IntentConfig, commands: Vec<IntentCommand>, llm: std::sync::Arc<dyn LlmClient>) -> Self {
+        let index = PreparedIndex::new(&commands);
+        let llm_budget = (cfg.llm_rate_limit_per_min > 0).then(|| Mutex::new(TokenBucket::per_minute(cfg.llm_rate_limit_per_min)));
+        Self { cfg, commands, llm, embeddings: None, index, llm_budget, threshold_overrides: HashMap::new() }
+    }
+
     pub fn from_file(commands_path: &PathBuf, cfg: IntentConfig, llm: std::sync::Arc<dyn LlmClient>) -> Result<Self> {
-        let s = fs::read_to_string(commands_path).map_err(|e| BtwError::ReadError { path: commands_path.clone(), source: e })?;
-        let cmds: Vec<IntentCommand> = serde_json::from_str(&s).map_err(|e| BtwError::ParseError { path: commands_path.clone(), kind: "json", message: e.to_string() })?;
-        Ok(Self { cfg, commands: cmds, llm })
+        Ok(Self::new(cfg, load_commands(commands_path)?, llm))
     }
 
-    pub fn route(&self, text: &str) -> IntentResult {
-        let norm = normalize(text);
+    /// Examples that can't reach their own command; see [`self_check`].
+    pub fn self_check(&self) -> Vec<CrossMatch> {
+        self_check(&self.commands, &self.index)
+    }
+
+    /// Deterministic score of every command for `text`; see [`score_all`].
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn score_all(&self, text: &str) -> Vec<ScoreEntry> {
+        score_all(&self.commands, &self.index, text, |id| self.threshold_for(id))
+    }
+
+    /// Swap in reloaded thresholds. The LLM budget starts over only when
+    /// its rate changes.
+    pub fn set_config(&mut self, cfg: IntentConfig) {
+        if cfg.llm_rate_limit_per_min != self.cfg.llm_rate_limit_per_min {
+            self.llm_budget = (cfg.llm_rate_limit_per_min > 0).then(|| Mutex::new(TokenBucket::per_minute(cfg.llm_rate_limit_per_min)));
+        }
+        self.cfg = cfg;
+    }
+
+    /// Use these deterministic thresholds (by command id) instead of
+    /// `deterministic_threshold`. They survive command reloads.
+    pub fn set_threshold_overrides(&mut self, overrides: HashMap<String, f32>) {
+        for id in overrides.keys().filter(|id| !self.commands.iter().any(|c| &c.id == *id)) {
+            log::warn!("intent: threshold override for unknown command '{}'", id);
+        }
+        self.threshold_overrides = overrides;
+    }
+
+    /// The deterministic threshold `command_id` has to reach.
+    pub fn threshold_for(&self, command_id: &str) -> f32 {
+        let threshold = self.threshold_overrides.get(command_id).copied().unwrap_or(self.cfg.deterministic_threshold);
         // Safety guard: a zero/negative threshold effectively disables intent gating.
         // Never allow that, even if config is mis-parsed.
-        let det_threshold = if self.cfg.deterministic_threshold > 0.0 {
-            self.cfg.deterministic_threshold
+        if threshold > 0.0 {
+            threshold
         } else {
             0.75
-        };
+        }
+    }
+
+    /// The cosine similarity an embedding match has to reach.
+    pub fn embedding_threshold(&self) -> f32 {
+        self.cfg.embedding_threshold
+    }
+
+    /// Why `text` scores the way it does against `command_id`.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn explain(&self, text: &str, command_id: &str) -> Option<Explanation> {
+        let i = self.commands.iter().position(|c| c.id == command_id)?;
+        Some(explain_prepared(&normalize(text), &self.commands[i], &self.index.commands[i]))
+    }
+
+    /// Enable the embedding tier with a precomputed example index.
+    /// Swap in a reloaded command list. The embedding index no longer
+    /// matches and is dropped; rebuild it with [`IntentRouter::set_embeddings`].
+    pub fn replace_commands(&mut self, commands: Vec<IntentCommand>) {
+        self.index = PreparedIndex::new(&commands);
+        self.commands = commands;
+        self.embeddings = None;
+    }
+
+    pub fn set_embeddings(&mut self, index: EmbeddingIndex) {
+        self.embeddings = Some(index);
+    }
+
+    pub fn has_embeddings(&self) -> bool {
+        self.embeddings.as_ref().map(|i| !i.is_empty()).unwrap_or(false)
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn route(&self, text: &str) -> IntentResult {
+        self.route_with_embedding(text, None)
+    }
+
+    /// Route with an optional precomputed embedding of `text`.
+    ///
+    /// Tiers, in order: deterministic token scoring, embedding similarity and
+    /// the blend of both (only when both an index and `query_embedding` are
+    /// present), LLM fallback.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn route_with_embedding(&self, text: &str, query_embedding: Option<&[f32]>) -> IntentResult {
+        self.route_ranked(text, query_embedding).best
+    }
+
+    /// Like [`route_with_embedding`](Self::route_with_embedding), but also
+    /// returns the runner-up of the deterministic tier.
+    pub fn route_ranked(&self, text: &str, query_embedding: Option<&[f32]>) -> RankedIntent {
+        let norm = normalize(text);
         if is_obvious_question(&norm) {
             // Avoid running commands for informational questions.
             // Still allow deterministic routing for explicit action phrases.
@@ -68,23 +508,35 @@ impl IntentRouter {
         }
         // Deterministic matching
         let mut best: Option<(f32, &IntentCommand)> = None;
-        for cmd in &self.commands {
-            let score = self.score_command(&norm, cmd);
-                best = match best {
-                    Some((b, _)) if score > b => Some((score, cmd)),
-                    None => Some((score, cmd)),
-                    _ => best,
-                };
+        let mut second: Option<(f32, &IntentCommand)> = None;
+        let entries = score_entries(&self.commands, &self.index, &norm, |id| self.threshold_for(id));
+        for (cmd, entry) in self.commands.iter().zip(&entries) {
+            let score = entry.score;
+            match best {
+                Some(b) if outranks((score, cmd), b) => {
+                    second = best;
+                    best = Some((score, cmd));
+                }
+                None => best = Some((score, cmd)),
+                // Ties go to the runner-up; they are exactly what it is for.
+                _ if !matches!(second, Some(s) if !outranks((score, cmd), s)) => second = Some((score, cmd)),
+                _ => {}
+            }
         }
+        let ranked = |cmd: &IntentCommand, score: f32| RankedIntent {
+            best: self.result_for(cmd, norm.as_str(), score),
+            runner_up: second.filter(|(s, _)| *s > 0.0).map(|(s, c)| self.result_for(c, norm.as_str(), s)),
+        };
         if let Some((score, cmd)) = best {
+            let det_threshold = self.threshold_for(&cmd.id);
             if score <= 0.0 {
-                eprintln!(
+                log::debug!(
                     "intent: no deterministic match (best was id={} score={:.3} < min=0.001)",
                     cmd.id,
                     score
                 );
             } else {
-                eprintln!(
+                log::debug!(
                     "intent: best deterministic match id={} score={:.3} threshold={:.3}",
                     cmd.id,
                     score,
@@ -99,91 +551,129 @@ impl IntentRouter {
                 if is_obvious_question(&norm) {
                     let strict = (det_threshold + 0.20).min(0.95);
                     if score < strict {
-                        eprintln!(
+                        log::debug!(
                             "intent: question-like input; rejecting deterministic match (score={:.3} < strict={:.3})",
                             score,
                             strict
                         );
                     } else {
-                        return self.result_for(cmd, norm.as_str(), score);
+                        return ranked(cmd, score);
                     }
                 } else {
-                    return self.result_for(cmd, norm.as_str(), score);
+                    return ranked(cmd, score);
                 }
             }
         }
+        // Embedding tier: catches paraphrases that token overlap misses,
+        // without paying for an LLM round trip.
+        if let Some(r) = self.embedding_match(&norm, query_embedding) {
+            return RankedIntent { best: r, runner_up: None };
+        }
+        let lexical: Vec<f32> = entries.iter().map(|e| e.score).collect();
+        if let Some(r) = self.blended_match(&norm, query_embedding, &lexical) {
+            return RankedIntent { best: r, runner_up: None };
+        }
         // LLM fallback (classification only)
-        match self.llm_classify(text) {
+        let best = match self.llm_classify(text) {
             Ok(r) => r,
-            Err(_) => IntentResult {
-                intent_type: "unknown_intent".into(),
-                command_id: None,
-                parameters: serde_json::json!({}),
-                deterministic_score: None,
-                dangerous: false,
-                requires_confirmation: false,
-            },
+            Err(_) => unknown_intent(),
+        };
+        RankedIntent { best, runner_up: None }
+    }
+
+    /// Split "lower the volume and then lock the screen" into one result
+    /// per command, in spoken order. It only counts as a chain when every
+    /// part is a confident deterministic match on its own, so "play rock and
+    /// roll" stays one utterance. `None` means: route `text` as a whole.
+    pub fn route_chain(&self, text: &str) -> Option<Vec<IntentResult>> {
+        let parts = split_chain(&normalize(text));
+        if parts.len() < 2 || parts.len() > MAX_CHAIN {
+            return None;
         }
+        parts.iter().map(|part| self.confident_match(part)).collect()
     }
 
-    fn score_command(&self, norm_text: &str, cmd: &IntentCommand) -> f32 {
-        // Extra safety: for sensitive commands (e.g., lock/logout), require at least
-        // one explicit action keyword to even consider overlap/substrings.
-        if is_sensitive_command_id(&cmd.id) {
-            let keywords = ["lock", "logout", "log out", "sign out", "suspend", "shutdown", "shut down", "reboot", "restart"];
-            let has_keyword = keywords.iter().any(|k| norm_text.contains(k));
-            if !has_keyword {
-                return 0.0;
-            }
+    /// The deterministic tier alone: the best command if it clears its
+    /// threshold. Embeddings and the LLM have no part in chains.
+    fn confident_match(&self, norm: &str) -> Option<IntentResult> {
+        if is_obvious_question(norm) {
+            return None;
         }
+        let entries = score_entries(&self.commands, &self.index, norm, |id| self.threshold_for(id));
+        let (score, cmd) = self
+            .commands
+            .iter()
+            .zip(&entries)
+            .map(|(cmd, e)| (e.score, cmd))
+            .reduce(|best, next| if outranks(next, best) { next } else { best })?;
+        (score > 0.0 && score >= self.threshold_for(&cmd.id)).then(|| self.result_for(cmd, norm, score))
+    }
 
-        let mut score: f32 = 0.0;
-        // If the input is very short, be conservative with overlap-based scoring.
-        let input_tokens: Vec<&str> = norm_text.split_whitespace().collect();
-        let is_short_input = input_tokens.len() <= 3;
-
-        // exact match against examples
-        for ex in &cmd.examples {
-            let e = normalize(ex);
-            if e == norm_text { return 1.0; }
-            if !e.is_empty() && norm_text.contains(&e) { score = score.max(0.85); }
-        }
-        // substring match against description
-        let desc = normalize(&cmd.description);
-        if !desc.is_empty() && norm_text.contains(&desc) { score = score.max(0.8); }
-        // token overlap (simple Jaccard-like)
-        let tset: std::collections::HashSet<_> = norm_text.split_whitespace().collect();
-        let mut candidates: Vec<String> = cmd.examples.clone();
-        candidates.push(cmd.description.clone());
-        let mut best_overlap: f32 = 0.0;
-        for c in candidates {
-            let cnorm = normalize(&c);
-            let cset: std::collections::HashSet<_> = cnorm.split_whitespace().collect();
-            let inter = tset.intersection(&cset).count() as f32;
-            let union = tset.union(&cset).count() as f32;
-            if union > 0.0 {
-                best_overlap = best_overlap.max(inter / union);
-            }
+    fn embedding_match(&self, norm: &str, query_embedding: Option<&[f32]>) -> Option<IntentResult> {
+        let index = self.embeddings.as_ref()?;
+        let query = query_embedding?;
+        // Same conservatism as the deterministic tier: questions are never
+        // routed to commands on similarity alone.
+        if is_obvious_question(norm) {
+            return None;
         }
-        // Overlap alone is weak evidence. Cap its influence, and require a minimum
-        // number of overlapping tokens to avoid accidental matches.
-        if best_overlap > 0.0 {
-            let mut max_inter: usize = 0;
-            for c in &cmd.examples {
-                let cnorm = normalize(c);
-                let cset: std::collections::HashSet<_> = cnorm.split_whitespace().collect();
-                max_inter = max_inter.max(tset.intersection(&cset).count());
-            }
-            let descset: std::collections::HashSet<_> = desc.split_whitespace().collect();
-            max_inter = max_inter.max(tset.intersection(&descset).count());
+        let (id, sim) = index.best(query)?;
+        log::debug!(
+            "intent: best embedding match id={} cosine={:.3} threshold={:.3}",
+            id,
+            sim,
+            self.cfg.embedding_threshold
+        );
+        if self.cfg.embedding_threshold <= 0.0 || sim < self.cfg.embedding_threshold {
+            return None;
+        }
+        let cmd = self.commands.iter().find(|c| c.id == id)?;
+        if is_sensitive_command_id(&cmd.id) && !has_sensitive_keyword(norm) {
+            log::warn!("intent: embedding match for sensitive command '{}' lacks explicit keyword; rejecting", cmd.id);
+            return None;
+        }
+        let mut r = self.result_for(cmd, norm, sim);
+        r.deterministic_score = None;
+        r.embedding_score = Some(sim);
+        Some(r)
+    }
 
-            // Need at least 2 shared tokens unless the input is short.
-            let min_inter = if is_short_input { 1 } else { 2 };
-            if max_inter >= min_inter {
-                score = score.max(0.55 * best_overlap);
-            }
+    /// `embedding_weight × cosine + (1 − embedding_weight) × token score` per
+    /// command, held to the command's deterministic threshold. Catches
+    /// "make the screen dimmer" style rewordings that share a word or two
+    /// with an example but aren't close enough for either tier alone.
+    fn blended_match(&self, norm: &str, query_embedding: Option<&[f32]>, lexical: &[f32]) -> Option<IntentResult> {
+        let weight = self.cfg.embedding_weight.clamp(0.0, 1.0);
+        if weight <= 0.0 || is_obvious_question(norm) {
+            return None;
         }
-        score
+        let similarities: HashMap<String, f32> = self.embeddings.as_ref()?.rank(query_embedding?).into_iter().collect();
+        let (cmd, lex, sim, blended) = self
+            .commands
+            .iter()
+            .zip(lexical)
+            // Without any token overlap this is the embedding tier's call.
+            .filter(|(_, lex)| **lex > 0.0)
+            .filter_map(|(cmd, &lex)| {
+                let sim = *similarities.get(&cmd.id)?;
+                Some((cmd, lex, sim, weight * sim + (1.0 - weight) * lex))
+            })
+            .max_by(|a, b| a.3.total_cmp(&b.3))?;
+        let threshold = self.threshold_for(&cmd.id);
+        log::debug!(
+            "intent: best blended match id={} score={:.3} cosine={:.3} blended={:.3} threshold={:.3}",
+            cmd.id,
+            lex,
+            sim,
+            blended,
+            threshold
+        );
+        if blended < threshold {
+            return None;
+        }
+        let mut r = self.result_for(cmd, norm, lex);
+        r.embedding_score = Some(sim);
+        Some(r)
     }
 
     fn result_for(&self, cmd: &IntentCommand, text: &str, score: f32) -> IntentResult {
@@ -198,14 +688,21 @@ impl IntentRouter {
             command_id: Some(cmd.id.clone()),
             parameters: params,
             deterministic_score: Some(score),
+            embedding_score: None,
             dangerous,
             requires_confirmation,
         }
     }
 
     fn llm_classify(&self, text: &str) -> Result<IntentResult> {
+        if let Some(budget) = &self.llm_budget {
+            if !budget.lock().unwrap_or_else(|p| p.into_inner()).try_consume(1) {
+                log::debug!("intent: LLM rate limit reached; not classifying");
+                return Ok(unknown_intent());
+            }
+        }
         let llm_result: LlmIntent = self.llm.classify_intent(text, &self.commands)
-            .map_err(|e| BtwError::ParseError { path: PathBuf::new(), kind: "llm", message: e })?;
+            .map_err(|message| BtwError::LlmError { provider: self.llm.provider(), message })?;
         if let Some(id) = llm_result.command_id {
             if llm_result.confidence >= self.cfg.llm_fallback_threshold {
                 let dangerous = self.commands.iter().find(|c| c.id == id).map(|c| c.dangerous).unwrap_or(false);
@@ -213,24 +710,111 @@ impl IntentRouter {
                     return Ok(IntentResult {
                         intent_type: if dangerous { "dangerous_command".into() } else { "command".into() },
                         command_id: Some(id),
-                        parameters: llm_result.parameters,
+                        parameters: Params::from_value(&llm_result.parameters, Provenance::Llm),
                         deterministic_score: None,
+                        embedding_score: None,
                         dangerous,
                         requires_confirmation,
                     });
             }
         }
-        Ok(IntentResult {
-            intent_type: "unknown_intent".into(),
-            command_id: None,
-            parameters: serde_json::json!({}),
-            deterministic_score: None,
-            dangerous: false,
-            requires_confirmation: false,
-        })
+        Ok(unknown_intent())
+    }
+}
+
+fn unknown_intent() -> IntentResult {
+    IntentResult {
+        intent_type: "unknown_intent".into(),
+        command_id: None,
+        parameters: Params::new(),
+        deterministic_score: None,
+        embedding_score: None,
+        dangerous: false,
+        requires_confirmation: false,
     }
 }
 
+fn score_prepared(norm_text: &str, cmd: &IntentCommand, prep: &PreparedCommand) -> f32 {
+    score_detailed(norm_text, cmd, prep).0
+}
+
+/// The score, which rule produced it and, for example-based rules, the example.
+fn score_detailed<'a>(norm_text: &str, cmd: &'a IntentCommand, prep: &PreparedCommand) -> (f32, MatchType, Option<&'a str>) {
+    // Extra safety: for sensitive commands (e.g., lock/logout), require at least
+    // one explicit action keyword to even consider overlap/substrings.
+    if is_sensitive_command_id(&cmd.id) && !has_sensitive_keyword(norm_text) {
+        return (0.0, MatchType::SensitiveKeywordBlocked, None);
+    }
+
+    let mut best: (f32, MatchType, Option<&str>) = (0.0, MatchType::Zero, None);
+    // If the input is very short, be conservative with overlap-based scoring.
+    let input_tokens: Vec<&str> = norm_text.split_whitespace().collect();
+    let is_short_input = input_tokens.len() <= 3;
+
+    // exact match against examples
+    for (raw, e) in cmd.examples.iter().zip(&prep.examples) {
+        if e.norm == norm_text { return (1.0, MatchType::ExactExample, Some(raw.as_str())); }
+        if !e.norm.is_empty() && norm_text.contains(&e.norm) && best.0 < 0.85 {
+            best = (0.85, MatchType::SubstringExample, Some(raw.as_str()));
+        }
+    }
+    // substring match against description
+    let desc = &prep.description;
+    if !desc.norm.is_empty() && norm_text.contains(&desc.norm) && best.0 < 0.8 {
+        best = (0.8, MatchType::SubstringDescription, None);
+    }
+    // token overlap (simple Jaccard-like)
+    let tset: HashSet<&str> = input_tokens.iter().copied().collect();
+    let overlap = |cset: &HashSet<String>| -> (usize, usize) {
+        let inter = cset.iter().filter(|t| tset.contains(t.as_str())).count();
+        (inter, tset.len() + cset.len() - inter)
+    };
+    // Examples carry their raw text; the description carries None.
+    let phrases = || prep.examples.iter().zip(cmd.examples.iter().map(|e| Some(e.as_str()))).chain(std::iter::once((desc, None)));
+    let mut best_overlap: (f32, Option<&str>) = (0.0, None);
+    for (c, raw) in phrases() {
+        let (inter, union) = overlap(&c.tokens);
+        if union > 0 && inter as f32 / union as f32 > best_overlap.0 {
+            best_overlap = (inter as f32 / union as f32, raw);
+        }
+    }
+    // Overlap alone is weak evidence. Cap its influence, and require a minimum
+    // number of overlapping tokens to avoid accidental matches.
+    if best_overlap.0 > 0.0 {
+        let max_inter = phrases().map(|(c, _)| overlap(&c.tokens).0).max().unwrap_or(0);
+
+        // Need at least 2 shared tokens unless the input is short.
+        let min_inter = if is_short_input { 1 } else { 2 };
+        if max_inter >= min_inter && 0.55 * best_overlap.0 > best.0 {
+            best = (0.55 * best_overlap.0, MatchType::TokenOverlap, best_overlap.1);
+        }
+    }
+    best
+}
+
+/// The most commands one utterance may chain.
+const MAX_CHAIN: usize = 4;
+
+/// Pieces of normalized `text` between "and", "then" and "and then".
+fn split_chain(norm_text: &str) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    for word in norm_text.split_whitespace() {
+        if word == "and" || word == "then" {
+            if parts.last().is_some_and(|p| !p.is_empty()) {
+                parts.push(String::new());
+            }
+            continue;
+        }
+        let part = parts.last_mut().expect("parts is never empty");
+        if !part.is_empty() {
+            part.push(' ');
+        }
+        part.push_str(word);
+    }
+    parts.retain(|p| !p.is_empty());
+    parts
+}
+
 fn is_obvious_question(norm_text: &str) -> bool {
     let t = norm_text.trim();
     if t.is_empty() { return false; }
@@ -241,12 +825,17 @@ fn is_obvious_question(norm_text: &str) -> bool {
     t.ends_with('?')
 }
 
-fn is_sensitive_command_id(id: &str) -> bool {
+pub fn is_sensitive_command_id(id: &str) -> bool {
     let id = id.to_ascii_lowercase();
     // Conservative list: commands that change session/security state.
     id.contains("lock") || id.contains("logout") || id.contains("suspend") || id.contains("shutdown") || id.contains("reboot")
 }
 
+fn has_sensitive_keyword(norm_text: &str) -> bool {
+    let keywords = ["lock", "logout", "log out", "sign out", "suspend", "shutdown", "shut down", "reboot", "restart"];
+    keywords.iter().any(|k| norm_text.contains(k))
+}
+
 fn normalize(s: &str) -> String {
     let mut out = String::with_capacity(s.len());
     for ch in s.chars() {
@@ -255,17 +844,68 @@ fn normalize(s: &str) -> String {
     out.trim().to_string()
 }
 
-fn extract_parameters(cmd: &IntentCommand, text: &str) -> serde_json::Value {
+fn extract_parameters(cmd: &IntentCommand, text: &str) -> Params {
+    if cmd.slots.is_empty() {
+        return guess_parameters(cmd, text);
+    }
+    // A declared pattern is authoritative for its slot: no match means no
+    // value (and the spec default, if any), never a guess.
+    let mut params = Params::new();
+    for (name, param) in guess_parameters(cmd, text).iter() {
+        if !cmd.slots.iter().any(|s| s.name == name) {
+            params.insert(name, param.value.clone(), param.provenance);
+        }
+    }
+    for (name, value) in extract_slots(&cmd.slots, text) {
+        params.insert(&name, value, Provenance::Deterministic);
+    }
+    params
+}
+
+/// Values for every slot whose pattern matches `text`: whole numbers as
+/// integers, anything else as the captured text, which the executor checks
+/// against the parameter's type.
+fn extract_slots(slots: &[Slot], text: &str) -> Vec<(String, serde_json::Value)> {
+    let numeric_text = crate::decision::normalize_number_words(&text.to_lowercase());
+    let mut out: Vec<(String, serde_json::Value)> = Vec::new();
+    for slot in slots {
+        let haystack = if slot.numeric { numeric_text.as_str() } else { text };
+        let Some(caps) = slot.pattern.captures(haystack) else { continue };
+        let main = caps.name(&slot.name).or_else(|| caps.get(1)).or_else(|| caps.get(0));
+        let named = slot.pattern.capture_names().flatten().filter(|n| *n != slot.name).filter_map(|n| Some((n, caps.name(n)?)));
+        for (name, m) in std::iter::once((slot.name.as_str(), main)).filter_map(|(n, m)| Some((n, m?))).chain(named) {
+            if out.iter().any(|(n, _)| n == name) {
+                continue;
+            }
+            let captured = m.as_str().trim();
+            let value = captured.parse::<i64>().map_or_else(|_| serde_json::json!(captured), |v| serde_json::json!(v));
+            out.push((name.to_string(), value));
+        }
+    }
+    out
+}
+
+fn guess_parameters(cmd: &IntentCommand, text: &str) -> Params {
     // Minimal heuristic: extract first integer and map by common ids
+    let mut params = Params::new();
+    if ["timer", "alarm", "remind"].iter().any(|k| cmd.id.contains(k)) {
+        if let Some(secs) = extract_duration_secs(text) {
+            params.insert("duration_secs", serde_json::json!(secs), Provenance::Deterministic);
+        }
+        if let Some((hour, minute)) = extract_time_of_day(text) {
+            params.insert("hour", serde_json::json!(hour), Provenance::Deterministic);
+            params.insert("minute", serde_json::json!(minute), Provenance::Deterministic);
+        }
+        return params;
+    }
     if let Some(num) = first_int(text) {
         if cmd.id.contains("brightness") || cmd.id.contains("volume") {
-            return serde_json::json!({"value": num});
-        }
-        if cmd.id.contains("up") || cmd.id.contains("down") {
-            return serde_json::json!({"delta": num});
+            params.insert("value", serde_json::json!(num), Provenance::Deterministic);
+        } else if cmd.id.contains("up") || cmd.id.contains("down") {
+            params.insert("delta", serde_json::json!(num), Provenance::Deterministic);
         }
     }
-    serde_json::json!({})
+    params
 }
 
 fn first_int(s: &str) -> Option<i64> {
@@ -276,6 +916,98 @@ fn first_int(s: &str) -> Option<i64> {
     if buf.is_empty() { None } else { buf.parse::<i64>().ok() }
 }
 
+/// Lowercased tokens with number words as digits; `3:30` stays one token.
+fn entity_tokens(text: &str) -> Vec<String> {
+    let cleaned: String = text
+        .chars()
+        .filter(|c| *c != '\'' && *c != '.')
+        .map(|c| if c.is_ascii_alphanumeric() || c == ':' { c.to_ascii_lowercase() } else { ' ' })
+        .collect();
+    crate::decision::normalize_number_words(&cleaned).split_whitespace().map(str::to_string).collect()
+}
+
+/// Total of every "N seconds/minutes/hours" in `text` ("an hour" counts as 1),
+/// so "1 hour and 30 minutes" is 5400.
+pub fn extract_duration_secs(text: &str) -> Option<u64> {
+    let toks = entity_tokens(text);
+    let mut total: Option<u64> = None;
+    for pair in toks.windows(2) {
+        let n = match pair[0].as_str() {
+            "a" | "an" => 1,
+            t => match t.parse::<u64>() {
+                Ok(n) => n,
+                Err(_) => continue,
+            },
+        };
+        let unit = match pair[1].as_str() {
+            "second" | "seconds" | "sec" | "secs" => 1,
+            "minute" | "minutes" | "min" | "mins" => 60,
+            "hour" | "hours" | "hr" | "hrs" => 3600,
+            _ => continue,
+        };
+        total = Some(total.unwrap_or(0).saturating_add(n.saturating_mul(unit)));
+    }
+    total
+}
+
+/// 24-hour (hour, minute) from "3:30 pm", "3 30 pm", "330 pm", "3 pm",
+/// "7 o'clock" or "15:45".
+pub fn extract_time_of_day(text: &str) -> Option<(u8, u8)> {
+    let toks = entity_tokens(text);
+    let at = |i: usize| toks.get(i).map(String::as_str).unwrap_or("");
+    let meridiem = |t: &str| match t {
+        "am" => Some(false),
+        "pm" => Some(true),
+        _ => None,
+    };
+    let to_24h = |h: u32, m: u32, pm: Option<bool>| -> Option<(u8, u8)> {
+        if m > 59 {
+            return None;
+        }
+        let h = match pm {
+            Some(_) if !(1..=12).contains(&h) => return None,
+            Some(true) if h < 12 => h + 12,
+            Some(false) if h == 12 => 0,
+            _ if h > 23 => return None,
+            _ => h,
+        };
+        Some((h as u8, m as u8))
+    };
+    for i in 0..toks.len() {
+        let tok = at(i);
+        if let Some((h, m)) = tok.split_once(':') {
+            if let (Ok(h), Ok(m)) = (h.parse(), m.parse()) {
+                if let Some(t) = to_24h(h, m, meridiem(at(i + 1))) {
+                    return Some(t);
+                }
+            }
+            continue;
+        }
+        let Ok(n) = tok.parse::<u32>() else { continue };
+        let oclock = at(i + 1) == "oclock" || (at(i + 1) == "o" && at(i + 2) == "clock");
+        if oclock {
+            let next = if at(i + 1) == "oclock" { i + 2 } else { i + 3 };
+            if let Some(t) = to_24h(n, 0, meridiem(at(next))) {
+                return Some(t);
+            }
+        }
+        if let Some(pm) = meridiem(at(i + 1)) {
+            // "330 pm" is what "3:30 pm" looks like once punctuation is gone.
+            let (h, m) = if tok.len() >= 3 { (n / 100, n % 100) } else { (n, 0) };
+            if let Some(t) = to_24h(h, m, Some(pm)) {
+                return Some(t);
+            }
+        }
+        if let (Ok(m), Some(pm)) = (at(i + 1).parse::<u32>(), meridiem(at(i + 2))) {
+            if at(i + 1).len() == 2 {
+                if let Some(t) = to_24h(n, m, Some(pm)) {
+                    return Some(t);
+                }
+            }
+        }
+    }
+    None
+}
 
 #[cfg(test)]
 mod tests {
@@ -286,9 +1018,6 @@ mod tests {
         fn classify_intent(&self, _text: &str, _commands: &[crate::intent::IntentCommand]) -> std::result::Result<crate::llm::LlmIntent, String> {
             Ok(crate::llm::LlmIntent { command_id: None, confidence: 0.0, parameters: serde_json::json!({}) })
         }
-        fn summarize_search(&self, _query: &str, _snippets: &[String]) -> std::result::Result<String, String> {
-            Err("not implemented in tests".into())
-        }
         fn tts(&self, _text: &str) -> std::result::Result<Vec<u8>, String> {
             Err("not implemented in tests".into())
         }
@@ -301,6 +1030,9 @@ mod tests {
         let cfg = IntentConfig {
             deterministic_threshold: 0.6,
             llm_fallback_threshold: 0.9,
+            embedding_threshold: 0.8,
+            embedding_weight: 0.0,
+            llm_rate_limit_per_min: 0,
         };
 
         let commands = vec![
@@ -312,6 +1044,9 @@ mod tests {
                     "set screen brightness to 70".into(),
                 ],
                 dangerous: false,
+                priority: 0,
+                alias_of: None,
+                slots: Vec::new(),
             },
             IntentCommand {
                 id: "volume_up".into(),
@@ -321,6 +1056,9 @@ mod tests {
                     "turn volume up".into(),
                 ],
                 dangerous: false,
+                priority: 0,
+                alias_of: None,
+                slots: Vec::new(),
             },
             IntentCommand {
                 id: "system_reboot".into(),
@@ -330,10 +1068,23 @@ mod tests {
                     "reboot".into(),
                 ],
                 dangerous: true,
+                priority: 0,
+                alias_of: None,
+                slots: Vec::new(),
             },
         ];
 
-        IntentRouter { cfg, commands, llm: std::sync::Arc::new(DummyLlm) }
+        IntentRouter::new(cfg, commands, std::sync::Arc::new(DummyLlm))
+    }
+
+    fn fixture_embeddings() -> EmbeddingIndex {
+        use crate::embedding::ExampleVector;
+        let ev = |id: &str, v: [f32; 3]| ExampleVector { command_id: id.into(), example: String::new(), vector: v.to_vec() };
+        EmbeddingIndex::new(vec![
+            ev("brightness_set", [0.9, 0.1, 0.0]),
+            ev("volume_up", [0.0, 0.95, 0.1]),
+            ev("system_reboot", [0.1, 0.0, 0.9]),
+        ])
     }
 
     #[test]
@@ -358,4 +1109,367 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn conjunctions_chain_confident_commands() {
+        let router = test_router();
+        assert_eq!(split_chain("increase volume and then reboot"), ["increase volume", "reboot"]);
+        let chain = router.route_chain("Increase volume, and set brightness to 40 percent").unwrap();
+        let ids: Vec<_> = chain.iter().map(|r| r.command_id.as_deref().unwrap()).collect();
+        assert_eq!(ids, ["volume_up", "brightness_set"]);
+        assert_eq!(chain[1].parameters.get_int("value"), Some(40));
+        assert!(router.route_chain("reboot then increase volume").unwrap()[0].requires_confirmation);
+        // Every part has to stand on its own.
+        assert!(router.route_chain("increase volume and bananas").is_none());
+        assert!(router.route_chain("increase volume").is_none());
+    }
+
+    #[test]
+    fn ranked_routing_reports_a_distinct_runner_up() {
+        let router = test_router();
+        let ranked = router.route_ranked("set brightness to 40 percent", None);
+        assert_eq!(ranked.best.command_id.as_deref(), Some("brightness_set"));
+        if let Some(r) = &ranked.runner_up {
+            assert_ne!(r.command_id, ranked.best.command_id);
+            assert!(r.deterministic_score <= ranked.best.deterministic_score);
+        }
+        // Only the deterministic tier has a runner-up.
+        assert!(router.route_ranked("what is the weather tomorrow", None).runner_up.is_none());
+    }
+
+    #[test]
+    fn deterministic_parameters_are_tagged() {
+        let router = test_router();
+        let intent = router.route("set brightness to 40 percent");
+        assert_eq!(intent.parameters.get_int("value"), Some(40));
+        assert_eq!(intent.parameters.provenance("value"), Some(Provenance::Deterministic));
+    }
+
+    #[test]
+    fn llm_parameters_are_tagged() {
+        struct ParamLlm;
+        impl crate::llm::LlmClient for ParamLlm {
+            fn classify_intent(&self, _text: &str, _commands: &[crate::intent::IntentCommand]) -> std::result::Result<crate::llm::LlmIntent, String> {
+                Ok(crate::llm::LlmIntent { command_id: Some("volume_up".into()), confidence: 0.95, parameters: serde_json::json!({"delta": 5}) })
+            }
+            fn tts(&self, _text: &str) -> std::result::Result<Vec<u8>, String> { Err("unused".into()) }
+            fn answer_short(&self, _prompt: &str) -> std::result::Result<String, String> { Err("unused".into()) }
+        }
+        let mut router = test_router();
+        router.llm = std::sync::Arc::new(ParamLlm);
+        let intent = router.route("make it a bit louder");
+        assert_eq!(intent.command_id.as_deref(), Some("volume_up"));
+        assert_eq!(intent.parameters.get_int("delta"), Some(5));
+        assert_eq!(intent.parameters.provenance("delta"), Some(Provenance::Llm));
+    }
+
+    #[test]
+    fn embedding_tier_accepts_paraphrase_above_threshold() {
+        let mut router = test_router();
+        router.set_embeddings(fixture_embeddings());
+        // No token overlap with any example; near the brightness vector.
+        let intent = router.route_with_embedding("make the display dimmer please", Some(&[0.88, 0.12, 0.02]));
+        assert_eq!(intent.command_id.as_deref(), Some("brightness_set"));
+        assert!(intent.deterministic_score.is_none());
+        assert!(intent.embedding_score.unwrap() >= 0.8);
+    }
+
+    #[test]
+    fn embedding_tier_rejects_below_threshold() {
+        let mut router = test_router();
+        router.set_embeddings(fixture_embeddings());
+        // Halfway between brightness and volume: cosine < 0.8 to both.
+        let intent = router.route_with_embedding("make the display dimmer please", Some(&[0.5, 0.5, 0.05]));
+        assert_eq!(intent.command_id, None);
+        assert!(intent.embedding_score.is_none());
+    }
+
+    #[test]
+    fn embedding_tier_skipped_without_query_or_for_sensitive_without_keyword() {
+        let mut router = test_router();
+        router.set_embeddings(fixture_embeddings());
+        assert_eq!(router.route_with_embedding("make the display dimmer please", None).command_id, None);
+        // Close to system_reboot, but no explicit restart/reboot keyword.
+        let intent = router.route_with_embedding("start everything over again", Some(&[0.1, 0.0, 0.9]));
+        assert_eq!(intent.command_id, None);
+    }
+
+    #[test]
+    fn blended_score_backs_up_a_partial_token_match() {
+        // Shares "screen brightness" with the description, too little to route.
+        let text = "dim the screen brightness a little";
+        let mut router = test_router();
+        router.set_embeddings(fixture_embeddings());
+        let entries = score_entries(&router.commands, &router.index, &normalize(text), |_| 0.0);
+        let lex = entries.iter().find(|e| e.command_id == "brightness_set").unwrap().score;
+        assert!(lex > 0.0 && lex < 0.85, "token score {}", lex);
+        // Neither tier alone: the token score misses its threshold and the
+        // cosine (0.98) misses a deliberately strict embedding threshold.
+        router.set_threshold_overrides(HashMap::from([("brightness_set".to_string(), lex + 0.05)]));
+        let query = [0.9, 0.1, 0.2];
+        let weighted = |embedding_weight| IntentConfig {
+            deterministic_threshold: 0.6,
+            llm_fallback_threshold: 0.9,
+            embedding_threshold: 0.99,
+            embedding_weight,
+            llm_rate_limit_per_min: 0,
+        };
+        router.set_config(weighted(0.0));
+        assert_eq!(router.route_with_embedding(text, Some(&query)).command_id, None);
+
+        router.set_config(weighted(0.5));
+        let intent = router.route_with_embedding(text, Some(&query));
+        assert_eq!(intent.command_id.as_deref(), Some("brightness_set"));
+        assert_eq!(intent.deterministic_score, Some(lex));
+        assert!(intent.embedding_score.unwrap() > 0.95);
+        // No shared word: blending never applies.
+        assert_eq!(router.route_with_embedding("make the display dimmer please", Some(&query)).command_id, None);
+    }
+
+    fn cmd(id: &str, examples: &[&str]) -> IntentCommand {
+        IntentCommand { id: id.into(), description: String::new(), examples: examples.iter().map(|e| e.to_string()).collect(), dangerous: false, priority: 0, alias_of: None, slots: Vec::new() }
+    }
+
+    #[test]
+    fn aliases_route_like_their_source() {
+        let alias = |id: &str, of: &str| IntentCommand { alias_of: Some(of.into()), ..cmd(id, &[]) };
+        let mut commands = vec![
+            IntentCommand { description: "Raise the volume".into(), ..cmd("volume_up_small", &["turn it up", "louder"]) },
+            alias("volume_up_large", "volume_up_small"),
+            alias("volume_up_huge", "volume_up_large"),
+        ];
+        resolve_aliases(&mut commands).unwrap();
+        assert_eq!(commands[2].examples, vec!["turn it up", "louder"]);
+        assert_eq!(commands[1].description, "Raise the volume");
+        let cfg = IntentConfig { deterministic_threshold: 0.6, llm_fallback_threshold: 0.9, embedding_threshold: 0.8, embedding_weight: 0.0, llm_rate_limit_per_min: 0 };
+        let router = IntentRouter::new(cfg, commands, std::sync::Arc::new(DummyLlm));
+        let source = router.explain("louder please", "volume_up_small").unwrap();
+        let aliased = router.explain("louder please", "volume_up_large").unwrap();
+        assert_eq!((source.score, &source.closest), (aliased.score, &aliased.closest));
+        // Equal scores: document order picks the source, the alias is the runner-up.
+        let ranked = router.route_ranked("louder please", None);
+        assert_eq!(ranked.best.command_id.as_deref(), Some("volume_up_small"));
+        assert_eq!(ranked.runner_up.unwrap().deterministic_score, ranked.best.deterministic_score);
+    }
+
+    #[test]
+    fn alias_cycles_and_unknown_targets_fail() {
+        let alias = |id: &str, of: &str| IntentCommand { alias_of: Some(of.into()), ..cmd(id, &["x"]) };
+        let err = resolve_aliases(&mut [alias("a", "b"), alias("b", "c"), alias("c", "a")]).unwrap_err();
+        assert_eq!(err, "alias cycle: a -> b -> c -> a");
+        assert_eq!(resolve_aliases(&mut [alias("a", "a")]).unwrap_err(), "alias cycle: a -> a");
+        assert!(resolve_aliases(&mut [alias("a", "nope")]).unwrap_err().contains("'nope' is not a known command"));
+    }
+
+    #[test]
+    fn self_check_passes_for_consistent_set() {
+        assert!(test_router().self_check().is_empty());
+    }
+
+    #[test]
+    fn self_check_reports_conflicting_examples() {
+        let commands = vec![
+            cmd("wifi_off", &["turn it off", "disable wifi"]),
+            // Duplicate phrasing: ties go to wifi_off, so this one is unreachable.
+            cmd("bluetooth_off", &["turn it off", "disable bluetooth"]),
+            // Sensitive ids need an explicit keyword, so this example routes elsewhere.
+            cmd("lock_screen", &["disable the screen wifi"]),
+        ];
+        let index = PreparedIndex::new(&commands);
+        let report = self_check(&commands, &index);
+        assert_eq!(report.len(), 2, "{:?}", report);
+
+        assert_eq!(report[0].example, "turn it off");
+        assert_eq!(report[0].owner, "bluetooth_off");
+        assert_eq!(report[0].winner, "wifi_off");
+        assert_eq!(report[0].owner_score, 1.0);
+        assert_eq!(report[0].winner_score, 1.0);
+        assert_eq!(report[0].shared_tokens, vec!["it", "off", "turn"]);
+
+        assert_eq!(report[1].owner, "lock_screen");
+        assert_eq!(report[1].owner_score, 0.0);
+        assert_eq!(report[1].winner, "wifi_off");
+        assert!(report[1].winner_score > 0.0);
+        assert_eq!(report[1].shared_tokens, vec!["disable", "wifi"]);
+        assert!(report[1].to_string().contains("shared tokens: disable, wifi"));
+    }
+
+    #[test]
+    fn llm_calls_past_the_rate_limit_fall_back_to_unknown() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        struct CountingLlm(std::sync::Arc<AtomicUsize>);
+        impl crate::llm::LlmClient for CountingLlm {
+            fn classify_intent(&self, _text: &str, _commands: &[IntentCommand]) -> std::result::Result<LlmIntent, String> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Ok(LlmIntent { command_id: Some("volume_up".into()), confidence: 1.0, parameters: serde_json::json!({}) })
+            }
+            fn tts(&self, _text: &str) -> std::result::Result<Vec<u8>, String> {
+                Err("not implemented in tests".into())
+            }
+            fn answer_short(&self, _prompt: &str) -> std::result::Result<String, String> {
+                Err("not implemented in tests".into())
+            }
+        }
+        let calls = std::sync::Arc::new(AtomicUsize::new(0));
+        let cfg = IntentConfig { deterministic_threshold: 0.6, llm_fallback_threshold: 0.9, embedding_threshold: 0.8, embedding_weight: 0.0, llm_rate_limit_per_min: 20 };
+        let router = IntentRouter::new(cfg, vec![cmd("volume_up", &["volume up"])], std::sync::Arc::new(CountingLlm(calls.clone())));
+        for _ in 0..20 {
+            assert_eq!(router.llm_classify("something unrelated").unwrap().command_id.as_deref(), Some("volume_up"));
+        }
+        let r = router.llm_classify("something unrelated").unwrap();
+        assert_eq!((r.intent_type.as_str(), r.command_id), ("unknown_intent", None));
+        assert_eq!(calls.load(Ordering::SeqCst), 20);
+    }
+
+    #[test]
+    fn priority_breaks_ties_and_equal_priority_keeps_document_order() {
+        let cfg = || IntentConfig { deterministic_threshold: 0.6, llm_fallback_threshold: 0.9, embedding_threshold: 0.8, embedding_weight: 0.0, llm_rate_limit_per_min: 0 };
+        let route = |commands: Vec<IntentCommand>| {
+            let ranked = IntentRouter::new(cfg(), commands, std::sync::Arc::new(DummyLlm)).route_ranked("turn it off", None);
+            (ranked.best.command_id.unwrap(), ranked.runner_up.and_then(|r| r.command_id))
+        };
+        let pair = |bluetooth_priority: i32| {
+            let bluetooth = IntentCommand { priority: bluetooth_priority, ..cmd("bluetooth_off", &["turn it off"]) };
+            vec![cmd("wifi_off", &["turn it off"]), bluetooth]
+        };
+        assert_eq!(route(pair(0)), ("wifi_off".into(), Some("bluetooth_off".into())));
+        assert_eq!(route(pair(1)), ("bluetooth_off".into(), Some("wifi_off".into())));
+        let commands = pair(1);
+        assert!(self_check(&commands, &PreparedIndex::new(&commands)).iter().all(|c| c.winner == "bluetooth_off"));
+    }
+
+    #[test]
+    fn score_all_reports_how_each_command_matched() {
+        let router = test_router();
+        let table = router.score_all("please increase volume");
+        let rows: Vec<(&str, MatchType, Option<&str>)> =
+            table.iter().map(|e| (e.command_id.as_str(), e.match_type, e.matched_example.as_deref())).collect();
+        assert_eq!(
+            rows,
+            [
+                ("volume_up", MatchType::SubstringExample, Some("increase volume")),
+                ("brightness_set", MatchType::Zero, None),
+                ("system_reboot", MatchType::SensitiveKeywordBlocked, None),
+            ]
+        );
+        assert_eq!(table[0].score, 0.85);
+
+        let top = &router.score_all("Increase system volume please")[0];
+        assert_eq!((top.match_type, top.score), (MatchType::SubstringDescription, 0.8));
+        let top = &router.score_all("reboot")[0];
+        assert_eq!((top.command_id.as_str(), top.match_type, top.matched_example.as_deref()), ("system_reboot", MatchType::ExactExample, Some("reboot")));
+        let overlap = router.score_all("brightness to max").into_iter().find(|e| e.command_id == "brightness_set").unwrap();
+        assert_eq!((overlap.match_type, overlap.matched_example.as_deref()), (MatchType::TokenOverlap, Some("set brightness to 40 percent")));
+        assert!(overlap.score > 0.0 && overlap.score <= 0.55);
+    }
+
+    #[test]
+    fn threshold_overrides_apply_per_command() {
+        let mut router = test_router();
+        assert_eq!(router.route("please increase volume").command_id.as_deref(), Some("volume_up"));
+
+        router.set_threshold_overrides(HashMap::from([("volume_up".to_string(), 0.9)]));
+        assert_eq!(router.route("please increase volume").command_id, None);
+        let table = router.score_all("please increase volume");
+        let thresholds: Vec<(&str, f32)> = table.iter().map(|e| (e.command_id.as_str(), e.threshold)).collect();
+        assert_eq!(thresholds, [("volume_up", 0.9), ("brightness_set", 0.6), ("system_reboot", 0.6)]);
+
+        // Lower than the global threshold works too.
+        router.cfg.deterministic_threshold = 0.9;
+        router.set_threshold_overrides(HashMap::from([("volume_up".to_string(), 0.8)]));
+        assert_eq!(router.route("please increase volume").command_id.as_deref(), Some("volume_up"));
+        assert_eq!(router.threshold_for("system_reboot"), 0.9);
+    }
+
+    #[test]
+    fn reloaded_config_replaces_thresholds() {
+        let mut router = test_router();
+        router.set_config(IntentConfig {
+            deterministic_threshold: 0.95,
+            llm_fallback_threshold: 0.9,
+            embedding_threshold: 0.7,
+            embedding_weight: 0.0,
+            llm_rate_limit_per_min: 0,
+        });
+        assert_eq!(router.route("please increase volume").command_id, None);
+        assert_eq!(router.embedding_threshold(), 0.7);
+        assert!(router.llm_budget.is_none());
+    }
+
+    #[test]
+    fn explain_lists_shared_tokens() {
+        let router = test_router();
+        let e = router.explain("please turn the volume up", "volume_up").unwrap();
+        assert_eq!(e.closest, "turn volume up");
+        assert_eq!(e.shared_tokens, vec!["turn", "up", "volume"]);
+        assert!(e.score > 0.0);
+        assert!(router.explain("anything", "missing").is_none());
+    }
+
+    #[test]
+    fn durations_in_digits_and_words() {
+        assert_eq!(extract_duration_secs("set a timer for 5 minutes"), Some(300));
+        assert_eq!(extract_duration_secs("set a timer for twenty five seconds"), Some(25));
+        assert_eq!(extract_duration_secs("remind me in an hour"), Some(3600));
+        assert_eq!(extract_duration_secs("1 hour and 30 minutes"), Some(5400));
+        assert_eq!(extract_duration_secs("set volume to 5"), None);
+    }
+
+    #[test]
+    fn times_of_day() {
+        assert_eq!(extract_time_of_day("remind me at 3 30 pm"), Some((15, 30)));
+        assert_eq!(extract_time_of_day("wake me at 3:30 am"), Some((3, 30)));
+        assert_eq!(extract_time_of_day("alarm at 330 pm"), Some((15, 30)));
+        assert_eq!(extract_time_of_day("at 12 am"), Some((0, 0)));
+        assert_eq!(extract_time_of_day("at seven o'clock"), Some((7, 0)));
+        assert_eq!(extract_time_of_day("at 9 oclock pm"), Some((21, 0)));
+        assert_eq!(extract_time_of_day("meeting at 15:45"), Some((15, 45)));
+        assert_eq!(extract_time_of_day("at 13 pm"), None);
+        assert_eq!(extract_time_of_day("in 5 minutes"), None);
+    }
+
+    #[test]
+    fn timer_commands_get_entity_parameters() {
+        let timer = cmd("timer_start", &["set a timer"]);
+        let params = extract_parameters(&timer, &normalize("Set a timer for 5 minutes"));
+        assert_eq!(params.to_value(), serde_json::json!({"duration_secs": 300}));
+        let remind = cmd("remind_at", &["remind me"]);
+        let params = extract_parameters(&remind, &normalize("remind me at 3 30 pm"));
+        assert_eq!(params.to_value(), serde_json::json!({"hour": 15, "minute": 30}));
+        // Other commands keep the plain integer heuristic.
+        assert_eq!(extract_parameters(&cmd("volume_set", &[]), "set volume to 40").get_int("value"), Some(40));
+    }
+
+    #[test]
+    fn declared_patterns_fill_typed_slots() {
+        let fan: IntentCommand = serde_json::from_value(serde_json::json!({
+            "id": "fan_set",
+            "examples": ["set the fan to 3"],
+            "parameters": {
+                "level": {"type": "int", "min": 0, "max": 5, "pattern": "(?:to|at) (\\d+)"},
+                "minutes": "int 1-120 default=30",
+                "hour": {"type": "int", "pattern": "until (?P<hour>\\d+) (?P<minute>\\d+)"}
+            },
+        }))
+        .unwrap();
+        assert_eq!(fan.slots.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(), ["hour", "level"]);
+        // The first number is 2; the pattern knows the level comes after "to".
+        let params = extract_parameters(&fan, &normalize("in room 2 set the fan to three until 22 30"));
+        assert_eq!(params.to_value(), serde_json::json!({"level": 3, "hour": 22, "minute": 30}));
+        assert_eq!(params.provenance("level"), Some(Provenance::Deterministic));
+        // No match: no guess either.
+        assert!(extract_parameters(&fan, "fan 4").is_empty());
+
+        let play: IntentCommand = serde_json::from_value(serde_json::json!({
+            "id": "music_play", "parameters": {"playlist": {"type": "string", "max_length": 32, "pattern": "play (.+)"}}
+        }))
+        .unwrap();
+        // Text slots keep their words.
+        assert_eq!(extract_parameters(&play, "play three little birds").get_str("playlist"), Some("three little birds"));
+
+        let bad = serde_json::from_value::<IntentCommand>(serde_json::json!({
+            "id": "fan_set", "parameters": {"level": {"type": "int", "pattern": "to (\\d+"}}
+        }));
+        assert!(bad.unwrap_err().to_string().contains("parameter 'level': invalid pattern: unclosed group"));
+    }
 }
@@ -1,5 +1,8 @@
 use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
 
+#[derive(Debug, Clone)]
 pub struct LlmIntent {
     pub command_id: Option<String>,
     pub parameters: Value,
@@ -7,99 +10,338 @@ pub struct LlmIntent {
 }
 
 pub trait LlmClient: Send + Sync {
+    /// Provider name for errors and logs.
+    fn provider(&self) -> &'static str {
+        "llm"
+    }
     fn classify_intent(&self, text: &str, commands: &[crate::intent::IntentCommand]) -> Result<LlmIntent, String>;
-    fn summarize_search(&self, query: &str, snippets: &[String]) -> Result<String, String>;
     fn answer_short(&self, prompt: &str) -> Result<String, String>;
+    /// [`LlmClient::answer_short`], handing the answer to `on_sentence` a
+    /// sentence at a time as it arrives so speech can start before the reply
+    /// is complete. Returns the whole answer. The default waits for all of it.
+    fn answer_streaming(&self, prompt: &str, on_sentence: &mut dyn FnMut(&str)) -> Result<String, String> {
+        let answer = self.answer_short(prompt)?;
+        on_sentence(&answer);
+        Ok(answer)
+    }
+    #[cfg_attr(not(test), allow(dead_code))]
     fn tts(&self, text: &str) -> Result<Vec<u8>, String>; // return WAV bytes
 }
 
-pub struct GroqClient { api_key: String }
+/// Spoken when the assistant cannot be reached (errors, timeouts, open circuit).
+pub const UNREACHABLE_REPLY: &str = "Sorry, I can't reach the assistant.";
 
-impl GroqClient {
-    pub fn new(api_key: String) -> Self { Self { api_key } }
+const CLASSIFY_SYSTEM: &str = "You are an intent classifier. Return ONLY a JSON object with keys: command_id, parameters, confidence. Choose the best matching command_id from the provided list or null if none.";
+const ANSWER_SYSTEM: &str = "You are a helpful voice assistant named Bumblebee. Answer the user's question concisely in one or two sentences. Avoid markdown; output plain text only.";
+
+/// The user message for intent classification: the utterance and the command list.
+fn classify_prompt(text: &str, commands: &[crate::intent::IntentCommand]) -> String {
+    let commands_list: Vec<_> = commands.iter().map(|c| serde_json::json!({"id": c.id, "description": c.description})).collect();
+    serde_json::json!({"text": text, "commands": commands_list}).to_string()
 }
 
-impl LlmClient for GroqClient {
-    fn classify_intent(&self, text: &str, commands: &[crate::intent::IntentCommand]) -> Result<LlmIntent, String> {
-        let url = "https://api.groq.com/openai/v1/chat/completions";
-        let commands_list: Vec<_> = commands.iter().map(|c| serde_json::json!({"id": c.id, "description": c.description})).collect();
-        let system = "You are an intent classifier. Return ONLY a JSON object with keys: command_id, parameters, confidence. Choose the best matching command_id from the provided list or null if none.";
-        let user_prompt = serde_json::json!({"text": text, "commands": commands_list}).to_string();
-        let client = reqwest::blocking::Client::new();
-        let req_body = serde_json::json!({
-            "model": "llama-3.1-8b-instant",
-            "temperature": 0.0,
-            "response_format": {"type": "json_object"},
+/// Read the classifier's JSON answer. Anything unparseable counts as "no
+/// command" with zero confidence. Local models like to wrap the object in a
+/// ```json fence, which is stripped first.
+pub fn parse_intent(content: &str) -> LlmIntent {
+    let trimmed = content.trim();
+    let unfenced = trimmed
+        .strip_prefix("```")
+        .map(|rest| rest.trim_start_matches("json").trim_end().trim_end_matches("```"))
+        .unwrap_or(trimmed);
+    let parsed: Value = serde_json::from_str(unfenced.trim()).unwrap_or(serde_json::json!({"command_id": null, "parameters": {}, "confidence": 0.0}));
+    LlmIntent {
+        command_id: parsed["command_id"].as_str().map(|s| s.to_string()),
+        parameters: parsed.get("parameters").cloned().unwrap_or(serde_json::json!({})),
+        confidence: parsed["confidence"].as_f64().unwrap_or(0.0) as f32,
+    }
+}
+
+/// Streamed text shorter than this is held back rather than spoken on its
+/// own, so "Dr." or "e.g." don't become separate utterances.
+const MIN_SENTENCE_CHARS: usize = 12;
+
+/// Remove and return the complete sentences at the start of `buf` (ending in
+/// `.`, `!` or `?` followed by whitespace); the unfinished rest stays.
+pub fn take_sentences(buf: &mut String) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut start = 0;
+    let mut chars = buf.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if matches!(c, '.' | '!' | '?') && chars.peek().is_some_and(|(_, next)| next.is_whitespace()) {
+            let end = i + c.len_utf8();
+            let sentence = buf[start..end].trim();
+            if sentence.chars().count() >= MIN_SENTENCE_CHARS {
+                out.push(sentence.to_string());
+                start = end;
+            }
+        }
+    }
+    buf.drain(..start);
+    out
+}
+
+/// The text in one server-sent event line of a streamed chat completion;
+/// None for keep-alives, `[DONE]` and anything unparseable.
+fn stream_delta(line: &str) -> Option<String> {
+    let data = line.strip_prefix("data:")?.trim();
+    if data == "[DONE]" {
+        return None;
+    }
+    let v: Value = serde_json::from_str(data).ok()?;
+    v["choices"][0]["delta"]["content"].as_str().filter(|s| !s.is_empty()).map(str::to_string)
+}
+
+/// Why a request failed, so retries can tell a flaky network from a bad request.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransportError {
+    Connect(String),
+    Timeout { after: Duration, url: String },
+    Status { code: u16, body_preview: String },
+    /// Refused without trying: the provider failed too often recently.
+    CircuitOpen { remaining: Duration },
+    Other(String),
+}
+
+impl TransportError {
+    /// Connect errors, timeouts, 429 and 5xx are worth another try.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Connect(_) | Self::Timeout { .. } => true,
+            Self::Status { code, .. } => *code == 429 || *code >= 500,
+            Self::CircuitOpen { .. } | Self::Other(_) => false,
+        }
+    }
+}
+
+impl std::fmt::Display for TransportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Connect(message) | Self::Other(message) => f.write_str(message),
+            Self::Timeout { after, url } => write!(f, "timed out after {}ms waiting for {}", after.as_millis(), url),
+            Self::Status { code, body_preview } => write!(f, "http status {} body_preview={}", code, body_preview),
+            Self::CircuitOpen { remaining } => write!(f, "failing repeatedly; not retrying for another {}s", remaining.as_secs().max(1)),
+        }
+    }
+}
+
+/// Sends one JSON POST and returns the JSON reply. [`HttpTransport`] in the
+/// daemon; tests inject fakes, and `llm_retry` wraps it with retries.
+pub trait Transport: Send + Sync {
+    /// `what` ("classify", "answer", ...) is for logs only.
+    fn post_json(&self, what: &str, url: &str, bearer: Option<&str>, body: &Value, timeout: Duration) -> Result<Value, TransportError>;
+
+    /// POST `body` and hand each line of the streamed reply (server-sent
+    /// events) to `on_line`. Transports that can't stream return
+    /// [`TransportError::Other`]; callers then fall back to `post_json`.
+    fn post_stream(
+        &self,
+        what: &str,
+        _url: &str,
+        _bearer: Option<&str>,
+        _body: &Value,
+        _timeout: Duration,
+        _on_line: &mut dyn FnMut(&str),
+    ) -> Result<(), TransportError> {
+        Err(TransportError::Other(format!("{}: streaming not supported", what)))
+    }
+}
+
+pub struct HttpTransport;
+
+impl HttpTransport {
+    fn send(url: &str, bearer: Option<&str>, body: &Value, timeout: Duration) -> Result<reqwest::blocking::Response, TransportError> {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(timeout)
+            .build()
+            .map_err(|e| TransportError::Other(format!("http client: {}", e)))?;
+        let mut req = client.post(url).json(body);
+        if let Some(key) = bearer {
+            req = req.bearer_auth(key);
+        }
+        let resp = req.send().map_err(|e| {
+            if e.is_timeout() {
+                TransportError::Timeout { after: timeout, url: url.to_string() }
+            } else if e.is_connect() {
+                TransportError::Connect(format!("cannot connect to {} (is the server running?): {}", url, e))
+            } else {
+                TransportError::Other(format!("http error: {}", e))
+            }
+        })?;
+        let status = resp.status();
+        if !status.is_success() {
+            let body = resp.text().unwrap_or_default();
+            return Err(TransportError::Status { code: status.as_u16(), body_preview: body.chars().take(200).collect() });
+        }
+        Ok(resp)
+    }
+}
+
+impl Transport for HttpTransport {
+    fn post_json(&self, _what: &str, url: &str, bearer: Option<&str>, body: &Value, timeout: Duration) -> Result<Value, TransportError> {
+        Self::send(url, bearer, body, timeout)?.json().map_err(|e| TransportError::Other(format!("json error: {}", e)))
+    }
+
+    fn post_stream(
+        &self,
+        _what: &str,
+        url: &str,
+        bearer: Option<&str>,
+        body: &Value,
+        timeout: Duration,
+        on_line: &mut dyn FnMut(&str),
+    ) -> Result<(), TransportError> {
+        use std::io::BufRead;
+        let resp = Self::send(url, bearer, body, timeout)?;
+        for line in std::io::BufReader::new(resp).lines() {
+            let line = line.map_err(|e| {
+                if e.kind() == std::io::ErrorKind::TimedOut {
+                    TransportError::Timeout { after: timeout, url: url.to_string() }
+                } else {
+                    TransportError::Other(format!("stream error: {}", e))
+                }
+            })?;
+            on_line(&line);
+        }
+        Ok(())
+    }
+}
+
+/// Per-call time budgets, retries included. Classification sits in front of
+/// every unmatched utterance, so it gets the tight one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Timeouts {
+    pub classify: Duration,
+    /// Answers and search summaries.
+    pub answer: Duration,
+}
+
+/// What the chat completions providers share.
+struct ChatApi {
+    provider: &'static str,
+    /// The full `.../chat/completions` URL.
+    url: String,
+    model: String,
+    api_key: Option<String>,
+    transport: Arc<dyn Transport>,
+    timeouts: Timeouts,
+}
+
+impl ChatApi {
+    fn request_body(&self, temperature: f32, stream: bool, system: &str, user: &str) -> Value {
+        serde_json::json!({
+            "model": self.model,
+            "temperature": temperature,
+            "stream": stream,
             "messages": [
                 {"role": "system", "content": system},
-                {"role": "user", "content": user_prompt}
+                {"role": "user", "content": user}
             ]
-        });
-        let resp = client.post(url)
-            .bearer_auth(&self.api_key)
-            .json(&req_body)
-            .send().map_err(|e| format!("http error: {}", e))?;
-        let val: Value = resp.json().map_err(|e| format!("json error: {}", e))?;
-        let content = val["choices"][0]["message"]["content"].as_str().unwrap_or("{}");
-        let parsed: Value = serde_json::from_str(content).unwrap_or(serde_json::json!({"command_id": null, "parameters": {}, "confidence": 0.0}));
-        Ok(LlmIntent {
-            command_id: parsed["command_id"].as_str().map(|s| s.to_string()),
-            parameters: parsed.get("parameters").cloned().unwrap_or(serde_json::json!({})),
-            confidence: parsed["confidence"].as_f64().unwrap_or(0.0) as f32,
         })
     }
 
-    fn summarize_search(&self, _query: &str, snippets: &[String]) -> Result<String, String> {
-        let api_key = &self.api_key;
-        let url = "https://api.groq.com/openai/v1/chat/completions";
-        let text = if let Some(s) = snippets.first() { s } else { return Err("no snippets".into()) };
-        let system = "Summarize the following answer into a few concise sentences suitable for speech. Return only the sentences.";
-        let user_prompt = text;
-        let req_body = serde_json::json!({
-            "model": "llama-3.1-8b-instant",
-            "temperature": 0.0,
-            "messages": [
-                {"role": "system", "content": system},
-                {"role": "user", "content": user_prompt}
-            ]
+    /// POST a chat completion and return the first choice's content.
+    fn chat(&self, what: &str, timeout: Duration, temperature: f32, json: bool, system: &str, user: &str) -> Result<String, String> {
+        let mut req_body = self.request_body(temperature, false, system, user);
+        if json {
+            req_body["response_format"] = serde_json::json!({"type": "json_object"});
+        }
+        let val = self
+            .transport
+            .post_json(what, &self.url, self.api_key.as_deref(), &req_body, timeout)
+            .map_err(|e| format!("{} {}: {}", self.provider, what, e))?;
+        Ok(val["choices"][0]["message"]["content"].as_str().unwrap_or("").to_string())
+    }
+
+    fn classify(&self, text: &str, commands: &[crate::intent::IntentCommand]) -> Result<LlmIntent, String> {
+        let content = self.chat("classify", self.timeouts.classify, 0.0, true, CLASSIFY_SYSTEM, &classify_prompt(text, commands))?;
+        Ok(parse_intent(&content))
+    }
+
+    fn answer(&self, prompt: &str) -> Result<String, String> {
+        let content = self.chat("answer", self.timeouts.answer, 0.2, false, ANSWER_SYSTEM, prompt)?;
+        if content.trim().is_empty() { Err("empty answer".into()) } else { Ok(content.trim().to_string()) }
+    }
+
+    /// [`ChatApi::answer`] with `"stream": true`. A server that refuses to
+    /// stream gets the plain request instead; a stream that breaks off after
+    /// some sentences keeps what was said.
+    fn answer_streaming(&self, prompt: &str, on_sentence: &mut dyn FnMut(&str)) -> Result<String, String> {
+        let body = self.request_body(0.2, true, ANSWER_SYSTEM, prompt);
+        let (mut full, mut rest, mut emitted) = (String::new(), String::new(), false);
+        let result = self.transport.post_stream("answer", &self.url, self.api_key.as_deref(), &body, self.timeouts.answer, &mut |line| {
+            let Some(delta) = stream_delta(line) else { return };
+            full.push_str(&delta);
+            rest.push_str(&delta);
+            for sentence in take_sentences(&mut rest) {
+                emitted = true;
+                on_sentence(&sentence);
+            }
         });
-        let client = reqwest::blocking::Client::new();
-        let resp = client.post(url)
-            .bearer_auth(api_key)
-            .json(&req_body)
-            .send().map_err(|e| format!("http error: {}", e))?;
-        let val: Value = resp.json().map_err(|e| e.to_string())?;
-        let content = val["choices"][0]["message"]["content"].as_str().unwrap_or("").to_string();
-        if content.is_empty() { Err("empty summary".into()) } else { Ok(content) }
+        match result {
+            Ok(()) => {
+                if !rest.trim().is_empty() {
+                    on_sentence(rest.trim());
+                }
+                if full.trim().is_empty() { Err("empty answer".into()) } else { Ok(full.trim().to_string()) }
+            }
+            Err(e) if emitted => {
+                log::warn!("llm: {} answer stream broke off: {}", self.provider, e);
+                Ok(full.trim().to_string())
+            }
+            Err(e @ (TransportError::Other(_) | TransportError::Status { .. })) if !e.is_transient() => {
+                log::debug!("llm: {} cannot stream ({}); waiting for the whole answer", self.provider, e);
+                let answer = self.answer(prompt)?;
+                on_sentence(&answer);
+                Ok(answer)
+            }
+            Err(e) => Err(format!("{} answer: {}", self.provider, e)),
+        }
+    }
+}
+
+pub struct GroqClient {
+    chat: ChatApi,
+    #[cfg_attr(not(test), allow(dead_code))]
+    api_key: String,
+}
+
+impl GroqClient {
+    pub fn new(api_key: String, transport: Arc<dyn Transport>, timeouts: Timeouts) -> Self {
+        let chat = ChatApi {
+            provider: "groq",
+            url: "https://api.groq.com/openai/v1/chat/completions".into(),
+            model: "llama-3.1-8b-instant".into(),
+            api_key: Some(api_key.clone()),
+            transport,
+            timeouts,
+        };
+        Self { chat, api_key }
+    }
+}
+
+impl LlmClient for GroqClient {
+    fn provider(&self) -> &'static str {
+        "groq"
+    }
+
+    fn classify_intent(&self, text: &str, commands: &[crate::intent::IntentCommand]) -> Result<LlmIntent, String> {
+        self.chat.classify(text, commands)
     }
 
     fn answer_short(&self, prompt: &str) -> Result<String, String> {
-        let api_key = &self.api_key;
-        let url = "https://api.groq.com/openai/v1/chat/completions";
-        let system = "You are a helpful voice assistant named Bumblebee. Answer the user's question concisely in one or two sentences. Avoid markdown; output plain text only.";
-        let req_body = serde_json::json!({
-            "model": "llama-3.1-8b-instant",
-            "temperature": 0.2,
-            "messages": [
-                {"role": "system", "content": system},
-                {"role": "user", "content": prompt}
-            ]
-        });
-        let client = reqwest::blocking::Client::new();
-        let resp = client.post(url)
-            .bearer_auth(api_key)
-            .json(&req_body)
-            .send().map_err(|e| format!("http error: {}", e))?;
-        let val: Value = resp.json().map_err(|e| e.to_string())?;
-        let content = val["choices"][0]["message"]["content"].as_str().unwrap_or("").to_string();
-        if content.trim().is_empty() { Err("empty answer".into()) } else { Ok(content.trim().to_string()) }
+        self.chat.answer(prompt)
+    }
+
+    fn answer_streaming(&self, prompt: &str, on_sentence: &mut dyn FnMut(&str)) -> Result<String, String> {
+        self.chat.answer_streaming(prompt, on_sentence)
     }
 
     fn tts(&self, text: &str) -> Result<Vec<u8>, String> {
         let url = "https://api.groq.com/openai/v1/audio/speech";
-        let model = std::env::var("BTWD_TTS_MODEL").unwrap_or_else(|_| "canopylabs/orpheus-v1-english".to_string());
-        let voice = std::env::var("BTWD_TTS_VOICE").unwrap_or_else(|_| "alloy".to_string());
-        let response_format = std::env::var("BTWD_TTS_FORMAT").unwrap_or_else(|_| "wav".to_string());
+        let model = crate::config_env::var::<String>("BTWD_TTS_MODEL").unwrap_or_else(|| "canopylabs/orpheus-v1-english".to_string());
+        let voice = crate::config_env::var::<String>("BTWD_TTS_VOICE").unwrap_or_else(|| "alloy".to_string());
+        let response_format = crate::config_env::var::<String>("BTWD_TTS_FORMAT").unwrap_or_else(|| "wav".to_string());
         let req_body = serde_json::json!({
             "model": model,
             "voice": voice,
@@ -118,112 +360,252 @@ impl LlmClient for GroqClient {
     }
 }
 
-pub struct MistralClient { api_key: String }
+pub struct MistralClient {
+    chat: ChatApi,
+}
 
-impl MistralClient { pub fn new(api_key: String) -> Self { Self { api_key } } }
+impl MistralClient {
+    pub fn new(api_key: String, transport: Arc<dyn Transport>, timeouts: Timeouts) -> Self {
+        let chat = ChatApi {
+            provider: "mistral",
+            url: "https://api.mistral.ai/v1/chat/completions".into(),
+            model: "mistral-small-latest".into(),
+            api_key: Some(api_key),
+            transport,
+            timeouts,
+        };
+        Self { chat }
+    }
+}
 
 impl LlmClient for MistralClient {
-    fn classify_intent(&self, text: &str, commands: &[crate::intent::IntentCommand]) -> Result<LlmIntent, String> {
-        let url = "https://api.mistral.ai/v1/chat/completions";
-        let commands_list: Vec<_> = commands.iter().map(|c| serde_json::json!({"id": c.id, "description": c.description})).collect();
-        let system = "You are an intent classifier. Return ONLY a JSON object with keys: command_id, parameters, confidence. Choose the best matching command_id from the provided list or null if none.";
-        let user_prompt = serde_json::json!({"text": text, "commands": commands_list}).to_string();
-        let client = reqwest::blocking::Client::new();
-        let req_body = serde_json::json!({
-            "model": "mistral-small-latest",
-            "temperature": 0.0,
-            "response_format": {"type": "json_object"},
-            "messages": [
-                {"role": "system", "content": system},
-                {"role": "user", "content": user_prompt}
-            ]
-        });
-        let resp = client
-            .post(url)
-            .bearer_auth(&self.api_key)
-            .json(&req_body)
-            .send()
-            .map_err(|e| {
-                format!(
-                    "http error (mistral classify): connect={} timeout={} source={}",
-                    e.is_connect(),
-                    e.is_timeout(),
-                    e
-                )
-            })?;
-        let val: Value = resp.json().map_err(|e| format!("json error: {}", e))?;
-        let content = val["choices"][0]["message"]["content"].as_str().unwrap_or("{}");
-        let parsed: Value = serde_json::from_str(content).unwrap_or(serde_json::json!({"command_id": null, "parameters": {}, "confidence": 0.0}));
-        Ok(LlmIntent {
-            command_id: parsed["command_id"].as_str().map(|s| s.to_string()),
-            parameters: parsed.get("parameters").cloned().unwrap_or(serde_json::json!({})),
-            confidence: parsed["confidence"].as_f64().unwrap_or(0.0) as f32,
-        })
+    fn provider(&self) -> &'static str {
+        "mistral"
     }
 
-    fn summarize_search(&self, _query: &str, snippets: &[String]) -> Result<String, String> {
-        let url = "https://api.mistral.ai/v1/chat/completions";
-        let text = if let Some(s) = snippets.first() { s } else { return Err("no snippets".into()) };
-        let system = "Summarize the following answer into a few concise sentences suitable for speech. Return only the sentences.";
-        let user_prompt = text;
-        let req_body = serde_json::json!({
-            "model": "mistral-small-latest",
-            "temperature": 0.0,
-            "messages": [
-                {"role": "system", "content": system},
-                {"role": "user", "content": user_prompt}
-            ]
-        });
-        let client = reqwest::blocking::Client::new();
-        let resp = client
-            .post(url)
-            .bearer_auth(&self.api_key)
-            .json(&req_body)
-            .send()
-            .map_err(|e| {
-                format!(
-                    "http error (mistral summarize): connect={} timeout={} source={}",
-                    e.is_connect(),
-                    e.is_timeout(),
-                    e
-                )
-            })?;
-        let val: Value = resp.json().map_err(|e| e.to_string())?;
-        let content = val["choices"][0]["message"]["content"].as_str().unwrap_or("").to_string();
-        if content.is_empty() { Err("empty summary".into()) } else { Ok(content) }
+    fn classify_intent(&self, text: &str, commands: &[crate::intent::IntentCommand]) -> Result<LlmIntent, String> {
+        self.chat.classify(text, commands)
     }
 
     fn answer_short(&self, prompt: &str) -> Result<String, String> {
-        let url = "https://api.mistral.ai/v1/chat/completions";
-        let system = "You are a helpful voice assistant named Bumblebee. Answer the user's question concisely in one or two sentences. Avoid markdown; output plain text only.";
-        let req_body = serde_json::json!({
-            "model": "mistral-small-latest",
-            "temperature": 0.2,
-            "messages": [
-                {"role": "system", "content": system},
-                {"role": "user", "content": prompt}
-            ]
-        });
-        let client = reqwest::blocking::Client::new();
-        let resp = client
-            .post(url)
-            .bearer_auth(&self.api_key)
-            .json(&req_body)
-            .send()
-            .map_err(|e| {
-                format!(
-                    "http error (mistral answer): connect={} timeout={} source={}",
-                    e.is_connect(),
-                    e.is_timeout(),
-                    e
-                )
-            })?;
-        let val: Value = resp.json().map_err(|e| e.to_string())?;
-        let content = val["choices"][0]["message"]["content"].as_str().unwrap_or("").to_string();
-        if content.trim().is_empty() { Err("empty answer".into()) } else { Ok(content.trim().to_string()) }
+        self.chat.answer(prompt)
+    }
+
+    fn answer_streaming(&self, prompt: &str, on_sentence: &mut dyn FnMut(&str)) -> Result<String, String> {
+        self.chat.answer_streaming(prompt, on_sentence)
     }
 
     fn tts(&self, _text: &str) -> Result<Vec<u8>, String> {
         Err("Mistral TTS not supported".into())
     }
 }
+
+/// Any server speaking the OpenAI chat completions API: Ollama
+/// (`http://localhost:11434/v1`), llama.cpp server, vLLM, ...
+pub struct OpenAiCompatClient {
+    chat: ChatApi,
+}
+
+impl OpenAiCompatClient {
+    /// `base_url` goes up to and including the version segment, e.g. `http://localhost:11434/v1`.
+    pub fn new(base_url: &str, model: &str, api_key: Option<String>, transport: Arc<dyn Transport>, timeouts: Timeouts) -> Self {
+        let chat = ChatApi {
+            provider: "openai_compat",
+            url: format!("{}/chat/completions", base_url.trim_end_matches('/')),
+            model: model.to_string(),
+            api_key,
+            transport,
+            timeouts,
+        };
+        Self { chat }
+    }
+}
+
+impl LlmClient for OpenAiCompatClient {
+    fn provider(&self) -> &'static str {
+        "openai_compat"
+    }
+
+    fn classify_intent(&self, text: &str, commands: &[crate::intent::IntentCommand]) -> Result<LlmIntent, String> {
+        self.chat.classify(text, commands)
+    }
+
+    fn answer_short(&self, prompt: &str) -> Result<String, String> {
+        self.chat.answer(prompt)
+    }
+
+    fn answer_streaming(&self, prompt: &str, on_sentence: &mut dyn FnMut(&str)) -> Result<String, String> {
+        self.chat.answer_streaming(prompt, on_sentence)
+    }
+
+    fn tts(&self, _text: &str) -> Result<Vec<u8>, String> {
+        Err("openai_compat TTS not supported".into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    /// Serve one request with `reply` as the chat content; returns the
+    /// server's address and a handle yielding (request line, headers, body).
+    fn mock_server(reply: &'static str) -> (String, std::thread::JoinHandle<(String, Vec<String>, Value)>) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let addr = format!("http://{}/v1", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut headers = Vec::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                headers.push(line.trim().to_ascii_lowercase());
+            }
+            let len: usize = headers
+                .iter()
+                .find_map(|h| h.strip_prefix("content-length:"))
+                .map(|v| v.trim().parse().unwrap())
+                .unwrap_or(0);
+            let mut body = vec![0u8; len];
+            reader.read_exact(&mut body).unwrap();
+            let response = serde_json::json!({"choices": [{"message": {"role": "assistant", "content": reply}}]}).to_string();
+            let mut stream = stream;
+            write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", response.len(), response)
+                .unwrap();
+            (request_line.trim().to_string(), headers, serde_json::from_slice(&body).unwrap())
+        });
+        (addr, handle)
+    }
+
+    fn compat(base_url: &str, api_key: Option<&str>, timeout: Duration) -> OpenAiCompatClient {
+        let timeouts = Timeouts { classify: timeout, answer: timeout };
+        OpenAiCompatClient::new(base_url, "llama3.2", api_key.map(String::from), Arc::new(HttpTransport), timeouts)
+    }
+
+    fn commands() -> Vec<crate::intent::IntentCommand> {
+        serde_json::from_value(serde_json::json!([
+            {"id": "lock_screen", "description": "Lock the screen", "examples": ["lock my screen"]},
+            {"id": "volume_up", "description": "Turn it up", "examples": ["louder"]}
+        ]))
+        .unwrap()
+    }
+
+    #[test]
+    fn classify_sends_chat_completion_and_parses_the_intent() {
+        let (addr, server) = mock_server("```json\n{\"command_id\": \"lock_screen\", \"parameters\": {}, \"confidence\": 0.92}\n```");
+        let client = compat(&format!("{}/", addr), Some("sk-local"), Duration::from_secs(5));
+        let intent = client.classify_intent("lock it", &commands()).unwrap();
+        assert_eq!(intent.command_id.as_deref(), Some("lock_screen"));
+        assert!((intent.confidence - 0.92).abs() < 1e-6);
+
+        let (request_line, headers, body) = server.join().unwrap();
+        assert_eq!(request_line, "POST /v1/chat/completions HTTP/1.1");
+        assert!(headers.contains(&"authorization: bearer sk-local".to_string()), "{:?}", headers);
+        assert_eq!(body["model"], "llama3.2");
+        assert_eq!(body["response_format"]["type"], "json_object");
+        assert_eq!(body["messages"][0]["content"], CLASSIFY_SYSTEM);
+        let user: Value = serde_json::from_str(body["messages"][1]["content"].as_str().unwrap()).unwrap();
+        assert_eq!(user["text"], "lock it");
+        assert_eq!(user["commands"][1]["id"], "volume_up");
+    }
+
+    #[test]
+    fn answer_without_api_key_sends_no_authorization() {
+        let (addr, server) = mock_server("  Paris.  ");
+        let client = compat(&addr, None, Duration::from_secs(5));
+        assert_eq!(client.answer_short("capital of France?").unwrap(), "Paris.");
+        let (_, headers, body) = server.join().unwrap();
+        assert!(!headers.iter().any(|h| h.starts_with("authorization:")));
+        assert!(body.get("response_format").is_none());
+        assert!(client.tts("hi").unwrap_err().contains("not supported"));
+    }
+
+    #[test]
+    fn refused_and_timed_out_connections_read_differently() {
+        let port = TcpListener::bind(("127.0.0.1", 0)).unwrap().local_addr().unwrap().port();
+        let refused = compat(&format!("http://127.0.0.1:{}/v1", port), None, Duration::from_secs(5));
+        let err = refused.answer_short("hi").unwrap_err();
+        assert!(err.contains("cannot connect"), "{}", err);
+
+        // Accepts the connection but never answers.
+        let silent = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let slow = compat(&format!("http://{}/v1", silent.local_addr().unwrap()), None, Duration::from_millis(200));
+        let err = slow.answer_short("hi").unwrap_err();
+        assert!(err.contains("timed out after 200ms"), "{}", err);
+    }
+
+    /// Streams `lines` as the reply; `post_json` answers `whole` (the fallback).
+    struct Sse {
+        lines: Option<Vec<String>>,
+        whole: &'static str,
+    }
+
+    impl Transport for Sse {
+        fn post_json(&self, _what: &str, _url: &str, _bearer: Option<&str>, body: &Value, _timeout: Duration) -> Result<Value, TransportError> {
+            assert_eq!(body["stream"], false);
+            Ok(serde_json::json!({"choices": [{"message": {"content": self.whole}}]}))
+        }
+
+        fn post_stream(
+            &self,
+            what: &str,
+            _url: &str,
+            _bearer: Option<&str>,
+            body: &Value,
+            _timeout: Duration,
+            on_line: &mut dyn FnMut(&str),
+        ) -> Result<(), TransportError> {
+            assert_eq!(body["stream"], true);
+            let Some(lines) = &self.lines else { return Err(TransportError::Other(format!("{}: streaming not supported", what))) };
+            lines.iter().for_each(|l| on_line(l));
+            Ok(())
+        }
+    }
+
+    fn streamed(transport: Sse) -> (Result<String, String>, Vec<String>) {
+        let timeouts = Timeouts { classify: Duration::from_secs(1), answer: Duration::from_secs(1) };
+        let client = OpenAiCompatClient::new("http://llm/v1", "llama3.2", None, Arc::new(transport), timeouts);
+        let mut said = Vec::new();
+        let answer = client.answer_streaming("tell me about Rust", &mut |s| said.push(s.to_string()));
+        (answer, said)
+    }
+
+    #[test]
+    fn streamed_answers_arrive_a_sentence_at_a_time() {
+        let delta = |t: &str| format!("data: {}", serde_json::json!({"choices": [{"delta": {"content": t}}]}));
+        let lines = ["Rust is a systems", " language. It was", " started at Mozilla! Dr. Hoare", " designed it"]
+            .iter()
+            .map(|t| delta(t))
+            .chain([": keep-alive", "", "data: [DONE]"].map(String::from))
+            .collect();
+        let (answer, said) = streamed(Sse { lines: Some(lines), whole: "unused" });
+        assert_eq!(answer.unwrap(), "Rust is a systems language. It was started at Mozilla! Dr. Hoare designed it");
+        assert_eq!(said, ["Rust is a systems language.", "It was started at Mozilla!", "Dr. Hoare designed it"]);
+    }
+
+    #[test]
+    fn servers_that_cannot_stream_get_the_plain_request() {
+        let (answer, said) = streamed(Sse { lines: None, whole: "  Paris.  " });
+        assert_eq!(answer.unwrap(), "Paris.");
+        assert_eq!(said, ["Paris."]);
+
+        let mut buf = String::from("Short. A longer sentence here. Unfinished");
+        assert_eq!(take_sentences(&mut buf), ["Short. A longer sentence here."]);
+        assert_eq!(buf, " Unfinished");
+        assert_eq!(stream_delta("data: [DONE]"), None);
+    }
+
+    #[test]
+    fn unparseable_intent_is_no_command() {
+        let intent = parse_intent("I think you want to lock the screen");
+        assert_eq!((intent.command_id, intent.confidence), (None, 0.0));
+        assert_eq!(parse_intent("{\"command_id\": \"volume_up\", \"confidence\": 0.5}").command_id.as_deref(), Some("volume_up"));
+    }
+}
@@ -0,0 +1,172 @@
+use crate::intent::IntentCommand;
+use crate::llm::{LlmClient, LlmIntent};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Wraps an `LlmClient` and memoizes `classify_intent` results for `ttl`.
+///
+/// Only classification is cached: it is deterministic (temperature 0) and keyed on
+/// the transcript plus the command set. Answers, summaries and TTS always go to
+/// the inner client.
+pub struct CachingLlmClient<Inner: LlmClient> {
+    inner: Inner,
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (LlmIntent, Instant)>>,
+}
+
+impl<Inner: LlmClient> CachingLlmClient<Inner> {
+    /// A zero `ttl` disables caching.
+    pub fn new(inner: Inner, ttl: Duration) -> Self {
+        Self { inner, ttl, entries: Mutex::new(HashMap::new()) }
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn clear_cache(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, (LlmIntent, Instant)>> {
+        // A panic while holding the lock leaves only a partially updated cache.
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// sha256 of the text and the sorted command ids, so reordering commands.json
+/// keeps hits while adding or removing a command invalidates them.
+pub fn cache_key(text: &str, commands: &[IntentCommand]) -> String {
+    use sha2::{Digest, Sha256};
+    let mut ids: Vec<&str> = commands.iter().map(|c| c.id.as_str()).collect();
+    ids.sort_unstable();
+    let mut h = Sha256::new();
+    h.update(text.as_bytes());
+    for id in ids {
+        h.update([0u8]);
+        h.update(id.as_bytes());
+    }
+    h.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl<Inner: LlmClient> LlmClient for CachingLlmClient<Inner> {
+    fn provider(&self) -> &'static str {
+        self.inner.provider()
+    }
+
+    fn classify_intent(&self, text: &str, commands: &[IntentCommand]) -> Result<LlmIntent, String> {
+        if self.ttl.is_zero() {
+            return self.inner.classify_intent(text, commands);
+        }
+        let key = cache_key(text, commands);
+        if let Some((hit, at)) = self.lock().get(&key) {
+            if at.elapsed() < self.ttl {
+                log::debug!("llm: classify cache hit");
+                return Ok(hit.clone());
+            }
+        }
+        // Don't hold the lock across the network call.
+        let result = self.inner.classify_intent(text, commands)?;
+        let mut entries = self.lock();
+        let ttl = self.ttl;
+        entries.retain(|_, (_, at)| at.elapsed() < ttl);
+        entries.insert(key, (result.clone(), Instant::now()));
+        Ok(result)
+    }
+
+    fn answer_short(&self, prompt: &str) -> Result<String, String> {
+        self.inner.answer_short(prompt)
+    }
+
+    fn answer_streaming(&self, prompt: &str, on_sentence: &mut dyn FnMut(&str)) -> Result<String, String> {
+        self.inner.answer_streaming(prompt, on_sentence)
+    }
+
+    fn tts(&self, text: &str) -> Result<Vec<u8>, String> {
+        self.inner.tts(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct CountingLlm {
+        classify: AtomicUsize,
+        answers: AtomicUsize,
+    }
+
+    impl CountingLlm {
+        fn new() -> Self {
+            Self { classify: AtomicUsize::new(0), answers: AtomicUsize::new(0) }
+        }
+    }
+
+    impl LlmClient for CountingLlm {
+        fn classify_intent(&self, text: &str, _commands: &[IntentCommand]) -> Result<LlmIntent, String> {
+            self.classify.fetch_add(1, Ordering::SeqCst);
+            if text == "fail" {
+                return Err("http error".into());
+            }
+            Ok(LlmIntent { command_id: Some("volume_up".into()), parameters: serde_json::json!({}), confidence: 0.9 })
+        }
+        fn answer_short(&self, _prompt: &str) -> Result<String, String> {
+            self.answers.fetch_add(1, Ordering::SeqCst);
+            Ok("42".into())
+        }
+        fn tts(&self, _text: &str) -> Result<Vec<u8>, String> {
+            Ok(Vec::new())
+        }
+    }
+
+    fn cmd(id: &str) -> IntentCommand {
+        IntentCommand { id: id.into(), description: String::new(), examples: Vec::new(), dangerous: false, priority: 0, alias_of: None, slots: Vec::new() }
+    }
+
+    #[test]
+    fn repeated_classification_hits_the_cache() {
+        let client = CachingLlmClient::new(CountingLlm::new(), Duration::from_secs(300));
+        let cmds = vec![cmd("a"), cmd("b")];
+        let first = client.classify_intent("louder", &cmds).unwrap();
+        let second = client.classify_intent("louder", &cmds).unwrap();
+        assert_eq!(first.command_id, second.command_id);
+        assert_eq!(client.inner.classify.load(Ordering::SeqCst), 1);
+
+        client.clear_cache();
+        client.classify_intent("louder", &cmds).unwrap();
+        assert_eq!(client.inner.classify.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn key_ignores_command_order_but_not_membership() {
+        assert_eq!(cache_key("x", &[cmd("a"), cmd("b")]), cache_key("x", &[cmd("b"), cmd("a")]));
+        assert_ne!(cache_key("x", &[cmd("a")]), cache_key("x", &[cmd("a"), cmd("b")]));
+        assert_ne!(cache_key("x", &[cmd("a")]), cache_key("y", &[cmd("a")]));
+    }
+
+    #[test]
+    fn zero_ttl_expiry_and_errors_are_not_cached() {
+        let cmds = vec![cmd("a")];
+        let off = CachingLlmClient::new(CountingLlm::new(), Duration::ZERO);
+        off.classify_intent("louder", &cmds).unwrap();
+        off.classify_intent("louder", &cmds).unwrap();
+        assert_eq!(off.inner.classify.load(Ordering::SeqCst), 2);
+
+        let short = CachingLlmClient::new(CountingLlm::new(), Duration::from_millis(20));
+        short.classify_intent("louder", &cmds).unwrap();
+        std::thread::sleep(Duration::from_millis(40));
+        short.classify_intent("louder", &cmds).unwrap();
+        assert_eq!(short.inner.classify.load(Ordering::SeqCst), 2);
+
+        assert!(short.classify_intent("fail", &cmds).is_err());
+        assert!(short.classify_intent("fail", &cmds).is_err());
+        assert_eq!(short.inner.classify.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn answers_pass_through_uncached() {
+        let client = CachingLlmClient::new(CountingLlm::new(), Duration::from_secs(300));
+        client.answer_short("q").unwrap();
+        client.answer_short("q").unwrap();
+        assert_eq!(client.inner.answers.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,296 @@
+use crate::llm::{Transport, TransportError};
+use serde_json::Value;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How often a failed LLM request is tried again.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Extra attempts after the first, for transient failures only.
+    pub max_retries: u32,
+    /// Wait before the first retry; doubles with every further one.
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Wait before retry number `retry` (from 0): between half and all of the
+    /// exponential step, depending on `jitter` in [0, 1].
+    fn delay(&self, retry: u32, jitter: f32) -> Duration {
+        let step = self.backoff.saturating_mul(1 << retry.min(16));
+        step.mul_f64(0.5 + 0.5 * f64::from(jitter.clamp(0.0, 1.0)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BreakerState {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    /// Cooldown over; the next outcome decides.
+    HalfOpen,
+}
+
+/// After `threshold` consecutive transient failures every call is refused
+/// for `cooldown`, so a dead endpoint costs one log line per utterance
+/// instead of a full retry cycle. The first call after the cooldown decides
+/// whether it closes again.
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    /// A zero `threshold` never opens.
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self { threshold, cooldown, state: Mutex::new(BreakerState::Closed { failures: 0 }) }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BreakerState> {
+        self.state.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    /// `Err(remaining cooldown)` while open.
+    fn allow_at(&self, now: Instant) -> Result<(), Duration> {
+        let mut state = self.lock();
+        match *state {
+            BreakerState::Open { until } if now < until => Err(until - now),
+            BreakerState::Open { .. } => {
+                log::info!("llm: circuit half-open; trying the provider again");
+                *state = BreakerState::HalfOpen;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn record_at(&self, ok: bool, now: Instant) {
+        let mut state = self.lock();
+        let open = BreakerState::Open { until: now + self.cooldown };
+        *state = match (*state, ok) {
+            (BreakerState::Closed { .. }, true) => BreakerState::Closed { failures: 0 },
+            (_, true) => {
+                log::info!("llm: circuit closed; provider is answering again");
+                BreakerState::Closed { failures: 0 }
+            }
+            (BreakerState::Closed { failures }, false) if self.threshold == 0 || failures + 1 < self.threshold => {
+                BreakerState::Closed { failures: failures + 1 }
+            }
+            (BreakerState::Open { until }, false) => BreakerState::Open { until },
+            (_, false) => {
+                log::warn!("llm: circuit open; not calling the provider for {}s", self.cooldown.as_secs());
+                open
+            }
+        };
+    }
+}
+
+/// A [`Transport`] that retries transient failures with jittered exponential
+/// backoff, all within the caller's timeout, behind a [`CircuitBreaker`].
+pub struct ResilientTransport {
+    inner: Arc<dyn Transport>,
+    policy: RetryPolicy,
+    breaker: CircuitBreaker,
+    jitter: fn() -> f32,
+    /// Lifetime counters, for the logs.
+    attempts: AtomicU64,
+    failures: AtomicU64,
+}
+
+impl ResilientTransport {
+    pub fn new(inner: Arc<dyn Transport>, policy: RetryPolicy, breaker: CircuitBreaker) -> Self {
+        Self { inner, policy, breaker, jitter: random_jitter, attempts: AtomicU64::new(0), failures: AtomicU64::new(0) }
+    }
+}
+
+/// Good enough for spreading retries; not for anything else.
+fn random_jitter() -> f32 {
+    use std::hash::{BuildHasher, Hasher};
+    let bits = std::collections::hash_map::RandomState::new().build_hasher().finish();
+    (bits >> 40) as f32 / (1u64 << 24) as f32
+}
+
+impl Transport for ResilientTransport {
+    fn post_json(&self, what: &str, url: &str, bearer: Option<&str>, body: &Value, timeout: Duration) -> Result<Value, TransportError> {
+        if let Err(remaining) = self.breaker.allow_at(Instant::now()) {
+            log::info!("llm: {} skipped; circuit open for another {}s", what, remaining.as_secs().max(1));
+            return Err(TransportError::CircuitOpen { remaining });
+        }
+        let deadline = Instant::now() + timeout;
+        let mut retry = 0;
+        loop {
+            let attempts = self.attempts.fetch_add(1, Ordering::Relaxed) + 1;
+            let left = deadline.saturating_duration_since(Instant::now());
+            let err = match self.inner.post_json(what, url, bearer, body, left) {
+                Ok(v) => {
+                    self.breaker.record_at(true, Instant::now());
+                    return Ok(v);
+                }
+                Err(TransportError::Timeout { url, .. }) => TransportError::Timeout { after: timeout, url },
+                Err(e) => e,
+            };
+            let failures = self.failures.fetch_add(1, Ordering::Relaxed) + 1;
+            let delay = self.policy.delay(retry, (self.jitter)());
+            if !err.is_transient() || retry >= self.policy.max_retries || Instant::now() + delay >= deadline {
+                log::warn!("llm: {} failed after {} attempt(s): {} (attempts={} failures={})", what, retry + 1, err, attempts, failures);
+                if err.is_transient() {
+                    self.breaker.record_at(false, Instant::now());
+                }
+                return Err(err);
+            }
+            log::info!(
+                "llm: {} attempt {} failed: {}; retrying in {}ms (attempts={} failures={})",
+                what,
+                retry + 1,
+                err,
+                delay.as_millis(),
+                attempts,
+                failures
+            );
+            std::thread::sleep(delay);
+            retry += 1;
+        }
+    }
+
+    /// One attempt behind the breaker: once part of a reply has been handed
+    /// on (and maybe spoken), starting over would repeat it.
+    fn post_stream(
+        &self,
+        what: &str,
+        url: &str,
+        bearer: Option<&str>,
+        body: &Value,
+        timeout: Duration,
+        on_line: &mut dyn FnMut(&str),
+    ) -> Result<(), TransportError> {
+        if let Err(remaining) = self.breaker.allow_at(Instant::now()) {
+            log::info!("llm: {} skipped; circuit open for another {}s", what, remaining.as_secs().max(1));
+            return Err(TransportError::CircuitOpen { remaining });
+        }
+        self.attempts.fetch_add(1, Ordering::Relaxed);
+        let result = self.inner.post_stream(what, url, bearer, body, timeout, on_line);
+        match &result {
+            Ok(()) => self.breaker.record_at(true, Instant::now()),
+            Err(e) => {
+                self.failures.fetch_add(1, Ordering::Relaxed);
+                if e.is_transient() {
+                    self.breaker.record_at(false, Instant::now());
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// Replies from a script and counts the calls.
+    struct Scripted {
+        replies: Mutex<VecDeque<Result<Value, TransportError>>>,
+        calls: AtomicU64,
+    }
+
+    impl Scripted {
+        fn new(replies: Vec<Result<Value, TransportError>>) -> Arc<Self> {
+            Arc::new(Self { replies: Mutex::new(replies.into()), calls: AtomicU64::new(0) })
+        }
+
+        fn calls(&self) -> u64 {
+            self.calls.load(Ordering::SeqCst)
+        }
+    }
+
+    impl Transport for Scripted {
+        fn post_json(&self, _what: &str, _url: &str, _bearer: Option<&str>, _body: &Value, _timeout: Duration) -> Result<Value, TransportError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            self.replies.lock().unwrap().pop_front().unwrap_or(Ok(Value::Null))
+        }
+    }
+
+    fn resilient(inner: Arc<Scripted>, max_retries: u32, breaker: CircuitBreaker) -> ResilientTransport {
+        let mut t = ResilientTransport::new(inner, RetryPolicy { max_retries, backoff: Duration::from_millis(1) }, breaker);
+        t.jitter = || 0.5;
+        t
+    }
+
+    fn call(t: &ResilientTransport, timeout: Duration) -> Result<Value, TransportError> {
+        t.post_json("classify", "http://llm/v1/chat/completions", None, &Value::Null, timeout)
+    }
+
+    fn status(code: u16) -> Result<Value, TransportError> {
+        Err(TransportError::Status { code, body_preview: String::new() })
+    }
+
+    #[test]
+    fn transient_failures_are_retried_until_success() {
+        let inner = Scripted::new(vec![Err(TransportError::Connect("refused".into())), status(503), status(429), Ok(Value::Bool(true))]);
+        let t = resilient(inner.clone(), 3, CircuitBreaker::new(0, Duration::ZERO));
+        assert_eq!(call(&t, Duration::from_secs(5)), Ok(Value::Bool(true)));
+        assert_eq!(inner.calls(), 4);
+    }
+
+    #[test]
+    fn permanent_failures_and_exhausted_retries_give_up() {
+        let inner = Scripted::new(vec![status(400)]);
+        let t = resilient(inner.clone(), 3, CircuitBreaker::new(0, Duration::ZERO));
+        assert_eq!(call(&t, Duration::from_secs(5)), status(400));
+        assert_eq!(inner.calls(), 1);
+
+        let inner = Scripted::new(vec![status(500), status(502), status(504)]);
+        let t = resilient(inner.clone(), 1, CircuitBreaker::new(0, Duration::ZERO));
+        assert_eq!(call(&t, Duration::from_secs(5)), status(502));
+        assert_eq!(inner.calls(), 2);
+    }
+
+    #[test]
+    fn retries_stay_within_the_time_budget() {
+        let inner = Scripted::new(vec![Err(TransportError::Timeout { after: Duration::from_millis(3), url: "u".into() }); 3]);
+        let mut t = resilient(inner.clone(), 2, CircuitBreaker::new(0, Duration::ZERO));
+        t.policy.backoff = Duration::from_secs(10);
+        let err = call(&t, Duration::from_millis(50)).unwrap_err();
+        assert_eq!(inner.calls(), 1);
+        // Reported against the whole budget, not the last attempt's share.
+        assert_eq!(err.to_string(), "timed out after 50ms waiting for u");
+    }
+
+    #[test]
+    fn breaker_opens_after_consecutive_failures_and_probes_after_cooldown() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(30));
+        let t0 = Instant::now();
+        breaker.record_at(false, t0);
+        breaker.record_at(true, t0);
+        breaker.record_at(false, t0);
+        assert!(breaker.allow_at(t0).is_ok());
+        breaker.record_at(false, t0);
+        assert_eq!(breaker.allow_at(t0 + Duration::from_secs(10)), Err(Duration::from_secs(20)));
+
+        // One failed probe reopens it straight away; a good one closes it.
+        assert!(breaker.allow_at(t0 + Duration::from_secs(30)).is_ok());
+        breaker.record_at(false, t0 + Duration::from_secs(30));
+        assert!(breaker.allow_at(t0 + Duration::from_secs(31)).is_err());
+        assert!(breaker.allow_at(t0 + Duration::from_secs(60)).is_ok());
+        breaker.record_at(true, t0 + Duration::from_secs(60));
+        assert_eq!(*breaker.lock(), BreakerState::Closed { failures: 0 });
+    }
+
+    #[test]
+    fn open_circuit_short_circuits_without_calling_the_provider() {
+        let inner = Scripted::new(vec![Err(TransportError::Connect("refused".into())); 3]);
+        let t = resilient(inner.clone(), 0, CircuitBreaker::new(1, Duration::from_secs(60)));
+        assert!(matches!(call(&t, Duration::from_secs(5)), Err(TransportError::Connect(_))));
+        assert!(matches!(call(&t, Duration::from_secs(5)), Err(TransportError::CircuitOpen { .. })));
+        assert_eq!(inner.calls(), 1);
+    }
+
+    #[test]
+    fn backoff_doubles_with_jitter() {
+        let policy = RetryPolicy { max_retries: 3, backoff: Duration::from_millis(200) };
+        assert_eq!(policy.delay(0, 0.0), Duration::from_millis(100));
+        assert_eq!(policy.delay(0, 1.0), Duration::from_millis(200));
+        assert_eq!(policy.delay(2, 1.0), Duration::from_millis(800));
+        assert!((0..100).map(|_| random_jitter()).all(|j| (0.0..1.0).contains(&j)));
+    }
+}
@@ -0,0 +1,212 @@
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::span::EnteredSpan;
+use tracing::Subscriber;
+use tracing_subscriber::filter::{EnvFilter, LevelFilter};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, Layer};
+
+/// Parse a `[logging] level` value ("error", "warn", "info", "debug", "trace", "off").
+pub fn parse_level(level: &str) -> Option<LevelFilter> {
+    level.trim().parse().ok()
+}
+
+/// `[logging] format`: human-readable lines, or one JSON object per line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl LogFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "" | "text" => Some(Self::Text),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+static NEXT_INTERACTION: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    /// The `interaction` span this thread is in. A wake (or a control
+    /// request) opens one on the main loop, so every line from its ASR, intent
+    /// and execution is logged inside it; long-lived background threads
+    /// (capture, TTS, worker readers) stay outside.
+    static INTERACTION: RefCell<Option<EnteredSpan>> = const { RefCell::new(None) };
+}
+
+/// Enter a fresh `interaction` span on this thread and return its id.
+///
+/// The span is at ERROR level so it survives any `[logging] level`, and the
+/// lines of a quiet run still say which interaction they belong to.
+pub fn begin_interaction() -> u64 {
+    end_interaction();
+    let id = NEXT_INTERACTION.fetch_add(1, Ordering::Relaxed);
+    let span = tracing::error_span!("interaction", interaction = id).entered();
+    INTERACTION.with(|i| *i.borrow_mut() = Some(span));
+    id
+}
+
+/// Leave this thread's `interaction` span; the daemon is idle again.
+pub fn end_interaction() {
+    // Taken out first: exiting the span must not happen while borrowed.
+    let span = INTERACTION.with(|i| i.borrow_mut().take());
+    drop(span);
+}
+
+/// One JSON object per line (`timestamp`, `level`, `target`, `message` and
+/// the enclosing `spans`), for log shippers and `journalctl -o cat | jq`.
+fn json_layer<S, W>(writer: W) -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    fmt::layer().json().flatten_event(true).with_current_span(false).with_span_list(true).with_writer(writer)
+}
+
+/// Install the global subscriber; `log::` records from the rest of the daemon
+/// are forwarded to it and so land in the current interaction's spans.
+///
+/// Interactive runs (stdout is a TTY) print text lines to stderr, where `RUST_LOG`
+/// overrides the configured level. Under systemd output goes to the journal with
+/// proper priorities and the span fields as journal fields (`F_INTERACTION=12`);
+/// if the journal socket is unavailable we fall back to text on stderr.
+/// `format = "json"` always writes JSON lines to stderr, which journald captures as is.
+pub fn init(level: &str, format: &str) {
+    let level = parse_level(level).unwrap_or(LevelFilter::INFO);
+    let tty = atty::is(atty::Stream::Stdout);
+    let filter = if tty {
+        EnvFilter::builder().with_default_directive(level.into()).from_env_lossy()
+    } else {
+        EnvFilter::default().add_directive(level.into())
+    };
+    let json = LogFormat::parse(format) == Some(LogFormat::Json);
+    let journald = if json || tty {
+        None
+    } else {
+        match tracing_journald::layer() {
+            Ok(layer) => Some(layer),
+            Err(e) => {
+                eprintln!("logging: journald unavailable ({}); logging to stderr", e);
+                None
+            }
+        }
+    };
+    let text = (!json && journald.is_none()).then(|| fmt::layer().with_writer(std::io::stderr).with_ansi(atty::is(atty::Stream::Stderr)));
+    let json = json.then(|| json_layer(std::io::stderr));
+    if let Err(e) = tracing_subscriber::registry().with(filter).with(json).with(journald).with(text).try_init() {
+        eprintln!("logging: failed to install logger: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn levels_parse_case_insensitively() {
+        assert_eq!(parse_level("debug"), Some(LevelFilter::DEBUG));
+        assert_eq!(parse_level(" WARN "), Some(LevelFilter::WARN));
+        assert_eq!(parse_level("off"), Some(LevelFilter::OFF));
+        assert_eq!(parse_level("loud"), None);
+    }
+
+    /// Runs `f` with a JSON subscriber (and the `log` bridge) and returns the lines it wrote.
+    fn json_lines(f: impl FnOnce()) -> Vec<serde_json::Value> {
+        let _ = tracing_log::LogTracer::init();
+        let buf = Arc::new(Mutex::new(Vec::new()));
+        let sink = buf.clone();
+        let writer = move || Capture(sink.clone());
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(json_layer(writer)), f);
+        let out = String::from_utf8(buf.lock().unwrap().clone()).unwrap();
+        out.lines().map(|l| serde_json::from_str(l).unwrap()).collect()
+    }
+
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Capture {
+        fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(data);
+            Ok(data.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn interactions(line: &serde_json::Value) -> Vec<u64> {
+        let spans = line.get("spans").and_then(|s| s.as_array()).cloned().unwrap_or_default();
+        spans.iter().filter(|s| s["name"] == "interaction").map(|s| s["interaction"].as_u64().unwrap()).collect()
+    }
+
+    #[test]
+    fn json_lines_carry_the_interaction() {
+        assert_eq!(LogFormat::parse("JSON"), Some(LogFormat::Json));
+        assert_eq!(LogFormat::parse(""), Some(LogFormat::Text));
+        assert_eq!(LogFormat::parse("xml"), None);
+
+        let mut id = 0;
+        let lines = json_lines(|| {
+            id = begin_interaction();
+            let _exec = tracing::info_span!("exec", command = "volume_up").entered();
+            log::info!(target: "btwd::executor", "exec: {}", "volume_up");
+            drop(_exec);
+            end_interaction();
+            log::info!("idle");
+        });
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["level"], "INFO");
+        assert_eq!(lines[0]["target"], "btwd::executor");
+        assert_eq!(lines[0]["message"], "exec: volume_up");
+        assert_eq!(interactions(&lines[0]), vec![id]);
+        assert_eq!(lines[0]["spans"][1]["name"], "exec");
+        assert!(lines[0].get("timestamp").is_some());
+        assert!(lines[1].get("spans").is_none());
+    }
+
+    #[test]
+    fn interactions_get_fresh_ids_and_do_not_nest() {
+        let mut ids = Vec::new();
+        let lines = json_lines(|| {
+            ids.push(begin_interaction());
+            ids.push(begin_interaction());
+            tracing::info!("second");
+            end_interaction();
+        });
+        assert!(ids[1] > ids[0]);
+        assert_eq!(interactions(&lines[0]), vec![ids[1]]);
+    }
+
+    #[test]
+    fn only_threads_given_the_interaction_carry_it() {
+        let lines = json_lines(|| {
+            begin_interaction();
+            let dispatch = tracing::dispatcher::get_default(|d| d.clone());
+            let background = dispatch.clone();
+            std::thread::spawn(move || tracing::dispatcher::with_default(&background, || tracing::info!("capture")))
+                .join()
+                .unwrap();
+            let span = tracing::Span::current();
+            std::thread::spawn(move || {
+                tracing::dispatcher::with_default(&dispatch, || {
+                    let _entered = span.enter();
+                    tracing::info!("search");
+                })
+            })
+            .join()
+            .unwrap();
+            end_interaction();
+        });
+        assert_eq!(lines.len(), 2);
+        assert!(interactions(&lines[0]).is_empty());
+        assert_eq!(interactions(&lines[1]).len(), 1);
+    }
+}
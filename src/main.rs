@@ -1,241 +1,754 @@
 mod config;
+mod config_env;
 mod commands;
+mod commands_schema;
 mod error;
 mod porcupine_sys;
 mod porcupine;
+mod wake;
 mod audio;
 mod vad;
 mod intent;
 mod ml;
+mod asr;
 mod ui;
+mod notifications;
 mod tts;
 mod search;
+mod search_cache;
+mod signals;
 mod net;
 mod executor;
 mod llm;
+mod llm_cache;
+mod llm_retry;
+mod rate_limiter;
+mod status;
 mod decision;
 mod manager;
+mod embedding;
+mod cancel;
+mod control_socket;
+mod params;
+mod paths;
+mod query_url;
+mod history;
+mod context;
+mod logging;
+mod health;
+mod observers;
+mod commands_watcher;
+mod dbus_service;
 
 use error::{BtwError, Result};
 use std::{fs, time::Instant};
 use xdg::BaseDirectories;
-use std::sync::mpsc::Receiver;
-use std::sync::Arc;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use std::path::PathBuf;
-
-fn normalize_short(s: &str) -> String {
-    let mut out = String::with_capacity(s.len());
-    for ch in s.chars() {
-        if ch.is_ascii_alphanumeric() || ch.is_whitespace() {
-            out.push(ch.to_ascii_lowercase());
-        }
-    }
-    out.split_whitespace().collect::<Vec<_>>().join(" ")
-}
+use std::path::{Path, PathBuf};
 
 // NOTE: web-search gating is handled by the strict
 // LLM knowledge-check → Tavily → LLM workflow in `search`.
 
-fn handle_transcript(
-    text: &str,
-    cfg: &config::Config,
-    exec: &mut executor::Executor,
-    intent_router: &intent::IntentRouter,
-    llm_client: &Arc<dyn llm::LlmClient>,
-) {
-    let norm = normalize_short(text);
+/// The main loop's state a transcript is handled against, borrowed for one call.
+struct TranscriptContext<'a> {
+    cfg: &'a config::Config,
+    exec: &'a mut executor::Executor,
+    intent_router: &'a intent::IntentRouter,
+    llm_client: &'a Arc<dyn llm::LlmClient>,
+    search_provider: &'a Arc<dyn search::SearchProvider>,
+    worker: &'a mut ml::MLWorker,
+    /// The current interaction; an abort stops handling at the next check.
+    cancel: &'a cancel::CancelToken,
+    follow_up: &'a mut context::FollowUpContext,
+    mgr: &'a mut manager::Manager,
+}
+
+fn handle_transcript(text: &str, ctx: TranscriptContext<'_>) -> (&'static str, Option<intent::IntentResult>) {
+    let TranscriptContext { cfg, exec, intent_router, llm_client, search_provider, worker, cancel, follow_up, mgr } = ctx;
+    if cancel.is_canceled() {
+        log::info!("assistant: interaction aborted; ignoring transcript");
+        return ("aborted", None);
+    }
+
+    // The pick after "Did you mean volume up or brightness up?". Once that
+    // question has expired the transcript is routed as usual.
+    if let Some(outcome) = mgr.answer_clarification(text) {
+        return match outcome {
+            manager::ManagerOutcome::Execute { intent } => {
+                log::info!("intent: clarified -> {}", intent.command_id.as_deref().unwrap_or("?"));
+                run_command(cfg, exec, follow_up, &intent);
+                ("clarification_answer", Some(intent))
+            }
+            manager::ManagerOutcome::Canceled => ("canceled", None),
+            // Neither option: ask again until it is answered, canceled or expires.
+            _ => {
+                if let Some(options) = mgr.clarification_options() {
+                    prompt_user(cfg, &clarification_prompt(&options));
+                }
+                ("clarification_answer", None)
+            }
+        };
+    }
+
+    // 0) The value a command was missing: "set the brightness" -> "to what
+    // percent?" -> "sixty". Anything without a value is routed as usual.
+    if exec.awaiting_parameter().is_some() {
+        if let Some((intent, status)) = exec.fill_parameter(text) {
+            log::info!("exec: parameter answer -> {:?}", status);
+            match &status {
+                executor::ExecStatus::Executed { params, .. } | executor::ExecStatus::DryRun { params, .. } => follow_up.record(&intent, params),
+                executor::ExecStatus::NeedsParameter { prompt, .. } => prompt_user(cfg, prompt),
+                _ => {}
+            }
+            return ("parameter_answer", Some(intent));
+        }
+    }
 
     // 1) Confirmation/cancellation ONLY if a command is pending.
+    // Must ignore everything else while pending, except a new command when
+    // the pending policy lets it replace or queue behind the current one.
     if exec.has_pending() {
-        let proceed = matches!(norm.as_str(), "yes" | "confirm" | "do it");
-        let cancel = matches!(norm.as_str(), "no" | "cancel" | "stop");
-
-        if !proceed && !cancel {
-            // Must ignore everything else while pending.
-            return;
+        let answer = manager::classify_confirmation(text);
+        if answer != manager::VoiceAnswer::Other || exec.pending_policy() == executor::PendingPolicy::Reject {
+            answer_pending(text, cfg, exec, follow_up);
+            return ("confirmation_answer", None);
         }
+    }
 
-        let status = exec.handle_confirmation_text(&norm);
-        eprintln!("exec: confirmation text -> {:?}", status);
-        return;
+    // "Lower the volume and lock the screen": every part a confident match
+    // runs in order, behind one confirmation if any part needs it.
+    if !exec.has_pending() {
+        if let Some(chain) = intent_router.route_chain(text) {
+            let statuses = exec.handle_chain(&chain);
+            log::info!("exec: command chain -> {:?}", statuses);
+            follow_up.clear();
+            for (intent, status) in chain.iter().zip(&statuses) {
+                if let executor::ExecStatus::Executed { params, .. } | executor::ExecStatus::DryRun { params, .. } = status {
+                    follow_up.record(intent, params);
+                }
+            }
+            return ("command_chain", chain.into_iter().next());
+        }
     }
 
     // 2) Command detection (ALLOW-LIST ONLY).
     // NOTE: IntentRouter currently includes LLM fallback; we must not guess commands.
     // We enforce allow-list + deterministic score gate, and treat anything else as a question.
-    let routed = intent_router.route(text);
+    let query_embedding = if intent_router.has_embeddings() {
+        match worker.embed(&[text.to_string()]) {
+            Ok(mut v) => v.pop(),
+            Err(e) => {
+                log::warn!("intent: utterance embedding failed: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let intent::RankedIntent { best: routed, runner_up } =
+        tracing::info_span!("intent").in_scope(|| intent_router.route_ranked(text, query_embedding.as_deref()));
     let det_score = routed.deterministic_score.unwrap_or(0.0);
     let is_valid_allowlisted = routed.command_id.is_some();
-    let passed_threshold = det_score >= cfg.intent.deterministic_threshold;
+    let passed_threshold = routed.command_id.as_deref().is_some_and(|id| det_score >= intent_router.threshold_for(id))
+        || routed.embedding_score.is_some_and(|s| s >= intent_router.embedding_threshold());
+
+    // Routing may have blocked on the LLM; honour an abort that arrived meanwhile.
+    if cancel.is_canceled() {
+        log::info!("assistant: interaction aborted while deciding");
+        return ("aborted", Some(routed));
+    }
+
+    if exec.has_pending() && !(is_valid_allowlisted && passed_threshold) {
+        answer_pending(text, cfg, exec, follow_up);
+        return ("confirmation_answer", Some(routed));
+    }
 
     if is_valid_allowlisted && passed_threshold {
-        if routed.dangerous {
-            let status = exec.handle_intent(&intent::IntentResult {
-                requires_confirmation: true,
-                ..routed
-            });
-            eprintln!("exec: dangerous command -> {:?}", status);
-            return;
+        // Two commands too close to call: ask rather than guess.
+        if !exec.has_pending() {
+            if let Some(options) = mgr.clarify(text, &routed, runner_up.as_ref()) {
+                log::info!("intent: asking which of {:?} was meant", options);
+                prompt_user(cfg, &clarification_prompt(&options));
+                return ("clarification", Some(routed));
+            }
         }
+        let kind = run_command(cfg, exec, follow_up, &routed);
+        return (kind, Some(routed));
+    }
 
-        // Non-dangerous executes immediately.
-        let status = exec.handle_intent(&routed);
-        eprintln!("exec: command -> {:?}", status);
-        return;
+    // "a bit more" / "again" / "60" right after a command refers back to it.
+    if let Some(fu) = follow_up.resolve(text, &intent_router.commands) {
+        log::info!("intent: follow-up -> {} ({})", fu.command_id.as_deref().unwrap_or("?"), fu.parameters);
+        let status = exec.handle_intent(&fu);
+        log::info!("exec: follow-up -> {:?}", status);
+        if let executor::ExecStatus::Executed { params, .. } | executor::ExecStatus::DryRun { params, .. } = &status {
+            follow_up.record(&fu, params);
+        }
+        return ("follow_up", Some(fu));
     }
 
     // 3) Non-command -> Question routing.
     // If below threshold, treat as question (never command).
     let question = text.trim();
     if question.is_empty() {
-        return;
+        mgr.ignore_transcript();
+        return ("ignored", Some(routed));
     }
 
     // Strict workflow: ask LLM first with a knowledge-check. Only if it explicitly
     // returns the sentinel string do we call Tavily and then re-ask.
     // No UI notifications are shown until the final answer is ready.
     if cfg.search.enabled {
-        eprintln!("assistant: question; strict LLM→Tavily gating");
+        log::debug!("assistant: question; strict LLM→Tavily gating");
         search::search_and_summarize_async(
             question.to_string(),
-            cfg.search.clone(),
-            cfg.ui.osd,
-            cfg.ui.osd_timeout_ms,
-            cfg.speech_output.clone(),
+            search::AnswerSettings::from_config(cfg),
             llm_client.clone(),
+            search_provider.clone(),
+            cancel.clone(),
+            query_url::build_search_url(question, query_url::SearchEngine::from_config(&cfg.ui.search_engine)),
         );
-        return;
+        return ("web_query", Some(routed));
     }
 
     // If search is disabled, fall back to direct LLM answer.
-    eprintln!("assistant: question; asking LLM (search disabled)");
-    let ans = llm_client.answer_short(question).unwrap_or_else(|e| {
-        eprintln!("assistant: LLM answer error: {}", e);
-        "I don’t know.".to_string()
-    });
+    log::debug!("assistant: question; asking LLM (search disabled)");
+    let speak = cfg.speech_output.enabled || ui::delivery() == ui::Delivery::TtsOnly;
+    let mut tts_cfg = cfg.speech_output.clone();
+    tts_cfg.enabled = true;
+    // Streamed sentences are queued as they arrive; past the first few the rest
+    // is gathered into one utterance so a long answer can't overflow the queue.
+    let (mut streamed, mut tail) = (0, String::new());
+    // Earlier answers in this conversation come first, for "what about tomorrow?".
+    let prompt = mgr.question_prompt(question);
+    let result = if speak && cfg.llm.stream_answers {
+        llm_client.answer_streaming(&prompt, &mut |sentence| {
+            if cancel.is_canceled() {
+                return;
+            }
+            if streamed < STREAMED_SENTENCES {
+                tts::speak_async(sentence.to_string(), tts_cfg.clone());
+                streamed += 1;
+            } else {
+                tail.push_str(sentence);
+                tail.push(' ');
+            }
+        })
+    } else {
+        llm_client.answer_short(&prompt)
+    };
+    let ans = match result {
+        Ok(ans) => {
+            if !cancel.is_canceled() {
+                mgr.record_answer(question, &ans);
+            }
+            ans
+        }
+        Err(e) => {
+            log::error!("assistant: LLM answer error: {}", e);
+            llm::UNREACHABLE_REPLY.to_string()
+        }
+    };
+    if cancel.is_canceled() {
+        log::info!("assistant: interaction aborted; dropping answer");
+        return ("aborted", Some(routed));
+    }
     ui::notify_text(cfg.ui.osd, cfg.ui.osd_timeout_ms, "Btw", &ans);
-    if cfg.speech_output.enabled {
-        tts::speak_async(ans, cfg.speech_output.clone());
+    if ui::delivery() == ui::Delivery::TtsOnly {
+        history::record("answer", &ans, "tts");
+    }
+    if speak {
+        let rest = if streamed == 0 { ans } else { tail.trim_end().to_string() };
+        if !rest.is_empty() {
+            tts::speak_async(rest, tts_cfg);
+        }
+    }
+    ("question", Some(routed))
+}
+
+/// Run (or ask to confirm) a routed command; "confirmation" when a
+/// dangerous one is held for a yes, else "command".
+fn run_command(cfg: &config::Config, exec: &mut executor::Executor, follow_up: &mut context::FollowUpContext, routed: &intent::IntentResult) -> &'static str {
+    if routed.dangerous {
+        follow_up.clear();
+        let status = exec.handle_intent(&intent::IntentResult {
+            requires_confirmation: true,
+            ..routed.clone()
+        });
+        log::info!("exec: dangerous command -> {:?}", status);
+        match &status {
+            executor::ExecStatus::Queued { id } => {
+                ui::notify_text(cfg.ui.osd, cfg.ui.osd_timeout_ms, "btwd", &format!("Queued after the current confirmation: {}", id));
+            }
+            executor::ExecStatus::NeedsParameter { prompt, .. } => prompt_user(cfg, prompt),
+            _ => {}
+        }
+        return "confirmation";
+    }
+
+    // Non-dangerous executes immediately.
+    let status = exec.handle_intent(routed);
+    log::info!("exec: command -> {:?}", status);
+    match &status {
+        executor::ExecStatus::Executed { params, .. } | executor::ExecStatus::DryRun { params, .. } => follow_up.record(routed, params),
+        executor::ExecStatus::Queued { id } => {
+            ui::notify_text(cfg.ui.osd, cfg.ui.osd_timeout_ms, "btwd", &format!("Queued after the current confirmation: {}", id));
+        }
+        executor::ExecStatus::NeedsParameter { prompt, .. } => prompt_user(cfg, prompt),
+        _ => {}
+    }
+    "command"
+}
+
+/// "Did you mean volume up or brightness up?"
+fn clarification_prompt(command_ids: &[String]) -> String {
+    let names: Vec<String> = command_ids.iter().map(|id| id.replace('_', " ")).collect();
+    format!("Did you mean {}?", names.join(" or "))
+}
+
+/// Streamed answer sentences spoken one by one before the rest is batched.
+const STREAMED_SENTENCES: usize = 3;
+
+/// Treat `text` as the answer to the pending confirmation; an unclear answer
+/// is asked again (when voice confirmation is on).
+fn answer_pending(text: &str, cfg: &config::Config, exec: &mut executor::Executor, follow_up: &mut context::FollowUpContext) {
+    if manager::classify_confirmation(text) == manager::VoiceAnswer::Deny {
+        follow_up.clear();
     }
+    let status = exec.handle_confirmation_text(text);
+    log::info!("exec: confirmation text -> {:?}", status);
+    if let executor::ExecStatus::PendingConfirmation { description, .. } = &status {
+        prompt_user(cfg, &format!("Please answer yes or no: {}", description));
+    }
+}
+
+/// A question the user answers by voice: spoken ahead of anything queued
+/// when notifications are hidden, shown otherwise.
+fn prompt_user(cfg: &config::Config, prompt: &str) {
+    if ui::delivery() == ui::Delivery::TtsOnly {
+        let mut tts_cfg = cfg.speech_output.clone();
+        tts_cfg.enabled = true;
+        tts::speak_async_with(prompt.to_string(), tts_cfg, tts::Priority::Urgent);
+    } else {
+        ui::notify_text(cfg.ui.osd, cfg.ui.osd_timeout_ms, "btwd", prompt);
+    }
+}
+
+/// Run ASR on a finished utterance unless the interaction was aborted while
+/// recording, in which case the buffered audio is never sent. `poll_abort`
+/// runs once ASR returns, to pick up an abort requested meanwhile; the
+/// transcript is dropped if there was one. None means nothing is to be handled.
+fn transcribe_unless_aborted(
+    interaction: &cancel::CancelToken,
+    transcribe: impl FnOnce() -> Result<ml::AsrResponse>,
+    poll_abort: impl FnOnce(),
+) -> Option<Result<ml::AsrResponse>> {
+    if interaction.is_canceled() {
+        log::info!("asr: interaction aborted; utterance not sent");
+        return None;
+    }
+    let result = transcribe();
+    poll_abort();
+    if interaction.is_canceled() {
+        log::info!("asr: interaction aborted; discarding transcript");
+        return None;
+    }
+    Some(result)
 }
+
 fn main() {
     if let Err(e) = run() {
         eprintln!("btwd startup error: {}", e);
-        std::process::exit(1);
+        std::process::exit(e.exit_code());
+    }
+}
+
+/// The `--score-all` table, best match first.
+fn print_score_table(entries: &[intent::ScoreEntry]) {
+    let id_width = entries.iter().map(|e| e.command_id.len()).max().unwrap_or(0).max("command".len());
+    println!("{:>5}  {:>9}  {:<25}  {:<id_width$}  example", "score", "threshold", "match", "command");
+    for e in entries {
+        println!(
+            "{:>5.3}  {:>9.3}  {:<25}  {:<id_width$}  {}",
+            e.score,
+            e.threshold,
+            e.match_type,
+            e.command_id,
+            e.matched_example.as_deref().map(|x| format!("{:?}", x)).unwrap_or_default()
+        );
+    }
+}
+
+/// Print intent self-check findings; true if there were any.
+fn report_self_check(conflicts: &[intent::CrossMatch]) -> bool {
+    for c in conflicts {
+        log::warn!("intent: self-check: {}", c);
     }
+    !conflicts.is_empty()
+}
+
+/// Log ids the intent router and the executor disagree on (see
+/// [`commands_schema::cross_check`]). With `intent.consistency_check = "error"`
+/// any mismatch fails.
+fn check_consistency<'a>(
+    cfg: &config::Config,
+    path: &Path,
+    intent_ids: impl IntoIterator<Item = &'a str>,
+    exec_ids: impl IntoIterator<Item = &'a str>,
+) -> Result<()> {
+    if cfg.intent.consistency_check == "off" {
+        return Ok(());
+    }
+    let mismatches = commands_schema::cross_check(intent_ids, exec_ids);
+    for m in &mismatches {
+        log::warn!("commands: {}", m);
+    }
+    if !mismatches.is_empty() && cfg.intent.consistency_check == "error" {
+        return Err(BtwError::ParseError {
+            path: path.to_path_buf(),
+            kind: "json",
+            message: format!("{} command id(s) differ between intent routing and execution (intent.consistency_check = \"error\")", mismatches.len()),
+            cause: None,
+        });
+    }
+    Ok(())
+}
+
+/// Read and parse the config at `path`, apply `BTWD_*` overrides and
+/// validate the result. Every violation is printed before failing, so one
+/// run shows them all. Returns the overrides that were applied.
+fn load_config(path: &Path) -> Result<(config::Config, Vec<config_env::Override>)> {
+    let cfg_str = fs::read_to_string(path)
+        .map_err(|e| BtwError::ReadError { path: path.to_path_buf(), source: e })?;
+    let mut cfg = config::Config::from_toml_str(&cfg_str)
+        .map_err(|msg| BtwError::ParseError { path: path.to_path_buf(), kind: "toml", message: msg, cause: None })?;
+    let (overrides, mut errors) = match config_env::apply_process_env(&mut cfg) {
+        Ok(overrides) => (overrides, Vec::new()),
+        Err(errors) => (Vec::new(), errors),
+    };
+    errors.extend(cfg.validate());
+    if !errors.is_empty() {
+        for e in &errors {
+            eprintln!("config: error: {}", e);
+        }
+        return Err(BtwError::ParseError {
+            path: path.to_path_buf(),
+            kind: "config",
+            message: format!("{} invalid value(s)", errors.len()),
+            cause: None,
+        });
+    }
+    Ok((cfg, overrides))
 }
 
 fn run() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    // `--validate`: check config and commands, report, and exit without starting audio.
+    let validate_only = args.iter().any(|a| a == "--validate");
+    // `--score-all <utterance>`: print every command's deterministic score and exit.
+    let score_text = args.iter().position(|a| a == "--score-all").map(|i| args[i + 1..].join(" "));
+
     let xdg = BaseDirectories::with_prefix("btw")
         .map_err(|e| BtwError::XdgError { message: e.to_string() })?;
+    let paths = paths::Paths::from_env()?;
+    let config_override = paths::explicit_path(&args, "--config", "BTWD_CONFIG");
+    let commands_override = paths::explicit_path(&args, "--commands", "BTWD_COMMANDS");
+
+    // `btwd paths`: show where every file is (or would be) looked up.
+    if args.first().map(String::as_str) == Some("paths") {
+        print_paths(&paths, config_override, commands_override);
+        return Ok(());
+    }
 
-    let config_path = xdg.find_config_file("config.toml")
-        .ok_or_else(|| expected_missing(&xdg, "config.toml", "config"))?;
-    let commands_path = xdg.find_config_file("commands.json")
-        .ok_or_else(|| expected_missing(&xdg, "commands.json", "commands"))?;
-    let env_path = xdg.find_config_file(".env")
-        .ok_or_else(|| expected_missing(&xdg, ".env", "env"))?;
+    // `--check-config [PATH]`: validate only the config (the XDG one by
+    // default) without needing .env, commands or audio. Meant for CI.
+    if let Some(i) = args.iter().position(|a| a == "--check-config") {
+        let path = match args.get(i + 1).filter(|a| !a.starts_with("--")) {
+            Some(p) => PathBuf::from(p),
+            None => paths.config_file("config.toml", config_override)?,
+        };
+        let (cfg, overrides) = load_config(&path)?;
+        for o in &overrides {
+            eprintln!("config: override: {}", o);
+        }
+        for w in cfg.warnings() {
+            eprintln!("config: warning: {}", w);
+        }
+        eprintln!("check-config: {} is valid", path.display());
+        return Ok(());
+    }
+
+    let config_path = paths.config_file("config.toml", config_override)?;
+    let commands_path = paths.config_file("commands.json", commands_override)?;
+    let env_path = paths.config_file(".env", None)?;
 
     dotenvy::from_path(&env_path)
         .map_err(|e| BtwError::EnvLoadError { path: env_path.clone(), source: e })?;
 
-    let cfg_str = fs::read_to_string(&config_path)
-        .map_err(|e| BtwError::ReadError { path: config_path.clone(), source: e })?;
-    let cfg = config::Config::from_toml_str(&cfg_str)
-        .map_err(|msg| BtwError::ParseError { path: config_path.clone(), kind: "toml", message: msg })?;
+    let (cfg, overrides) = load_config(&config_path)?;
+    logging::init(&cfg.logging.level, &cfg.logging.format);
+    for o in &overrides {
+        log::info!("config: override: {}", o);
+    }
+    for w in cfg.warnings() {
+        log::warn!("config: warning: {}", w);
+    }
 
     let commands_str = fs::read_to_string(&commands_path)
         .map_err(|e| BtwError::ReadError { path: commands_path.clone(), source: e })?;
     let _commands = commands::parse_commands_json(&commands_str)
-        .map_err(|msg| BtwError::ParseError { path: commands_path.clone(), kind: "json", message: msg })?;
+        .map_err(|msg| BtwError::ParseError { path: commands_path.clone(), kind: "json", message: msg, cause: None })?;
+
+    if let Some(text) = score_text {
+        let intent_commands = intent::load_commands(&commands_path)?;
+        let index = intent::PreparedIndex::new(&intent_commands);
+        let overrides = cfg.intent.threshold_overrides();
+        let threshold_for = |id: &str| overrides.get(id).copied().unwrap_or(cfg.intent.deterministic_threshold);
+        print_score_table(&intent::score_all(&intent_commands, &index, &text, threshold_for));
+        return Ok(());
+    }
+
+    if validate_only {
+        let intent_commands = intent::load_commands(&commands_path)?;
+        let index = intent::PreparedIndex::new(&intent_commands);
+        let conflicts = intent::self_check(&intent_commands, &index);
+        if report_self_check(&conflicts) {
+            return Err(BtwError::ParseError {
+                path: commands_path.clone(),
+                kind: "intent",
+                message: format!("{} example(s) route to a different command", conflicts.len()),
+                cause: None,
+            });
+        }
+        let by_id = executor::load_commands(&commands_path)?;
+        check_consistency(&cfg, &commands_path, intent_commands.iter().map(|c| c.id.as_str()), by_id.keys().map(String::as_str))?;
+        eprintln!("validate: {} and {} look good", config_path.display(), commands_path.display());
+        return Ok(());
+    }
 
-    eprintln!("btwd started successfully");
-    eprintln!("Loaded config from {}", config_path.display());
-    eprintln!("Loaded commands from {}", commands_path.display());
-    eprintln!("Environment loaded from {}", env_path.display());
+    log::info!("btwd started successfully");
+    log::info!("Loaded config from {}", config_path.display());
+    log::info!("Loaded commands from {}", commands_path.display());
+    log::info!("Environment loaded from {}", env_path.display());
 
     // ---- Porcupine init (CORRECT PLACE)
-    let porcupine = porcupine::Porcupine::new(
-        cfg.wake_word.model_path.as_ref(),
-        &cfg.wake_word.device,
-        cfg.wake_word.ppn_path.as_ref(),
-        cfg.wake_word.sensitivity,
-    )?;
-
-    eprintln!("Porcupine version: {}", porcupine::Porcupine::version());
-    eprintln!("Porcupine sample rate: {}", porcupine.sample_rate());
-    eprintln!("Porcupine frame length: {}", porcupine.frame_length());
-    eprintln!("Porcupine device: {}", porcupine.device());
+    let wake_keywords = cfg.wake_word.keyword_list();
+    let keyword_specs: Vec<(PathBuf, f32)> = wake_keywords
+        .iter()
+        .map(|k| (PathBuf::from(&k.ppn_path), k.sensitivity.unwrap_or(cfg.wake_word.sensitivity)))
+        .collect();
+    let wake_labels: Vec<String> = wake_keywords.iter().map(|k| k.label.clone().unwrap_or_default()).collect();
+    let wake_actions: Vec<wake::WakeAction> = wake_keywords.iter().map(|k| wake::WakeAction::from_config(&k.action)).collect();
+    let detector: Arc<Mutex<dyn wake::WakeWordDetector>> = if cfg.wake_word.push_to_talk {
+        log::info!("wake: push-to-talk; Porcupine is not loaded");
+        Arc::new(Mutex::new(wake::PushToTalk))
+    } else if cfg.wake_word.language_models.is_empty() {
+        log::info!("Porcupine version: {}", porcupine::Porcupine::version());
+        let porcupine = porcupine::Porcupine::new(
+            cfg.wake_word.model_path.as_ref(),
+            &cfg.wake_word.device,
+            &keyword_specs,
+        )?;
+        log::debug!("Porcupine device: {}", porcupine.device());
+        Arc::new(Mutex::new(porcupine))
+    } else {
+        log::info!("Porcupine version: {}", porcupine::Porcupine::version());
+        // One instance per language; keyword_list() yields one keyword per model, in order.
+        let mut instances = Vec::new();
+        for (model, spec) in cfg.wake_word.language_models.iter().zip(&keyword_specs) {
+            let porcupine = porcupine::Porcupine::new(model.model_path.as_ref(), &cfg.wake_word.device, std::slice::from_ref(spec))?;
+            log::info!("Porcupine language '{}': model={}", model.language, model.model_path);
+            instances.push(wake::LanguageDetector { language: model.language.clone(), detector: Box::new(porcupine), keywords: 1 });
+        }
+        let sensitivities = keyword_specs.iter().map(|(_, s)| *s).collect();
+        Arc::new(Mutex::new(wake::MultiLanguageDetector::new(instances, sensitivities)?))
+    };
+    let (sample_rate, frame_length) = {
+        let d = detector.lock().unwrap_or_else(|p| p.into_inner());
+        (d.sample_rate(), d.frame_length())
+    };
+    log::debug!("Porcupine sample rate: {}", sample_rate);
+    log::debug!("Porcupine frame length: {}", frame_length);
+    log::info!("Porcupine keywords: {}", wake_labels.join(", "));
 
     // ---- Audio thread
-    let (_audio_handle, rx): (std::thread::JoinHandle<()>, Receiver<Vec<i16>>) =
-        audio::start_listening(&porcupine)?;
 
-    eprintln!("Listening for wake word...");
+    let (mut audio_capture, rx, audio_events): (audio::AudioCapture, Receiver<Vec<i16>>, Receiver<audio::AudioEvent>) =
+        audio::start_listening(detector.clone(), &cfg.audio, cfg.speech.pre_emphasis_coefficient)?;
 
-    let mut worker = ml::MLWorker::new()?;
-    let mut vad = vad::Vad::new(cfg.speech.vad_mode)?;
+    let idle_message = if cfg.wake_word.push_to_talk { "Waiting for push-to-talk..." } else { "Listening for wake word..." };
+    log::info!("{}", idle_message);
 
-    let sample_rate = porcupine.sample_rate();
-    let frame_length = porcupine.frame_length();
+    // In-process ASR replaces the worker for transcription only; the worker
+    // still serves embeddings, so without them Python is never started.
+    let mut local_asr = asr::local_engine(&cfg.asr)?;
+    let mut worker = if local_asr.is_some() && !cfg.intent.embeddings {
+        ml::MLWorker::deferred(cfg.asr.clone())?
+    } else {
+        ml::MLWorker::new(cfg.asr.clone())?
+    };
+    if let Some(engine) = &local_asr {
+        log::info!("asr: using in-process engine '{}'", engine.name());
+    }
+
+    // SIGTERM (systemd stop) / SIGINT: leave the main loop and stop the worker
+    // cleanly. SIGUSR1: re-read wake word sensitivities, or start listening
+    // in push-to-talk mode. SIGHUP: reload config.toml and commands.json.
+    let signals = signals::Signals::install()?;
     let frame_ms = (frame_length as f64) * 1000.0 / sample_rate as f64;
+    let mut vad = vad::AdaptiveVad::new(
+        vad::Vad::with_rate(cfg.speech.vad_mode, sample_rate, cfg.speech.vad_window_ms, cfg.speech.vad_vote_ratio)?,
+        cfg.speech.vad_mode,
+        cfg.speech.noise_threshold,
+        cfg.speech.adaptive_vad,
+        frame_ms,
+    );
 
+    let llm_cache_ttl = Duration::from_secs(cfg.intent.llm_cache_ttl_secs);
+    let llm_transport: Arc<dyn llm::Transport> = Arc::new(llm_retry::ResilientTransport::new(
+        Arc::new(llm::HttpTransport),
+        llm_retry::RetryPolicy { max_retries: cfg.llm.max_retries, backoff: Duration::from_millis(cfg.llm.retry_backoff_ms) },
+        llm_retry::CircuitBreaker::new(cfg.llm.breaker_failures, Duration::from_secs(cfg.llm.breaker_cooldown_secs)),
+    ));
+    let llm_timeouts = llm::Timeouts {
+        classify: Duration::from_millis(cfg.llm.classify_timeout_ms),
+        answer: Duration::from_millis(cfg.llm.answer_timeout_ms),
+    };
     let llm_client: Arc<dyn llm::LlmClient> = match cfg.llm.provider.as_str() {
         "groq" => {
-            std::env::var("GROQ_API_KEY").map_err(|e| {
-                BtwError::ParseError {
-                    path: config_path.clone(),
-                    kind: "env",
-                    message: format!("missing GROQ_API_KEY: {}", e),
-                }
-            })?;
-            Arc::new(llm::GroqClient::new(std::env::var("GROQ_API_KEY").unwrap()))
+            std::env::var("GROQ_API_KEY")
+                .map_err(|e| BtwError::LlmError { provider: "groq", message: format!("missing GROQ_API_KEY: {}", e) })?;
+            Arc::new(llm_cache::CachingLlmClient::new(
+                llm::GroqClient::new(std::env::var("GROQ_API_KEY").unwrap(), llm_transport, llm_timeouts),
+                llm_cache_ttl,
+            ))
         }
         "mistral" => {
-            std::env::var("MISTRAL_API_KEY").map_err(|e| {
-                BtwError::ParseError {
-                    path: config_path.clone(),
-                    kind: "env",
-                    message: format!("missing MISTRAL_API_KEY: {}", e),
-                }
-            })?;
-            Arc::new(llm::MistralClient::new(std::env::var("MISTRAL_API_KEY").unwrap()))
+            std::env::var("MISTRAL_API_KEY")
+                .map_err(|e| BtwError::LlmError { provider: "mistral", message: format!("missing MISTRAL_API_KEY: {}", e) })?;
+            Arc::new(llm_cache::CachingLlmClient::new(
+                llm::MistralClient::new(std::env::var("MISTRAL_API_KEY").unwrap(), llm_transport, llm_timeouts),
+                llm_cache_ttl,
+            ))
+        }
+        "openai_compat" => {
+            let api_key = match &cfg.llm.api_key_env {
+                Some(var) => Some(
+                    std::env::var(var)
+                        .map_err(|e| BtwError::LlmError { provider: "openai_compat", message: format!("missing {}: {}", var, e) })?,
+                ),
+                None => None,
+            };
+            log::info!("llm: openai_compat base_url={} model={}", cfg.llm.base_url, cfg.llm.model);
+            Arc::new(llm_cache::CachingLlmClient::new(
+                llm::OpenAiCompatClient::new(&cfg.llm.base_url, &cfg.llm.model, api_key, llm_transport, llm_timeouts),
+                llm_cache_ttl,
+            ))
         }
         p => {
-            return Err(BtwError::ParseError {
-                path: config_path.clone(),
-                kind: "llm",
-                message: format!("unknown provider '{}'", p),
-            })
+            return Err(BtwError::InvalidSetting { key: "llm.provider", message: format!("unknown provider '{}'", p) })
         }
     };
 
-    let intent_router = intent::IntentRouter::from_file(
-        &commands_path,
-        intent::IntentConfig {
-            deterministic_threshold: cfg.intent.deterministic_threshold,
-            llm_fallback_threshold: cfg.intent.llm_fallback_threshold,
-        },
-        llm_client.clone(),
-    )?;
+    let search_provider = search::session_provider(&cfg.search);
+
+    let mut intent_router = intent::IntentRouter::from_file(&commands_path, intent_config(&cfg.intent), llm_client.clone())?;
+    intent_router.set_threshold_overrides(cfg.intent.threshold_overrides());
+
+    if cfg.intent.self_check != "off" {
+        let conflicts = intent_router.self_check();
+        if report_self_check(&conflicts) && cfg.intent.self_check == "error" {
+            return Err(BtwError::ParseError {
+                path: commands_path.clone(),
+                kind: "intent",
+                message: format!("{} example(s) route to a different command (intent.self_check = \"error\")", conflicts.len()),
+                cause: None,
+            });
+        }
+    }
+
+    let embedding_cache = xdg.place_cache_file("intent-embeddings.json").ok();
+    if cfg.intent.embeddings {
+        if worker.supports("embed") {
+            match embedding::build_index(&mut worker, &intent_router.commands, embedding_cache.as_deref()) {
+                Ok(index) => intent_router.set_embeddings(index),
+                Err(e) => log::warn!("intent: embedding tier disabled: {}", e),
+            }
+        } else {
+            log::info!("intent: embedding tier skipped (ML worker lacks 'embed' capability)");
+        }
+    }
+
+    let exec_cfg = executor::ExecutionCfg {
+        confirmation_timeout_seconds: cfg.execution.confirmation_timeout_seconds,
+        dry_run: cfg.execution.dry_run,
+        voice_confirmation: cfg.execution.voice_confirmation,
+        pending_policy: executor::PendingPolicy::from_config(&cfg.execution.pending_policy),
+        default_env_allowlist: cfg.execution.default_env_allowlist.clone(),
+        confirmation: executor::ConfirmationPolicy::from_config(&cfg.execution.confirmation),
+    };
+    let mut exec = executor::Executor::new_from_path(&commands_path, exec_cfg.clone())?;
+    check_consistency(&cfg, &commands_path, intent_router.commands.iter().map(|c| c.id.as_str()), exec.command_ids())?;
+    exec.add_observer(Box::new(observers::NotifyObserver { osd: cfg.ui.osd, timeout_ms: cfg.ui.osd_timeout_ms }));
+    exec.add_observer(Box::new(observers::LogObserver::new()));
+    let dbus = match dbus_service::DbusService::start() {
+        Ok(service) => {
+            log::info!("dbus: serving {} at {}", dbus_service::BUS_NAME, dbus_service::OBJECT_PATH);
+            Some(service)
+        }
+        Err(e) => {
+            log::warn!("dbus: {} unavailable: {}", dbus_service::BUS_NAME, e);
+            None
+        }
+    };
+    let dbus_signals = dbus.as_ref().map(dbus_service::DbusService::signals).unwrap_or_default();
+    exec.add_observer(Box::new(dbus_signals.clone()));
 
     let decision_manager = decision::DecisionManager::new(decision::DecisionConfig {
         deterministic_threshold: cfg.intent.deterministic_threshold,
-    });
+        threshold_overrides: cfg.intent.threshold_overrides(),
+        question_starters: decision::merge_keywords(
+            decision::default_question_starters(),
+            &cfg.decision.question_starters,
+            config::MatchMode::StartsWith,
+            cfg.decision.replace_defaults,
+        ),
+        web_keywords: decision::merge_keywords(
+            decision::default_web_keywords(),
+            &cfg.decision.web_keywords,
+            config::MatchMode::Contains,
+            cfg.decision.replace_defaults,
+        ),
+        clarify_margin: cfg.decision.clarify_margin,
+        context_window: cfg.decision.context_window,
+        context_ttl: Duration::from_secs(cfg.decision.context_ttl_secs),
+    })
+    .map_err(|message| BtwError::InvalidSetting { key: "decision", message })?;
 
-    let mut exec = executor::Executor::new_from_path(
-        &commands_path,
-        executor::ExecutionCfg {
-            confirmation_timeout_seconds: cfg.execution.confirmation_timeout_seconds,
-            dry_run: cfg.execution.dry_run,
-        },
-    )?;
+    // Edits to commands.json take effect without a restart.
+    let commands_watcher = match commands_watcher::CommandsWatcher::start(&commands_path) {
+        Ok(w) => Some(w),
+        Err(e) => {
+            log::warn!("commands: hot reload disabled; cannot watch {}: {}", commands_path.display(), e);
+            None
+        }
+    };
+    let mut follow_up = context::FollowUpContext::new(Duration::from_secs(cfg.intent.follow_up_ttl_secs));
 
     // NOTE: The legacy `Manager` state machine is retained for unit tests and
     // module compatibility, but runtime behavior is centralized in
     // `handle_transcript` + `Executor` pending confirmation.
-    let mut _mgr = manager::Manager::new(decision_manager);
+    let mut mgr = manager::Manager::with_execution_cfg(decision_manager, &exec_cfg);
+    // The manager is driven at the runtime transition points below so the
+    // status file sees every state change.
+    if cfg.ui.status_file || cfg.ui.status_fifo {
+        let dir = status::status_dir();
+        let file = cfg.ui.status_file.then(|| dir.join("status.json"));
+        let fifo = cfg.ui.status_fifo.then(|| dir.join("status.fifo"));
+        status::StatusPublisher::new(file, fifo).spawn(mgr.events());
+    }
 
     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     enum ListenState {
@@ -245,43 +758,104 @@ fn run() -> Result<()> {
     }
 
     let mut state = ListenState::Idle;
+    let health_status = Arc::new(Mutex::new(health::ManagerState::default()));
+    if let Err(e) = health::start(cfg.health.port, health_status.clone()) {
+        log::warn!("health: endpoint disabled; cannot listen on 127.0.0.1:{}: {}", cfg.health.port, e);
+    }
     let mut samples: Vec<i16> = Vec::new();
-    let mut silence_ms = 0.0;
+    let mut endpoint = vad::EndpointDetector::new(vad::EndpointCfg {
+        frame_ms,
+        hangover_ms: cfg.speech.hangover_ms(),
+        min_speech_ms: cfg.speech.min_speech_ms,
+        max_utterance_ms: cfg.speech.max_utterance_seconds.saturating_mul(1000),
+        silence_threshold: cfg.speech.silence_threshold,
+    });
     let mut start_time: Option<Instant> = None;
     let mut saw_post_wake_speech = false;
+    // Which configured keyword armed the current interaction.
+    let mut wake_keyword: usize = 0;
 
-    let mut porcupine = porcupine;
     let mut last_heartbeat = Instant::now();
+    // Time-of-day sensitivity profiles; None means the configured sensitivities.
+    let base_sensitivities: Vec<f32> = keyword_specs.iter().map(|(_, s)| *s).collect();
+    let mut active_profile: Option<usize> = None;
+    let mut last_profile_check: Option<Instant> = None;
     let mut last_listening_debug = Instant::now();
     let mut pending_confirm_request_id: Option<String> = None;
+    let mut confirm_countdown: Option<ui::ConfirmCountdown> = None;
+    // Incremental ASR: open while Recording when the worker advertises asr_stream.
+    let mut asr_stream: Option<std::sync::mpsc::Receiver<ml::AsrEvent>> = None;
+    let mut stream_buf: Vec<i16> = Vec::new();
+    let stream_chunk = (sample_rate / 4) as usize;
+    // Minted per wake; the listening notification's Cancel action and the
+    // control spool both trip it, as does any stage that sees it set.
+    let mut interaction = cancel::CancelToken::new();
+    // A confirm/deny read from the control spool mid-ASR is replayed next frame.
+    let mut deferred_control: Option<cancel::ControlRequest> = None;
+    // Set by the control socket's `listen`; handled like a wake word on the next frame.
+    let mut listen_requested = false;
+    // With voice confirmation, the answer to a prompt is listened for without a
+    // wake word, as is the answer to a question for a missing parameter:
+    // `(request_id, deadline)` of the request being answered.
+    let mut answer_for: Option<(String, Instant)> = None;
+    // Sampled once per wake so a whole interaction uses one delivery mode.
+    // Tell the user once when ASR goes degraded, not on every wake.
+    let mut asr_unavailable_notified = false;
+    let mut dnd = ui::DndMonitor::new(Box::new(ui::SystemDnd), Duration::from_secs(5));
+    ui::detect_notifiers();
+    let ui_actions = ui::init_notifier(&cfg.ui);
+    let control_server = match control_socket::ControlServer::bind(&control_socket::socket_path()) {
+        Ok(server) => {
+            log::info!("control: listening on {}", control_socket::socket_path().display());
+            Some(server)
+        }
+        Err(e) => {
+            log::warn!("control: socket unavailable, btwctl will not work: {}", e);
+            None
+        }
+    };
 
     // Optional: dump recorded audio for debugging, controlled by env var.
     // Example: export BTWD_DEBUG_AUDIO_DIR=/tmp/btwd-audio
-    let debug_audio_dir: Option<PathBuf> = std::env::var("BTWD_DEBUG_AUDIO_DIR")
-        .ok()
-        .filter(|s| !s.trim().is_empty())
+    let debug_audio_dir: Option<PathBuf> = config_env::var::<Option<String>>("BTWD_DEBUG_AUDIO_DIR")
+        .flatten()
         .map(PathBuf::from);
     if let Some(dir) = &debug_audio_dir {
-        eprintln!("debug: BTWD_DEBUG_AUDIO_DIR enabled: {}", dir.display());
+        log::info!("debug: BTWD_DEBUG_AUDIO_DIR enabled: {}", dir.display());
     }
 
     loop {
+        if let Some(sig) = signals.shutdown_signal() {
+            log::info!("btwd: {} received; shutting down", signals::signal_name(sig));
+            interaction.cancel();
+            if exec.has_pending() {
+                let status = exec.cancel_pending("daemon shutting down");
+                log::info!("exec: {:?}", status);
+            }
+            tts::stop_speaking();
+            ui::dismiss_listening();
+            audio_capture.stop();
+            // history.jsonl is appended and closed per record, so nothing is left to flush.
+            worker.shutdown();
+            return Ok(());
+        }
+
         // Confirmation polling happens ONLY when the Executor has a pending command.
-        // The UI helper writes 'yes'/'no' into $XDG_RUNTIME_DIR/btwd-confirm-<request_id>.
+        // The confirmation prompt writes 'yes'/'no' into $XDG_RUNTIME_DIR/btwd-confirm-<request_id>.
         if let Some(req_id) = exec.pending_request_id().map(|s| s.to_string()) {
-            let runtime_dir = std::env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".to_string());
-            let path = std::path::Path::new(&runtime_dir).join(format!("btwd-confirm-{}", req_id));
+            let path = ui::confirm_path(&req_id);
             if let Ok(action) = std::fs::read_to_string(&path) {
                 let _ = std::fs::remove_file(&path);
                 let action = action.trim().to_ascii_lowercase();
                 if action == "no" {
-                    eprintln!("exec: cancel via notification");
+                    log::info!("exec: cancel via notification");
                     let _ = exec.cancel_pending("user canceled");
+                    follow_up.clear();
                     // Best-effort: ensure no stale spool survives.
                     let _ = std::fs::remove_file(&path);
                     pending_confirm_request_id = None;
                 } else if action == "yes" {
-                    eprintln!("exec: confirm via notification");
+                    log::info!("exec: confirm via notification");
                     let _ = exec.confirm_pending();
                     pending_confirm_request_id = None;
                 }
@@ -289,59 +863,351 @@ fn run() -> Result<()> {
                 let should_notify = pending_confirm_request_id.as_deref() != Some(&req_id);
                 if should_notify {
                     pending_confirm_request_id = Some(req_id.clone());
-                    ui::notify_confirm_actions(cfg.ui.osd, &req_id, "btwd", "Confirm command");
+                    let desc = exec.pending_description().unwrap_or("a command").to_string();
+                    mgr.mirror_confirmation(&req_id, &desc);
+                    if ui::delivery() == ui::Delivery::TtsOnly {
+                        // The actionable notification would be hidden; ask aloud and
+                        // accept the answer via the control spool (confirm/deny).
+                        log::info!("exec: DND active; confirmation prompt via TTS");
+                        history::record("confirmation", &desc, "tts");
+                        let mut tts_cfg = cfg.speech_output.clone();
+                        tts_cfg.enabled = true;
+                        tts::speak_async_with(format!("Confirmation needed: {}. Say confirm or deny.", desc), tts_cfg, tts::Priority::Urgent);
+                    } else if let Some(deadline) = exec.pending_deadline() {
+                        confirm_countdown = Some(ui::ConfirmCountdown::start(cfg.ui.osd, &req_id, &desc, deadline));
+                    }
                 }
             }
         } else {
             pending_confirm_request_id = None;
+            // Answered, canceled or expired: stop counting down.
+            if let Some(mut countdown) = confirm_countdown.take() {
+                countdown.stop();
+            }
+            let mirrored = match mgr.state() {
+                manager::State::Confirming => true,
+                manager::State::Clarifying => exec.awaiting_parameter().is_none() && !mgr.is_clarifying(),
+                _ => false,
+            };
+            if mirrored {
+                mgr.reset_to_idle();
+            }
+        }
+
+        // Local hour is checked once a minute; the detector is only rebuilt
+        // when a different profile takes over.
+        let profile_check_due = !matches!(last_profile_check, Some(t) if t.elapsed() < Duration::from_secs(60));
+        if !cfg.wake_word.sensitivity_profiles.is_empty() && profile_check_due {
+            last_profile_check = Some(Instant::now());
+            let profile = cfg.wake_word.active_profile(local_hour());
+            if profile != active_profile {
+                let mut d = detector.lock().unwrap_or_else(|p| p.into_inner());
+                let result = match profile {
+                    Some(i) => d.reinitialize_sensitivity(cfg.wake_word.sensitivity_profiles[i].sensitivity),
+                    None => d.reinit(&base_sensitivities),
+                };
+                match result {
+                    Ok(()) => {
+                        match profile.map(|i| &cfg.wake_word.sensitivity_profiles[i]) {
+                            Some(p) => log::info!("wake: sensitivity {} ({:02}:00-{:02}:00 profile)", p.sensitivity, p.start_hour, p.end_hour),
+                            None => log::info!("wake: sensitivity back to configured values {:?}", base_sensitivities),
+                        }
+                        active_profile = profile;
+                    }
+                    Err(e) => log::warn!("wake: profile switch failed, keeping previous settings: {}", e),
+                }
+            }
+        }
+
+        if commands_watcher.as_ref().is_some_and(|w| w.take_changed()) {
+            match reload_commands(&commands_path, &cfg, &mut intent_router, &mut exec, &mut worker, embedding_cache.as_deref()) {
+                Ok(n) => log::info!("commands: reloaded {} command(s) from {} (version {})", n, commands_path.display(), commands_watcher::bump_version()),
+                Err(e) => log::error!("commands: reload failed, keeping the current set: {}", e),
+            }
+        }
+
+        // btwctl and D-Bus requests are served between utterances, never mid-capture.
+        let next_request = || control_server.as_ref().and_then(|s| s.try_recv()).or_else(|| dbus.as_ref().and_then(|d| d.try_recv()));
+        while let Some(req) = (state == ListenState::Idle).then(next_request).flatten() {
+            let command = match req.command.clone() {
+                Ok(command) => command,
+                Err(e) => {
+                    req.respond(serde_json::json!({"ok": false, "error": e}));
+                    continue;
+                }
+            };
+            logging::begin_interaction();
+            log::info!("control: {:?} requested", command);
+            let response = if command == control_socket::ControlCommand::Reload {
+                // Same work as a SIGHUP.
+                match reload_all(&config_path, &commands_path, &detector, &mut intent_router, &mut exec, &mut worker, embedding_cache.as_deref()) {
+                    Ok(n) => serde_json::json!({"ok": true, "commands": n, "commands_version": commands_watcher::version()}),
+                    Err(e) => serde_json::json!({"ok": false, "error": e.to_string()}),
+                }
+            } else {
+                let mut say = |text: &str, exec: &mut executor::Executor, mgr: &mut manager::Manager| {
+                    let handled = handle_transcript(
+                        text,
+                        TranscriptContext {
+                            cfg: &cfg,
+                            exec,
+                            intent_router: &intent_router,
+                            llm_client: &llm_client,
+                            search_provider: &search_provider,
+                            worker: &mut worker,
+                            cancel: &interaction,
+                            follow_up: &mut follow_up,
+                            mgr,
+                        },
+                    );
+                    dbus_signals.transcript_ready(text, handled.0);
+                    handled
+                };
+                control_socket::Pipeline { exec: &mut exec, mgr: &mut mgr, say: &mut say }.handle(&command)
+            };
+            if response["ok"].as_bool() == Some(true) {
+                match command {
+                    control_socket::ControlCommand::Say(_) => {
+                        let history = mgr.transcript_history();
+                        let recent = history.iter().skip(history.len().saturating_sub(status::RECENT_TRANSCRIPTS)).cloned().collect();
+                        health_status.lock().unwrap_or_else(|p| p.into_inner()).recent_transcripts = recent;
+                    }
+                    control_socket::ControlCommand::Cancel { .. } => {
+                        follow_up.clear();
+                        pending_confirm_request_id = None;
+                    }
+                    control_socket::ControlCommand::Confirm { .. } => pending_confirm_request_id = None,
+                    control_socket::ControlCommand::Listen => {
+                        listen_requested = true;
+                        answer_for = None;
+                    }
+                    control_socket::ControlCommand::State | control_socket::ControlCommand::Reload | control_socket::ControlCommand::StopSpeaking => {}
+                }
+            }
+            req.respond(response);
+            logging::end_interaction();
         }
 
-        let frame = rx.recv().map_err(|_| {
-            BtwError::ParseError {
-                path: config_path.clone(),
-                kind: "audio",
-                message: "audio stream ended".into(),
+        // Time out now and then so a shutdown signal is noticed while the
+        // capture thread is reconnecting and no frames arrive.
+        let frame = rx.recv_timeout(Duration::from_millis(250));
+        notify_audio_events(&audio_events, &cfg);
+        let frame = match frame {
+            Ok(frame) => frame,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(BtwError::AudioDevice {
+                    message: audio_capture.failure().unwrap_or_else(|| "audio stream ended".into()),
+                });
             }
-        })?;
+        };
+
+        {
+            let mut h = health_status.lock().unwrap_or_else(|p| p.into_inner());
+            h.state = format!("{:?}", state);
+            h.pending_request_id = exec.pending_request_id().map(str::to_string);
+            h.asr_worker_alive = local_asr.is_some() || worker.is_alive();
+            h.background_rms = vad.background_rms();
+            h.commands_version = commands_watcher::version();
+        }
 
         // Ticks should be serviced regardless of audio state.
-        exec.handle_tick(Instant::now());
+        let now = Instant::now();
+        // The expiry notification comes from NotifyObserver.
+        if let Some(executor::ExecStatus::Canceled { .. }) = exec.handle_tick(now) {
+            pending_confirm_request_id = None;
+        }
+        if let Some(manager::ManagerOutcome::ConfirmationExpired { request_id }) = mgr.handle_tick(now) {
+            ui::notify_confirmation_expired(cfg.ui.osd, cfg.ui.osd_timeout_ms, &request_id);
+        }
+
+        // Abort: drop buffered audio unheard, clear any pending command and go Idle.
+        let control = deferred_control.take().or_else(cancel::take_control_request);
+        let abort_requested = control == Some(cancel::ControlRequest::Abort);
+        if abort_requested {
+            interaction.cancel();
+        }
+        // Between frames, so the swap can't overlap a `process` call.
+        let usr1 = signals.take_reload_wake();
+        if usr1 && cfg.wake_word.push_to_talk {
+            // Nothing to re-read without Porcupine: SIGUSR1 is the talk button.
+            log::info!("signals: SIGUSR1; push-to-talk");
+            listen_requested = true;
+            answer_for = None;
+        } else if usr1 || control == Some(cancel::ControlRequest::ReloadWake) {
+            reload_wake_sensitivity(&config_path, &detector);
+        }
+        if signals.take_reload_all() {
+            let _ = reload_all(&config_path, &commands_path, &detector, &mut intent_router, &mut exec, &mut worker, embedding_cache.as_deref());
+        }
+        while let Ok(action) = ui_actions.try_recv() {
+            match action {
+                notifications::UiAction::Confirm(id) | notifications::UiAction::Cancel(id) if exec.pending_request_id() != Some(id.as_str()) => {
+                    log::debug!("ui: ignoring answer for expired confirmation {}", id);
+                }
+                notifications::UiAction::Confirm(_) => {
+                    log::info!("exec: confirm via notification");
+                    let status = exec.confirm_pending();
+                    log::info!("exec: {:?}", status);
+                    pending_confirm_request_id = None;
+                }
+                notifications::UiAction::Cancel(_) => {
+                    log::info!("exec: cancel via notification");
+                    let status = exec.cancel_pending("user canceled");
+                    follow_up.clear();
+                    log::info!("exec: {:?}", status);
+                    pending_confirm_request_id = None;
+                }
+                notifications::UiAction::Open(url) => {
+                    std::thread::spawn(move || ui::open_url(&url));
+                }
+            }
+        }
+        if let Some(req @ (cancel::ControlRequest::Confirm | cancel::ControlRequest::Deny)) = control {
+            if !exec.has_pending() {
+                log::warn!("control: {:?} requested but no command is pending", req);
+            } else if req == cancel::ControlRequest::Confirm {
+                log::info!("exec: confirm via control spool");
+                let status = exec.confirm_pending();
+                log::info!("exec: {:?}", status);
+                pending_confirm_request_id = None;
+            } else {
+                log::info!("exec: cancel via control spool");
+                let status = exec.cancel_pending("user canceled");
+                follow_up.clear();
+                log::info!("exec: {:?}", status);
+                pending_confirm_request_id = None;
+            }
+        }
+        if interaction.is_canceled() && (state != ListenState::Idle || abort_requested) {
+            log::info!("control: abort in {:?}; discarding {} buffered samples", state, samples.len());
+            if let Some(id) = exec.clear_queue() {
+                log::info!("exec: dropped queued command '{}'", id);
+            }
+            if exec.awaiting_parameter().is_some() {
+                let status = exec.cancel_parameter_request("aborted");
+                log::info!("exec: {:?}", status);
+            }
+            if exec.has_pending() {
+                let status = exec.cancel_pending("aborted");
+                log::info!("exec: {:?}", status);
+            }
+            mgr.cancel();
+            follow_up.clear();
+            ui::dismiss_listening();
+            listen_requested = false;
+            answer_for = None;
+            state = ListenState::Idle;
+            logging::end_interaction();
+            samples.clear();
+            asr_stream = None;
+            stream_buf.clear();
+            endpoint.reset();
+            start_time = None;
+            saw_post_wake_speech = false;
+            log::debug!("state: -> Idle");
+            continue;
+        }
 
         // Periodic heartbeat so it's obvious we're alive while idle.
         if matches!(state, ListenState::Idle) && last_heartbeat.elapsed() >= Duration::from_secs(30) {
-            eprintln!("Listening for wake word...");
+            log::info!("{}", idle_message);
             last_heartbeat = Instant::now();
         }
 
         match state {
             ListenState::Idle => {
-                // Wake word detection.
-                if porcupine.process(&frame)? {
-                    eprintln!("wake: detected (porcupine)");
+                // Wake word detection. A spoken answer waits for the prompt to
+                // finish playing, so we don't record our own voice.
+                let synthetic = listen_requested && (answer_for.is_none() || !tts::is_speaking());
+                let hit = if synthetic {
+                    listen_requested = false;
+                    let keyword_index = wake_actions.iter().position(|a| *a == wake::WakeAction::Listen).unwrap_or(0);
+                    if answer_for.is_some() {
+                        log::info!("exec: listening for a spoken answer");
+                    } else {
+                        log::info!("control: listen requested");
+                    }
+                    Some(wake::WakeEvent { keyword_index, language: String::new() })
+                } else {
+                    detector.lock().unwrap_or_else(|p| p.into_inner()).detect(&frame)?
+                };
+                if let Some(ev) = hit {
+                    let kw = ev.keyword_index;
+                    logging::begin_interaction();
+                    log::info!(
+                        "wake: detected (porcupine keyword={} '{}'{})",
+                        kw,
+                        wake_labels.get(kw).map(String::as_str).unwrap_or("?"),
+                        if ev.language.is_empty() { String::new() } else { format!(", language={}", ev.language) }
+                    );
+                    if wake_actions.get(kw) == Some(&wake::WakeAction::Cancel) {
+                        log::info!("wake: cancel keyword; aborting");
+                        tts::stop_speaking();
+                        deferred_control = Some(cancel::ControlRequest::Abort);
+                        continue;
+                    }
+                    wake_keyword = kw;
+                    dbus_signals.wake_detected(wake_labels.get(kw).map(String::as_str).unwrap_or(""));
+                    if !synthetic {
+                        // A real wake word starts a fresh interaction.
+                        listen_requested = false;
+                        answer_for = None;
+                    }
+                    interaction = cancel::CancelToken::new();
+                    // Don't record over our own voice: cut any answer still playing.
+                    if tts::is_speaking() {
+                        log::info!("tts: interrupted by wake word");
+                    }
+                    tts::stop_speaking();
+                    ui::set_delivery(ui::delivery_for(dnd.is_active(), cfg.ui.ignore_dnd));
                     // Single source of truth: notification only on Idle -> Listening.
-                    ui::notify_listening(cfg.ui.osd, cfg.ui.osd_timeout_ms);
+                    ui::notify_listening(cfg.ui.osd, cfg.ui.osd_timeout_ms, &interaction);
 
                     // Do NOT reuse this frame as user speech.
                     state = ListenState::Listening;
-                    // Legacy manager wake handling removed from runtime path.
+                    vad.reset();
+                    mgr.on_wake();
                     samples.clear();
-                    silence_ms = 0.0;
+                    endpoint.reset();
                     start_time = None;
                     saw_post_wake_speech = false;
-                    eprintln!("state: Idle -> Listening (armed, waiting for speech)");
+                    log::debug!("state: Idle -> Listening (armed, waiting for speech)");
                 }
                 continue;
             }
             ListenState::Listening => {
+                // Stop waiting for a spoken answer once its confirmation is gone.
+                if let Some((id, deadline)) = &answer_for {
+                    if Instant::now() >= *deadline || exec.answer_request_id() != Some(id.as_str()) {
+                        log::info!("exec: no spoken answer for {}; back to idle", id);
+                        answer_for = None;
+                        ui::dismiss_listening();
+                        state = ListenState::Idle;
+                        logging::end_interaction();
+                        match exec.pending_request_id() {
+                            Some(req_id) => mgr.mirror_confirmation(req_id, exec.pending_description().unwrap_or("a command")),
+                            None => mgr.reset_to_idle(),
+                        }
+                        continue;
+                    }
+                }
                 // We're "armed" after wake word. We start recording only once we see actual speech.
                 // This prevents the wake-word tail from being fed to ASR/UI/routing.
 
                 // Allow re-wake while armed (useful if we got stuck waiting for speech).
-                if porcupine.process(&frame)? {
-                    eprintln!("wake: detected again while Listening (re-arming)");
-                    ui::notify_listening(cfg.ui.osd, cfg.ui.osd_timeout_ms);
+                let hit = detector.lock().unwrap_or_else(|p| p.into_inner()).process(&frame)?;
+                if let Some(kw) = hit.filter(|kw| wake_actions.get(*kw) == Some(&wake::WakeAction::Cancel)) {
+                    log::info!("wake: cancel keyword {} while Listening; aborting", kw);
+                    deferred_control = Some(cancel::ControlRequest::Abort);
+                    continue;
+                }
+                if let Some(kw) = hit {
+                    log::info!("wake: detected again while Listening (re-arming, keyword={})", kw);
+                    wake_keyword = kw;
+                    ui::notify_listening(cfg.ui.osd, cfg.ui.osd_timeout_ms, &interaction);
                     samples.clear();
-                    silence_ms = 0.0;
+                    endpoint.reset();
                     start_time = None;
                     saw_post_wake_speech = false;
                     last_listening_debug = Instant::now();
@@ -362,12 +1228,12 @@ fn run() -> Result<()> {
 
                 // Debug every ~2s while waiting for speech so we can confirm if VAD is firing.
                 if last_listening_debug.elapsed() >= Duration::from_secs(2) {
-                    eprintln!(
+                    log::debug!(
                         "listening: awaiting speech (vad_speech={}, rms_speech={}, rms={:.4}, vad_mode={})",
                         vad_speech,
                         rms_speech,
                         rms,
-                        cfg.speech.vad_mode
+                        vad.mode()
                     );
                     last_listening_debug = Instant::now();
                 }
@@ -376,52 +1242,76 @@ fn run() -> Result<()> {
                     state = ListenState::Recording;
                     // Legacy manager deciding state removed from runtime path.
                     samples.clear();
-                    silence_ms = 0.0;
+                    endpoint.reset();
                     start_time = Some(Instant::now());
                     saw_post_wake_speech = true;
+                    endpoint.push(&frame, Some(vad_speech));
                     samples.extend_from_slice(&frame);
-                    eprintln!("speech: detected (vad) -> start recording");
-                    eprintln!("state: Listening -> Recording");
+                    stream_buf.clear();
+                    asr_stream = None;
+                    if local_asr.is_none() && worker.supports("asr_stream") {
+                        match worker.begin_stream(sample_rate) {
+                            Ok(srx) => {
+                                stream_buf.extend_from_slice(&frame);
+                                asr_stream = Some(srx);
+                            }
+                            Err(e) => log::warn!("asr: streaming unavailable, using batch: {}", e),
+                        }
+                    }
+                    log::debug!("speech: detected (vad) -> start recording");
+                    log::debug!("state: Listening -> Recording");
                 }
                 continue;
             }
             ListenState::Recording => {
                 // Keep buffering audio during recording.
                 samples.extend_from_slice(&frame);
-            }
-        }
-
-        // RMS (existing logic)
-        let sum_sq: f64 = frame.iter().map(|&s| {
-            let v = s as f64;
-            v * v
-        }).sum();
-        let rms = (sum_sq / frame_length as f64).sqrt() / i16::MAX as f64;
-
-        let speech = vad.is_speech(&frame);
 
-        if !speech && rms < cfg.speech.silence_threshold as f64 {
-            silence_ms += frame_ms;
-        } else {
-            silence_ms = 0.0;
+                if let Some(srx) = &asr_stream {
+                    let mut drop_stream = false;
+                    stream_buf.extend_from_slice(&frame);
+                    if stream_buf.len() >= stream_chunk {
+                        if let Err(e) = worker.push_chunk(&stream_buf) {
+                            log::warn!("asr: stream push failed, falling back to batch: {}", e);
+                            drop_stream = true;
+                        }
+                        stream_buf.clear();
+                    }
+                    while let Ok(ev) = srx.try_recv() {
+                        match ev {
+                            ml::AsrEvent::Partial(t) => {
+                                ui::notify_partial(cfg.ui.osd, cfg.ui.osd_timeout_ms, &t);
+                            }
+                            // A final before asr_end means the stream broke; batch takes over.
+                            ml::AsrEvent::Final(_) | ml::AsrEvent::Failed(_) => {
+                                log::warn!("asr: stream ended early, falling back to batch");
+                                drop_stream = true;
+                            }
+                        }
+                    }
+                    if drop_stream {
+                        asr_stream = None;
+                        stream_buf.clear();
+                    }
+                }
+            }
         }
 
-        let elapsed = start_time.map(|t| t.elapsed().as_secs_f64()).unwrap_or(0.0);
-
-        if silence_ms >= cfg.speech.silence_duration_ms as f64 ||
-           elapsed >= cfg.speech.max_utterance_seconds as f64 {
-
-            eprintln!(
-                "recording: stop (samples={}, elapsed_sec={:.2}, silence_ms={:.0})",
+        let end = endpoint.push(&frame, vad.classify(&frame));
+        if end != vad::Endpoint::Continue {
+            let elapsed = start_time.map(|t| t.elapsed().as_secs_f64()).unwrap_or(0.0);
+            log::debug!(
+                "recording: stop ({:?}, samples={}, elapsed_sec={:.2}, silence_ms={:.0})",
+                end,
                 samples.len(),
                 elapsed,
-                silence_ms
+                endpoint.silence_ms()
             );
 
             // Optionally dump captured audio to disk for debugging.
             if let Some(dir) = &debug_audio_dir {
                 if let Err(e) = std::fs::create_dir_all(dir) {
-                    eprintln!("debug: failed to create BTWD_DEBUG_AUDIO_DIR: {}", e);
+                    log::warn!("debug: failed to create BTWD_DEBUG_AUDIO_DIR: {}", e);
                 } else {
                     let ts = std::time::SystemTime::now()
                         .duration_since(std::time::UNIX_EPOCH)
@@ -433,50 +1323,544 @@ fn run() -> Result<()> {
                         .flat_map(|s| s.to_le_bytes())
                         .collect();
                     match std::fs::write(&path, bytes) {
-                        Ok(_) => eprintln!("debug: audio saved: {}", path.display()),
-                        Err(e) => eprintln!("debug: failed to save audio: {}", e),
+                        Ok(_) => log::info!("debug: audio saved: {}", path.display()),
+                        Err(e) => log::warn!("debug: failed to save audio: {}", e),
                     }
                 }
             }
 
             // Only attempt ASR if we actually transitioned to Recording because we saw speech.
             // (This should always be true in Recording state, but keep the invariant explicit.)
-            if saw_post_wake_speech && !samples.is_empty() {
-                eprintln!("asr: sending audio to worker");
-                match worker.transcribe(samples.clone(), sample_rate) {
-                    Ok(resp) => {
+            if end == vad::Endpoint::TooShort {
+                log::info!("asr: skipped (less than {} ms of speech)", cfg.speech.min_speech_ms);
+            } else if saw_post_wake_speech && !samples.is_empty() {
+                mgr.enter_deciding();
+                let transcribed = tracing::info_span!("asr").in_scope(|| {
+                    transcribe_unless_aborted(
+                        &interaction,
+                        || {
+                            let streamed = asr_stream.take().and_then(|srx| {
+                                let finished = if stream_buf.is_empty() { Ok(()) } else { worker.push_chunk(&stream_buf) }
+                                    .and_then(|_| worker.end_stream());
+                                if let Err(e) = finished {
+                                    log::warn!("asr: stream finish failed, falling back to batch: {}", e);
+                                    return None;
+                                }
+                                let (osd, osd_timeout_ms) = (cfg.ui.osd, cfg.ui.osd_timeout_ms);
+                                match worker.wait_stream_final(&srx, |p| ui::notify_partial(osd, osd_timeout_ms, p)) {
+                                    Ok(resp) => Some(resp),
+                                    Err(e) => {
+                                        log::warn!("asr: stream failed, falling back to batch: {}", e);
+                                        None
+                                    }
+                                }
+                            });
+                            match streamed {
+                                Some(resp) => Ok(resp),
+                                // No live stream, but the worker can still send partials
+                                // while it decodes the whole utterance.
+                                None if local_asr.is_none() && worker.supports("asr_stream_batch") => {
+                                    let (osd, osd_timeout_ms) = (cfg.ui.osd, cfg.ui.osd_timeout_ms);
+                                    worker.transcribe_with_partials(samples.clone(), sample_rate, |p| ui::notify_partial(osd, osd_timeout_ms, p))
+                                }
+                                None => {
+                                    let engine = asr::engine(&mut local_asr, &mut worker);
+                                    log::debug!("asr: sending audio to {} engine", engine.name());
+                                    engine.transcribe(samples.clone(), sample_rate)
+                                }
+                            }
+                        },
+                        // ASR can take seconds; an abort that arrived meanwhile wins.
+                        || match cancel::take_control_request() {
+                            Some(cancel::ControlRequest::Abort) => interaction.cancel(),
+                            other => deferred_control = other,
+                        },
+                    )
+                });
+                stream_buf.clear();
+                match transcribed {
+                    None => {
+                        mgr.reset_to_idle();
+                        ui::dismiss_listening();
+                    }
+                    // The answer was meant for a confirmation that has since expired
+                    // or been replaced; it must not answer the new one.
+                    Some(Ok(_)) if answer_for.as_ref().is_some_and(|(id, _)| exec.answer_request_id() != Some(id.as_str())) => {
+                        log::info!("exec: confirmation changed while answering; discarding transcript");
+                        ui::dismiss_listening();
+                    }
+                    Some(Ok(resp)) => {
+                        asr_unavailable_notified = false;
                         if let Some(err) = resp.error.as_deref() {
                             if !err.is_empty() {
-                                eprintln!("asr: worker returned error: {}", err);
+                                log::error!("asr: worker returned error: {}", err);
                             }
                         }
                         let raw_text = resp.text;
                         let text = raw_text.trim();
-                        eprintln!("asr: text='{}'", raw_text);
+                        log::info!("asr: text='{}' (wake keyword '{}')", raw_text, wake_labels.get(wake_keyword).map(String::as_str).unwrap_or("?"));
 
                         // Never show a transcript for the wake word alone; this is post-wake speech only.
                         ui::notify_text(cfg.ui.osd, cfg.ui.osd_timeout_ms, "You", text);
 
                         // Centralized strict decision logic: exactly one path.
-                        handle_transcript(text, &cfg, &mut exec, &intent_router, &llm_client);
+                        let (kind, routed) = handle_transcript(
+                            text,
+                            TranscriptContext {
+                                cfg: &cfg,
+                                exec: &mut exec,
+                                intent_router: &intent_router,
+                                llm_client: &llm_client,
+                                search_provider: &search_provider,
+                                worker: &mut worker,
+                                cancel: &interaction,
+                                follow_up: &mut follow_up,
+                                mgr: &mut mgr,
+                            },
+                        );
+                        dbus_signals.transcript_ready(text, kind);
+                        mgr.record_transcript(manager::TranscriptEntry::new(&raw_text, kind, routed.as_ref(), resp.confidence));
+                        let history = mgr.transcript_history();
+                        let recent = history.iter().skip(history.len().saturating_sub(status::RECENT_TRANSCRIPTS)).cloned().collect();
+                        health_status.lock().unwrap_or_else(|p| p.into_inner()).recent_transcripts = recent;
+                    }
+                    Some(Err(e)) => {
+                        log::error!("ASR error: {}", e);
+                        if worker.is_degraded() && !asr_unavailable_notified {
+                            ui::notify_text(cfg.ui.osd, cfg.ui.osd_timeout_ms, "Btw", "ASR unavailable");
+                            asr_unavailable_notified = true;
+                        }
                     }
-                    Err(e) => eprintln!("ASR error: {}", e),
                 }
             } else {
-                eprintln!("asr: skipped (no post-wake speech captured)");
+                log::info!("asr: skipped (no post-wake speech captured)");
             }
 
             state = ListenState::Idle;
+            match exec.pending_request_id() {
+                Some(req_id) => mgr.mirror_confirmation(req_id, exec.pending_description().unwrap_or("a command")),
+                None if exec.awaiting_parameter().is_some() => mgr.mirror_parameter_request(),
+                None => mgr.end_turn(),
+            }
+            // A prompt (new or repeated) is answered by voice right away, and
+            // so is a question for a missing parameter.
+            answer_for = None;
+            if cfg.execution.voice_confirmation {
+                if let (Some(req_id), Some(deadline)) = (exec.pending_request_id(), exec.pending_deadline()) {
+                    answer_for = Some((req_id.to_string(), deadline));
+                    listen_requested = true;
+                }
+            }
+            if answer_for.is_none() {
+                if let Some((req_id, deadline)) = exec.awaiting_parameter() {
+                    answer_for = Some((req_id.to_string(), deadline));
+                    listen_requested = true;
+                }
+            }
+            // "Volume up or brightness up?" is answered the same way.
+            if mgr.is_clarifying() {
+                listen_requested = true;
+            }
             samples.clear();
-            silence_ms = 0.0;
+            asr_stream = None;
+            stream_buf.clear();
+            endpoint.reset();
             start_time = None;
             saw_post_wake_speech = false;
-            eprintln!("state: -> Idle");
+            log::debug!("state: -> Idle");
+            logging::end_interaction();
         }
     }
 }
 
-fn expected_missing(xdg: &BaseDirectories, filename: &str, kind: &'static str) -> BtwError {
-    let expected = xdg.get_config_home().join(filename);
-    BtwError::MissingFile { path: expected, kind }
+/// Re-read commands.json into the router and executor. Everything is parsed
+/// and checked first; on any error both keep their current commands.
+fn reload_commands(
+    path: &PathBuf,
+    cfg: &config::Config,
+    router: &mut intent::IntentRouter,
+    exec: &mut executor::Executor,
+    worker: &mut ml::MLWorker,
+    embedding_cache: Option<&Path>,
+) -> Result<usize> {
+    let commands = intent::load_commands(path)?;
+    let by_id = executor::load_commands(path)?;
+    if cfg.intent.self_check != "off" {
+        let index = intent::PreparedIndex::new(&commands);
+        let conflicts = intent::self_check(&commands, &index);
+        if report_self_check(&conflicts) && cfg.intent.self_check == "error" {
+            return Err(BtwError::ParseError {
+                path: path.clone(),
+                kind: "intent",
+                message: format!("{} example(s) route to a different command (intent.self_check = \"error\")", conflicts.len()),
+                cause: None,
+            });
+        }
+    }
+    check_consistency(cfg, path, commands.iter().map(|c| c.id.as_str()), by_id.keys().map(String::as_str))?;
+    let embeddings = if cfg.intent.embeddings && worker.supports("embed") {
+        match embedding::build_index(worker, &commands, embedding_cache) {
+            Ok(index) => Some(index),
+            Err(e) => {
+                log::warn!("intent: embedding tier disabled after reload: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let n = commands.len();
+    router.replace_commands(commands);
+    if let Some(index) = embeddings {
+        router.set_embeddings(index);
+    }
+    exec.replace_commands(by_id);
+    Ok(n)
+}
+
+/// SIGHUP / `btwctl reload`: re-read config.toml and commands.json, then swap
+/// in the commands, the intent thresholds and the wake word sensitivities.
+/// Nothing changes unless both files load; other settings need a restart.
+fn reload_all(
+    config_path: &Path,
+    commands_path: &PathBuf,
+    detector: &Mutex<dyn wake::WakeWordDetector>,
+    router: &mut intent::IntentRouter,
+    exec: &mut executor::Executor,
+    worker: &mut ml::MLWorker,
+    embedding_cache: Option<&Path>,
+) -> Result<usize> {
+    let result = load_config(config_path).and_then(|(fresh, _)| {
+        let n = reload_commands(commands_path, &fresh, router, exec, worker, embedding_cache)?;
+        router.set_config(intent_config(&fresh.intent));
+        router.set_threshold_overrides(fresh.intent.threshold_overrides());
+        apply_wake_sensitivity(&fresh, detector);
+        Ok(n)
+    });
+    match &result {
+        Ok(n) => log::info!(
+            "config: reloaded {} and {} command(s) from {} (version {})",
+            config_path.display(),
+            n,
+            commands_path.display(),
+            commands_watcher::bump_version()
+        ),
+        Err(e) => log::error!("config: reload failed, keeping the current settings: {}", e),
+    }
+    result
+}
+
+fn intent_config(cfg: &config::IntentCfg) -> intent::IntentConfig {
+    intent::IntentConfig {
+        deterministic_threshold: cfg.deterministic_threshold,
+        llm_fallback_threshold: cfg.llm_fallback_threshold,
+        embedding_threshold: cfg.embedding_threshold,
+        embedding_weight: cfg.embedding_weight,
+        llm_rate_limit_per_min: cfg.llm_rate_limit_per_min,
+    }
+}
+
+/// Tell the user about microphone loss and recovery.
+fn notify_audio_events(events: &Receiver<audio::AudioEvent>, cfg: &config::Config) {
+    while let Ok(ev) = events.try_recv() {
+        let msg = match ev {
+            audio::AudioEvent::StreamOk => continue,
+            audio::AudioEvent::StreamError(e) => format!("Microphone lost: {}", e),
+            audio::AudioEvent::DeviceReconnected(name) => format!("Microphone reconnected: {}", name),
+        };
+        ui::notify_text(cfg.ui.osd, cfg.ui.osd_timeout_ms, "btwd", &msg);
+    }
+}
+
+/// Current local hour (0..=23) in the system time zone.
+fn local_hour() -> u8 {
+    // SAFETY: `time` accepts a null pointer; `localtime_r` only writes to `tm`.
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&now, &mut tm).is_null() {
+            return 0;
+        }
+        tm.tm_hour as u8
+    }
+}
+
+/// Re-read the wake word sensitivities from `config_path` and rebuild the
+/// detector with them. Keyword files and count are fixed until restart.
+fn reload_wake_sensitivity(config_path: &Path, detector: &Mutex<dyn wake::WakeWordDetector>) {
+    match load_config(config_path) {
+        Ok((fresh, _)) => apply_wake_sensitivity(&fresh, detector),
+        Err(e) => log::warn!("wake: reload skipped; {}", e),
+    }
+}
+
+fn apply_wake_sensitivity(fresh: &config::Config, detector: &Mutex<dyn wake::WakeWordDetector>) {
+    let sensitivities: Vec<f32> = fresh
+        .wake_word
+        .keyword_list()
+        .iter()
+        .map(|k| k.sensitivity.unwrap_or(fresh.wake_word.sensitivity))
+        .collect();
+    match detector.lock().unwrap_or_else(|p| p.into_inner()).reinit(&sensitivities) {
+        Ok(()) => log::info!("wake: sensitivity reloaded: {:?}", sensitivities),
+        Err(e) => log::warn!("wake: reload failed, keeping previous settings: {}", e),
+    }
+}
+
+/// The `btwd paths` report: each file's resolved location, or every place tried.
+fn print_paths(paths: &paths::Paths, config: Option<PathBuf>, commands: Option<PathBuf>) {
+    let files = [
+        ("config.toml", paths.config_file("config.toml", config)),
+        ("commands.json", paths.config_file("commands.json", commands)),
+        (".env", paths.config_file(".env", None)),
+        ("ml worker", paths.data_file(paths::ML_SCRIPT, config_env::var::<String>("BTWD_ML_PATH").map(PathBuf::from))),
+        ("confirm helper", paths.data_file(paths::CONFIRM_HELPER, None)),
+    ];
+    for (label, found) in files {
+        match found {
+            Ok(p) => println!("{:<15} {}", label, p.display()),
+            Err(BtwError::XdgError { message }) => println!("{:<15} missing: {}", label, message),
+            Err(e) => println!("{:<15} {}", label, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Aborts the interaction from inside the LLM call, the way a Cancel
+    /// pressed while the daemon is still deciding would.
+    struct AbortingLlm {
+        interaction: cancel::CancelToken,
+    }
+
+    impl llm::LlmClient for AbortingLlm {
+        fn classify_intent(&self, _text: &str, _commands: &[intent::IntentCommand]) -> std::result::Result<llm::LlmIntent, String> {
+            self.interaction.cancel();
+            Ok(llm::LlmIntent { command_id: Some("volume_mute".into()), confidence: 1.0, parameters: serde_json::json!({}) })
+        }
+        fn answer_short(&self, _prompt: &str) -> std::result::Result<String, String> {
+            self.interaction.cancel();
+            Ok("It is noon in Tokyo.".into())
+        }
+        fn tts(&self, _text: &str) -> std::result::Result<Vec<u8>, String> {
+            Err("not implemented in tests".into())
+        }
+    }
+
+    /// Every command the executor ran (dry run), by id.
+    struct Ran(Arc<Mutex<Vec<String>>>);
+
+    impl executor::ExecObserver for Ran {
+        fn on_executed(&self, id: &str, _stdout: &str) {
+            self.0.lock().unwrap().push(id.to_string());
+        }
+        fn on_pending(&self, _id: &str, _preview: &str, _deadline: Instant) {}
+        fn on_canceled(&self, _id: &str, _reason: &str) {}
+        fn on_rejected(&self, _reason: &str) {}
+    }
+
+    /// What `handle_transcript` runs against: dry-run commands from
+    /// `commands`, recording what ran.
+    struct Harness {
+        cfg: config::Config,
+        router: intent::IntentRouter,
+        exec: executor::Executor,
+        ran: Arc<Mutex<Vec<String>>>,
+        worker: ml::MLWorker,
+        follow_up: context::FollowUpContext,
+        mgr: manager::Manager,
+        llm: Arc<dyn llm::LlmClient>,
+        provider: Arc<dyn search::SearchProvider>,
+    }
+
+    impl Harness {
+        fn new(commands: &PathBuf, threshold: f32, interaction: &cancel::CancelToken) -> Self {
+            let cfg = config::Config::from_toml_str(&format!(
+                r#"
+[wake_word]
+ppn_path = "/tmp/btw.ppn"
+model_path = "/tmp/porcupine_params.pv"
+
+[intent]
+deterministic_threshold = {}
+
+[search]
+enabled = false
+
+[speech_output]
+enabled = false
+"#,
+                threshold
+            ))
+            .unwrap();
+            let llm: Arc<dyn llm::LlmClient> = Arc::new(AbortingLlm { interaction: interaction.clone() });
+            let router = intent::IntentRouter::from_file(commands, intent_config(&cfg.intent), llm.clone()).unwrap();
+            let exec_cfg = executor::ExecutionCfg {
+                confirmation_timeout_seconds: 10,
+                dry_run: true,
+                voice_confirmation: false,
+                pending_policy: executor::PendingPolicy::Reject,
+                default_env_allowlist: Vec::new(),
+                confirmation: executor::ConfirmationPolicy::DangerousOnly,
+            };
+            let mut exec = executor::Executor::new_from_path(commands, exec_cfg).unwrap();
+            let ran = Arc::new(Mutex::new(Vec::new()));
+            exec.add_observer(Box::new(Ran(ran.clone())));
+            let decision = decision::DecisionManager::new(decision::DecisionConfig::with_threshold(threshold)).unwrap();
+            Harness {
+                provider: search::session_provider(&cfg.search),
+                cfg,
+                router,
+                exec,
+                ran,
+                worker: ml::MLWorker::unstarted(PathBuf::new(), config::AsrCfg::default()),
+                follow_up: context::FollowUpContext::new(Duration::from_secs(30)),
+                mgr: manager::Manager::new(decision),
+                llm,
+            }
+        }
+
+        /// One utterance in its own turn, as the main loop handles it.
+        fn say(&mut self, text: &str, interaction: &cancel::CancelToken) -> &'static str {
+            self.mgr.on_wake();
+            self.mgr.enter_deciding();
+            let ctx = TranscriptContext {
+                cfg: &self.cfg,
+                exec: &mut self.exec,
+                intent_router: &self.router,
+                llm_client: &self.llm,
+                search_provider: &self.provider,
+                worker: &mut self.worker,
+                cancel: interaction,
+                follow_up: &mut self.follow_up,
+                mgr: &mut self.mgr,
+            };
+            let (kind, _) = handle_transcript(text, ctx);
+            self.mgr.end_turn();
+            kind
+        }
+
+        fn ran(&self) -> Vec<String> {
+            self.ran.lock().unwrap().clone()
+        }
+    }
+
+    /// Runs one transcript through `handle_transcript` with the example commands.
+    fn decide(text: &str, interaction: &cancel::CancelToken) -> (&'static str, Vec<String>, manager::Manager) {
+        let commands = PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/example.commands.json"));
+        let mut h = Harness::new(&commands, 0.75, interaction);
+        let kind = h.say(text, interaction);
+        (kind, h.ran(), h.mgr)
+    }
+
+    #[test]
+    fn abort_while_recording_never_sends_the_audio() {
+        let interaction = cancel::CancelToken::new();
+        let samples = vec![0i16; 16_000];
+        interaction.cancel();
+        let mut sent = Vec::new();
+        let result = transcribe_unless_aborted(
+            &interaction,
+            || {
+                sent.push(samples.len());
+                Ok(ml::AsrResponse::from_text("lock the screen".into()))
+            },
+            || {},
+        );
+        assert!(result.is_none());
+        assert!(sent.is_empty());
+    }
+
+    #[test]
+    fn abort_during_asr_discards_the_transcript() {
+        // Canceled directly (the notification's Cancel) or picked up from
+        // the control spool once ASR returns.
+        for via_poll in [false, true] {
+            let interaction = cancel::CancelToken::new();
+            let mut sent = 0;
+            let result = transcribe_unless_aborted(
+                &interaction,
+                || {
+                    sent += 1;
+                    if !via_poll {
+                        interaction.cancel();
+                    }
+                    Ok(ml::AsrResponse::from_text("lock the screen".into()))
+                },
+                || {
+                    if via_poll {
+                        interaction.cancel();
+                    }
+                },
+            );
+            assert!(result.is_none(), "via_poll={}", via_poll);
+            assert_eq!(sent, 1);
+        }
+
+        let interaction = cancel::CancelToken::new();
+        let result = transcribe_unless_aborted(&interaction, || Ok(ml::AsrResponse::from_text("volume up".into())), || {});
+        assert_eq!(result.unwrap().unwrap().text, "volume up");
+    }
+
+    #[test]
+    fn abort_while_deciding_handles_nothing() {
+        // Aborted before the transcript is routed.
+        let interaction = cancel::CancelToken::new();
+        interaction.cancel();
+        let (kind, ran, _) = decide("mute volume", &interaction);
+        assert_eq!(kind, "aborted");
+        assert!(ran.is_empty());
+
+        // Aborted while the LLM is answering: nothing is shown, spoken or remembered.
+        let interaction = cancel::CancelToken::new();
+        let (kind, ran, mgr) = decide("what time is it in tokyo", &interaction);
+        assert!(interaction.is_canceled());
+        assert_eq!(kind, "aborted");
+        assert!(ran.is_empty());
+        assert_eq!(mgr.question_prompt("and in paris"), "and in paris");
+
+        // The same transcript runs when nothing aborts it.
+        let (kind, ran, _) = decide("mute volume", &cancel::CancelToken::new());
+        assert_eq!(kind, "command");
+        assert_eq!(ran, ["volume_mute"]);
+    }
+
+    #[test]
+    fn near_tie_asks_which_command_then_runs_the_pick() {
+        // "turn it up" scores about 0.41 against volume_up and 0.37 against
+        // brightness_up: both pass the threshold, within the 0.08 margin.
+        let commands = std::env::temp_dir().join(format!("btwd-clarify-commands-{}.json", std::process::id()));
+        fs::write(
+            &commands,
+            r#"[
+  {"id": "volume_up", "examples": ["turn it up loud"], "shell_command_template": "true"},
+  {"id": "brightness_up", "examples": ["turn up"], "shell_command_template": "true"}
+]"#,
+        )
+        .unwrap();
+        let interaction = cancel::CancelToken::new();
+        let mut h = Harness::new(&commands, 0.35, &interaction);
+        let _ = fs::remove_file(&commands);
+
+        assert_eq!(h.say("turn it up", &interaction), "clarification");
+        assert!(h.ran().is_empty());
+        assert_eq!(h.mgr.state(), manager::State::Clarifying);
+        assert_eq!(h.mgr.clarification_options(), Some(vec!["volume_up".to_string(), "brightness_up".to_string()]));
+
+        // Neither option: the question stays open.
+        assert_eq!(h.say("hmm", &interaction), "clarification_answer");
+        assert!(h.ran().is_empty());
+        assert_eq!(h.say("brightness", &interaction), "clarification_answer");
+        assert_eq!(h.ran(), ["brightness_up"]);
+        assert_eq!(h.mgr.state(), manager::State::Idle);
+
+        // Canceling the question runs nothing.
+        assert_eq!(h.say("turn it up", &interaction), "clarification");
+        assert_eq!(h.say("neither", &interaction), "canceled");
+        assert_eq!(h.ran(), ["brightness_up"]);
+        assert!(!h.mgr.is_clarifying());
+    }
 }
@@ -1,18 +1,28 @@
 use crate::decision::{Decision, DecisionManager};
-use crate::executor::{ExecStatus, Executor};
+use crate::executor::{ExecStatus, ExecutionCfg, Executor};
 use crate::intent::IntentResult;
-use std::time::SystemTime;
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum State {
     Idle,
     Listening,
     Deciding,
+    /// Waiting for the user to pick one of several close matches, or to
+    /// give a value the command is missing ("to what percent?").
+    Clarifying,
     Confirming,
     Responding,
+    /// Between turns of a conversation: the next question (after a wake,
+    /// as always) is asked with the recent answers, until
+    /// `decision.context_ttl_secs` passes.
+    FollowUp,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(not(test), allow(dead_code))]
 pub struct ConfirmationToken {
     request_id: String,
 }
@@ -20,75 +30,410 @@ pub struct ConfirmationToken {
 #[derive(Debug, Clone)]
 pub struct PendingCommand {
     pub request_id: String,
+    #[cfg_attr(not(test), allow(dead_code))]
     pub intent: IntentResult,
+    #[cfg_attr(not(test), allow(dead_code))]
     pub preview: String,
-    pub dangerous: bool,
+    /// Unconfirmed past this point, the command is dropped by `handle_tick`.
+    pub deadline: Instant,
+}
+
+/// Observable Manager transitions, e.g. for a tray indicator.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StateEvent {
+    StateChanged { from: State, to: State },
+    ConfirmationRequested { request_id: String, preview: String },
+    /// The pending command was confirmed, or dropped (cancel, reset, timeout).
+    ConfirmationResolved { accepted: bool },
+    TranscriptIgnored,
+    /// A transcript was added to [`Manager::transcript_history`].
+    TranscriptRecorded(TranscriptEntry),
+}
+
+/// Transcripts kept by [`Manager::transcript_history`].
+pub const TRANSCRIPT_HISTORY_LEN: usize = 20;
+
+/// What ASR heard and what was done with it, for diagnosing misroutes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranscriptEntry {
+    pub ts: SystemTime,
+    pub raw_text: String,
+    /// e.g. "command", "confirmation", "question", "web_query", "ignored".
+    pub decision_type: String,
+    /// Best deterministic candidate, even when it was below the threshold.
+    pub command_id: Option<String>,
+    pub deterministic_score: Option<f32>,
+    pub asr_confidence: Option<f32>,
+}
+
+impl TranscriptEntry {
+    pub fn new(raw_text: &str, decision_type: &str, intent: Option<&IntentResult>, asr_confidence: Option<f32>) -> Self {
+        Self {
+            ts: SystemTime::now(),
+            raw_text: raw_text.to_string(),
+            decision_type: decision_type.to_string(),
+            command_id: intent.and_then(|i| i.command_id.clone()),
+            deterministic_score: intent.and_then(|i| i.deterministic_score),
+            asr_confidence,
+        }
+    }
+
+    /// `ts` as Unix seconds, for `/health` and the status file.
+    pub fn to_json(&self) -> serde_json::Value {
+        let ts = self.ts.duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        serde_json::json!({
+            "ts": ts,
+            "raw_text": self.raw_text,
+            "decision_type": self.decision_type,
+            "command_id": self.command_id,
+            "deterministic_score": self.deterministic_score,
+            "asr_confidence": self.asr_confidence,
+        })
+    }
+}
+
+/// Undelivered events kept per receiver before the oldest are dropped.
+const EVENT_CAPACITY: usize = 64;
+
+#[derive(Default)]
+struct EventQueue {
+    events: Mutex<VecDeque<StateEvent>>,
+    ready: Condvar,
+}
+
+impl EventQueue {
+    fn push(&self, ev: StateEvent) {
+        let mut q = self.events.lock().unwrap_or_else(|p| p.into_inner());
+        if q.len() == EVENT_CAPACITY {
+            q.pop_front();
+        }
+        q.push_back(ev);
+        self.ready.notify_one();
+    }
+}
+
+/// Receiving end of [`Manager::events`]. A slow reader loses the oldest
+/// events; it can never hold up a state change.
+pub struct EventReceiver {
+    queue: Arc<EventQueue>,
+}
+
+impl EventReceiver {
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn try_recv(&self) -> Option<StateEvent> {
+        self.queue.events.lock().unwrap_or_else(|p| p.into_inner()).pop_front()
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<StateEvent> {
+        let q = self.queue.events.lock().unwrap_or_else(|p| p.into_inner());
+        let (mut q, _) = self
+            .queue
+            .ready
+            .wait_timeout_while(q, timeout, |q| q.is_empty())
+            .unwrap_or_else(|p| p.into_inner());
+        q.pop_front()
+    }
+
+    /// Everything queued so far, oldest first.
+    pub fn drain(&self) -> Vec<StateEvent> {
+        self.queue.events.lock().unwrap_or_else(|p| p.into_inner()).drain(..).collect()
+    }
+}
+
+/// Candidates offered by [`Decision::Clarify`], in the order they were offered.
+struct Clarification {
+    options: Vec<(IntentResult, String)>,
+    deadline: Instant,
 }
 
 pub struct Manager {
     pub state: State,
     pending: Option<PendingCommand>,
+    clarification: Option<Clarification>,
+    /// Applies to both confirmation and clarification questions.
+    timeout: Duration,
     decision: DecisionManager,
+    /// One queue per [`events`](Self::events) receiver still alive.
+    observers: Vec<Arc<EventQueue>>,
+    /// The last [`TRANSCRIPT_HISTORY_LEN`] transcripts, oldest first.
+    transcript_history: VecDeque<TranscriptEntry>,
 }
 
 impl Manager {
     pub fn new(decision: DecisionManager) -> Self {
-        Self { state: State::Idle, pending: None, decision }
+        Self {
+            state: State::Idle,
+            pending: None,
+            clarification: None,
+            timeout: Duration::from_secs(10),
+            decision,
+            observers: Vec::new(),
+            transcript_history: VecDeque::with_capacity(TRANSCRIPT_HISTORY_LEN),
+        }
+    }
+
+    /// Use the executor's confirmation timeout, so both layers expire together.
+    pub fn with_execution_cfg(decision: DecisionManager, cfg: &ExecutionCfg) -> Self {
+        Self {
+            timeout: Duration::from_secs(cfg.confirmation_timeout_seconds),
+            ..Self::new(decision)
+        }
+    }
+
+    /// Subscribe to [`StateEvent`]s from now on.
+    pub fn events(&mut self) -> EventReceiver {
+        let queue = Arc::new(EventQueue::default());
+        self.observers.push(queue.clone());
+        EventReceiver { queue }
+    }
+
+    fn emit(&mut self, ev: StateEvent) {
+        // A queue nobody else holds belongs to a dropped receiver.
+        self.observers.retain(|q| Arc::strong_count(q) > 1);
+        for q in &self.observers {
+            q.push(ev.clone());
+        }
+    }
+
+    /// Every state change goes through here so observers see all of them.
+    fn set_state(&mut self, to: State) {
+        let from = self.state;
+        self.state = to;
+        if from != to {
+            self.emit(StateEvent::StateChanged { from, to });
+        }
     }
 
     pub fn on_wake(&mut self) {
-        self.state = State::Listening;
+        self.set_state(State::Listening);
     }
 
+    #[cfg_attr(not(test), allow(dead_code))]
     pub fn on_transcript(&mut self, text: &str, deterministic: IntentResult) -> ManagerOutcome {
-        // Rule 3: Speech ignored unless relevant
+        self.on_transcript_ranked(text, deterministic, None)
+    }
+
+    /// Like [`on_transcript`](Self::on_transcript), with the router's runner-up
+    /// so near-ties can be turned into a clarification question.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn on_transcript_ranked(&mut self, text: &str, deterministic: IntentResult, runner_up: Option<IntentResult>) -> ManagerOutcome {
+        let entry_intent = deterministic.clone();
+        let outcome = self.route_transcript(text, deterministic, runner_up);
+        if matches!(outcome, ManagerOutcome::Ignored) {
+            self.emit(StateEvent::TranscriptIgnored);
+        }
+        self.record_transcript(TranscriptEntry::new(text, outcome.kind(), Some(&entry_intent), None));
+        outcome
+    }
+
+    /// Speech the main loop did not act on: like [`Decision::Ignored`] from
+    /// `on_transcript`, it ends the conversation.
+    pub fn ignore_transcript(&mut self) {
+        self.decision.observe(&Decision::Ignored);
+        self.emit(StateEvent::TranscriptIgnored);
+    }
+
+    /// Add a transcript to the history, dropping the oldest beyond
+    /// [`TRANSCRIPT_HISTORY_LEN`]. `on_transcript` does this itself.
+    pub fn record_transcript(&mut self, entry: TranscriptEntry) {
+        if self.transcript_history.len() == TRANSCRIPT_HISTORY_LEN {
+            self.transcript_history.pop_front();
+        }
+        self.transcript_history.push_back(entry.clone());
+        self.emit(StateEvent::TranscriptRecorded(entry));
+    }
+
+    pub fn transcript_history(&self) -> &VecDeque<TranscriptEntry> {
+        &self.transcript_history
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    fn route_transcript(&mut self, text: &str, deterministic: IntentResult, runner_up: Option<IntentResult>) -> ManagerOutcome {
+        if self.state == State::Clarifying {
+            return self.on_clarification(text);
+        }
+        // Rule 3: Speech ignored unless relevant; answers to a confirmation
+        // go to the Executor, which holds it.
         if self.state != State::Deciding {
             return ManagerOutcome::Ignored;
         }
 
         // Rule 4: unknown can never become command (Decision enforces this)
-        let d = self.decision.decide(text, deterministic);
+        let candidates = [Some(deterministic.clone()), runner_up.clone()];
+        let d = self.decision.decide_ranked(text, deterministic, runner_up);
+        self.decision.observe(&d);
         match d {
-            Decision::Command { intent, preview, requires_confirmation } => {
-                // Rule 1: No command may execute unless state == Confirming
-                // Enforce: ALL commands require explicit confirmation.
-                let _ = requires_confirmation;
-
-                // Enter explicit confirmation state.
-                let cmd_id = intent.command_id.clone().unwrap_or_else(|| "unknown".to_string());
-                let nonce = SystemTime::now()
-                    .duration_since(SystemTime::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_nanos();
-                let request_id = format!("{}-{}", cmd_id, nonce);
-                self.pending = Some(PendingCommand {
-                    request_id: request_id.clone(),
-                    intent,
-                    preview: preview.clone(),
-                    dangerous: true,
-                });
-                self.state = State::Confirming;
-                ManagerOutcome::NeedsConfirmation {
-                    request_id,
-                    preview,
-                }
+            Decision::Command { intent, preview, requires_confirmation: true } => self.enter_confirming(intent, preview),
+            // The command's confirmation policy lets it run straight away.
+            Decision::Command { intent, .. } => {
+                self.set_state(State::Responding);
+                ManagerOutcome::Execute { intent }
+            }
+            Decision::Clarify { options } => {
+                let candidates: Vec<&IntentResult> = candidates.iter().flatten().collect();
+                ManagerOutcome::NeedsClarification { options: self.enter_clarifying(options, &candidates) }
             }
-            Decision::Question { text } => {
-                self.state = State::Responding;
-                ManagerOutcome::Question { text }
+            Decision::Question => {
+                self.set_state(State::Responding);
+                ManagerOutcome::Question
             }
-            Decision::WebQuery { text } => {
-                self.state = State::Responding;
-                ManagerOutcome::WebQuery { text }
+            Decision::WebQuery => {
+                self.set_state(State::Responding);
+                ManagerOutcome::WebQuery
             }
             Decision::Ignored => ManagerOutcome::Ignored,
         }
     }
 
+    /// Ask which of `options` (command id and preview, from
+    /// [`Decision::Clarify`]) was meant; returns the ids offered.
+    fn enter_clarifying(&mut self, options: Vec<(String, String)>, candidates: &[&IntentResult]) -> Vec<String> {
+        let offered: Vec<(IntentResult, String)> = options
+            .into_iter()
+            .filter_map(|(id, preview)| {
+                let intent = candidates.iter().find(|c| c.command_id.as_deref() == Some(id.as_str()))?;
+                Some(((*intent).clone(), preview))
+            })
+            .collect();
+        let ids = offered.iter().filter_map(|(i, _)| i.command_id.clone()).collect();
+        self.clarification = Some(Clarification { options: offered, deadline: Instant::now() + self.timeout });
+        self.set_state(State::Clarifying);
+        ids
+    }
+
+    /// For the main loop, which runs commands itself: ask which command was
+    /// meant when the router's runner-up is within `clarify_margin` of `best`.
+    /// The offered command ids, or None when `best` should just run.
+    pub fn clarify(&mut self, text: &str, best: &IntentResult, runner_up: Option<&IntentResult>) -> Option<Vec<String>> {
+        let Decision::Clarify { options } = self.decision.decide_ranked(text, best.clone(), runner_up.cloned()) else {
+            return None;
+        };
+        let candidates: Vec<&IntentResult> = std::iter::once(best).chain(runner_up).collect();
+        Some(self.enter_clarifying(options, &candidates))
+    }
+
+    /// A clarification question is waiting for its answer.
+    pub fn is_clarifying(&self) -> bool {
+        self.clarification.is_some()
+    }
+
+    /// The command ids the open clarification offers, in order.
+    pub fn clarification_options(&self) -> Option<Vec<String>> {
+        let c = self.clarification.as_ref()?;
+        Some(c.options.iter().filter_map(|(i, _)| i.command_id.clone()).collect())
+    }
+
+    /// Treat `text` as the answer to the open clarification, whatever the
+    /// state. None when there is none (or it has expired): route `text` as usual.
+    pub fn answer_clarification(&mut self, text: &str) -> Option<ManagerOutcome> {
+        let deadline = self.clarification.as_ref()?.deadline;
+        if Instant::now() >= deadline {
+            self.clarification = None;
+            return None;
+        }
+        Some(self.on_clarification(text))
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    fn enter_confirming(&mut self, intent: IntentResult, preview: String) -> ManagerOutcome {
+        // Enter explicit confirmation state.
+        let cmd_id = intent.command_id.clone().unwrap_or_else(|| "unknown".to_string());
+        let nonce = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let request_id = format!("{}-{}", cmd_id, nonce);
+        self.pending = Some(PendingCommand {
+            request_id: request_id.clone(),
+            intent,
+            preview: preview.clone(),
+            deadline: Instant::now() + self.timeout,
+        });
+        self.set_state(State::Confirming);
+        self.emit(StateEvent::ConfirmationRequested { request_id: request_id.clone(), preview });
+        ManagerOutcome::NeedsConfirmation { request_id }
+    }
+
+    /// An answer to a clarification question: "volume", "the second one",
+    /// or "cancel". Anything unrecognized keeps waiting. The chosen command
+    /// is returned to run; whether it asks first is up to the Executor.
+    fn on_clarification(&mut self, text: &str) -> ManagerOutcome {
+        let norm = crate::decision::normalize_input(text);
+        if matches!(norm.as_str(), "no" | "cancel" | "stop" | "neither" | "none") {
+            self.cancel();
+            return ManagerOutcome::Canceled;
+        }
+        let Some(c) = self.clarification.as_ref() else {
+            self.cancel();
+            return ManagerOutcome::Ignored;
+        };
+        let ids: Vec<&str> = c.options.iter().map(|(i, _)| i.command_id.as_deref().unwrap_or("")).collect();
+        let Some(choice) = select_option(&norm, &ids) else {
+            return ManagerOutcome::Ignored;
+        };
+        let Some(mut c) = self.clarification.take() else {
+            return ManagerOutcome::Ignored;
+        };
+        let (intent, _) = c.options.swap_remove(choice);
+        self.set_state(State::Responding);
+        ManagerOutcome::Execute { intent }
+    }
+
+    /// Expire an unanswered confirmation or clarification once its deadline
+    /// has passed (a deadline equal to `now` counts as passed), returning to Idle.
+    /// A conversation past `context_ttl` is forgotten, and FollowUp ends with it.
+    pub fn handle_tick(&mut self, now: Instant) -> Option<ManagerOutcome> {
+        match self.state {
+            State::Confirming => {
+                let pending = self.pending.as_ref()?;
+                if now < pending.deadline {
+                    return None;
+                }
+                let request_id = pending.request_id.clone();
+                log::info!("manager: confirmation for '{}' timed out", request_id);
+                self.reset_to_idle();
+                Some(ManagerOutcome::ConfirmationExpired { request_id })
+            }
+            State::Clarifying => {
+                // Without a clarification of its own the state mirrors the
+                // Executor's parameter question, which expires there.
+                if self.clarification.as_ref().is_none_or(|c| now < c.deadline) {
+                    return None;
+                }
+                self.cancel();
+                Some(ManagerOutcome::Canceled)
+            }
+            State::FollowUp => {
+                if self.decision.expire_context(now) {
+                    log::debug!("manager: conversation expired");
+                    self.set_state(State::Idle);
+                }
+                None
+            }
+            _ => {
+                self.decision.expire_context(now);
+                None
+            }
+        }
+    }
+
     pub fn enter_deciding(&mut self) {
-        self.state = State::Deciding;
+        self.set_state(State::Deciding);
+    }
+
+    /// Show a confirmation the [`Executor`] is holding, so observers see the
+    /// daemon as Confirming even though this manager has nothing pending.
+    pub fn mirror_confirmation(&mut self, request_id: &str, preview: &str) {
+        self.set_state(State::Confirming);
+        self.emit(StateEvent::ConfirmationRequested { request_id: request_id.to_string(), preview: preview.to_string() });
+    }
+
+    /// Show the [`Executor`]'s question for a missing parameter as Clarifying.
+    pub fn mirror_parameter_request(&mut self) {
+        self.set_state(State::Clarifying);
     }
 
+    #[cfg_attr(not(test), allow(dead_code))]
     pub fn confirmation_token(&self) -> Option<ConfirmationToken> {
         if self.state != State::Confirming {
             return None;
@@ -98,6 +443,7 @@ impl Manager {
             .map(|p| ConfirmationToken { request_id: p.request_id.clone() })
     }
 
+    #[cfg_attr(not(test), allow(dead_code))]
     pub fn confirm(&mut self, token: &ConfirmationToken) -> Option<IntentResult> {
         if self.state != State::Confirming {
             return None;
@@ -108,34 +454,147 @@ impl Manager {
         }
         let intent = pending.intent.clone();
         self.pending = None;
-        self.state = State::Responding;
+        self.emit(StateEvent::ConfirmationResolved { accepted: true });
+        self.set_state(State::Responding);
         Some(intent)
     }
 
     pub fn cancel(&mut self) {
-        // Rule 2: Cancel = hard reset
-        self.pending = None;
-        self.state = State::Idle;
+        // Rule 2: Cancel = hard reset, conversation included
+        self.decision.clear_context();
+        self.reset_to_idle();
     }
 
     pub fn reset_to_idle(&mut self) {
-        self.pending = None;
-        self.state = State::Idle;
+        self.reset_to(State::Idle);
+    }
+
+    /// The turn is over: Idle, or FollowUp while an answered question is
+    /// still recent enough to follow up on. A clarification question stays
+    /// open for the next turn to answer.
+    pub fn end_turn(&mut self) {
+        if self.clarification.is_some() {
+            self.set_state(State::Clarifying);
+            return;
+        }
+        self.reset_to(if self.decision.has_context() { State::FollowUp } else { State::Idle });
     }
 
+    fn reset_to(&mut self, to: State) {
+        if self.pending.take().is_some() {
+            self.emit(StateEvent::ConfirmationResolved { accepted: false });
+        }
+        self.clarification = None;
+        self.set_state(to);
+    }
+
+    /// Prompt for answering a [`ManagerOutcome::Question`], with the recent
+    /// conversation prepended when `context_window` is enabled.
+    pub fn question_prompt(&self, text: &str) -> String {
+        self.decision.question_prompt(text)
+    }
+
+    /// Record the answer given to a [`ManagerOutcome::Question`].
+    pub fn record_answer(&mut self, text: &str, answer: &str) {
+        self.decision.record_answer(text, answer);
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
     pub fn pending_request_id(&self) -> Option<&str> {
         self.pending.as_ref().map(|p| p.request_id.as_str())
     }
 }
 
 pub enum ManagerOutcome {
-    NeedsConfirmation { request_id: String, preview: String },
-    Question { text: String },
-    WebQuery { text: String },
+    #[cfg_attr(not(test), allow(dead_code))]
+    NeedsConfirmation { request_id: String },
+    /// Needs no confirmation; the intent may be executed now.
+    Execute { intent: IntentResult },
+    /// Ask which of these command ids was meant.
+    #[cfg_attr(not(test), allow(dead_code))]
+    NeedsClarification { options: Vec<String> },
+    /// The pending confirmation was not answered in time and was dropped.
+    ConfirmationExpired { request_id: String },
+    Canceled,
+    #[cfg_attr(not(test), allow(dead_code))]
+    Question,
+    #[cfg_attr(not(test), allow(dead_code))]
+    WebQuery,
     Ignored,
 }
 
+impl ManagerOutcome {
+    /// Short name for [`TranscriptEntry::decision_type`].
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ManagerOutcome::NeedsConfirmation { .. } => "confirmation",
+            ManagerOutcome::Execute { .. } => "command",
+            ManagerOutcome::NeedsClarification { .. } => "clarification",
+            ManagerOutcome::ConfirmationExpired { .. } => "expired",
+            ManagerOutcome::Canceled => "canceled",
+            ManagerOutcome::Question => "question",
+            ManagerOutcome::WebQuery => "web_query",
+            ManagerOutcome::Ignored => "ignored",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoiceAnswer {
+    Affirm,
+    Deny,
+    Other,
+}
+
+/// Strict yes/no: the whole utterance must be one of the answer phrases, so
+/// "yes but change it to 50" is `Other`.
+pub fn classify_confirmation(text: &str) -> VoiceAnswer {
+    match crate::decision::normalize_input(text).as_str() {
+        "yes" | "confirm" | "do it" => VoiceAnswer::Affirm,
+        "no" | "cancel" | "stop" => VoiceAnswer::Deny,
+        _ => VoiceAnswer::Other,
+    }
+}
+
+/// Pick an option by ordinal ("first", "the second one", "2") or by a word
+/// of its command id that no other option shares ("volume" for `volume_up`
+/// vs `brightness_up`).
+fn select_option(norm: &str, ids: &[&str]) -> Option<usize> {
+    let tokens: Vec<&str> = norm.split_whitespace().collect();
+    let ordinal = tokens.iter().find_map(|t| match *t {
+        "first" => Some(0),
+        "second" => Some(1),
+        "third" => Some(2),
+        "last" => ids.len().checked_sub(1),
+        _ => None,
+    });
+    // Cardinals only when no ordinal word is present: "the second one".
+    let cardinal = || {
+        tokens
+            .iter()
+            .find_map(|t| t.parse::<usize>().ok().filter(|n| *n >= 1).map(|n| n - 1))
+    };
+    if let Some(i) = ordinal.or_else(cardinal) {
+        return (i < ids.len()).then_some(i);
+    }
+    let mut matched = ids.iter().enumerate().filter(|(i, id)| {
+        id.split('_').any(|word| {
+            tokens.contains(&word) && !ids.iter().enumerate().any(|(j, other)| j != *i && other.split('_').any(|w| w == word))
+        })
+    });
+    match (matched.next(), matched.next()) {
+        (Some((i, _)), None) => Some(i),
+        _ => None,
+    }
+}
+
 /// Executor gate: only manager-confirmed intents are allowed to execute.
+#[cfg_attr(not(test), allow(dead_code))]
 pub fn execute_with_token(executor: &mut Executor, intent: &IntentResult, token: &ConfirmationToken) -> ExecStatus {
     // Hard gate: if this function isn't called with a token from Manager::confirmation_token,
     // nothing can execute. (We don't expose token fields.)
@@ -147,36 +606,57 @@ pub fn execute_with_token(executor: &mut Executor, intent: &IntentResult, token:
 mod tests {
     use super::*;
     use crate::decision::{DecisionConfig, DecisionManager};
+    use crate::executor::PendingPolicy;
 
     fn cmd_intent(id: &str, score: f32) -> IntentResult {
         IntentResult {
             intent_type: "command".into(),
             command_id: Some(id.to_string()),
-            parameters: serde_json::json!({}),
+            parameters: crate::params::Params::new(),
             deterministic_score: Some(score),
+            embedding_score: None,
             dangerous: false,
             requires_confirmation: false,
         }
     }
 
+    fn dangerous_intent(id: &str, score: f32) -> IntentResult {
+        IntentResult { dangerous: true, ..cmd_intent(id, score) }
+    }
+
+    #[test]
+    fn harmless_commands_run_without_confirmation() {
+        let decision = DecisionManager::new(DecisionConfig::with_threshold(0.75)).unwrap();
+        let mut mgr = Manager::new(decision);
+        mgr.on_wake();
+        mgr.enter_deciding();
+        match mgr.on_transcript("volume up", cmd_intent("volume_up", 0.9)) {
+            ManagerOutcome::Execute { intent } => assert_eq!(intent.command_id.as_deref(), Some("volume_up")),
+            _ => panic!("expected Execute"),
+        }
+        assert_eq!(mgr.state, State::Responding);
+        assert!(mgr.pending_request_id().is_none());
+        assert_eq!(mgr.transcript_history().back().unwrap().decision_type, "command");
+    }
+
     #[test]
     fn ignores_transcript_unless_deciding() {
-        let decision = DecisionManager::new(DecisionConfig { deterministic_threshold: 0.75 });
+        let decision = DecisionManager::new(DecisionConfig::with_threshold(0.75)).unwrap();
         let mut mgr = Manager::new(decision);
         mgr.on_wake();
-        let out = mgr.on_transcript("lock screen", cmd_intent("lock_screen", 0.99));
+        let out = mgr.on_transcript("lock screen", dangerous_intent("lock_screen", 0.99));
         assert!(matches!(out, ManagerOutcome::Ignored));
     }
 
     #[test]
     fn command_always_requires_confirmation_state() {
-        let decision = DecisionManager::new(DecisionConfig { deterministic_threshold: 0.75 });
+        let decision = DecisionManager::new(DecisionConfig::with_threshold(0.75)).unwrap();
         let mut mgr = Manager::new(decision);
         mgr.on_wake();
         mgr.enter_deciding();
-        let out = mgr.on_transcript("lock my laptop", cmd_intent("lock_screen", 0.99));
+        let out = mgr.on_transcript("lock my laptop", dangerous_intent("lock_screen", 0.99));
         match out {
-            ManagerOutcome::NeedsConfirmation { request_id, preview: _ } => {
+            ManagerOutcome::NeedsConfirmation { request_id } => {
                 assert!(!request_id.is_empty());
                 assert_eq!(mgr.state, State::Confirming);
                 assert_eq!(mgr.pending_request_id(), Some(request_id.as_str()));
@@ -187,15 +667,339 @@ mod tests {
 
     #[test]
     fn cancel_is_hard_reset() {
-        let decision = DecisionManager::new(DecisionConfig { deterministic_threshold: 0.75 });
+        let decision = DecisionManager::new(DecisionConfig::with_threshold(0.75)).unwrap();
         let mut mgr = Manager::new(decision);
         mgr.on_wake();
         mgr.enter_deciding();
-        let _ = mgr.on_transcript("lock my laptop", cmd_intent("lock_screen", 0.99));
+        let _ = mgr.on_transcript("lock my laptop", dangerous_intent("lock_screen", 0.99));
         assert_eq!(mgr.state, State::Confirming);
         mgr.cancel();
         assert_eq!(mgr.state, State::Idle);
         assert!(mgr.pending_request_id().is_none());
         assert!(mgr.confirmation_token().is_none());
     }
+
+    #[test]
+    fn abort_resets_from_every_stage() {
+        let decision = DecisionManager::new(DecisionConfig::with_threshold(0.75)).unwrap();
+        let mut mgr = Manager::new(decision);
+
+        // Listening (recording / ASR in flight).
+        mgr.on_wake();
+        mgr.reset_to_idle();
+        assert_eq!(mgr.state, State::Idle);
+
+        // Deciding.
+        mgr.on_wake();
+        mgr.enter_deciding();
+        mgr.reset_to_idle();
+        assert_eq!(mgr.state, State::Idle);
+        // A late transcript after the abort must not be acted on.
+        let out = mgr.on_transcript("lock my laptop", dangerous_intent("lock_screen", 0.99));
+        assert!(matches!(out, ManagerOutcome::Ignored));
+
+        // Confirming: the pending command and its token are gone.
+        mgr.on_wake();
+        mgr.enter_deciding();
+        let _ = mgr.on_transcript("lock my laptop", dangerous_intent("lock_screen", 0.99));
+        let token = mgr.confirmation_token().expect("token while confirming");
+        mgr.reset_to_idle();
+        assert_eq!(mgr.state, State::Idle);
+        assert!(mgr.pending_request_id().is_none());
+        assert!(mgr.confirm(&token).is_none());
+    }
+
+    fn clarifying_manager() -> Manager {
+        let decision = DecisionManager::new(DecisionConfig::with_threshold(0.5)).unwrap();
+        let mut mgr = Manager::new(decision);
+        mgr.on_wake();
+        mgr.enter_deciding();
+        let out = mgr.on_transcript_ranked("turn it up", cmd_intent("volume_up", 0.62), Some(cmd_intent("brightness_up", 0.60)));
+        match out {
+            ManagerOutcome::NeedsClarification { options } => assert_eq!(options, vec!["volume_up", "brightness_up"]),
+            _ => panic!("expected NeedsClarification"),
+        }
+        assert_eq!(mgr.state, State::Clarifying);
+        assert!(mgr.confirmation_token().is_none(), "clarification must not be confirmable");
+        mgr
+    }
+
+    fn chosen(out: ManagerOutcome) -> Option<String> {
+        match out {
+            ManagerOutcome::Execute { intent } => intent.command_id,
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn clarification_selects_by_keyword() {
+        let mut mgr = clarifying_manager();
+        // "up" is shared by both options, so it does not choose.
+        assert!(matches!(mgr.on_transcript("up", cmd_intent("volume_up", 0.9)), ManagerOutcome::Ignored));
+        assert_eq!(mgr.state, State::Clarifying);
+        assert_eq!(chosen(mgr.on_transcript("brightness", cmd_intent("unused", 0.0))).as_deref(), Some("brightness_up"));
+        // The Executor decides whether the choice asks for confirmation.
+        assert_eq!(mgr.state, State::Responding);
+        assert!(!mgr.is_clarifying());
+    }
+
+    #[test]
+    fn clarification_selects_by_ordinal() {
+        let mut mgr = clarifying_manager();
+        assert_eq!(chosen(mgr.on_transcript("the second one", cmd_intent("unused", 0.0))).as_deref(), Some("brightness_up"));
+
+        let mut mgr = clarifying_manager();
+        assert_eq!(chosen(mgr.on_transcript("1", cmd_intent("unused", 0.0))).as_deref(), Some("volume_up"));
+
+        let mut mgr = clarifying_manager();
+        assert!(matches!(mgr.on_transcript("the third one", cmd_intent("unused", 0.0)), ManagerOutcome::Ignored));
+        assert_eq!(mgr.state, State::Clarifying);
+    }
+
+    #[test]
+    fn clarification_cancels_on_request_and_timeout() {
+        let mut mgr = clarifying_manager();
+        assert!(matches!(mgr.on_transcript("neither", cmd_intent("unused", 0.0)), ManagerOutcome::Canceled));
+        assert_eq!(mgr.state, State::Idle);
+
+        let mut mgr = clarifying_manager();
+        assert!(mgr.handle_tick(Instant::now()).is_none());
+        assert!(matches!(mgr.handle_tick(Instant::now() + Duration::from_secs(11)), Some(ManagerOutcome::Canceled)));
+        assert_eq!(mgr.state, State::Idle);
+        assert!(mgr.pending_request_id().is_none());
+    }
+
+    #[test]
+    fn clarification_outlives_the_turn_that_asked_it() {
+        let decision = DecisionManager::new(DecisionConfig::with_threshold(0.5)).unwrap();
+        let mut mgr = Manager::new(decision);
+        mgr.on_wake();
+        mgr.enter_deciding();
+        let (best, second) = (cmd_intent("volume_up", 0.62), cmd_intent("brightness_up", 0.60));
+        assert_eq!(mgr.clarify("turn it up", &best, Some(&second)), Some(vec!["volume_up".to_string(), "brightness_up".to_string()]));
+        mgr.end_turn();
+        assert_eq!(mgr.state, State::Clarifying);
+
+        // The answer arrives in a new turn, routed from Deciding.
+        mgr.on_wake();
+        mgr.enter_deciding();
+        assert_eq!(chosen(mgr.answer_clarification("the second one").unwrap()).as_deref(), Some("brightness_up"));
+        assert!(mgr.answer_clarification("volume").is_none());
+
+        // A clear winner is not a question.
+        assert!(mgr.clarify("turn it up", &cmd_intent("volume_up", 0.9), Some(&second)).is_none());
+        assert!(!mgr.is_clarifying());
+    }
+
+    fn confirming_manager(timeout_secs: u64) -> (Manager, Instant) {
+        let decision = DecisionManager::new(DecisionConfig::with_threshold(0.75)).unwrap();
+        let cfg = ExecutionCfg { confirmation_timeout_seconds: timeout_secs, dry_run: true, voice_confirmation: false, pending_policy: PendingPolicy::Reject, default_env_allowlist: Vec::new(), confirmation: Default::default() };
+        let mut mgr = Manager::with_execution_cfg(decision, &cfg);
+        mgr.on_wake();
+        mgr.enter_deciding();
+        let _ = mgr.on_transcript("lock my laptop", dangerous_intent("lock_screen", 0.99));
+        assert_eq!(mgr.state, State::Confirming);
+        let deadline = mgr.pending.as_ref().unwrap().deadline;
+        (mgr, deadline)
+    }
+
+    #[test]
+    fn confirmation_survives_ticks_before_the_deadline() {
+        let (mut mgr, deadline) = confirming_manager(10);
+        assert!(mgr.handle_tick(deadline - Duration::from_millis(1)).is_none());
+        assert_eq!(mgr.state, State::Confirming);
+        assert!(mgr.confirmation_token().is_some());
+    }
+
+    #[test]
+    fn confirmation_expires_at_and_after_the_deadline() {
+        for offset in [Duration::ZERO, Duration::from_secs(5)] {
+            let (mut mgr, deadline) = confirming_manager(10);
+            let request_id = mgr.pending_request_id().unwrap().to_string();
+            match mgr.handle_tick(deadline + offset) {
+                Some(ManagerOutcome::ConfirmationExpired { request_id: expired }) => assert_eq!(expired, request_id),
+                _ => panic!("expected ConfirmationExpired"),
+            }
+            assert_eq!(mgr.state, State::Idle);
+            assert!(mgr.handle_tick(deadline + offset).is_none());
+        }
+    }
+
+    #[test]
+    fn expired_confirmation_frees_the_next_interaction() {
+        let (mut mgr, deadline) = confirming_manager(3);
+        let token = mgr.confirmation_token().unwrap();
+        mgr.handle_tick(deadline);
+        assert!(mgr.confirm(&token).is_none());
+        mgr.on_wake();
+        mgr.enter_deciding();
+        let out = mgr.on_transcript("lock my laptop", dangerous_intent("lock_screen", 0.99));
+        assert!(matches!(out, ManagerOutcome::NeedsConfirmation { .. }));
+    }
+
+    #[test]
+    fn speech_while_confirming_is_left_to_the_executor() {
+        let decision = DecisionManager::new(DecisionConfig::with_threshold(0.75)).unwrap();
+        let mut mgr = Manager::new(decision);
+        mgr.on_wake();
+        mgr.enter_deciding();
+        let _ = mgr.on_transcript("lock my laptop", dangerous_intent("lock_screen", 0.99));
+        assert!(matches!(mgr.on_transcript("yes", cmd_intent("unused", 0.0)), ManagerOutcome::Ignored));
+        assert_eq!(mgr.state, State::Confirming);
+    }
+
+    #[test]
+    fn strict_yes_no_matcher() {
+        assert_eq!(classify_confirmation("Do it!"), VoiceAnswer::Affirm);
+        assert_eq!(classify_confirmation(" no "), VoiceAnswer::Deny);
+        assert_eq!(classify_confirmation("yes please"), VoiceAnswer::Other);
+        assert_eq!(classify_confirmation("stop the music"), VoiceAnswer::Other);
+        assert_eq!(classify_confirmation(""), VoiceAnswer::Other);
+    }
+
+    #[test]
+    fn happy_path_emits_exact_event_sequence() {
+        let decision = DecisionManager::new(DecisionConfig::with_threshold(0.75)).unwrap();
+        let mut mgr = Manager::new(decision);
+        let events = mgr.events();
+        mgr.on_wake();
+        mgr.enter_deciding();
+        let request_id = match mgr.on_transcript("lock my laptop", dangerous_intent("lock_screen", 0.99)) {
+            ManagerOutcome::NeedsConfirmation { request_id, .. } => request_id,
+            _ => panic!("expected NeedsConfirmation"),
+        };
+        let preview = mgr.pending.as_ref().unwrap().preview.clone();
+        let transcript = mgr.transcript_history()[0].clone();
+        let token = mgr.confirmation_token().unwrap();
+        let intent = mgr.confirm(&token).unwrap();
+        let path = std::env::temp_dir().join(format!("btwd-manager-events-{}.json", std::process::id()));
+        std::fs::write(&path, r#"[{"id": "lock_screen", "shell_command_template": "loginctl lock-session"}]"#).unwrap();
+        let cfg = ExecutionCfg { confirmation_timeout_seconds: 10, dry_run: true, voice_confirmation: false, pending_policy: PendingPolicy::Reject, default_env_allowlist: Vec::new(), confirmation: Default::default() };
+        let mut exec = Executor::new_from_path(&path, cfg).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert!(matches!(execute_with_token(&mut exec, &intent, &token), ExecStatus::DryRun { .. }));
+        mgr.reset_to_idle();
+
+        use StateEvent::*;
+        assert_eq!(
+            events.drain(),
+            vec![
+                StateChanged { from: State::Idle, to: State::Listening },
+                StateChanged { from: State::Listening, to: State::Deciding },
+                StateChanged { from: State::Deciding, to: State::Confirming },
+                ConfirmationRequested { request_id, preview },
+                TranscriptRecorded(transcript),
+                ConfirmationResolved { accepted: true },
+                StateChanged { from: State::Confirming, to: State::Responding },
+                StateChanged { from: State::Responding, to: State::Idle },
+            ]
+        );
+    }
+
+    #[test]
+    fn cancel_timeout_and_ignored_speech_emit_events() {
+        let (mut mgr, deadline) = confirming_manager(10);
+        let events = mgr.events();
+        mgr.cancel();
+        assert_eq!(
+            events.drain(),
+            vec![
+                StateEvent::ConfirmationResolved { accepted: false },
+                StateEvent::StateChanged { from: State::Confirming, to: State::Idle },
+            ]
+        );
+
+        let (mut mgr, _) = confirming_manager(10);
+        let events = mgr.events();
+        assert!(mgr.handle_tick(deadline + Duration::from_secs(60)).is_some());
+        assert_eq!(events.try_recv(), Some(StateEvent::ConfirmationResolved { accepted: false }));
+        assert_eq!(events.try_recv(), Some(StateEvent::StateChanged { from: State::Confirming, to: State::Idle }));
+
+        let _ = mgr.on_transcript("hello", dangerous_intent("lock_screen", 0.99));
+        mgr.reset_to_idle();
+        let drained = events.drain();
+        assert_eq!(drained[0], StateEvent::TranscriptIgnored);
+        assert!(matches!(&drained[1..], [StateEvent::TranscriptRecorded(e)] if e.decision_type == "ignored"), "{:?}", drained);
+    }
+
+    #[test]
+    fn transcript_history_keeps_the_last_twenty() {
+        let decision = DecisionManager::new(DecisionConfig::with_threshold(0.75)).unwrap();
+        let mut mgr = Manager::new(decision);
+        mgr.on_wake();
+        mgr.enter_deciding();
+        let _ = mgr.on_transcript("lock my laptop", dangerous_intent("lock_screen", 0.99));
+        let first = &mgr.transcript_history()[0];
+        assert_eq!((first.raw_text.as_str(), first.decision_type.as_str()), ("lock my laptop", "confirmation"));
+        assert_eq!((first.command_id.as_deref(), first.deterministic_score, first.asr_confidence), (Some("lock_screen"), Some(0.99), None));
+
+        for i in 0..TRANSCRIPT_HISTORY_LEN + 5 {
+            mgr.record_transcript(TranscriptEntry::new(&format!("utterance {}", i), "question", None, Some(0.8)));
+        }
+        let history = mgr.transcript_history();
+        assert_eq!(history.len(), TRANSCRIPT_HISTORY_LEN);
+        assert_eq!(history.front().unwrap().raw_text, "utterance 5");
+        assert_eq!(history.back().unwrap().to_json()["asr_confidence"].as_f64().map(|c| c as f32), Some(0.8));
+    }
+
+    #[test]
+    fn slow_reader_drops_oldest_events() {
+        let decision = DecisionManager::new(DecisionConfig::with_threshold(0.75)).unwrap();
+        let mut mgr = Manager::new(decision);
+        let events = mgr.events();
+        for _ in 0..EVENT_CAPACITY {
+            mgr.on_wake();
+            mgr.reset_to_idle();
+        }
+        let got = events.drain();
+        assert_eq!(got.len(), EVENT_CAPACITY);
+        assert_eq!(got[0], StateEvent::StateChanged { from: State::Idle, to: State::Listening });
+        assert_eq!(got.last(), Some(&StateEvent::StateChanged { from: State::Listening, to: State::Idle }));
+        assert!(events.recv_timeout(Duration::from_millis(1)).is_none());
+
+        drop(events);
+        mgr.on_wake();
+        assert!(mgr.observers.is_empty());
+    }
+
+    #[test]
+    fn ignored_speech_ends_the_conversation() {
+        let cfg = DecisionConfig { context_window: 3, ..DecisionConfig::with_threshold(0.75) };
+        let mut mgr = Manager::new(DecisionManager::new(cfg).unwrap());
+        let events = mgr.events();
+        mgr.record_answer("what's the weather in paris?", "Sunny.");
+        mgr.ignore_transcript();
+        assert_eq!(mgr.question_prompt("and tomorrow?"), "and tomorrow?");
+        assert_eq!(events.drain(), vec![StateEvent::TranscriptIgnored]);
+        mgr.end_turn();
+        assert_eq!(mgr.state, State::Idle);
+    }
+
+    #[test]
+    fn answered_questions_leave_a_follow_up_window() {
+        let cfg = DecisionConfig { context_window: 3, context_ttl: Duration::from_secs(60), ..DecisionConfig::with_threshold(0.75) };
+        let mut mgr = Manager::new(DecisionManager::new(cfg).unwrap());
+        mgr.on_wake();
+        mgr.enter_deciding();
+        mgr.end_turn();
+        assert_eq!(mgr.state, State::Idle);
+
+        mgr.record_answer("what's the weather in paris?", "Sunny.");
+        mgr.end_turn();
+        assert_eq!(mgr.state, State::FollowUp);
+        mgr.on_wake();
+        assert!(mgr.question_prompt("what about tomorrow?").starts_with("User: what's the weather in paris?\n"));
+        mgr.end_turn();
+        assert!(mgr.handle_tick(Instant::now() + Duration::from_secs(30)).is_none());
+        assert_eq!(mgr.state, State::FollowUp);
+        mgr.handle_tick(Instant::now() + Duration::from_secs(61));
+        assert_eq!(mgr.state, State::Idle);
+        assert_eq!(mgr.question_prompt("and sunday?"), "and sunday?");
+
+        // Cancel ends the conversation too.
+        mgr.record_answer("q", "a");
+        mgr.cancel();
+        mgr.end_turn();
+        assert_eq!(mgr.state, State::Idle);
+    }
 }
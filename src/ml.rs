@@ -1,52 +1,296 @@
-use std::io::{BufRead, BufReader, Write};
+use std::io::{BufReader, Read, Write};
 use std::path::PathBuf;
 use std::process::{Child, ChildStdin, Command, Stdio};
-use std::sync::mpsc::{self, Receiver};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use std::time::Instant;
 
 use serde::{Deserialize, Serialize};
 
+use crate::config::AsrCfg;
 use crate::error::{BtwError, Result};
 
+/// Wire protocol spoken with the worker. v2 sends audio as a JSON header line
+/// followed by `byte_len` bytes of raw little-endian PCM (v1 used JSON arrays).
+/// v3 adds a request `id` that the worker echoes in every response.
+/// v4 replaces newline-delimited JSON with frames, in both directions: a
+/// 4-byte little-endian length, then that many bytes of JSON. Transcripts
+/// may then contain raw newlines.
+pub const PROTOCOL_VERSION: u32 = 4;
+
+/// A larger length prefix can only come from a corrupt stream.
+const MAX_FRAME_BYTES: usize = 1024 * 1024;
+
+/// Write one length-prefixed frame.
+fn write_frame(w: &mut impl Write, payload: &[u8]) -> std::io::Result<()> {
+    let len = u32::try_from(payload.len()).map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "frame too large"))?;
+    w.write_all(&len.to_le_bytes())?;
+    w.write_all(payload)
+}
+
+/// Read one length-prefixed frame. `Ok(None)` on a clean EOF between frames.
+fn read_frame(r: &mut impl Read) -> std::io::Result<Option<String>> {
+    let mut len = [0u8; 4];
+    match r.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_FRAME_BYTES {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("frame of {} bytes exceeds the {} byte limit", len, MAX_FRAME_BYTES),
+        ));
+    }
+    let mut body = vec![0u8; len];
+    r.read_exact(&mut body)?;
+    String::from_utf8(body).map(Some).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// Header frame for `asr` and `asr_chunk` requests; the PCM payload follows it
+/// directly on stdin, unframed. `asr_chunk` pieces of a streamed utterance are closed by
+/// `{"type":"asr_end"}`, after which the worker emits a regular `asr_result`.
+///
+/// ASR options (`language`, `model`, `options`) ride along flattened; workers
+/// ignore fields they don't know.
 #[derive(Serialize)]
-struct AsrRequest {
+struct AudioHeader<'a> {
     #[serde(rename = "type")]
     typ: &'static str,
+    id: u64,
+    protocol: u32,
     audio_format: &'static str,
     sample_rate: u32,
-    samples: Vec<i16>,
+    byte_len: usize,
+    #[serde(flatten)]
+    asr: &'a AsrCfg,
+}
+
+fn audio_header_line(typ: &'static str, id: u64, sample_rate: u32, byte_len: usize, asr: &AsrCfg) -> serde_json::Result<String> {
+    serde_json::to_string(&AudioHeader {
+        typ,
+        id,
+        protocol: PROTOCOL_VERSION,
+        audio_format: "pcm_s16le",
+        sample_rate,
+        byte_len,
+        asr,
+    })
+}
+
+fn encode_pcm(samples: &[i16]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(samples.len() * 2);
+    for s in samples {
+        out.extend_from_slice(&s.to_le_bytes());
+    }
+    out
+}
+
+/// Texts per `embed` request. A 384-dim vector is ~8 KB of JSON, so a batch
+/// of 32 keeps each reply well under [`MAX_FRAME_BYTES`] even for larger models.
+const EMBED_BATCH: usize = 32;
+
+#[derive(Serialize)]
+struct EmbedRequest<'a> {
+    #[serde(rename = "type")]
+    typ: &'static str,
+    id: u64,
+    texts: &'a [String],
 }
 
 #[derive(Deserialize)]
+struct EmbedResponse {
+    #[serde(default)]
+    vectors: Vec<Vec<f32>>,
+    error: Option<String>,
+}
+
+/// Reply to the `{"type":"hello"}` handshake. Workers that predate the
+/// handshake answer with an `unknown_request_type` error instead, which is
+/// treated as "no optional capabilities".
+#[derive(Deserialize)]
+struct HelloResponse {
+    #[serde(rename = "type")]
+    typ: String,
+    #[serde(default)]
+    capabilities: Vec<String>,
+    #[serde(default)]
+    embed_model: Option<String>,
+    /// Absent in workers that predate versioning, i.e. protocol 1.
+    #[serde(default = "default_worker_protocol")]
+    protocol: u32,
+}
+
+fn default_worker_protocol() -> u32 { 1 }
+
+#[derive(Debug, Deserialize)]
 pub struct AsrResponse {
     #[serde(rename = "type")]
+    #[allow(dead_code)]
     pub typ: String,
+    /// Echo of the request id this answers.
+    #[serde(default)]
+    #[allow(dead_code)]
+    pub id: Option<u64>,
     pub text: String,
     pub confidence: Option<f32>,
     pub error: Option<String>,
 }
 
+impl AsrResponse {
+    /// Result produced without the worker (in-process engines).
+    #[cfg_attr(not(feature = "whisper"), allow(dead_code))]
+    pub fn from_text(text: String) -> Self {
+        Self { typ: "asr_result".into(), id: None, text, confidence: None, error: None }
+    }
+
+    #[cfg_attr(not(feature = "whisper"), allow(dead_code))]
+    pub fn failed(error: String) -> Self {
+        Self { error: Some(error), ..Self::from_text(String::new()) }
+    }
+}
+
+/// Event delivered while a streamed transcription is in flight.
+#[derive(Debug)]
+pub enum AsrEvent {
+    /// Best-effort transcript of the audio received so far.
+    Partial(String),
+    /// The final result for the whole utterance; the stream is over.
+    Final(AsrResponse),
+    /// The stream could not complete (e.g. worker exited mid-stream).
+    Failed(String),
+}
+
+/// Text from [`MLWorker::transcribe_streaming`]: zero or more partials, then
+/// exactly one final transcript.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(not(test), allow(dead_code))]
+pub struct PartialTranscript {
+    pub text: String,
+    pub is_final: bool,
+    /// Set on the final transcript when recognition failed.
+    pub error: Option<String>,
+}
+
+/// Active stream: its request id and where to deliver its events.
+type StreamSink = Arc<Mutex<Option<(u64, Sender<AsrEvent>)>>>;
+
+#[derive(Deserialize)]
+struct LinePeek {
+    #[serde(rename = "type")]
+    typ: String,
+    #[serde(default)]
+    id: Option<u64>,
+    #[serde(default)]
+    text: String,
+}
+
+fn response_id(line: &str) -> Option<u64> {
+    serde_json::from_str::<LinePeek>(line.trim()).ok().and_then(|p| p.id)
+}
+
+/// Called by the stdout reader thread for every worker line. Events carrying
+/// the active stream's id are diverted to it; everything else is returned for
+/// the request/response channel. Stray partials are always dropped so they can
+/// never be mistaken for a response.
+fn route_stream_line(sink: &Mutex<Option<(u64, Sender<AsrEvent>)>>, line: String) -> Option<String> {
+    let peek: LinePeek = match serde_json::from_str(line.trim()) {
+        Ok(p) => p,
+        Err(_) => return Some(line),
+    };
+    let mut guard = sink.lock().unwrap_or_else(|p| p.into_inner());
+    let for_stream = matches!((guard.as_ref(), peek.id), (Some((sid, _)), Some(id)) if *sid == id);
+    match peek.typ.as_str() {
+        "partial" => {
+            if let (true, Some((_, tx))) = (for_stream, guard.as_ref()) {
+                let _ = tx.send(AsrEvent::Partial(peek.text));
+            }
+            None
+        }
+        "asr_result" if for_stream => {
+            let ev = match serde_json::from_str::<AsrResponse>(line.trim()) {
+                Ok(r) => AsrEvent::Final(r),
+                Err(e) => AsrEvent::Failed(format!("parse ASR resp failed: {}", e)),
+            };
+            if let Some((_, tx)) = guard.take() {
+                let _ = tx.send(ev);
+            }
+            None
+        }
+        _ => Some(line),
+    }
+}
+
 pub struct MLWorker {
     script_path: PathBuf,
     child: Option<Child>,
     stdin: Option<ChildStdin>,
     resp_rx: Option<Receiver<String>>, // lines read from worker stdout
+    capabilities: Vec<String>,
+    embed_model: Option<String>,
+    stream_sink: StreamSink,
+    stream_rate: Option<u32>,
+    stream_id: u64,
+    /// Cleared by the reader thread when it stops; the child may still be
+    /// running, but nothing reads its replies any more.
+    reader_alive: Arc<AtomicBool>,
+    /// Monotonic request id; responses with any other id are stale.
+    next_id: u64,
+    asr: AsrCfg,
+    /// Consecutive spawn/response failures; reset by any good response.
+    failures: u32,
+    last_failure: String,
+    /// No respawn before this instant (exponential backoff).
+    retry_at: Option<Instant>,
+    max_failures: u32,
+    backoff_max: Duration,
+}
+
+/// Delay before the next respawn after `failures` consecutive failures:
+/// 1s, 2s, 4s, ... capped at `max`.
+fn backoff_delay(failures: u32, max: Duration) -> Duration {
+    if failures == 0 {
+        return Duration::ZERO;
+    }
+    let secs = 1u64.checked_shl(failures - 1).unwrap_or(u64::MAX);
+    Duration::from_secs(secs).min(max)
 }
 
 impl MLWorker {
     fn read_timeout_secs() -> u64 {
-        std::env::var("BTWD_ASR_TIMEOUT_SECS")
-            .ok()
-            .and_then(|v| v.parse::<u64>().ok())
+        crate::config_env::var::<u64>("BTWD_ASR_TIMEOUT_SECS")
             .filter(|&v| v >= 1)
             .unwrap_or(25)
     }
 
     fn read_timeout_retry_secs() -> u64 {
-        std::env::var("BTWD_ASR_TIMEOUT_RETRY_SECS")
-            .ok()
-            .and_then(|v| v.parse::<u64>().ok())
+        crate::config_env::var::<u64>("BTWD_ASR_TIMEOUT_RETRY_SECS")
+            .filter(|&v| v >= 1)
+            .unwrap_or(10)
+    }
+
+    fn shutdown_grace_ms() -> u64 {
+        crate::config_env::var::<u64>("BTWD_ML_SHUTDOWN_GRACE_MS")
+            .unwrap_or(1500)
+    }
+
+    fn max_failures() -> u32 {
+        crate::config_env::var::<u32>("BTWD_ML_MAX_FAILURES")
+            .filter(|&v| v >= 1)
+            .unwrap_or(5)
+    }
+
+    fn backoff_max_secs() -> u64 {
+        crate::config_env::var::<u64>("BTWD_ML_BACKOFF_MAX_SECS")
+            .filter(|&v| v >= 1)
+            .unwrap_or(60)
+    }
+
+    fn handshake_timeout_secs() -> u64 {
+        crate::config_env::var::<u64>("BTWD_ML_HANDSHAKE_TIMEOUT_SECS")
             .filter(|&v| v >= 1)
             .unwrap_or(10)
     }
@@ -66,60 +310,66 @@ impl MLWorker {
         }
         "python3".to_string()
     }
-    pub fn new() -> Result<Self> {
-        let script_path = Self::default_script_path()?;
-        let mut worker = MLWorker {
+    pub fn new(asr: AsrCfg) -> Result<Self> {
+        Self::with_script(Self::default_script_path()?, asr)
+    }
+
+    /// Like [`new`](Self::new), but Python is only started by the first
+    /// request, e.g. when ASR runs in-process and nothing may ever need it.
+    pub fn deferred(asr: AsrCfg) -> Result<Self> {
+        Ok(Self::unstarted(Self::default_script_path()?, asr))
+    }
+
+    /// Spawn a worker from an explicit script path.
+    pub fn with_script(script_path: PathBuf, asr: AsrCfg) -> Result<Self> {
+        let mut worker = Self::unstarted(script_path, asr);
+        worker.spawn()?;
+        Ok(worker)
+    }
+
+    /// A worker that starts Python on its first request.
+    pub(crate) fn unstarted(script_path: PathBuf, asr: AsrCfg) -> Self {
+        MLWorker {
             script_path,
             child: None,
             stdin: None,
             resp_rx: None,
-        };
-        worker.spawn()?;
-        Ok(worker)
+            capabilities: Vec::new(),
+            embed_model: None,
+            stream_sink: Arc::new(Mutex::new(None)),
+            stream_rate: None,
+            stream_id: 0,
+            reader_alive: Arc::new(AtomicBool::new(false)),
+            next_id: 1,
+            asr,
+            failures: 0,
+            last_failure: String::new(),
+            retry_at: None,
+            max_failures: Self::max_failures(),
+            backoff_max: Duration::from_secs(Self::backoff_max_secs()),
+        }
     }
 
+    /// `BTWD_ML_PATH`, else the data-file cascade in [`crate::paths`].
     fn default_script_path() -> Result<PathBuf> {
-        if let Ok(p) = std::env::var("BTWD_ML_PATH") {
-            return Ok(PathBuf::from(p));
-        }
-
-        // Try relative to current working directory first (systemd may set WorkingDirectory)
-        // This supports running from a checked-out repo without additional env vars.
-        if let Ok(cwd) = std::env::current_dir() {
-            let candidate = cwd.join("ml").join("btw_ml.py");
-            if candidate.exists() {
-                return Ok(candidate);
-            }
-        }
-
-        let exe = std::env::current_exe().map_err(|e| BtwError::ParseError { path: PathBuf::new(), kind: "ml", message: format!("current_exe error: {}", e) })?;
-        let exe_dir = exe
-            .parent()
-            .ok_or_else(|| BtwError::ParseError { path: PathBuf::new(), kind: "ml", message: "could not get exe parent".into() })?;
-        // Try alongside the binary (packaged deploy)
-        let candidate1 = exe_dir.join("ml").join("btw_ml.py");
-        if candidate1.exists() {
-            return Ok(candidate1);
-        }
-        // Try project root (../.. from target/release)
-        if let Some(project_root) = exe_dir.parent().and_then(|p| p.parent()) {
-            let candidate2 = project_root.join("ml").join("btw_ml.py");
-            if candidate2.exists() {
-                return Ok(candidate2);
-            }
-        }
-        Err(BtwError::MissingFile {
-            path: PathBuf::from("ml").join("btw_ml.py"),
-            kind: "ml_worker",
-        })
+        let explicit = crate::config_env::var::<String>("BTWD_ML_PATH").map(PathBuf::from);
+        crate::paths::Paths::from_env()?.data_file(crate::paths::ML_SCRIPT, explicit)
     }
 
     fn spawn(&mut self) -> Result<()> {
+        // Respawn after a timeout/crash: never leave the previous child behind.
+        self.shutdown();
         let python = Self::python_cmd();
 
         // Log which Python interpreter we spawn. This is critical under systemd,
         // where PATH/env can differ from interactive shells.
-        println!("ML worker: spawning with python={}", python);
+        log::info!("ml: spawning worker with python={}", python);
+        log::info!(
+            "ml: asr language={} model={} options={}",
+            self.asr.language.as_deref().unwrap_or("auto"),
+            self.asr.model.as_deref().unwrap_or("default"),
+            serde_json::to_string(&self.asr.options).unwrap_or_default()
+        );
 
         let mut child = Command::new(python)
             .arg(&self.script_path)
@@ -127,129 +377,876 @@ impl MLWorker {
             .stdout(Stdio::piped())
             .stderr(Stdio::inherit())
             .spawn()
-            .map_err(|e| BtwError::ParseError { path: self.script_path.clone(), kind: "ml", message: format!("spawn ML worker failed: {}", e) })?;
+            .map_err(|source| BtwError::WorkerSpawn { source })?;
         let stdin = child
             .stdin
             .take()
-            .ok_or_else(|| BtwError::ParseError { path: self.script_path.clone(), kind: "ml", message: "worker stdin missing".into() })?;
+            .ok_or_else(|| BtwError::Worker { message: "worker stdin missing".into() })?;
         let stdout = child
             .stdout
             .take()
-            .ok_or_else(|| BtwError::ParseError { path: self.script_path.clone(), kind: "ml", message: "worker stdout missing".into() })?;
-        // Spawn a reader thread to forward lines to a channel
+            .ok_or_else(|| BtwError::Worker { message: "worker stdout missing".into() })?;
+        // Spawn a reader thread to forward frames to a channel.
+        // Each spawn gets a fresh stream sink so a previous worker's reader can't
+        // feed events into a new stream.
         let (tx, rx) = mpsc::sync_channel::<String>(100);
+        let sink: StreamSink = Arc::new(Mutex::new(None));
+        self.stream_sink = sink.clone();
+        self.stream_rate = None;
+        let alive = Arc::new(AtomicBool::new(true));
+        self.reader_alive = alive.clone();
         std::thread::spawn(move || {
             let mut br = BufReader::new(stdout);
             loop {
-                let mut buf = String::new();
-                match br.read_line(&mut buf) {
-                    Ok(0) => break, // EOF
-                    Ok(_) => {
-                        let _ = tx.send(buf);
+                match read_frame(&mut br) {
+                    Ok(None) => break, // EOF
+                    Ok(Some(frame)) => {
+                        if let Some(line) = route_stream_line(&sink, frame) {
+                            // Receiver dropped: the MLWorker moved on (respawn/shutdown).
+                            if tx.send(line).is_err() {
+                                break;
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        // A bad length leaves no way to find the next frame.
+                        log::warn!("ml: unreadable worker output, dropping the stream: {}", e);
+                        break;
                     }
-                    Err(_) => break,
                 }
             }
+            alive.store(false, Ordering::SeqCst);
+            // Worker gone: unblock any in-flight stream consumer.
+            if let Some((_, tx)) = sink.lock().unwrap_or_else(|p| p.into_inner()).take() {
+                let _ = tx.send(AsrEvent::Failed("worker exited mid-stream".into()));
+            }
         });
         self.stdin = Some(stdin);
         self.resp_rx = Some(rx);
         self.child = Some(child);
+        if let Err(e) = self.handshake() {
+            // Don't leave an incompatible worker running behind a failed spawn.
+            self.shutdown();
+            return Err(e);
+        }
         Ok(())
     }
 
+    /// Stop the worker: ask it to exit, close its stdin, give it a short grace
+    /// period, then kill. The child is always reaped so no zombie is left.
+    /// Safe to call repeatedly; also runs on Drop.
+    pub fn shutdown(&mut self) {
+        let Some(mut child) = self.child.take() else { return };
+        let _ = self.write_line(r#"{"type":"shutdown"}"#);
+        // Closing stdin is a second exit signal (EOF) for workers that ignore the request.
+        self.stdin = None;
+
+        let deadline = Instant::now() + Duration::from_millis(Self::shutdown_grace_ms());
+        loop {
+            match child.try_wait() {
+                Ok(Some(status)) => {
+                    log::info!("ml: worker exited ({})", status);
+                    break;
+                }
+                Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(20)),
+                _ => {
+                    log::warn!("ml: worker did not exit in time; killing");
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    break;
+                }
+            }
+        }
+        // Dropping the receiver lets the reader thread exit on its next line.
+        self.resp_rx = None;
+        self.clear_stream();
+    }
+
+    /// Ask the worker for its protocol version and optional request types.
+    ///
+    /// Fails fast when the worker speaks a different wire protocol, since every
+    /// audio request would otherwise end in an unreadable parse error or timeout.
+    fn handshake(&mut self) -> Result<()> {
+        self.capabilities.clear();
+        self.embed_model = None;
+
+        self.write_line(r#"{"type":"hello"}"#)?;
+        let timeout = Duration::from_secs(Self::handshake_timeout_secs());
+        let line = match self.resp_rx.as_ref().map(|rx| rx.recv_timeout(timeout)) {
+            Some(Ok(line)) => line,
+            _ => {
+                return Err(BtwError::Worker {
+                    message: format!(
+                        "worker did not answer the handshake within {}s (workers before protocol v{} cannot read framed requests)",
+                        timeout.as_secs(),
+                        PROTOCOL_VERSION
+                    ),
+                })
+            }
+        };
+        // Workers that predate the handshake answer with an error line: protocol 1.
+        let protocol = match serde_json::from_str::<HelloResponse>(line.trim()) {
+            Ok(hello) if hello.typ == "hello" => {
+                self.capabilities = hello.capabilities;
+                self.embed_model = hello.embed_model;
+                hello.protocol
+            }
+            _ => 1,
+        };
+        if protocol != PROTOCOL_VERSION {
+            self.capabilities.clear();
+            return Err(BtwError::Worker {
+                message: format!(
+                    "worker speaks protocol v{} but btwd requires v{}; update ml/btw_ml.py to match this build",
+                    protocol, PROTOCOL_VERSION
+                ),
+            });
+        }
+        log::info!("ml: worker protocol=v{} capabilities={:?}", protocol, self.capabilities);
+        Ok(())
+    }
+
+    /// Whether the running worker advertised `capability` during the handshake.
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
+
+    /// Name of the embedding model reported by the worker, if any.
+    pub fn embed_model(&self) -> Option<&str> {
+        self.embed_model.as_deref()
+    }
+
+    fn next_request_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Throw away anything already queued: by construction nobody is waiting
+    /// for it, so it can only be a late answer to an abandoned request.
+    fn drain_stale(&self) {
+        if let Some(rx) = &self.resp_rx {
+            let mut n = 0;
+            while rx.try_recv().is_ok() {
+                n += 1;
+            }
+            if n > 0 {
+                log::debug!("ml: discarded {} stale worker line(s)", n);
+            }
+        }
+    }
+
+    /// Wait for the response to request `id`, skipping lines for other ids.
+    /// None on timeout or if the reader is gone.
+    fn recv_response(&self, id: u64, timeout: Duration) -> Option<String> {
+        let rx = self.resp_rx.as_ref()?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            let line = rx.recv_timeout(deadline.saturating_duration_since(Instant::now())).ok()?;
+            match response_id(&line) {
+                Some(got) if got == id => return Some(line),
+                got => log::debug!("ml: discarding stale response (id={:?}, expected {})", got, id),
+            }
+        }
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        if let Some(stdin) = &mut self.stdin {
+            write_frame(stdin, line.as_bytes())
+                .and_then(|_| stdin.flush())
+                .map_err(|e| BtwError::Worker { message: format!("write to worker failed: {}", e) })
+        } else {
+            Err(BtwError::Worker { message: "worker stdin unavailable".into() })
+        }
+    }
+
+    /// Write an audio header frame followed by its binary PCM payload.
+    fn write_audio(&mut self, typ: &'static str, id: u64, sample_rate: u32, samples: &[i16]) -> Result<usize> {
+        let payload = encode_pcm(samples);
+        let line = audio_header_line(typ, id, sample_rate, payload.len(), &self.asr)
+            .map_err(|e| BtwError::Worker { message: format!("serialize {} header failed: {}", typ, e) })?;
+        if let Some(stdin) = &mut self.stdin {
+            write_frame(stdin, line.as_bytes())
+                .and_then(|_| stdin.write_all(&payload))
+                .and_then(|_| stdin.flush())
+                .map_err(|e| BtwError::Worker { message: format!("write to worker failed: {}", e) })?;
+            Ok(4 + line.len() + payload.len())
+        } else {
+            Err(BtwError::Worker { message: "worker stdin unavailable".into() })
+        }
+    }
+
+    /// Compute sentence embeddings for `texts` (one vector per input, same order).
+    ///
+    /// Requires the `embed` capability; callers should check `supports("embed")` first.
+    /// Texts are sent [`EMBED_BATCH`] at a time so no reply outgrows a frame.
+    pub fn embed(&mut self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let mut vectors = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(EMBED_BATCH) {
+            vectors.extend(self.embed_batch(batch)?);
+        }
+        Ok(vectors)
+    }
+
+    fn embed_batch(&mut self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        self.ensure_alive()?;
+        if !self.supports("embed") {
+            return Err(BtwError::Worker { message: "worker does not support embeddings".into() });
+        }
+
+        let id = self.next_request_id();
+        let line = serde_json::to_string(&EmbedRequest { typ: "embed", id, texts })
+            .map_err(|e| BtwError::Worker { message: format!("serialize embed req failed: {}", e) })?;
+        self.drain_stale();
+        self.write_line(&line)?;
+
+        let timeout = Duration::from_secs(Self::read_timeout_secs());
+        let buf = match self.recv_response(id, timeout) {
+            Some(line) => line,
+            None => return Err(BtwError::Worker { message: "embed read timeout".into() }),
+        };
+        let resp: EmbedResponse = serde_json::from_str(buf.trim())
+            .map_err(|e| BtwError::Worker { message: format!("parse embed resp failed: {}", e) })?;
+        if let Some(err) = resp.error.filter(|e| !e.is_empty()) {
+            return Err(BtwError::Worker { message: format!("worker embed error: {}", err) });
+        }
+        if resp.vectors.len() != texts.len() {
+            return Err(BtwError::Worker {
+                message: format!("embed returned {} vectors for {} texts", resp.vectors.len(), texts.len()),
+            });
+        }
+        Ok(resp.vectors)
+    }
+
+    /// A worker process is running and not failing every request.
+    pub fn is_alive(&self) -> bool {
+        self.child.is_some() && !self.is_degraded()
+    }
+
+    /// True once `max_failures` consecutive failures have been seen; requests
+    /// fail fast until a retry succeeds.
+    pub fn is_degraded(&self) -> bool {
+        self.failures >= self.max_failures
+    }
+
+    fn unavailable(&self, now: Instant) -> BtwError {
+        BtwError::WorkerUnavailable {
+            failures: self.failures,
+            retry_in: self.retry_at.map(|t| t.saturating_duration_since(now)).unwrap_or_default(),
+            last_error: self.last_failure.clone(),
+        }
+    }
+
+    /// Count failures and successes of a request so a crash-looping worker
+    /// backs off instead of being relaunched on every wake.
+    fn track<T>(&mut self, res: Result<T>) -> Result<T> {
+        match &res {
+            Ok(_) => {
+                if self.failures > 0 {
+                    log::info!("ml: worker recovered after {} failure(s)", self.failures);
+                }
+                self.failures = 0;
+                self.retry_at = None;
+            }
+            Err(BtwError::WorkerUnavailable { .. }) => {}
+            Err(e) => {
+                self.failures = self.failures.saturating_add(1);
+                self.last_failure = e.to_string();
+                let delay = backoff_delay(self.failures, self.backoff_max);
+                self.retry_at = Some(Instant::now() + delay);
+                log::warn!(
+                    "ml: worker failure {} (next respawn allowed in {}s{})",
+                    self.failures,
+                    delay.as_secs(),
+                    if self.is_degraded() { ", degraded" } else { "" }
+                );
+            }
+        }
+        res
+    }
+
     fn ensure_alive(&mut self) -> Result<()> {
+        let now = Instant::now();
+        let backing_off = self.retry_at.map(|t| now < t).unwrap_or(false);
+        if self.is_degraded() && backing_off {
+            return Err(self.unavailable(now));
+        }
         let need_respawn = if let Some(child) = &mut self.child {
             match child.try_wait() {
                 Ok(Some(_status)) => true,
-                Ok(None) => false,
+                // A live child whose output can no longer be read is as good as dead.
+                Ok(None) => !self.reader_alive.load(Ordering::SeqCst),
                 Err(_) => true,
             }
         } else {
             true
         };
         if need_respawn {
+            if backing_off {
+                return Err(self.unavailable(now));
+            }
             self.spawn()?;
         }
         Ok(())
     }
 
+    fn clear_stream(&mut self) {
+        self.stream_rate = None;
+        *self.stream_sink.lock().unwrap_or_else(|p| p.into_inner()) = None;
+    }
+
+    /// Start a streamed transcription. Audio is then fed with `push_chunk` and
+    /// closed with `end_stream`; partials and the final result arrive on the
+    /// returned receiver. Requires the `asr_stream` capability.
+    pub fn begin_stream(&mut self, sample_rate: u32) -> Result<Receiver<AsrEvent>> {
+        self.ensure_alive()?;
+        if !self.supports("asr_stream") {
+            return Err(BtwError::Worker { message: "worker does not support streaming ASR".into() });
+        }
+        let (tx, rx) = mpsc::channel();
+        self.stream_id = self.next_request_id();
+        *self.stream_sink.lock().unwrap_or_else(|p| p.into_inner()) = Some((self.stream_id, tx));
+        self.stream_rate = Some(sample_rate);
+        log::debug!("asr: stream start (sample_rate={})", sample_rate);
+        Ok(rx)
+    }
+
+    pub fn push_chunk(&mut self, samples: &[i16]) -> Result<()> {
+        let sample_rate = self.stream_rate.ok_or_else(|| BtwError::Worker {
+            message: "no ASR stream in progress".into(),
+        })?;
+        let res = self.write_audio("asr_chunk", self.stream_id, sample_rate, samples).map(|_| ());
+        if res.is_err() {
+            self.clear_stream();
+        }
+        res
+    }
+
+    pub fn end_stream(&mut self) -> Result<()> {
+        if self.stream_rate.take().is_none() {
+            return Err(BtwError::Worker { message: "no ASR stream in progress".into() });
+        }
+        let res = self.write_line(&format!(r#"{{"type":"asr_end","id":{}}}"#, self.stream_id));
+        if res.is_err() {
+            self.clear_stream();
+        }
+        res
+    }
+
+    /// Stream a complete buffer in half-second chunks over the chunked
+    /// (`asr_chunk`/`asr_end`) protocol.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn stream_buffer(&mut self, samples: Vec<i16>, sample_rate: u32) -> Result<Receiver<AsrEvent>> {
+        let rx = self.begin_stream(sample_rate)?;
+        let chunk = (sample_rate as usize / 2).max(1);
+        for c in samples.chunks(chunk) {
+            self.push_chunk(c)?;
+        }
+        self.end_stream()?;
+        Ok(rx)
+    }
+
+    /// Send a whole utterance as one `asr_stream` request. The worker answers
+    /// with partial transcripts of the audio so far and then the final result;
+    /// each is forwarded as soon as the reader thread sees it, and the receiver
+    /// closes after the final one. Requires the `asr_stream_batch` capability.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn transcribe_streaming(&mut self, samples: Vec<i16>, sample_rate: u32) -> Result<Receiver<PartialTranscript>> {
+        let events = self.start_asr_stream(samples, sample_rate)?;
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            for ev in events {
+                let (t, done) = match ev {
+                    AsrEvent::Partial(text) => (PartialTranscript { text, is_final: false, error: None }, false),
+                    AsrEvent::Final(r) => (PartialTranscript { text: r.text, is_final: true, error: r.error }, true),
+                    AsrEvent::Failed(msg) => (PartialTranscript { text: String::new(), is_final: true, error: Some(msg) }, true),
+                };
+                if tx.send(t).is_err() || done {
+                    break;
+                }
+            }
+        });
+        Ok(rx)
+    }
+
+    /// [`transcribe_streaming`](Self::transcribe_streaming) driven to
+    /// completion: partials go to `on_partial`, the final result is returned.
+    pub fn transcribe_with_partials(&mut self, samples: Vec<i16>, sample_rate: u32, on_partial: impl FnMut(&str)) -> Result<AsrResponse> {
+        let res = self.start_asr_stream(samples, sample_rate).and_then(|rx| self.wait_stream_final_once(&rx, on_partial));
+        self.track(res)
+    }
+
+    fn start_asr_stream(&mut self, samples: Vec<i16>, sample_rate: u32) -> Result<Receiver<AsrEvent>> {
+        self.ensure_alive()?;
+        if !self.supports("asr_stream_batch") {
+            return Err(BtwError::Worker { message: "worker does not support asr_stream requests".into() });
+        }
+        let (tx, rx) = mpsc::channel();
+        let id = self.next_request_id();
+        self.drain_stale();
+        self.stream_id = id;
+        *self.stream_sink.lock().unwrap_or_else(|p| p.into_inner()) = Some((id, tx));
+        if let Err(e) = self.write_audio("asr_stream", id, sample_rate, &samples) {
+            self.clear_stream();
+            return Err(e);
+        }
+        log::debug!("asr: asr_stream request sent (id={}, samples={})", id, samples.len());
+        Ok(rx)
+    }
+
+    /// Block until the stream's final result, forwarding partials to `on_partial`.
+    pub fn wait_stream_final(&mut self, rx: &Receiver<AsrEvent>, on_partial: impl FnMut(&str)) -> Result<AsrResponse> {
+        let res = self.wait_stream_final_once(rx, on_partial);
+        self.track(res)
+    }
+
+    fn wait_stream_final_once(&mut self, rx: &Receiver<AsrEvent>, mut on_partial: impl FnMut(&str)) -> Result<AsrResponse> {
+        let started = Instant::now();
+        let deadline = started + Duration::from_secs(Self::read_timeout_secs());
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match rx.recv_timeout(remaining) {
+                Ok(AsrEvent::Partial(text)) => on_partial(&text),
+                Ok(AsrEvent::Final(resp)) => return Ok(resp),
+                Ok(AsrEvent::Failed(msg)) => {
+                    // The worker either died or produced garbage; start fresh
+                    // rather than racing try_wait() on a half-exited child.
+                    log::warn!("asr: stream failed ({}); respawning worker", msg);
+                    self.clear_stream();
+                    self.spawn()?;
+                    return Err(BtwError::Worker { message: format!("ASR stream failed: {}", msg) });
+                }
+                Err(_) => {
+                    log::warn!("asr: stream final timeout/disconnect; respawning");
+                    self.clear_stream();
+                    self.spawn()?;
+                    return Err(BtwError::AsrTimeout { elapsed: started.elapsed() });
+                }
+            }
+        }
+    }
+
+    #[cfg(test)]
+    fn pid(&self) -> Option<u32> {
+        self.child.as_ref().map(|c| c.id())
+    }
+
     pub fn transcribe(&mut self, samples: Vec<i16>, sample_rate: u32) -> Result<AsrResponse> {
+        let res = self.transcribe_once(samples, sample_rate);
+        self.track(res)
+    }
+
+    fn transcribe_once(&mut self, samples: Vec<i16>, sample_rate: u32) -> Result<AsrResponse> {
         self.ensure_alive()?;
+        // A batch request must never have its result diverted to a stale stream.
+        self.clear_stream();
 
         let started = Instant::now();
-        eprintln!(
+        log::debug!(
             "asr: request start (sample_rate={}, samples={}, approx_sec={:.2})",
             sample_rate,
             samples.len(),
             samples.len() as f64 / sample_rate as f64
         );
 
-        let req = AsrRequest {
-            typ: "asr",
-            audio_format: "pcm_s16le",
-            sample_rate,
-            samples,
-        };
-        let line = serde_json::to_string(&req)
-            .map_err(|e| BtwError::ParseError { path: self.script_path.clone(), kind: "ml", message: format!("serialize ASR req failed: {}", e) })?;
-
-        eprintln!("asr: sending request to worker (bytes={})", line.len());
-
-        // Write request
-        if let Some(stdin) = &mut self.stdin {
-            stdin
-                .write_all(line.as_bytes())
-                .and_then(|_| stdin.write_all(b"\n"))
-                .and_then(|_| stdin.flush())
-                .map_err(|e| BtwError::ParseError { path: self.script_path.clone(), kind: "ml", message: format!("write to worker failed: {}", e) })?;
-        } else {
-            return Err(BtwError::ParseError { path: self.script_path.clone(), kind: "ml", message: "worker stdin unavailable".into() });
-        }
+        // Write request (header + binary PCM; ~2 bytes/sample vs ~6 as JSON numbers)
+        let id = self.next_request_id();
+        self.drain_stale();
+        let encode_started = Instant::now();
+        let bytes = self.write_audio("asr", id, sample_rate, &samples)?;
+        log::debug!(
+            "asr: sent request to worker (id={}, bytes={}, write_us={})",
+            id,
+            bytes,
+            encode_started.elapsed().as_micros()
+        );
 
         let timeout = Duration::from_secs(Self::read_timeout_secs());
         let timeout_retry = Duration::from_secs(Self::read_timeout_retry_secs());
 
-        // Read response line with timeout
-        let buf = if let Some(rx) = &self.resp_rx {
-            match rx.recv_timeout(timeout) {
-                Ok(line) => line,
-                Err(_) => {
-                    // Timeout or disconnected; respawn worker
-                    eprintln!(
-                        "asr: worker read timeout/disconnect after {}s; respawning",
-                        timeout.as_secs()
-                    );
-                    self.spawn()?;
-                    // Try once more after respawn
-                    if let Some(rx2) = &self.resp_rx {
-                        rx2.recv_timeout(timeout_retry)
-                            .map_err(|_| BtwError::ParseError { path: self.script_path.clone(), kind: "ml", message: "ASR read timeout".into() })?
-                    } else {
-                        return Err(BtwError::ParseError { path: self.script_path.clone(), kind: "ml", message: "ASR reader channel missing".into() });
-                    }
-                }
+        // Read the response for this id; anything else is a leftover and is skipped.
+        let buf = match self.recv_response(id, timeout) {
+            Some(line) => line,
+            None => {
+                // Timeout or disconnected; respawn worker (fresh channel, so nothing
+                // from the old process can leak through) and resend once.
+                log::warn!(
+                    "asr: worker read timeout/disconnect after {}s; respawning",
+                    timeout.as_secs()
+                );
+                self.spawn()?;
+                let retry_id = self.next_request_id();
+                self.write_audio("asr", retry_id, sample_rate, &samples)?;
+                self.recv_response(retry_id, timeout_retry)
+                    .ok_or_else(|| BtwError::AsrTimeout { elapsed: started.elapsed() })?
             }
-        } else {
-            return Err(BtwError::ParseError { path: self.script_path.clone(), kind: "ml", message: "ASR reader not initialized".into() });
         };
 
         let trimmed = buf.trim();
         let preview: String = trimmed.chars().take(240).collect();
-        eprintln!(
+        log::debug!(
             "asr: worker response received (elapsed_ms={}, preview={})",
             started.elapsed().as_millis(),
             preview
         );
 
         let resp: AsrResponse = serde_json::from_str(trimmed)
-            .map_err(|e| BtwError::ParseError { path: self.script_path.clone(), kind: "ml", message: format!("parse ASR resp failed: {}", e) })?;
+            .map_err(|e| BtwError::Worker { message: format!("parse ASR resp failed: {}", e) })?;
 
-        eprintln!(
+        log::debug!(
             "asr: parsed result (elapsed_ms={}, text_len={}, has_error={})",
             started.elapsed().as_millis(),
             resp.text.len(),
-            resp.error.as_deref().unwrap_or("").is_empty() == false
+            !resp.error.as_deref().unwrap_or("").is_empty()
         );
         Ok(resp)
     }
 }
+
+impl Drop for MLWorker {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Minimal stand-in for ml/btw_ml.py (speaking the framed v4 protocol): one
+    // partial per chunk, optional crash, optional stale output (a leftover frame
+    // after hello and a late reply to an older id before the first batch result).
+    // Embeddings are 384 full-precision floats, the first one numbering the text;
+    // like the real worker it refuses replies over the frame cap, and "@GARBAGE@"
+    // makes it write a corrupt length prefix.
+    const FAKE_WORKER: &str = r#"
+import json, struct, sys
+CRASH_AT_CHUNK = @CRASH@
+STALE = @STALE@
+MAX_FRAME_BYTES = 1024 * 1024
+chunks = 0
+batches = 0
+def send(out):
+    body = json.dumps(out).encode()
+    sys.stdout.buffer.write(struct.pack("<I", len(body)) + body)
+    sys.stdout.flush()
+inp = sys.stdin.buffer
+while True:
+    head = inp.read(4)
+    if len(head) < 4:
+        break
+    req = json.loads(inp.read(struct.unpack("<I", head)[0]))
+    if "byte_len" in req:
+        req["samples"] = [0] * (len(inp.read(req["byte_len"])) // 2)
+    t = req.get("type")
+    rid = req.get("id")
+    if t == "hello":
+        send({"type": "hello", "protocol": @PROTOCOL@, "capabilities": ["asr", "asr_stream", "asr_stream_batch", "embed"], "embed_model": "fake"})
+        if STALE:
+            send({"type": "asr_result", "id": 0, "text": "leftover", "confidence": None, "error": None})
+        continue
+    elif t == "asr_chunk":
+        chunks += 1
+        if chunks == CRASH_AT_CHUNK:
+            sys.exit(3)
+        out = {"type": "partial", "id": rid, "text": "partial %d" % chunks}
+    elif t == "asr_end":
+        out = {"type": "asr_result", "id": rid, "text": "final %d" % chunks, "confidence": None, "error": None}
+        chunks = 0
+    elif t == "asr_stream":
+        for n in (1, 2):
+            send({"type": "partial", "id": rid, "text": "heard %d" % n, "is_final": False})
+        out = {"type": "asr_result", "id": rid, "text": "stream %d" % len(req["samples"]), "confidence": None, "error": None, "is_final": True}
+    elif t == "embed":
+        if "@GARBAGE@" in req["texts"]:
+            sys.stdout.buffer.write(struct.pack("<I", 0xFFFFFFFF))
+            sys.stdout.flush()
+            continue
+        vectors = [[float(t.split()[-1])] + [0.012345678901234567] * 383 for t in req["texts"]]
+        out = {"type": "embed_result", "id": rid, "vectors": vectors}
+        if len(json.dumps(out)) > MAX_FRAME_BYTES:
+            out = {"type": "embed_result", "id": rid, "error": "response_too_large"}
+    elif t == "shutdown":
+        @ON_SHUTDOWN@
+    elif t == "asr":
+        batches += 1
+        if STALE and batches == 1:
+            send({"type": "asr_result", "id": rid - 1, "text": "late reply", "confidence": None, "error": None})
+        out = {"type": "asr_result", "id": rid, "text": "batch %d" % len(req["samples"]), "confidence": None, "error": None}
+    else:
+        continue
+    send(out)
+"#;
+
+    struct Fake {
+        crash_at_chunk: i32,
+        protocol: u32,
+        on_shutdown: &'static str,
+        stale: bool,
+    }
+
+    impl Default for Fake {
+        fn default() -> Self {
+            Fake { crash_at_chunk: -1, protocol: PROTOCOL_VERSION, on_shutdown: "break", stale: false }
+        }
+    }
+
+    impl Fake {
+        fn write(&self, name: &str) -> PathBuf {
+            let path = std::env::temp_dir().join(format!("btwd-fake-worker-{}-{}.py", name, std::process::id()));
+            let script = FAKE_WORKER
+                .replace("@CRASH@", &self.crash_at_chunk.to_string())
+                .replace("@PROTOCOL@", &self.protocol.to_string())
+                .replace("@ON_SHUTDOWN@", self.on_shutdown)
+                .replace("@STALE@", if self.stale { "True" } else { "False" });
+            std::fs::write(&path, script).unwrap();
+            path
+        }
+
+        fn spawn(&self, name: &str) -> MLWorker {
+            MLWorker::with_script(self.write(name), AsrCfg::default()).unwrap()
+        }
+    }
+
+    fn fake_worker(name: &str, crash_at_chunk: i32) -> MLWorker {
+        Fake { crash_at_chunk, ..Fake::default() }.spawn(name)
+    }
+
+    fn process_gone(pid: u32) -> bool {
+        // Reaped children disappear from /proc entirely (no zombie entry).
+        !std::path::Path::new(&format!("/proc/{}", pid)).exists()
+    }
+
+    #[test]
+    fn frames_carry_newlines_and_reject_bad_lengths() {
+        let mut buf = Vec::new();
+        // A raw newline inside a frame is just another byte.
+        write_frame(&mut buf, b"line one\nline two").unwrap();
+        write_frame(&mut buf, b"{}").unwrap();
+        assert_eq!(&buf[..4], &17u32.to_le_bytes());
+        let mut r = std::io::Cursor::new(buf);
+        assert_eq!(read_frame(&mut r).unwrap().as_deref(), Some("line one\nline two"));
+        assert_eq!(read_frame(&mut r).unwrap().as_deref(), Some("{}"));
+        assert_eq!(read_frame(&mut r).unwrap(), None);
+
+        let mut huge = ((MAX_FRAME_BYTES + 1) as u32).to_le_bytes().to_vec();
+        huge.extend_from_slice(b"{}");
+        assert_eq!(read_frame(&mut std::io::Cursor::new(huge)).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+        // A body cut short is an error, not a clean EOF.
+        let mut short = 10u32.to_le_bytes().to_vec();
+        short.extend_from_slice(b"{}");
+        assert!(read_frame(&mut std::io::Cursor::new(short)).is_err());
+    }
+
+    #[test]
+    fn drop_stops_and_reaps_the_worker() {
+        let w = fake_worker("drop", -1);
+        let pid = w.pid().expect("worker running");
+        assert!(!process_gone(pid));
+        drop(w);
+        assert!(process_gone(pid));
+    }
+
+    #[test]
+    fn shutdown_kills_a_worker_that_ignores_it() {
+        let mut w = Fake { on_shutdown: "import time; time.sleep(60)", ..Fake::default() }.spawn("stubborn");
+        let pid = w.pid().expect("worker running");
+        let started = Instant::now();
+        w.shutdown();
+        assert!(process_gone(pid));
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(w.pid().is_none());
+    }
+
+    #[test]
+    fn stale_responses_are_never_returned() {
+        let mut w = Fake { stale: true, ..Fake::default() }.spawn("stale");
+        // Both the leftover queued after hello and the late reply to an older id are skipped.
+        assert_eq!(w.transcribe(vec![0i16; 5], 16000).unwrap().text, "batch 5");
+        assert_eq!(w.transcribe(vec![0i16; 6], 16000).unwrap().text, "batch 6");
+    }
+
+    #[test]
+    fn stream_routing_checks_ids() {
+        let (tx, rx) = mpsc::channel();
+        let sink = Mutex::new(Some((7u64, tx)));
+        // Partial for another request: dropped, not forwarded anywhere.
+        assert!(route_stream_line(&sink, r#"{"type":"partial","id":6,"text":"old"}"#.into()).is_none());
+        // Result for another request: back to the normal response channel.
+        assert!(route_stream_line(&sink, r#"{"type":"asr_result","id":6,"text":"old","confidence":null,"error":null}"#.into()).is_some());
+        assert!(route_stream_line(&sink, r#"{"type":"partial","id":7,"text":"new"}"#.into()).is_none());
+        assert!(route_stream_line(&sink, r#"{"type":"asr_result","id":7,"text":"done","confidence":null,"error":null}"#.into()).is_none());
+        assert!(matches!(rx.try_recv(), Ok(AsrEvent::Partial(t)) if t == "new"));
+        assert!(matches!(rx.try_recv(), Ok(AsrEvent::Final(r)) if r.text == "done"));
+        assert!(sink.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn asr_options_are_sent_in_the_request_header() {
+        let mut asr = AsrCfg { language: Some("hi".into()), model: Some("whisper-large-v3".into()), ..AsrCfg::default() };
+        asr.options.insert("temperature".into(), serde_json::json!(0.2));
+        let line = audio_header_line("asr", 7, 16000, 4, &asr).unwrap();
+        let v: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(v["type"], "asr");
+        assert_eq!(v["id"], 7);
+        assert_eq!(v["language"], "hi");
+        assert_eq!(v["model"], "whisper-large-v3");
+        assert_eq!(v["options"]["temperature"], 0.2);
+        assert!(v.get("translate").is_none());
+
+        let asr = AsrCfg { translate: true, ..AsrCfg::default() };
+        let v: serde_json::Value = serde_json::from_str(&audio_header_line("asr", 9, 16000, 4, &asr).unwrap()).unwrap();
+        assert_eq!(v["translate"], true);
+
+        // Unset options are left out entirely.
+        let v: serde_json::Value = serde_json::from_str(&audio_header_line("asr", 8, 16000, 4, &AsrCfg::default()).unwrap()).unwrap();
+        assert!(v.get("language").is_none() && v.get("model").is_none() && v.get("options").is_none());
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let cap = Duration::from_secs(10);
+        let delays: Vec<u64> = (0..7).map(|f| backoff_delay(f, cap).as_secs()).collect();
+        assert_eq!(delays, vec![0, 1, 2, 4, 8, 10, 10]);
+        assert_eq!(backoff_delay(200, cap), cap);
+    }
+
+    #[test]
+    fn crash_looping_worker_backs_off_then_degrades() {
+        let mut w = fake_worker("backoff", -1);
+        w.max_failures = 2;
+        // Break the script and kill the worker: every respawn now fails.
+        std::fs::write(&w.script_path, "import sys\nsys.exit(1)\n").unwrap();
+        w.shutdown();
+
+        let first = w.transcribe(vec![0; 160], 16000);
+        assert!(matches!(first, Err(BtwError::Worker { .. })), "{:?}", first.err());
+        // Within the 1s backoff: refused without launching python.
+        match w.transcribe(vec![0; 160], 16000) {
+            Err(BtwError::WorkerUnavailable { failures: 1, retry_in, .. }) => assert!(retry_in <= Duration::from_secs(1)),
+            other => panic!("expected backoff, got {:?}", other.err()),
+        }
+        assert!(!w.is_degraded());
+
+        std::thread::sleep(Duration::from_millis(1100));
+        assert!(w.transcribe(vec![0; 160], 16000).is_err());
+        assert!(w.is_degraded());
+        let err = w.transcribe(vec![0; 160], 16000).err().unwrap();
+        assert!(matches!(err, BtwError::WorkerUnavailable { failures: 2, .. }));
+        assert!(err.to_string().contains("2 consecutive failures; next retry in"), "{}", err);
+    }
+
+    #[test]
+    fn good_response_resets_failures() {
+        let mut w = fake_worker("reset", -1);
+        w.failures = 3;
+        w.retry_at = Some(Instant::now());
+        assert!(w.transcribe(vec![0; 160], 16000).is_ok());
+        assert_eq!(w.failures, 0);
+        assert!(w.retry_at.is_none());
+    }
+
+    #[test]
+    fn deferred_worker_starts_on_first_request() {
+        let mut w = MLWorker::unstarted(Fake::default().write("deferred"), AsrCfg::default());
+        assert!(w.pid().is_none() && !w.is_alive());
+        assert!(w.transcribe(vec![0; 160], 16000).is_ok());
+        assert!(w.pid().is_some());
+    }
+
+    #[test]
+    fn pcm_is_little_endian() {
+        assert_eq!(encode_pcm(&[1, -2, 0x1234]), vec![0x01, 0x00, 0xfe, 0xff, 0x34, 0x12]);
+    }
+
+    #[test]
+    fn mismatched_protocol_fails_at_spawn() {
+        let path = Fake { protocol: 1, ..Fake::default() }.write("old");
+        let err = MLWorker::with_script(path, AsrCfg::default()).err().expect("v1 worker must be rejected");
+        let msg = err.to_string();
+        assert!(msg.contains("protocol v1") && msg.contains(&format!("requires v{}", PROTOCOL_VERSION)), "{}", msg);
+    }
+
+    #[test]
+    fn streaming_delivers_partials_then_final() {
+        let mut w = fake_worker("stream", -1);
+        assert!(w.supports("asr_stream"));
+        let rx = w.stream_buffer(vec![0i16; 32000], 16000).unwrap();
+        let mut partials = Vec::new();
+        let resp = w.wait_stream_final(&rx, |p| partials.push(p.to_string())).unwrap();
+        assert_eq!(partials, vec!["partial 1", "partial 2", "partial 3", "partial 4"]);
+        assert_eq!(resp.text, "final 4");
+
+        // Batch API still works on the same worker afterwards.
+        assert_eq!(w.transcribe(vec![0i16; 10], 16000).unwrap().text, "batch 10");
+    }
+
+    #[test]
+    fn asr_stream_request_forwards_partials_then_final() {
+        let mut w = fake_worker("asr-stream", -1);
+        let rx = w.transcribe_streaming(vec![0i16; 8], 16000).unwrap();
+        let got: Vec<PartialTranscript> = rx.iter().collect();
+        let texts: Vec<(&str, bool)> = got.iter().map(|t| (t.text.as_str(), t.is_final)).collect();
+        assert_eq!(texts, vec![("heard 1", false), ("heard 2", false), ("stream 8", true)]);
+
+        let mut partials = Vec::new();
+        let resp = w.transcribe_with_partials(vec![0i16; 9], 16000, |p| partials.push(p.to_string())).unwrap();
+        assert_eq!(partials, vec!["heard 1", "heard 2"]);
+        assert_eq!(resp.text, "stream 9");
+        assert_eq!(w.transcribe(vec![0i16; 3], 16000).unwrap().text, "batch 3");
+    }
+
+    #[test]
+    fn embeddings_for_many_examples_stay_under_the_frame_cap() {
+        let mut w = fake_worker("embed", -1);
+        // One reply for all of these would be ~1.7 MB.
+        let commands: Vec<crate::intent::IntentCommand> = (0..10)
+            .map(|c| crate::intent::IntentCommand {
+                id: format!("cmd{}", c),
+                description: String::new(),
+                examples: (0..20).map(|e| format!("example {}", c * 20 + e)).collect(),
+                dangerous: false,
+                priority: 0,
+                alias_of: None,
+                slots: Vec::new(),
+            })
+            .collect();
+        let index = crate::embedding::build_index(&mut w, &commands, None).unwrap();
+        assert_eq!(index.len(), 200);
+
+        let texts: Vec<String> = (0..200).map(|i| format!("text {}", i)).collect();
+        let vectors = w.embed(&texts).unwrap();
+        assert_eq!(vectors.len(), 200);
+        assert!(vectors.iter().enumerate().all(|(i, v)| v.len() == 384 && v[0] == i as f32));
+    }
+
+    #[test]
+    fn unreadable_output_respawns_the_worker_on_the_next_request() {
+        let mut w = fake_worker("garbage", -1);
+        let pid = w.pid().unwrap();
+        assert!(w.embed(&["@GARBAGE@".to_string()]).is_err());
+        // The child is still running, but its reader is gone.
+        let started = Instant::now();
+        assert_eq!(w.transcribe(vec![0i16; 5], 16000).unwrap().text, "batch 5");
+        assert_ne!(w.pid(), Some(pid));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn worker_crash_mid_stream_fails_the_stream_and_recovers() {
+        let mut w = fake_worker("crash", 2);
+        let rx = w.begin_stream(16000).unwrap();
+        for _ in 0..3 {
+            // Writes after the crash may fail with EPIPE; that's expected.
+            let _ = w.push_chunk(&[0i16; 100]);
+        }
+        let _ = w.end_stream();
+        let mut partials = Vec::new();
+        let res = w.wait_stream_final(&rx, |p| partials.push(p.to_string()));
+        assert!(res.is_err());
+        assert_eq!(partials, vec!["partial 1"]);
+
+        // Next request respawns the dead worker.
+        assert_eq!(w.transcribe(vec![0i16; 7], 16000).unwrap().text, "batch 7");
+    }
+}
@@ -0,0 +1,329 @@
+use crate::cancel::CancelToken;
+use std::collections::HashMap;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+
+const DEST: &str = "org.freedesktop.Notifications";
+const PATH: &str = "/org/freedesktop/Notifications";
+const IFACE: &str = "org.freedesktop.Notifications";
+
+/// One desktop notification, in the terms of the freedesktop spec.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    pub summary: String,
+    pub body: String,
+    /// `(key, label)` pairs; the key comes back in `ActionInvoked`.
+    pub actions: Vec<(String, String)>,
+    /// "low", "normal" or "critical".
+    pub urgency: &'static str,
+    pub category: Option<&'static str>,
+    pub transient: bool,
+    /// `x-canonical-private-synchronous` tag: replaces the previous
+    /// notification with the same tag on daemons that support it.
+    pub synchronous: Option<&'static str>,
+    /// Milliseconds; 0 never expires.
+    pub timeout_ms: i32,
+    /// Id of a notification of ours to update in place; 0 for a new one.
+    pub replaces_id: u32,
+}
+
+impl Notification {
+    pub fn new(summary: &str, body: &str, urgency: &'static str, timeout_ms: u64) -> Self {
+        Self {
+            summary: summary.to_string(),
+            body: body.to_string(),
+            actions: Vec::new(),
+            urgency,
+            category: None,
+            transient: false,
+            synchronous: None,
+            timeout_ms: timeout_ms.min(i32::MAX as u64) as i32,
+            replaces_id: 0,
+        }
+    }
+
+    pub fn action(mut self, key: &str, label: &str) -> Self {
+        self.actions.push((key.to_string(), label.to_string()));
+        self
+    }
+}
+
+/// Signals from the notification daemon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BusEvent {
+    ActionInvoked { id: u32, key: String },
+    Closed { id: u32 },
+}
+
+/// How notifications reach the daemon; the D-Bus client in production, a
+/// recorder in tests.
+pub trait NotificationTransport: Send + Sync {
+    /// Show `n` and return the id the daemon assigned to it.
+    fn notify(&self, n: &Notification) -> Result<u32, String>;
+
+    /// Take notification `id` down.
+    fn close(&self, id: u32) -> Result<(), String>;
+}
+
+/// What the user picked on an actionable notification, for main to act on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UiAction {
+    /// "Yes" on the confirmation for `request_id`.
+    Confirm(String),
+    /// "No" on the confirmation for `request_id`.
+    Cancel(String),
+    /// "Open in browser" on an answer.
+    Open(String),
+}
+
+/// What a shown notification's actions mean.
+enum Pending {
+    Confirm(String),
+    Open(String),
+    /// The listening toast's Cancel aborts the interaction directly.
+    Listening(CancelToken),
+}
+
+/// Sends notifications and turns their action signals into [`UiAction`]s.
+pub struct Notifier {
+    transport: Box<dyn NotificationTransport>,
+    pending: Mutex<HashMap<u32, Pending>>,
+    actions: Sender<UiAction>,
+}
+
+impl Notifier {
+    pub fn new(transport: Box<dyn NotificationTransport>) -> (Self, Receiver<UiAction>) {
+        let (actions, rx) = channel();
+        (Self { transport, pending: Mutex::new(HashMap::new()), actions }, rx)
+    }
+
+    /// A notification without actions.
+    pub fn show(&self, n: &Notification) {
+        if let Err(e) = self.transport.notify(n) {
+            log::warn!("ui: notification failed: {}", e);
+        }
+    }
+
+    /// Yes/No prompt for `request_id`; the answer arrives as
+    /// [`UiAction::Confirm`] or [`UiAction::Cancel`] carrying the same id.
+    /// Returns the notification id, to update it via `replaces_id`.
+    pub fn confirm(&self, request_id: &str, n: Notification) -> Option<u32> {
+        self.track(n.action("confirm", "Yes").action("cancel", "No"), Pending::Confirm(request_id.to_string()))
+    }
+
+    /// Take down notification `id`; its actions no longer resolve.
+    pub fn close(&self, id: u32) {
+        self.pending.lock().unwrap_or_else(|p| p.into_inner()).remove(&id);
+        if let Err(e) = self.transport.close(id) {
+            log::debug!("ui: closing notification {} failed: {}", id, e);
+        }
+    }
+
+    /// Answer with an "Open in browser" action for `url`.
+    pub fn answer_with_open(&self, url: &str, n: Notification) {
+        let _ = self.track(n.action("open", "Open in browser"), Pending::Open(url.to_string()));
+    }
+
+    /// Listening toast whose Cancel action cancels `cancel`.
+    pub fn listening(&self, cancel: &CancelToken, n: Notification) {
+        let _ = self.track(n.action("cancel", "Cancel"), Pending::Listening(cancel.clone()));
+    }
+
+    fn track(&self, n: Notification, pending: Pending) -> Option<u32> {
+        match self.transport.notify(&n) {
+            Ok(id) => {
+                self.pending.lock().unwrap_or_else(|p| p.into_inner()).insert(id, pending);
+                Some(id)
+            }
+            Err(e) => {
+                log::warn!("ui: notification failed: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Resolve a daemon signal against the notifications we showed. Signals
+    /// for other applications' notifications are ignored.
+    pub fn handle_event(&self, event: BusEvent) {
+        let (id, key) = match event {
+            BusEvent::ActionInvoked { id, key } => (id, key),
+            BusEvent::Closed { id } => {
+                self.pending.lock().unwrap_or_else(|p| p.into_inner()).remove(&id);
+                return;
+            }
+        };
+        let Some(pending) = self.pending.lock().unwrap_or_else(|p| p.into_inner()).remove(&id) else { return };
+        let action = match (pending, key.as_str()) {
+            (Pending::Confirm(request_id), "confirm") => UiAction::Confirm(request_id),
+            (Pending::Confirm(request_id), "cancel") => UiAction::Cancel(request_id),
+            (Pending::Open(url), "open") => UiAction::Open(url),
+            (Pending::Listening(cancel), "cancel") => {
+                log::info!("ui: cancel via listening notification");
+                cancel.cancel();
+                return;
+            }
+            (_, other) => {
+                log::debug!("ui: ignoring action '{}' on notification {}", other, id);
+                return;
+            }
+        };
+        let _ = self.actions.send(action);
+    }
+}
+
+/// org.freedesktop.Notifications over the session bus.
+pub struct ZbusTransport {
+    conn: zbus::blocking::Connection,
+}
+
+impl ZbusTransport {
+    /// Connect to the session bus and start forwarding `ActionInvoked` /
+    /// `NotificationClosed` on the returned receiver.
+    pub fn connect() -> Result<(Self, Receiver<BusEvent>), String> {
+        let conn = zbus::blocking::Connection::session().map_err(|e| format!("session bus: {}", e))?;
+        let proxy = zbus::blocking::Proxy::new(&conn, DEST, PATH, IFACE).map_err(|e| format!("notifications proxy: {}", e))?;
+        let signals = proxy.receive_all_signals().map_err(|e| format!("notification signals: {}", e))?;
+        let (tx, rx) = channel();
+        std::thread::spawn(move || {
+            for msg in signals {
+                let header = msg.header();
+                let event = match header.member().map(|m| m.as_str()) {
+                    Some("ActionInvoked") => msg.body().deserialize::<(u32, String)>().ok().map(|(id, key)| BusEvent::ActionInvoked { id, key }),
+                    Some("NotificationClosed") => msg.body().deserialize::<(u32, u32)>().ok().map(|(id, _reason)| BusEvent::Closed { id }),
+                    _ => None,
+                };
+                if let Some(event) = event {
+                    if tx.send(event).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        Ok((Self { conn }, rx))
+    }
+}
+
+fn urgency_level(urgency: &str) -> u8 {
+    match urgency {
+        "low" => 0,
+        "critical" => 2,
+        _ => 1,
+    }
+}
+
+impl NotificationTransport for ZbusTransport {
+    fn notify(&self, n: &Notification) -> Result<u32, String> {
+        use zbus::zvariant::Value;
+        let actions: Vec<&str> = n.actions.iter().flat_map(|(k, l)| [k.as_str(), l.as_str()]).collect();
+        let mut hints: HashMap<&str, Value> = HashMap::new();
+        hints.insert("urgency", Value::U8(urgency_level(n.urgency)));
+        if let Some(category) = n.category {
+            hints.insert("category", Value::from(category));
+        }
+        if n.transient {
+            hints.insert("transient", Value::Bool(true));
+        }
+        if let Some(tag) = n.synchronous {
+            hints.insert("x-canonical-private-synchronous", Value::from(tag));
+        }
+        let reply = self
+            .conn
+            .call_method(Some(DEST), PATH, Some(IFACE), "Notify", &("btwd", n.replaces_id, "", n.summary.as_str(), n.body.as_str(), actions, hints, n.timeout_ms))
+            .map_err(|e| e.to_string())?;
+        reply.body().deserialize::<u32>().map_err(|e| e.to_string())
+    }
+
+    fn close(&self, id: u32) -> Result<(), String> {
+        self.conn.call_method(Some(DEST), PATH, Some(IFACE), "CloseNotification", &(id,)).map(|_| ()).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// Records notifications and hands out ids 1, 2, 3...
+    #[derive(Default)]
+    struct Recorder {
+        sent: Arc<Mutex<Vec<Notification>>>,
+    }
+
+    impl NotificationTransport for Recorder {
+        fn notify(&self, n: &Notification) -> Result<u32, String> {
+            let mut sent = self.sent.lock().unwrap();
+            sent.push(n.clone());
+            Ok(if n.replaces_id != 0 { n.replaces_id } else { sent.len() as u32 })
+        }
+
+        fn close(&self, _id: u32) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    fn notifier() -> (Notifier, Receiver<UiAction>, Arc<Mutex<Vec<Notification>>>) {
+        let rec = Recorder::default();
+        let sent = rec.sent.clone();
+        let (n, rx) = Notifier::new(Box::new(rec));
+        (n, rx, sent)
+    }
+
+    fn invoke(id: u32, key: &str) -> BusEvent {
+        BusEvent::ActionInvoked { id, key: key.into() }
+    }
+
+    #[test]
+    fn confirmation_answers_carry_the_request_id() {
+        let (n, rx, sent) = notifier();
+        n.confirm("req-1", Notification::new("btwd", "Confirm command", "critical", 0));
+        n.confirm("req-2", Notification::new("btwd", "Confirm command", "critical", 0));
+        assert_eq!(sent.lock().unwrap()[0].actions, vec![("confirm".into(), "Yes".into()), ("cancel".into(), "No".into())]);
+        n.handle_event(invoke(2, "cancel"));
+        n.handle_event(invoke(1, "confirm"));
+        assert_eq!(rx.try_recv(), Ok(UiAction::Cancel("req-2".into())));
+        assert_eq!(rx.try_recv(), Ok(UiAction::Confirm("req-1".into())));
+        // Each notification answers once.
+        n.handle_event(invoke(1, "confirm"));
+        assert!(rx.try_recv().is_err());
+
+        // An updated prompt keeps answering for its request; a closed one doesn't.
+        let id = n.confirm("req-3", Notification::new("btwd", "5s left", "critical", 0)).unwrap();
+        let mut update = Notification::new("btwd", "4s left", "critical", 0);
+        update.replaces_id = id;
+        assert_eq!(n.confirm("req-3", update), Some(id));
+        n.handle_event(invoke(id, "confirm"));
+        assert_eq!(rx.try_recv(), Ok(UiAction::Confirm("req-3".into())));
+        let id = n.confirm("req-4", Notification::new("btwd", "5s left", "critical", 0)).unwrap();
+        n.close(id);
+        n.handle_event(invoke(id, "confirm"));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn closed_and_foreign_notifications_are_ignored() {
+        let (n, rx, _) = notifier();
+        n.answer_with_open("https://example.com/?q=x", Notification::new("btwd", "answer", "normal", 5000));
+        n.handle_event(BusEvent::Closed { id: 1 });
+        n.handle_event(invoke(1, "open"));
+        n.handle_event(invoke(42, "confirm"));
+        assert!(rx.try_recv().is_err());
+
+        n.answer_with_open("https://example.com/?q=y", Notification::new("btwd", "answer", "normal", 5000));
+        n.handle_event(invoke(2, "default"));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn open_and_listening_actions() {
+        let (n, rx, _) = notifier();
+        n.answer_with_open("https://example.com/?q=x", Notification::new("btwd", "answer", "normal", 5000));
+        n.handle_event(invoke(1, "open"));
+        assert_eq!(rx.try_recv(), Ok(UiAction::Open("https://example.com/?q=x".into())));
+
+        let cancel = CancelToken::new();
+        n.listening(&cancel, Notification::new("btwd", "Listening…", "normal", 1500));
+        n.handle_event(invoke(2, "cancel"));
+        assert!(cancel.is_canceled());
+        assert!(rx.try_recv().is_err());
+    }
+}
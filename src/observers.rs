@@ -0,0 +1,124 @@
+use crate::executor::ExecObserver;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Instant;
+
+/// Desktop notifications for execution events.
+pub struct NotifyObserver {
+    pub osd: bool,
+    pub timeout_ms: u64,
+}
+
+impl ExecObserver for NotifyObserver {
+    fn on_executed(&self, id: &str, stdout: &str) {
+        // Most commands print nothing; show output only when there is some.
+        if !stdout.is_empty() {
+            crate::ui::notify_text(self.osd, self.timeout_ms, "btwd", &format!("{}: {}", id, stdout));
+        }
+    }
+
+    // The actionable prompt needs the request id and is posted by the main
+    // loop, which also handles the Do-Not-Disturb fallback.
+    fn on_pending(&self, _id: &str, _preview: &str, _deadline: Instant) {}
+
+    fn on_canceled(&self, id: &str, reason: &str) {
+        if reason == "confirmation timed out" {
+            crate::ui::notify_confirmation_expired(self.osd, self.timeout_ms, id);
+        }
+    }
+
+    fn on_rejected(&self, _reason: &str) {}
+
+    fn on_dry_run(&self, id: &str, command: &str) {
+        crate::ui::notify_text(self.osd, self.timeout_ms, "btwd", &format!("Dry run ({}): {}", id, command));
+    }
+}
+
+/// Appends every execution event to `$XDG_STATE_HOME/btw/audit.jsonl`.
+///
+/// Best-effort like the history file: an unwritable log never blocks a command.
+/// Each line is written and the file closed before returning, so a shutdown
+/// never leaves a partial entry behind.
+pub struct LogObserver {
+    path: Option<PathBuf>,
+}
+
+impl LogObserver {
+    pub fn new() -> Self {
+        let path = xdg::BaseDirectories::with_prefix("btw").ok().and_then(|x| x.place_state_file("audit.jsonl").ok());
+        Self { path }
+    }
+
+    #[cfg(test)]
+    fn with_path(path: PathBuf) -> Self {
+        Self { path: Some(path) }
+    }
+
+    fn append(&self, entry: serde_json::Value) {
+        let Some(path) = &self.path else { return };
+        let ts = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let mut entry = entry;
+        entry["ts"] = ts.into();
+        let res = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut f| writeln!(f, "{}", entry));
+        if let Err(e) = res {
+            log::warn!("audit: failed to append to {}: {}", path.display(), e);
+        }
+    }
+}
+
+impl ExecObserver for LogObserver {
+    fn on_executed(&self, id: &str, stdout: &str) {
+        self.append(serde_json::json!({"event": "executed", "id": id, "stdout": stdout}));
+    }
+
+    fn on_pending(&self, id: &str, preview: &str, deadline: Instant) {
+        let secs = deadline.saturating_duration_since(Instant::now()).as_secs();
+        self.append(serde_json::json!({"event": "pending", "id": id, "preview": preview, "expires_in_secs": secs}));
+    }
+
+    fn on_canceled(&self, id: &str, reason: &str) {
+        self.append(serde_json::json!({"event": "canceled", "id": id, "reason": reason}));
+    }
+
+    fn on_rejected(&self, reason: &str) {
+        self.append(serde_json::json!({"event": "rejected", "reason": reason}));
+    }
+
+    fn on_dry_run(&self, id: &str, command: &str) {
+        self.append(serde_json::json!({"event": "dry_run", "id": id, "command": command}));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn audit_log_gets_one_line_per_event() {
+        let path = std::env::temp_dir().join(format!("btwd-audit-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let log = LogObserver::with_path(path.clone());
+        log.on_pending("shutdown", "Power off", Instant::now() + std::time::Duration::from_secs(10));
+        log.on_canceled("shutdown", "user canceled");
+        log.on_executed("volume_up", "");
+        log.on_rejected("unknown command id 'x': not in allow-list");
+
+        let lines: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        let events: Vec<&str> = lines.iter().map(|l| l["event"].as_str().unwrap()).collect();
+        assert_eq!(events, vec!["pending", "canceled", "executed", "rejected"]);
+        assert_eq!(lines[1]["reason"], "user canceled");
+        assert!(lines.iter().all(|l| l["ts"].is_u64()));
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,209 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Where a parameter value came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Provenance {
+    /// Extracted from the transcript by the deterministic router.
+    Deterministic,
+    /// Supplied by the LLM classifier.
+    Llm,
+    /// Filled in from the command's parameter spec.
+    Default,
+    /// Provided value was out of range and clamped to the spec bounds.
+    Clamped,
+    /// Parsed from a legacy flat JSON object with no provenance recorded.
+    Unknown,
+    /// Derived from the previous command by a follow-up ("a bit more", "again").
+    FollowUp,
+}
+
+/// Whether the parameters have been checked against the command's spec.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Validation {
+    #[default]
+    Unchecked,
+    Valid,
+    Invalid(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Param {
+    pub value: Value,
+    pub provenance: Provenance,
+}
+
+/// Typed command parameters: name → value with provenance, plus validation status.
+///
+/// Serializes as `{"entries": {name: {value, provenance}}, "validation": ...}`;
+/// a legacy flat object such as `{"value": 40}` still deserializes (provenance `unknown`).
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(from = "ParamsRepr")]
+pub struct Params {
+    entries: BTreeMap<String, Param>,
+    #[serde(default)]
+    validation: Validation,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ParamsRepr {
+    Typed {
+        entries: BTreeMap<String, Param>,
+        #[serde(default)]
+        validation: Validation,
+    },
+    Legacy(Value),
+}
+
+impl From<ParamsRepr> for Params {
+    fn from(r: ParamsRepr) -> Self {
+        match r {
+            ParamsRepr::Typed { entries, validation } => Params { entries, validation },
+            ParamsRepr::Legacy(v) => Params::from_value(&v, Provenance::Unknown),
+        }
+    }
+}
+
+impl Params {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Import a flat JSON object, tagging every entry with `provenance`.
+    /// Anything other than an object yields empty params.
+    pub fn from_value(v: &Value, provenance: Provenance) -> Self {
+        let mut p = Params::new();
+        if let Some(obj) = v.as_object() {
+            for (k, v) in obj {
+                p.insert(k, v.clone(), provenance);
+            }
+        }
+        p
+    }
+
+    pub fn insert(&mut self, name: &str, value: Value, provenance: Provenance) {
+        self.entries.insert(name.to_string(), Param { value, provenance });
+        // Any change invalidates a previous check.
+        self.validation = Validation::Unchecked;
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Param> {
+        self.entries.get(name)
+    }
+
+    pub fn get_int(&self, name: &str) -> Option<i64> {
+        self.entries.get(name).and_then(|p| p.value.as_i64())
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn get_str(&self, name: &str) -> Option<&str> {
+        self.entries.get(name).and_then(|p| p.value.as_str())
+    }
+
+    pub fn provenance(&self, name: &str) -> Option<Provenance> {
+        self.entries.get(name).map(|p| p.provenance)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Param)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn validation(&self) -> &Validation {
+        &self.validation
+    }
+
+    pub fn set_validation(&mut self, v: Validation) {
+        self.validation = v;
+    }
+
+    /// Flat `{name: value}` view, the shape templates and the LLM use.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn to_value(&self) -> Value {
+        Value::Object(self.entries.iter().map(|(k, p)| (k.clone(), p.value.clone())).collect())
+    }
+}
+
+impl fmt::Display for Params {
+    /// `value=40 (deterministic), delta=5 (clamped)`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        for (k, p) in &self.entries {
+            if !first {
+                write!(f, ", ")?;
+            }
+            first = false;
+            let prov = match p.provenance {
+                Provenance::Deterministic => "deterministic",
+                Provenance::Llm => "llm",
+                Provenance::Default => "default",
+                Provenance::Clamped => "clamped",
+                Provenance::Unknown => "unknown",
+                Provenance::FollowUp => "follow_up",
+            };
+            write!(f, "{}={} ({})", k, p.value, prov)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn accessors_and_provenance() {
+        let mut p = Params::new();
+        p.insert("value", json!(40), Provenance::Deterministic);
+        p.insert("name", json!("hdmi"), Provenance::Llm);
+        assert_eq!(p.get_int("value"), Some(40));
+        assert_eq!(p.get_str("name"), Some("hdmi"));
+        assert_eq!(p.get_int("name"), None);
+        assert_eq!(p.provenance("value"), Some(Provenance::Deterministic));
+        assert_eq!(p.provenance("name"), Some(Provenance::Llm));
+        assert_eq!(p.iter().map(|(k, _)| k).collect::<Vec<_>>(), vec!["name", "value"]);
+        assert_eq!(p.to_value(), json!({"value": 40, "name": "hdmi"}));
+        assert_eq!(p.to_string(), r#"name="hdmi" (llm), value=40 (deterministic)"#);
+    }
+
+    #[test]
+    fn insert_resets_validation() {
+        let mut p = Params::new();
+        p.set_validation(Validation::Valid);
+        p.insert("delta", json!(5), Provenance::Default);
+        assert_eq!(p.validation(), &Validation::Unchecked);
+    }
+
+    #[test]
+    fn typed_roundtrip_keeps_provenance() {
+        let mut p = Params::new();
+        p.insert("value", json!(100), Provenance::Clamped);
+        p.set_validation(Validation::Valid);
+        let s = serde_json::to_value(&p).unwrap();
+        assert_eq!(
+            s,
+            json!({"entries": {"value": {"value": 100, "provenance": "clamped"}}, "validation": "valid"})
+        );
+        let back: Params = serde_json::from_value(s).unwrap();
+        assert_eq!(back, p);
+    }
+
+    #[test]
+    fn legacy_flat_shape_still_parses() {
+        let p: Params = serde_json::from_value(json!({"value": 40})).unwrap();
+        assert_eq!(p.get_int("value"), Some(40));
+        assert_eq!(p.provenance("value"), Some(Provenance::Unknown));
+        let empty: Params = serde_json::from_value(json!(null)).unwrap();
+        assert!(empty.is_empty());
+    }
+}
@@ -0,0 +1,158 @@
+use crate::error::{BtwError, Result};
+use std::path::{Path, PathBuf};
+
+const APP: &str = "btwd";
+/// Config directory name used by earlier releases; still read after `btwd/`.
+const LEGACY_APP: &str = "btw";
+
+/// The Python ML worker, relative to a data directory.
+pub const ML_SCRIPT: &str = "ml/btw_ml.py";
+/// notify-send confirmation helper, relative to a data directory.
+pub const CONFIRM_HELPER: &str = "scripts/btwd-notify-confirm.sh";
+
+/// Lookup order for config and data files. An explicit path (CLI flag or
+/// env var) always wins; otherwise the first existing candidate is used:
+///
+/// - config: `$XDG_CONFIG_HOME/btwd/`, `$XDG_CONFIG_HOME/btw/`, each
+///   `$XDG_CONFIG_DIRS/btwd/`, then `/etc/btwd/`
+/// - data: `$XDG_DATA_HOME/btwd/`, each `$XDG_DATA_DIRS/btwd/`, then the
+///   working directory and the binary's checkout (for running from the repo)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Paths {
+    config_dirs: Vec<PathBuf>,
+    data_dirs: Vec<PathBuf>,
+}
+
+impl Paths {
+    pub fn new(config_home: &Path, config_dirs: &[PathBuf], data_home: &Path, data_dirs: &[PathBuf], checkout_dirs: &[PathBuf]) -> Self {
+        let mut config: Vec<PathBuf> = vec![config_home.join(APP), config_home.join(LEGACY_APP)];
+        config.extend(config_dirs.iter().map(|d| d.join(APP)));
+        config.push(Path::new("/etc").join(APP));
+        let mut data: Vec<PathBuf> = vec![data_home.join(APP)];
+        data.extend(data_dirs.iter().map(|d| d.join(APP)));
+        data.extend(checkout_dirs.iter().cloned());
+        dedup(&mut config);
+        dedup(&mut data);
+        Self { config_dirs: config, data_dirs: data }
+    }
+
+    /// The XDG base directories of the current process (with the spec's
+    /// defaults), plus the working directory and the binary's checkout.
+    pub fn from_env() -> Result<Self> {
+        // The spec says relative values are invalid and must be ignored.
+        let var = |k: &str| std::env::var_os(k).map(PathBuf::from).filter(|p| p.is_absolute());
+        let home = var("HOME");
+        let home_dir = |rel: &str| home.as_ref().map(|h| h.join(rel));
+        let (Some(config_home), Some(data_home)) =
+            (var("XDG_CONFIG_HOME").or_else(|| home_dir(".config")), var("XDG_DATA_HOME").or_else(|| home_dir(".local/share")))
+        else {
+            return Err(BtwError::XdgError { message: "neither $HOME nor $XDG_CONFIG_HOME/$XDG_DATA_HOME is set".into() });
+        };
+        let list = |k: &str, default: &str| -> Vec<PathBuf> {
+            let raw = std::env::var_os(k).filter(|v| !v.is_empty()).unwrap_or_else(|| default.into());
+            std::env::split_paths(&raw).filter(|p| p.is_absolute()).collect()
+        };
+        let mut checkout = Vec::new();
+        checkout.extend(std::env::current_dir().ok());
+        if let Some(exe_dir) = std::env::current_exe().ok().and_then(|e| e.parent().map(Path::to_path_buf)) {
+            // target/release/btwd -> the repository root
+            checkout.extend(exe_dir.parent().and_then(Path::parent).map(Path::to_path_buf));
+            checkout.push(exe_dir);
+        }
+        Ok(Self::new(&config_home, &list("XDG_CONFIG_DIRS", "/etc/xdg"), &data_home, &list("XDG_DATA_DIRS", "/usr/local/share:/usr/share"), &checkout))
+    }
+
+    pub fn config_candidates(&self, name: &str) -> Vec<PathBuf> {
+        self.config_dirs.iter().map(|d| d.join(name)).collect()
+    }
+
+    pub fn data_candidates(&self, name: &str) -> Vec<PathBuf> {
+        self.data_dirs.iter().map(|d| d.join(name)).collect()
+    }
+
+    /// `name` (e.g. "config.toml") from `explicit` or the config cascade.
+    pub fn config_file(&self, name: &str, explicit: Option<PathBuf>) -> Result<PathBuf> {
+        resolve(name, explicit, self.config_candidates(name))
+    }
+
+    /// `name` (e.g. [`ML_SCRIPT`]) from `explicit` or the data cascade.
+    pub fn data_file(&self, name: &str, explicit: Option<PathBuf>) -> Result<PathBuf> {
+        resolve(name, explicit, self.data_candidates(name))
+    }
+}
+
+fn dedup(dirs: &mut Vec<PathBuf>) {
+    let mut seen = std::collections::HashSet::new();
+    dirs.retain(|d| seen.insert(d.clone()));
+}
+
+fn resolve(name: &str, explicit: Option<PathBuf>, candidates: Vec<PathBuf>) -> Result<PathBuf> {
+    if let Some(path) = explicit {
+        return if path.is_file() {
+            Ok(path)
+        } else {
+            Err(BtwError::XdgError { message: format!("{} not found at {} (set explicitly)", name, path.display()) })
+        };
+    }
+    match candidates.iter().find(|p| p.is_file()) {
+        Some(found) => Ok(found.clone()),
+        None => {
+            let tried: Vec<String> = candidates.iter().map(|p| p.display().to_string()).collect();
+            Err(BtwError::XdgError { message: format!("{} not found; tried {}", name, tried.join(", ")) })
+        }
+    }
+}
+
+/// `--flag PATH` from `args`, else the `env` variable.
+pub fn explicit_path(args: &[String], flag: &str, env: &str) -> Option<PathBuf> {
+    let from_args = args.iter().position(|a| a == flag).and_then(|i| args.get(i + 1)).filter(|a| !a.starts_with("--"));
+    from_args.cloned().or_else(|| std::env::var(env).ok().filter(|v| !v.is_empty())).map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cascade_prefers_user_dirs_and_reports_what_was_tried() {
+        let root = std::env::temp_dir().join(format!("btwd-paths-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let (config_home, system, data_home, checkout) = (root.join("config"), root.join("xdg"), root.join("data"), root.join("repo"));
+        let paths = Paths::new(&config_home, std::slice::from_ref(&system), &data_home, &[], std::slice::from_ref(&checkout));
+        let touch = |p: PathBuf| {
+            std::fs::create_dir_all(p.parent().unwrap()).unwrap();
+            std::fs::write(&p, b"").unwrap();
+            p
+        };
+
+        let err = paths.config_file("config.toml", None).unwrap_err().to_string();
+        let expected_tried = [config_home.join("btwd"), config_home.join("btw"), system.join("btwd"), PathBuf::from("/etc/btwd")]
+            .map(|d| d.join("config.toml").display().to_string())
+            .join(", ");
+        assert!(err.ends_with(&format!("config.toml not found; tried {}", expected_tried)), "{}", err);
+
+        let system_cfg = touch(system.join("btwd/config.toml"));
+        assert_eq!(paths.config_file("config.toml", None).unwrap(), system_cfg);
+        let legacy = touch(config_home.join("btw/config.toml"));
+        assert_eq!(paths.config_file("config.toml", None).unwrap(), legacy);
+        let user = touch(config_home.join("btwd/config.toml"));
+        assert_eq!(paths.config_file("config.toml", None).unwrap(), user);
+
+        // An explicit path wins, and is never silently replaced by the cascade.
+        assert_eq!(paths.config_file("config.toml", Some(system_cfg.clone())).unwrap(), system_cfg);
+        assert!(paths.config_file("config.toml", Some(root.join("nope.toml"))).unwrap_err().to_string().contains("set explicitly"));
+
+        let in_repo = touch(checkout.join(ML_SCRIPT));
+        assert_eq!(paths.data_file(ML_SCRIPT, None).unwrap(), in_repo);
+        let installed = touch(data_home.join("btwd").join(ML_SCRIPT));
+        assert_eq!(paths.data_file(ML_SCRIPT, None).unwrap(), installed);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn explicit_path_from_flag() {
+        let args: Vec<String> = ["--config", "/tmp/c.toml", "--validate"].map(String::from).to_vec();
+        assert_eq!(explicit_path(&args, "--config", "BTWD_TEST_UNSET_PATH"), Some(PathBuf::from("/tmp/c.toml")));
+        assert_eq!(explicit_path(&args, "--commands", "BTWD_TEST_UNSET_PATH"), None);
+    }
+}
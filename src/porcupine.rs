@@ -1,10 +1,70 @@
 use crate::error::{BtwError, Result};
 use crate::porcupine_sys as sys;
+use crate::wake::WakeWordDetector;
 use std::ffi::{CString, CStr};
 use std::os::raw::c_char;
 use std::path::{Path, PathBuf};
 use std::ptr::null_mut;
 
+/// Every keyword file must be absolute and exist. All offenders are reported
+/// in one error so a multi-keyword config can be fixed in a single pass.
+fn check_keyword_paths(keywords: &[(PathBuf, f32)]) -> Result<()> {
+    let relative: Vec<&PathBuf> = keywords.iter().map(|(p, _)| p).filter(|p| !p.is_absolute()).collect();
+    let missing: Vec<&PathBuf> = keywords.iter().map(|(p, _)| p).filter(|p| p.is_absolute() && !p.exists()).collect();
+    match (relative.as_slice(), missing.as_slice()) {
+        ([], []) => Ok(()),
+        ([], [only]) => Err(BtwError::MissingFile { path: only.to_path_buf(), kind: "wake_word.ppn" }),
+        _ => {
+            let problems: Vec<String> = relative
+                .iter()
+                .map(|p| format!("{} (ppn_path must be absolute)", p.display()))
+                .chain(missing.iter().map(|p| format!("{} (missing)", p.display())))
+                .collect();
+            Err(BtwError::InvalidSetting {
+                key: "wake_word.ppn_path",
+                message: format!("invalid wake word keyword file(s): {}", problems.join("; ")),
+            })
+        }
+    }
+}
+
+/// Porcupine rejects sensitivities outside [0, 1]; pull them back in range
+/// instead of failing init over a typo.
+fn clamp_sensitivity(path: &Path, s: f32) -> f32 {
+    if s.is_nan() {
+        log::warn!("wake: sensitivity for {} is not a number; using 0.5", path.display());
+        return 0.5;
+    }
+    let clamped = s.clamp(0.0, 1.0);
+    if clamped != s {
+        log::warn!("wake: sensitivity {} for {} is outside [0, 1]; using {}", s, path.display(), clamped);
+    }
+    clamped
+}
+
+/// Leading major version of a Porcupine version string such as "4.0.1".
+fn major_version(version: &str) -> Option<u32> {
+    let digits: String = version.trim().chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
+
+/// The loaded library must have the major version of the header btwd was
+/// built against; calling into a mismatched ABI crashes instead of failing.
+/// `compiled` is empty when build.rs could not read the header's version.
+fn check_runtime_version(runtime: &str, compiled: &str) -> Result<()> {
+    let Ok(compiled) = compiled.parse::<u32>() else {
+        log::debug!("wake: compiled Porcupine version unknown; skipping version check");
+        return Ok(());
+    };
+    match major_version(runtime) {
+        Some(major) if major == compiled => Ok(()),
+        _ => Err(BtwError::PorcupineInitFailed {
+            status: sys::pv_status_t_PV_STATUS_INVALID_STATE as i32,
+            messages: vec![format!("runtime version {} != compiled version {}; please recompile btwd", runtime, compiled)],
+        }),
+    }
+}
+
 /// Safe RAII wrapper around Porcupine C SDK
 pub struct Porcupine {
     handle: *mut sys::pv_porcupine_t,
@@ -13,27 +73,25 @@ pub struct Porcupine {
     _access_key: CString,
     _model_path: CString,
     _device: CString,
-    _ppn_path: CString,
+    _ppn_paths: Vec<CString>,
 
-    ppn_path: PathBuf,
     device: String,
+    // Kept so `reinit` can rebuild the engine with the same model and keywords.
+    model_path: PathBuf,
+    keywords: Vec<(PathBuf, f32)>,
 }
 
 impl Porcupine {
-    /// Initialize Porcupine with a single keyword `.ppn` and sensitivity.
+    /// Initialize Porcupine with one or more keyword `.ppn` files, each with
+    /// its own sensitivity. `process` reports which one fired by index.
     /// Requires `PICOVOICE_ACCESS_KEY` in environment.
     pub fn new(
         model_path: &Path,
         device: &str,
-        ppn_path: &Path,
-        sensitivity: f32,
+        keywords: &[(PathBuf, f32)],
     ) -> Result<Self> {
         if !model_path.is_absolute() {
-            return Err(BtwError::ParseError {
-                path: model_path.to_path_buf(),
-                kind: "porcupine",
-                message: "wake_word.model_path must be absolute".into(),
-            });
+            return Err(BtwError::InvalidSetting { key: "wake_word.model_path", message: "must be an absolute path".into() });
         }
         if !model_path.exists() {
             return Err(BtwError::MissingFile {
@@ -41,59 +99,38 @@ impl Porcupine {
                 kind: "porcupine_params.pv",
             });
         }
-        if !ppn_path.is_absolute() {
-            return Err(BtwError::ParseError {
-                path: ppn_path.to_path_buf(),
-                kind: "porcupine",
-                message: "ppn_path must be absolute".into(),
-            });
-        }
-        if !ppn_path.exists() {
-            return Err(BtwError::MissingFile {
-                path: ppn_path.to_path_buf(),
-                kind: "wake_word.ppn",
-            });
+        if keywords.is_empty() {
+            return Err(BtwError::InvalidSetting { key: "wake_word.ppn_path", message: "no wake word keywords configured".into() });
         }
+        check_keyword_paths(keywords)?;
+        check_runtime_version(&Self::version(), env!("PORCUPINE_COMPILED_MAJOR"))?;
+        let keywords: Vec<(PathBuf, f32)> = keywords.iter().map(|(p, s)| (p.clone(), clamp_sensitivity(p, *s))).collect();
 
-        let access_key = std::env::var("PICOVOICE_ACCESS_KEY").map_err(|_| {
-            BtwError::ParseError {
-                path: ppn_path.to_path_buf(),
-                kind: "porcupine",
-                message: "missing PICOVOICE_ACCESS_KEY in environment".into(),
-            }
-        })?;
+        let access_key = std::env::var("PICOVOICE_ACCESS_KEY")
+            .map_err(|_| BtwError::Porcupine { message: "missing PICOVOICE_ACCESS_KEY in environment".into() })?;
 
         // --- C string preparation (explicit error mapping) ---
-        let access_key_c = CString::new(access_key).map_err(|e| BtwError::ParseError {
-            path: ppn_path.to_path_buf(),
-            kind: "porcupine",
-            message: format!("access key contains NUL byte: {}", e),
-        })?;
+        let access_key_c = CString::new(access_key)
+            .map_err(|e| BtwError::Porcupine { message: format!("access key contains NUL byte: {}", e) })?;
 
-        let model_c = CString::new(model_path.to_string_lossy().as_bytes()).map_err(|e| {
-            BtwError::ParseError {
-                path: model_path.to_path_buf(),
-                kind: "porcupine",
-                message: format!("model path contains NUL byte: {}", e),
-            }
+        let model_c = CString::new(model_path.to_string_lossy().as_bytes()).map_err(|e| BtwError::InvalidSetting {
+            key: "wake_word.model_path",
+            message: format!("contains NUL byte: {}", e),
         })?;
 
-        let device_c = CString::new(device).map_err(|e| BtwError::ParseError {
-            path: model_path.to_path_buf(),
-            kind: "porcupine",
-            message: format!("device string contains NUL byte: {}", e),
-        })?;
+        let device_c = CString::new(device)
+            .map_err(|e| BtwError::InvalidSetting { key: "wake_word.device", message: format!("contains NUL byte: {}", e) })?;
 
-        let ppn_c = CString::new(ppn_path.to_string_lossy().as_bytes()).map_err(|e| {
-            BtwError::ParseError {
-                path: ppn_path.to_path_buf(),
-                kind: "porcupine",
-                message: format!("ppn path contains NUL byte: {}", e),
-            }
-        })?;
+        let mut ppn_cs = Vec::with_capacity(keywords.len());
+        for (path, _) in &keywords {
+            ppn_cs.push(CString::new(path.to_string_lossy().as_bytes()).map_err(|e| BtwError::InvalidSetting {
+                key: "wake_word.ppn_path",
+                message: format!("{} contains NUL byte: {}", path.display(), e),
+            })?);
+        }
 
-        let keyword_paths = [ppn_c.as_ptr()];
-        let sensitivities = [sensitivity];
+        let keyword_paths: Vec<*const c_char> = ppn_cs.iter().map(|c| c.as_ptr()).collect();
+        let sensitivities: Vec<f32> = keywords.iter().map(|(_, s)| *s).collect();
 
         let mut handle: *mut sys::pv_porcupine_t = null_mut();
 
@@ -102,7 +139,7 @@ impl Porcupine {
                 access_key_c.as_ptr(),
                 model_c.as_ptr(),
                 device_c.as_ptr(),
-                1,
+                keywords.len() as i32,
                 keyword_paths.as_ptr(),
                 sensitivities.as_ptr(),
                 &mut handle,
@@ -122,12 +159,41 @@ impl Porcupine {
             _access_key: access_key_c,
             _model_path: model_c,
             _device: device_c,
-            _ppn_path: ppn_c,
-            ppn_path: ppn_path.to_path_buf(),
+            _ppn_paths: ppn_cs,
             device: device.to_string(),
+            model_path: model_path.to_path_buf(),
+            keywords,
         })
     }
 
+    /// Rebuild the engine with new per-keyword sensitivities (same model,
+    /// device and keyword files). The new handle is created before the old
+    /// one is deleted, so on error the current detector keeps running.
+    /// Callers reach this through the detector mutex, which is also held for
+    /// every `process`, so a frame can never hit a deleted handle.
+    pub fn reinit(&mut self, sensitivities: &[f32]) -> Result<()> {
+        if sensitivities.len() != self.keywords.len() {
+            return Err(BtwError::Porcupine {
+                message: format!(
+                    "keyword count changed ({} -> {}); restart to change wake words",
+                    self.keywords.len(),
+                    sensitivities.len()
+                ),
+            });
+        }
+        let keywords: Vec<(PathBuf, f32)> =
+            self.keywords.iter().zip(sensitivities).map(|((p, _), s)| (p.clone(), *s)).collect();
+        let fresh = Porcupine::new(&self.model_path, &self.device, &keywords)?;
+        *self = fresh;
+        Ok(())
+    }
+
+    /// Rebuild the engine with `new_sensitivity` for every keyword; see [`Porcupine::reinit`].
+    pub fn reinitialize_sensitivity(&mut self, new_sensitivity: f32) -> Result<()> {
+        let sensitivities = vec![new_sensitivity; self.keywords.len()];
+        self.reinit(&sensitivities)
+    }
+
     pub fn device(&self) -> &str {
         &self.device
     }
@@ -151,11 +217,10 @@ impl Porcupine {
         unsafe { sys::pv_sample_rate() as u32 }
     }
 
-    pub fn process(&mut self, pcm: &[i16]) -> Result<bool> {
+    /// Feed one frame; returns the zero-based index of the keyword that fired.
+    pub fn process(&mut self, pcm: &[i16]) -> Result<Option<usize>> {
         if pcm.len() != self.frame_length() {
-            return Err(BtwError::ParseError {
-                path: self.ppn_path.clone(),
-                kind: "porcupine",
+            return Err(BtwError::Porcupine {
                 message: format!(
                     "invalid frame length: expected {} got {}",
                     self.frame_length(),
@@ -177,14 +242,36 @@ impl Porcupine {
                     CStr::from_ptr(c).to_string_lossy().into_owned()
                 }
             };
-            return Err(BtwError::ParseError {
-                path: self.ppn_path.clone(),
-                kind: "porcupine",
-                message: format!("process failed: {}", msg),
-            });
+            return Err(BtwError::Porcupine { message: format!("process failed: {}", msg) });
         }
 
-        Ok(keyword_index >= 0)
+        Ok(usize::try_from(keyword_index).ok())
+    }
+}
+
+// SAFETY: the handle is only ever used through `&mut self` (or `&self` for
+// the stateless frame_length/sample_rate queries), so moving the wrapper to
+// another thread never results in concurrent access to the engine.
+unsafe impl Send for Porcupine {}
+
+impl WakeWordDetector for Porcupine {
+    fn process(&mut self, pcm: &[i16]) -> Result<Option<usize>> {
+        Porcupine::process(self, pcm)
+    }
+
+    fn frame_length(&self) -> usize {
+        Porcupine::frame_length(self)
+    }
+
+    fn sample_rate(&self) -> u32 {
+        Porcupine::sample_rate(self)
+    }
+
+    fn reinit(&mut self, sensitivities: &[f32]) -> Result<()> {
+        Porcupine::reinit(self, sensitivities)
+    }
+    fn reinitialize_sensitivity(&mut self, sensitivity: f32) -> Result<()> {
+        Porcupine::reinitialize_sensitivity(self, sensitivity)
     }
 }
 
@@ -217,3 +304,50 @@ unsafe fn get_error_stack_messages() -> Vec<String> {
     sys::pv_free_error_stack(stack);
     out
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyword_path_problems_are_reported_together() {
+        let present = std::env::temp_dir().join(format!("btwd-kw-{}.ppn", std::process::id()));
+        std::fs::write(&present, b"").unwrap();
+        let kw = |p: &str| (PathBuf::from(p), 0.5);
+
+        assert!(check_keyword_paths(&[(present.clone(), 0.5)]).is_ok());
+        let one = check_keyword_paths(&[(present.clone(), 0.5), kw("/nonexistent/a.ppn")]).unwrap_err();
+        assert!(matches!(one, BtwError::MissingFile { kind: "wake_word.ppn", .. }));
+
+        let all = check_keyword_paths(&[kw("/nonexistent/a.ppn"), kw("relative.ppn"), kw("/nonexistent/b.ppn")]).unwrap_err();
+        match all {
+            BtwError::InvalidSetting { key: "wake_word.ppn_path", message } => {
+                assert!(message.contains("relative.ppn (ppn_path must be absolute)"), "{}", message);
+                assert!(message.contains("/nonexistent/a.ppn (missing)"), "{}", message);
+                assert!(message.contains("/nonexistent/b.ppn (missing)"), "{}", message);
+            }
+            other => panic!("expected InvalidSetting, got {:?}", other),
+        }
+        let _ = std::fs::remove_file(&present);
+    }
+
+    #[test]
+    fn runtime_major_version_must_match_compiled() {
+        assert_eq!(major_version("4.0.1"), Some(4));
+        assert_eq!(major_version("unknown"), None);
+        assert!(check_runtime_version("4.0.1", "4").is_ok());
+        assert!(check_runtime_version("3.0.0", "").is_ok());
+        let err = check_runtime_version("3.0.0", "4").unwrap_err();
+        assert!(err.to_string().ends_with("runtime version 3.0.0 != compiled version 4; please recompile btwd"), "{}", err);
+        assert!(check_runtime_version("unknown", "4").is_err());
+    }
+
+    #[test]
+    fn out_of_range_sensitivity_is_clamped() {
+        let p = Path::new("/x.ppn");
+        assert_eq!(clamp_sensitivity(p, 0.7), 0.7);
+        assert_eq!(clamp_sensitivity(p, 1.5), 1.0);
+        assert_eq!(clamp_sensitivity(p, -0.2), 0.0);
+        assert_eq!(clamp_sensitivity(p, f32::NAN), 0.5);
+    }
+}
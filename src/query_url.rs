@@ -0,0 +1,51 @@
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+
+/// Where "open in browser" on an answer notification searches (`ui.search_engine`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchEngine {
+    #[default]
+    Google,
+    DuckDuckGo,
+    Brave,
+}
+
+impl SearchEngine {
+    /// `ui.search_engine`; anything unrecognized means Google.
+    pub fn from_config(s: &str) -> Self {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "duckduckgo" | "ddg" => Self::DuckDuckGo,
+            "brave" => Self::Brave,
+            _ => Self::Google,
+        }
+    }
+}
+
+/// The engine's results page for `query`, percent-encoding everything but
+/// ASCII letters and digits.
+pub fn build_search_url(query: &str, engine: SearchEngine) -> String {
+    let base = match engine {
+        SearchEngine::Google => "https://www.google.com/search?q=",
+        SearchEngine::DuckDuckGo => "https://duckduckgo.com/?q=",
+        SearchEngine::Brave => "https://search.brave.com/search?q=",
+    };
+    format!("{}{}", base, utf8_percent_encode(query.trim(), NON_ALPHANUMERIC))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queries_are_fully_percent_encoded() {
+        assert_eq!(build_search_url("weather in pune", SearchEngine::Google), "https://www.google.com/search?q=weather%20in%20pune");
+        assert_eq!(build_search_url("tom & jerry", SearchEngine::DuckDuckGo), "https://duckduckgo.com/?q=tom%20%26%20jerry");
+        assert_eq!(build_search_url("café ☕?", SearchEngine::Brave), "https://search.brave.com/search?q=caf%C3%A9%20%E2%98%95%3F");
+    }
+
+    #[test]
+    fn engine_is_read_from_config() {
+        assert_eq!(SearchEngine::from_config(" DuckDuckGo "), SearchEngine::DuckDuckGo);
+        assert_eq!(SearchEngine::from_config("brave"), SearchEngine::Brave);
+        assert_eq!(SearchEngine::from_config("bing"), SearchEngine::Google);
+    }
+}
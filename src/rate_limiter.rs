@@ -0,0 +1,60 @@
+use std::time::Instant;
+
+/// Classic token bucket: holds up to `capacity` tokens, refilled
+/// continuously at `refill_per_sec`.
+#[derive(Debug)]
+pub struct TokenBucket {
+    capacity: f32,
+    refill_per_sec: f32,
+    tokens: f32,
+    last: Instant,
+}
+
+impl TokenBucket {
+    /// Starts full.
+    pub fn new(capacity: u32, refill_per_sec: f32) -> Self {
+        Self { capacity: capacity as f32, refill_per_sec: refill_per_sec.max(0.0), tokens: capacity as f32, last: Instant::now() }
+    }
+
+    /// `per_min` calls a minute, in bursts of up to `per_min`.
+    pub fn per_minute(per_min: u32) -> Self {
+        Self::new(per_min, per_min as f32 / 60.0)
+    }
+
+    /// Take `n` tokens if that many are available.
+    pub fn try_consume(&mut self, n: u32) -> bool {
+        self.try_consume_at(n, Instant::now())
+    }
+
+    fn try_consume_at(&mut self, n: u32, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f32();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last = now.max(self.last);
+        if self.tokens >= n as f32 {
+            self.tokens -= n as f32;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn bucket_empties_then_refills() {
+        let mut b = TokenBucket::new(3, 1.0);
+        let t0 = b.last;
+        assert!((0..3).all(|_| b.try_consume_at(1, t0)));
+        assert!(!b.try_consume_at(1, t0));
+        assert!(!b.try_consume_at(1, t0 + Duration::from_millis(500)));
+        assert!(b.try_consume_at(1, t0 + Duration::from_millis(1000)));
+        // Refill is capped at capacity.
+        assert!(b.try_consume_at(3, t0 + Duration::from_secs(60)));
+        assert!(!b.try_consume_at(1, t0 + Duration::from_secs(60)));
+        assert!(!TokenBucket::new(2, 1.0).try_consume(3));
+    }
+}
@@ -1,14 +1,287 @@
-use crate::config::{SearchCfg, SpeechOutputCfg};
+use crate::config::{Config, SearchCfg, SpeechOutputCfg};
 use crate::llm::LlmClient;
+use crate::search_cache::CachedSearchProvider;
 use serde_json::Value;
+use std::sync::Arc;
 
 const KNOWLEDGE_CHECK_SENTINEL: &str =
     "I do not have enough up-to-date information to answer this.";
 
+/// One web result, reduced to what the answer prompt needs.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SearchSnippet {
+    pub title: String,
+    pub url: String,
+    pub content: String,
+}
+
+impl SearchSnippet {
+    /// `title — url\ncontent`, skipping empty parts.
+    fn to_fact(&self) -> String {
+        let mut chunk = String::new();
+        if !self.title.is_empty() {
+            chunk.push_str(&self.title);
+        }
+        if !self.url.is_empty() {
+            if !chunk.is_empty() {
+                chunk.push_str(" — ");
+            }
+            chunk.push_str(&self.url);
+        }
+        if !self.content.is_empty() {
+            if !chunk.is_empty() {
+                chunk.push('\n');
+            }
+            chunk.push_str(&self.content);
+        }
+        chunk
+    }
+}
+
+/// Compact "facts" text passed to the LLM.
+fn facts_text(snippets: &[SearchSnippet]) -> String {
+    snippets.iter().map(SearchSnippet::to_fact).filter(|f| !f.is_empty()).collect::<Vec<_>>().join("\n\n")
+}
+
+/// A web search backend (`[search] provider`).
+pub trait SearchProvider: Send + Sync {
+    fn search(&self, query: &str, country: Option<&str>) -> std::result::Result<Vec<SearchSnippet>, String>;
+}
+
+/// Build the provider named by `cfg.provider`: "tavily" (default),
+/// "searxng" (at `cfg.base_url`) or "duckduckgo".
+pub fn provider_for(cfg: &SearchCfg) -> Result<Box<dyn SearchProvider>, String> {
+    match cfg.provider.trim().to_ascii_lowercase().as_str() {
+        "" | "tavily" => Ok(Box::new(TavilySearch::new(cfg.timeout_ms))),
+        "searxng" | "searx" if cfg.base_url.trim().is_empty() => Err("search.provider = \"searxng\" needs search.base_url".into()),
+        "searxng" | "searx" => Ok(Box::new(SearxngSearch::new(&cfg.base_url, cfg.timeout_ms))),
+        "duckduckgo" | "ddg" => Ok(Box::new(DuckDuckGoSearch::new(cfg.timeout_ms))),
+        other => Err(format!("unknown search provider '{}'", other)),
+    }
+}
+
+impl SearchProvider for Box<dyn SearchProvider> {
+    fn search(&self, query: &str, country: Option<&str>) -> Result<Vec<SearchSnippet>, String> {
+        self.as_ref().search(query, country)
+    }
+}
+
+/// Stands in for a misconfigured provider so each question reports why.
+struct UnavailableSearch(String);
+
+impl SearchProvider for UnavailableSearch {
+    fn search(&self, _query: &str, _country: Option<&str>) -> Result<Vec<SearchSnippet>, String> {
+        Err(self.0.clone())
+    }
+}
+
+/// The provider shared by every question for the lifetime of the daemon,
+/// wrapped in a result cache when `search.cache_ttl_secs` is nonzero.
+pub fn session_provider(cfg: &SearchCfg) -> Arc<dyn SearchProvider> {
+    match provider_for(cfg) {
+        Ok(p) if cfg.cache_ttl_secs > 0 => {
+            Arc::new(CachedSearchProvider::new(p, std::time::Duration::from_secs(cfg.cache_ttl_secs)))
+        }
+        Ok(p) => Arc::from(p),
+        Err(e) => Arc::new(UnavailableSearch(e)),
+    }
+}
+
+fn http_client(timeout_ms: u64) -> Result<reqwest::blocking::Client, String> {
+    reqwest::blocking::Client::builder()
+        .timeout(std::time::Duration::from_millis(timeout_ms))
+        .build()
+        .map_err(|e| format!("client build: {}", e))
+}
+
+/// Tavily search API; needs `TAVILY_API_KEY`.
+pub struct TavilySearch {
+    endpoint: String,
+    timeout_ms: u64,
+}
+
+impl TavilySearch {
+    pub fn new(timeout_ms: u64) -> Self {
+        Self { endpoint: "https://api.tavily.com/search".into(), timeout_ms }
+    }
+}
+
+impl SearchProvider for TavilySearch {
+    fn search(&self, query: &str, country: Option<&str>) -> Result<Vec<SearchSnippet>, String> {
+        let api_key = std::env::var("TAVILY_API_KEY").map_err(|_| "missing TAVILY_API_KEY".to_string())?;
+        tavily_search(&self.endpoint, &api_key, query, self.timeout_ms, country)
+    }
+}
+
+/// A SearxNG instance's JSON API (`<base_url>/search?format=json`). The
+/// instance must list `json` under `search.formats` in its settings.yml.
+/// SearxNG has no country filter, so `country` is ignored.
+pub struct SearxngSearch {
+    base_url: String,
+    timeout_ms: u64,
+}
+
+impl SearxngSearch {
+    pub fn new(base_url: &str, timeout_ms: u64) -> Self {
+        Self { base_url: base_url.trim().trim_end_matches('/').to_string(), timeout_ms }
+    }
+}
+
+impl SearchProvider for SearxngSearch {
+    fn search(&self, query: &str, _country: Option<&str>) -> Result<Vec<SearchSnippet>, String> {
+        let url = format!("{}/search?q={}&format=json", self.base_url, urlencoding::encode(query));
+        let resp = http_client(self.timeout_ms)?
+            .get(url)
+            .header(reqwest::header::USER_AGENT, "btwd")
+            .send()
+            .map_err(|e| format!("http error (searxng): connect={} timeout={} source={}", e.is_connect(), e.is_timeout(), e))?;
+        let status = resp.status();
+        if status == reqwest::StatusCode::FORBIDDEN {
+            return Err("searxng status: 403 (is json enabled under search.formats?)".into());
+        }
+        if !status.is_success() {
+            return Err(format!("searxng status: {}", status));
+        }
+        let raw: Value = resp.json().map_err(|e| format!("json decode (searxng): {}", e))?;
+        let snippets = parse_searxng(&raw);
+        if snippets.is_empty() {
+            return Err("searxng returned no results".into());
+        }
+        Ok(snippets)
+    }
+}
+
+/// Direct answers and infoboxes first, then the top results.
+fn parse_searxng(raw: &Value) -> Vec<SearchSnippet> {
+    let mut out = Vec::new();
+    // Plain strings in older releases, `{"answer": ..., "url": ...}` in newer ones.
+    for a in raw.get("answers").and_then(|v| v.as_array()).into_iter().flatten() {
+        let (content, url) = match a.as_str() {
+            Some(text) => (text.trim(), ""),
+            None => (str_field(a, "answer"), str_field(a, "url")),
+        };
+        if !content.is_empty() {
+            out.push(SearchSnippet { title: "Answer".into(), url: url.into(), content: content.into() });
+        }
+    }
+    for b in raw.get("infoboxes").and_then(|v| v.as_array()).into_iter().flatten().take(1) {
+        let content = str_field(b, "content");
+        if !content.is_empty() {
+            out.push(SearchSnippet { title: str_field(b, "infobox").into(), url: str_field(b, "id").into(), content: content.into() });
+        }
+    }
+    for r in raw.get("results").and_then(|v| v.as_array()).into_iter().flatten().take(5) {
+        let snippet = SearchSnippet { title: str_field(r, "title").into(), url: str_field(r, "url").into(), content: str_field(r, "content").into() };
+        if !snippet.content.is_empty() || !snippet.title.is_empty() {
+            out.push(snippet);
+        }
+    }
+    out
+}
+
+/// DuckDuckGo Instant Answer API. No key required, but it only knows
+/// encyclopedic topics (abstracts, definitions, related topics), not news.
+/// It has no country filter, so `country` is ignored.
+pub struct DuckDuckGoSearch {
+    endpoint: String,
+    timeout_ms: u64,
+}
+
+impl DuckDuckGoSearch {
+    pub fn new(timeout_ms: u64) -> Self {
+        Self { endpoint: "https://api.duckduckgo.com/".into(), timeout_ms }
+    }
+}
+
+impl SearchProvider for DuckDuckGoSearch {
+    fn search(&self, query: &str, _country: Option<&str>) -> Result<Vec<SearchSnippet>, String> {
+        let url = format!("{}?q={}&format=json&no_html=1&skip_disambig=1", self.endpoint, urlencoding::encode(query));
+        let resp = http_client(self.timeout_ms)?
+            .get(url)
+            .header(reqwest::header::USER_AGENT, "btwd")
+            .send()
+            .map_err(|e| format!("http error (duckduckgo): connect={} timeout={} source={}", e.is_connect(), e.is_timeout(), e))?;
+        let status = resp.status();
+        if !status.is_success() {
+            return Err(format!("duckduckgo status: {}", status));
+        }
+        let raw: Value = resp.json().map_err(|e| format!("json decode (duckduckgo): {}", e))?;
+        let snippets = parse_duckduckgo(&raw);
+        if snippets.is_empty() {
+            return Err("duckduckgo returned no results".into());
+        }
+        Ok(snippets)
+    }
+}
+
+fn str_field<'a>(v: &'a Value, key: &str) -> &'a str {
+    v.get(key).and_then(|v| v.as_str()).unwrap_or("").trim()
+}
+
+fn parse_duckduckgo(raw: &Value) -> Vec<SearchSnippet> {
+    let mut out = Vec::new();
+    let answer = str_field(raw, "Answer");
+    if !answer.is_empty() {
+        out.push(SearchSnippet { title: "Answer".into(), url: String::new(), content: answer.into() });
+    }
+    let abstract_text = str_field(raw, "AbstractText");
+    if !abstract_text.is_empty() {
+        out.push(SearchSnippet {
+            title: str_field(raw, "Heading").into(),
+            url: str_field(raw, "AbstractURL").into(),
+            content: abstract_text.into(),
+        });
+    }
+    let definition = str_field(raw, "Definition");
+    if !definition.is_empty() {
+        out.push(SearchSnippet {
+            title: "Definition".into(),
+            url: str_field(raw, "DefinitionURL").into(),
+            content: definition.into(),
+        });
+    }
+    // Related topics are either entries or named groups of entries.
+    let mut topics: Vec<&Value> = Vec::new();
+    for t in raw.get("RelatedTopics").and_then(|v| v.as_array()).into_iter().flatten() {
+        match t.get("Topics").and_then(|v| v.as_array()) {
+            Some(group) => topics.extend(group),
+            None => topics.push(t),
+        }
+    }
+    for t in topics.into_iter().take(5) {
+        let text = str_field(t, "Text");
+        if !text.is_empty() {
+            out.push(SearchSnippet { title: String::new(), url: str_field(t, "FirstURL").into(), content: text.into() });
+        }
+    }
+    out
+}
+
+/// Canned results for tests; records every query it receives.
+#[cfg(test)]
+pub struct MockSearchProvider {
+    pub result: Result<Vec<SearchSnippet>, String>,
+    pub queries: std::sync::Mutex<Vec<(String, Option<String>)>>,
+}
+
+#[cfg(test)]
+impl MockSearchProvider {
+    pub fn new(result: Result<Vec<SearchSnippet>, String>) -> Self {
+        Self { result, queries: std::sync::Mutex::new(Vec::new()) }
+    }
+}
+
+#[cfg(test)]
+impl SearchProvider for MockSearchProvider {
+    fn search(&self, query: &str, country: Option<&str>) -> Result<Vec<SearchSnippet>, String> {
+        self.queries.lock().unwrap().push((query.to_string(), country.map(str::to_string)));
+        self.result.clone()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::sync::Arc;
 
     struct StubLlm {
         out: String,
@@ -23,10 +296,6 @@ mod tests {
             Err("not used".into())
         }
 
-        fn summarize_search(&self, _query: &str, _snippets: &[String]) -> Result<String, String> {
-            Err("not used".into())
-        }
-
         fn answer_short(&self, _prompt: &str) -> Result<String, String> {
             Ok(self.out.clone())
         }
@@ -36,6 +305,127 @@ mod tests {
         }
     }
 
+    #[test]
+    fn search_answer_uses_provider_snippets() {
+        let provider = MockSearchProvider::new(Ok(vec![SearchSnippet {
+            title: "Rust".into(),
+            url: "https://rust-lang.org".into(),
+            content: "A language empowering everyone.".into(),
+        }]));
+        let llm: Arc<dyn crate::llm::LlmClient> = Arc::new(StubLlm { out: "answer".into() });
+        let cfg = SearchCfg { country: Some("india".into()), ..SearchCfg::default() };
+        assert_eq!(answer_with_search("what is rust", &provider, &cfg, &llm).unwrap(), "answer");
+        assert_eq!(provider.queries.lock().unwrap()[0], ("what is rust".to_string(), Some("india".to_string())));
+
+        let failing = MockSearchProvider::new(Err("offline".into()));
+        assert_eq!(answer_with_search("what is rust", &failing, &cfg, &llm).unwrap_err(), SearchFailure::Provider("offline".into()));
+        assert!(answer_without_search("what is rust", &llm).unwrap().starts_with("answer (Web search failed"));
+    }
+
+    #[test]
+    fn duckduckgo_response_is_flattened_into_snippets() {
+        let raw = serde_json::json!({
+            "Heading": "Rust (programming language)",
+            "AbstractText": "Rust is a general-purpose programming language.",
+            "AbstractURL": "https://en.wikipedia.org/wiki/Rust_(programming_language)",
+            "Answer": "",
+            "RelatedTopics": [
+                {"Text": "Cargo - package manager", "FirstURL": "https://duckduckgo.com/Cargo"},
+                {"Name": "See also", "Topics": [{"Text": "Ferris - mascot", "FirstURL": "https://duckduckgo.com/Ferris"}]}
+            ]
+        });
+        let snippets = parse_duckduckgo(&raw);
+        assert_eq!(snippets.len(), 3);
+        assert_eq!(snippets[0].title, "Rust (programming language)");
+        assert_eq!(snippets[2].content, "Ferris - mascot");
+        assert!(facts_text(&snippets).starts_with("Rust (programming language) — https://en.wikipedia.org"));
+        assert!(parse_duckduckgo(&serde_json::json!({"RelatedTopics": []})).is_empty());
+    }
+
+    #[test]
+    fn provider_is_selected_by_name() {
+        let mut cfg = SearchCfg::default();
+        assert!(provider_for(&cfg).is_ok());
+        cfg.provider = "DuckDuckGo".into();
+        assert!(provider_for(&cfg).is_ok());
+        cfg.provider = "searxng".into();
+        assert!(provider_for(&cfg).err().unwrap().contains("base_url"));
+        cfg.base_url = "http://localhost:8888".into();
+        assert!(provider_for(&cfg).is_ok());
+        cfg.provider = "bing".into();
+        assert!(provider_for(&cfg).is_err());
+    }
+
+    /// Answer one HTTP request with `reply`; yields the request line and body.
+    fn serve_json(reply: Value) -> (String, std::thread::JoinHandle<(String, String)>) {
+        use std::io::{BufRead, BufReader, Read, Write};
+        let listener = std::net::TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut len = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some(v) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    len = v.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0u8; len];
+            reader.read_exact(&mut body).unwrap();
+            let reply = reply.to_string();
+            write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", reply.len(), reply).unwrap();
+            (request_line.trim().to_string(), String::from_utf8(body).unwrap())
+        });
+        (base, handle)
+    }
+
+    #[test]
+    fn searxng_answers_infobox_and_results_become_snippets() {
+        let (base, server) = serve_json(serde_json::json!({
+            "query": "what is rust",
+            "answers": ["Rust is a programming language", {"answer": "", "url": "https://x"}],
+            "infoboxes": [{"infobox": "Rust", "id": "https://rust-lang.org", "content": "A language empowering everyone."}],
+            "results": [
+                {"title": "Rust Programming Language", "url": "https://www.rust-lang.org/", "content": "Fast and reliable."},
+                {"title": "", "url": "https://empty.example", "content": ""}
+            ]
+        }));
+        let snippets = SearxngSearch::new(&format!("{}/", base), 2000).search("what is rust", Some("india")).unwrap();
+        assert_eq!(server.join().unwrap().0, "GET /search?q=what%20is%20rust&format=json HTTP/1.1");
+        let titles: Vec<&str> = snippets.iter().map(|s| s.title.as_str()).collect();
+        assert_eq!(titles, ["Answer", "Rust", "Rust Programming Language"]);
+        assert_eq!(snippets[2].url, "https://www.rust-lang.org/");
+        assert!(parse_searxng(&serde_json::json!({"results": []})).is_empty());
+    }
+
+    #[test]
+    fn tavily_request_and_results() {
+        let (base, server) = serve_json(serde_json::json!({
+            "results": [{"title": "Weather", "url": "https://weather.example", "content": "Sunny, 31°C"}, {"title": "", "url": "", "content": ""}]
+        }));
+        let snippets = tavily_search(&format!("{}/search", base), "tvly-test", "weather in pune", 2000, Some(" india ")).unwrap();
+        assert_eq!(snippets, [SearchSnippet { title: "Weather".into(), url: "https://weather.example".into(), content: "Sunny, 31°C".into() }]);
+        let (request_line, body) = server.join().unwrap();
+        assert_eq!(request_line, "POST /search HTTP/1.1");
+        let body: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!((body["query"].as_str(), body["country"].as_str()), (Some("weather in pune"), Some("india")));
+    }
+
+    #[test]
+    fn duckduckgo_request_goes_to_the_instant_answer_api() {
+        let (base, server) = serve_json(serde_json::json!({"Answer": "42", "RelatedTopics": []}));
+        let ddg = DuckDuckGoSearch { endpoint: format!("{}/", base), timeout_ms: 2000 };
+        assert_eq!(ddg.search("meaning of life", None).unwrap()[0].content, "42");
+        assert_eq!(server.join().unwrap().0, "GET /?q=meaning%20of%20life&format=json&no_html=1&skip_disambig=1 HTTP/1.1");
+    }
+
     #[test]
     fn knowledge_check_exact_sentinel_triggers_unknown() {
         let llm: Arc<dyn crate::llm::LlmClient> = Arc::new(StubLlm {
@@ -62,7 +452,7 @@ enum KnownOrUnknown {
 
 fn answer_with_llm_if_known(
     query: &str,
-    llm: &std::sync::Arc<dyn LlmClient>,
+    llm: &Arc<dyn LlmClient>,
 ) -> Result<KnownOrUnknown, String> {
     // Stage 1: strict knowledge check.
     // Must return the exact sentinel string if it cannot answer confidently from static knowledge.
@@ -84,46 +474,75 @@ fn answer_with_llm_if_known(
     Ok(KnownOrUnknown::Known(ans.to_string()))
 }
 
-fn answer_with_tavily(
+/// Which half of [`answer_with_search`] failed.
+#[derive(Debug, PartialEq)]
+enum SearchFailure {
+    Provider(String),
+    Llm(String),
+}
+
+fn answer_with_search(
     query: &str,
+    provider: &dyn SearchProvider,
     cfg: &SearchCfg,
-    llm: &std::sync::Arc<dyn LlmClient>,
-) -> Result<String, String> {
-    // Stage 2: Tavily -> facts-only Mistral compose.
-    let facts = tavily_search(query, cfg.timeout_ms, cfg.country.as_deref())?;
+    llm: &Arc<dyn LlmClient>,
+) -> Result<String, SearchFailure> {
+    // Stage 2: web search -> facts-only Mistral compose.
+    let snippets = provider.search(query, cfg.country.as_deref()).map_err(SearchFailure::Provider)?;
 
     let prompt = format!(
         "User question:\n{}\n\nRetrieved web information:\n{}\n\nAnswer the question clearly and concisely using ONLY the information above.\nIf the information is insufficient or contradictory, say \"I don’t know.\"\n\nImportant: Never mention knowledge cutoff, training data, or that you are an AI language model.",
         query,
-        facts.facts_text
+        facts_text(&snippets)
     );
 
-    llm.answer_short(&prompt)
+    llm.answer_short(&prompt).map_err(SearchFailure::Llm)
+}
+
+/// The search provider failed: answer from the LLM alone, and say so.
+fn answer_without_search(query: &str, llm: &Arc<dyn LlmClient>) -> Result<String, String> {
+    let ans = llm.answer_short(query)?;
+    Ok(format!("{} (Web search failed, so this may be out of date.)", ans))
 }
 
+/// The configuration a web answer is produced and delivered with, copied
+/// out of the config since the answer is produced on its own thread.
 #[derive(Debug, Clone)]
-pub struct TavilyResult {
-    pub raw: Value,
-    pub facts_text: String,
+pub struct AnswerSettings {
+    pub search: SearchCfg,
+    /// `[ui] osd` and `osd_timeout_ms`.
+    pub osd: bool,
+    pub osd_timeout_ms: u64,
+    pub tts: SpeechOutputCfg,
+}
+
+impl AnswerSettings {
+    pub fn from_config(cfg: &Config) -> Self {
+        Self { search: cfg.search.clone(), osd: cfg.ui.osd, osd_timeout_ms: cfg.ui.osd_timeout_ms, tts: cfg.speech_output.clone() }
+    }
 }
 
 pub fn search_and_summarize_async(
     question: String,
-    search_cfg: SearchCfg,
-    ui_enabled: bool,
-    ui_timeout_ms: u64,
-    tts: SpeechOutputCfg,
-    llm: std::sync::Arc<dyn LlmClient>,
+    settings: AnswerSettings,
+    llm: Arc<dyn LlmClient>,
+    provider: Arc<dyn SearchProvider>,
+    cancel: crate::cancel::CancelToken,
+    open_url: String,
 ) {
+    let AnswerSettings { search: search_cfg, osd: ui_enabled, osd_timeout_ms: ui_timeout_ms, tts } = settings;
     if !search_cfg.enabled {
         return;
     }
 
+    // Keep the search's log lines in the interaction that asked the question.
+    let span = tracing::Span::current();
     std::thread::spawn(move || {
+        let _entered = span.enter();
         let answer_timeout_ms = ui_timeout_ms.max(15_000);
 
         // For web results, abort early if offline.
-        // Important: do not call Tavily and do not fall back to any other web flow.
+        // Important: do not call the search provider and do not fall back to any other web flow.
         if !crate::net::has_internet(800) {
             if ui_enabled {
                 crate::ui::notify_answer(
@@ -138,42 +557,60 @@ pub fn search_and_summarize_async(
 
         // Strict 2-stage gating:
         // 1) Ask LLM to answer only if it is certain (else return exact sentinel)
-        // 2) Only if sentinel, call Tavily and then ask LLM again using ONLY retrieved info
+        // 2) Only if sentinel, search the web and then ask LLM again using ONLY retrieved info
+        let web_label = match search_cfg.provider.trim() {
+            "" => "tavily".to_string(),
+            p => p.to_ascii_lowercase(),
+        };
         let (final_answer_res, source_label) = match answer_with_llm_if_known(&question, &llm) {
-            Ok(KnownOrUnknown::Known(ans)) => (Ok(ans), "mistral"),
-            Ok(KnownOrUnknown::Unknown) => (answer_with_tavily(&question, &search_cfg, &llm), "tavily"),
-            Err(e) => (Err(e), "tavily"),
+            Ok(_) if cancel.is_canceled() => {
+                log::info!("assistant: interaction aborted; dropping answer");
+                return;
+            }
+            Ok(KnownOrUnknown::Known(ans)) => (Ok(ans), "mistral".to_string()),
+            Ok(KnownOrUnknown::Unknown) => match answer_with_search(&question, provider.as_ref(), &search_cfg, &llm) {
+                Ok(ans) => (Ok(ans), web_label),
+                Err(SearchFailure::Llm(e)) => (Err(e), web_label),
+                Err(SearchFailure::Provider(e)) => {
+                    log::warn!("search: {} failed: {}; answering without web results", web_label, e);
+                    (answer_without_search(&question, &llm), "llm".to_string())
+                }
+            },
+            Err(e) => (Err(e), "llm".to_string()),
         };
 
+        // Abort may land while we were waiting on the network; never speak after it.
+        if cancel.is_canceled() {
+            log::info!("assistant: interaction aborted; dropping answer");
+            return;
+        }
+
         match final_answer_res {
             Ok(answer) => {
                 if ui_enabled {
                     let ui_text = format!("{}\n\n:source: {}", answer, source_label);
 
-                    if source_label == "tavily" {
-                        let google_url = format!(
-                            "https://www.google.com/search?q={}",
-                            urlencoding::encode(&question)
-                        );
+                    if source_label != "mistral" {
                         crate::ui::notify_answer_with_open_in_browser(
                             ui_enabled,
                             answer_timeout_ms,
                             "Btw",
                             &ui_text,
-                            &google_url,
+                            &open_url,
                         );
                     } else {
                         crate::ui::notify_answer(ui_enabled, answer_timeout_ms, "Btw", &ui_text);
                     }
                 }
-                // Speak the *Mistral-produced* answer only. Never speak raw Tavily facts.
+                // Speak the *Mistral-produced* answer only. Never speak raw search facts.
                 let mut tts_force = tts.clone();
                 tts_force.enabled = true;
                 crate::tts::speak_async(answer, tts_force);
             }
             Err(e) => {
-                eprintln!("TAVILY error: {}", e);
-                let msg = "I couldn’t find reliable information.".to_string();
+                log::error!("search error ({}): {}", source_label, e);
+                // The knowledge check never got an answer: the LLM itself is unreachable.
+                let msg = if source_label == "llm" { crate::llm::UNREACHABLE_REPLY } else { "I couldn’t find reliable information." }.to_string();
                 if ui_enabled {
                     let ui_text = format!("{}\n\n:source: {}", msg, source_label);
                     crate::ui::notify_answer(ui_enabled, answer_timeout_ms, "Btw", &ui_text);
@@ -186,16 +623,8 @@ pub fn search_and_summarize_async(
     });
 }
 
-pub fn tavily_search(query: &str, timeout_ms: u64, country: Option<&str>) -> Result<TavilyResult, String> {
-    let api_key = std::env::var("TAVILY_API_KEY")
-        .map_err(|_| "missing TAVILY_API_KEY".to_string())?;
-
-    let client = reqwest::blocking::Client::builder()
-        .timeout(std::time::Duration::from_millis(timeout_ms))
-        .build()
-        .map_err(|e| format!("client build: {}", e))?;
-
-    let url = "https://api.tavily.com/search";
+fn tavily_search(url: &str, api_key: &str, query: &str, timeout_ms: u64, country: Option<&str>) -> Result<Vec<SearchSnippet>, String> {
+    let client = http_client(timeout_ms)?;
 
     // Match required request shape:
     // - Use `Authorization: Bearer <key>` header
@@ -235,52 +664,22 @@ pub fn tavily_search(query: &str, timeout_ms: u64, country: Option<&str>) -> Res
         return Err(format!("tavily status: {} body={}", status, raw));
     }
 
-    // Convert the result list into compact "facts" text to pass to Mistral.
-    let mut lines: Vec<String> = Vec::new();
-
-    if let Some(results) = raw.get("results").and_then(|r| r.as_array()) {
-        for r in results {
-            let title = r
-                .get("title")
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .trim();
-            let url = r.get("url").and_then(|v| v.as_str()).unwrap_or("").trim();
-            let content = r
-                .get("content")
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .trim();
-
-            let mut chunk = String::new();
-            if !title.is_empty() {
-                chunk.push_str(title);
-            }
-            if !url.is_empty() {
-                if !chunk.is_empty() {
-                    chunk.push_str(" — ");
-                }
-                chunk.push_str(url);
-            }
-            if !content.is_empty() {
-                if !chunk.is_empty() {
-                    chunk.push('\n');
-                }
-                chunk.push_str(content);
-            }
-
-            if !chunk.is_empty() {
-                lines.push(chunk);
-            }
-        }
-    }
-
-    if lines.is_empty() {
+    let snippets: Vec<SearchSnippet> = raw
+        .get("results")
+        .and_then(|r| r.as_array())
+        .into_iter()
+        .flatten()
+        .map(|r| SearchSnippet {
+            title: str_field(r, "title").into(),
+            url: str_field(r, "url").into(),
+            content: str_field(r, "content").into(),
+        })
+        .filter(|s| !s.to_fact().is_empty())
+        .collect();
+
+    if snippets.is_empty() {
         return Err("tavily returned no results".into());
     }
 
-    Ok(TavilyResult {
-        raw,
-        facts_text: lines.join("\n\n"),
-    })
+    Ok(snippets)
 }
@@ -0,0 +1,127 @@
+use crate::search::{SearchProvider, SearchSnippet};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Entries kept before the least recently used one is evicted.
+pub const MAX_ENTRIES: usize = 50;
+
+struct Entry {
+    snippets: Vec<SearchSnippet>,
+    stored: Instant,
+    used: Instant,
+}
+
+/// Wraps a `SearchProvider` and memoizes successful results for `ttl`, so
+/// asking the same question twice in a session costs one API call.
+///
+/// Errors are never cached; the next ask retries the inner provider.
+pub struct CachedSearchProvider<Inner: SearchProvider> {
+    inner: Inner,
+    ttl: Duration,
+    max_entries: usize,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl<Inner: SearchProvider> CachedSearchProvider<Inner> {
+    /// A zero `ttl` disables caching.
+    pub fn new(inner: Inner, ttl: Duration) -> Self {
+        Self { inner, ttl, max_entries: MAX_ENTRIES, entries: Mutex::new(HashMap::new()) }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Entry>> {
+        // A panic while holding the lock leaves only a partially updated cache.
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Lowercased, whitespace-collapsed query without trailing punctuation, plus
+/// the country filter: "What's the weather?" and "what's the  weather" share
+/// an entry, the same query for another country does not.
+pub fn cache_key(query: &str, country: Option<&str>) -> String {
+    let q = query.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    let q = q.trim_end_matches(|c: char| c.is_ascii_punctuation());
+    format!("{}\u{0}{}", q, country.map(str::trim).unwrap_or("").to_lowercase())
+}
+
+impl<Inner: SearchProvider> SearchProvider for CachedSearchProvider<Inner> {
+    fn search(&self, query: &str, country: Option<&str>) -> Result<Vec<SearchSnippet>, String> {
+        if self.ttl.is_zero() {
+            return self.inner.search(query, country);
+        }
+        let key = cache_key(query, country);
+        if let Some(e) = self.lock().get_mut(&key) {
+            if e.stored.elapsed() < self.ttl {
+                log::debug!("search: cache hit");
+                e.used = Instant::now();
+                return Ok(e.snippets.clone());
+            }
+        }
+        // Don't hold the lock across the network call.
+        let snippets = self.inner.search(query, country)?;
+        let mut entries = self.lock();
+        let ttl = self.ttl;
+        entries.retain(|_, e| e.stored.elapsed() < ttl);
+        while entries.len() >= self.max_entries {
+            let Some(oldest) = entries.iter().min_by_key(|(_, e)| e.used).map(|(k, _)| k.clone()) else { break };
+            entries.remove(&oldest);
+        }
+        let now = Instant::now();
+        entries.insert(key, Entry { snippets: snippets.clone(), stored: now, used: now });
+        Ok(snippets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::MockSearchProvider;
+
+    fn mock() -> MockSearchProvider {
+        MockSearchProvider::new(Ok(vec![SearchSnippet { title: "Weather".into(), url: String::new(), content: "Sunny".into() }]))
+    }
+
+    fn calls(p: &CachedSearchProvider<MockSearchProvider>) -> usize {
+        p.inner.queries.lock().unwrap().len()
+    }
+
+    #[test]
+    fn identical_queries_within_ttl_hit_once() {
+        let cached = CachedSearchProvider::new(mock(), Duration::from_secs(120));
+        let first = cached.search("what's the weather today", None).unwrap();
+        let second = cached.search("What's the  weather today?", None).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(calls(&cached), 1);
+        cached.search("what's the weather today", Some("india")).unwrap();
+        assert_eq!(calls(&cached), 2);
+    }
+
+    #[test]
+    fn expired_entries_and_errors_go_to_the_inner_provider() {
+        let short = CachedSearchProvider::new(mock(), Duration::from_millis(20));
+        short.search("what's the weather today", None).unwrap();
+        std::thread::sleep(Duration::from_millis(40));
+        short.search("what's the weather today", None).unwrap();
+        assert_eq!(calls(&short), 2);
+
+        let failing = CachedSearchProvider::new(MockSearchProvider::new(Err("offline".into())), Duration::from_secs(120));
+        assert!(failing.search("q", None).is_err());
+        assert!(failing.search("q", None).is_err());
+        assert_eq!(failing.inner.queries.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn least_recently_used_entry_is_evicted() {
+        let mut cached = CachedSearchProvider::new(mock(), Duration::from_secs(120));
+        cached.max_entries = 2;
+        cached.search("a", None).unwrap();
+        cached.search("b", None).unwrap();
+        cached.search("a", None).unwrap();
+        cached.search("c", None).unwrap();
+        assert_eq!(calls(&cached), 3);
+        cached.search("a", None).unwrap();
+        assert_eq!(calls(&cached), 3);
+        cached.search("b", None).unwrap();
+        assert_eq!(calls(&cached), 4);
+    }
+}
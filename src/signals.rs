@@ -0,0 +1,89 @@
+use crate::error::{BtwError, Result};
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM, SIGUSR1};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Flags set from signal handlers and polled by the main loop between frames.
+///
+/// SIGTERM (systemd stop) and SIGINT request shutdown; SIGUSR1 asks for the
+/// wake word sensitivity to be re-read from config.toml (or, with
+/// `wake_word.push_to_talk`, to start listening), SIGHUP for config.toml and
+/// commands.json to be reloaded as a whole.
+pub struct Signals {
+    /// Number of the shutdown signal received, 0 while running.
+    shutdown: Arc<AtomicUsize>,
+    reload_wake: Arc<AtomicBool>,
+    reload_all: Arc<AtomicBool>,
+}
+
+fn install_error(sig: i32, source: std::io::Error) -> BtwError {
+    BtwError::SignalHandler { signal: signal_name(sig), source }
+}
+
+impl Signals {
+    pub fn install() -> Result<Self> {
+        let shutdown = Arc::new(AtomicUsize::new(0));
+        for sig in [SIGTERM, SIGINT] {
+            signal_hook::flag::register_usize(sig, shutdown.clone(), sig as usize).map_err(|e| install_error(sig, e))?;
+        }
+        let reload_wake = Arc::new(AtomicBool::new(false));
+        signal_hook::flag::register(SIGUSR1, reload_wake.clone()).map_err(|e| install_error(SIGUSR1, e))?;
+        let reload_all = Arc::new(AtomicBool::new(false));
+        signal_hook::flag::register(SIGHUP, reload_all.clone()).map_err(|e| install_error(SIGHUP, e))?;
+        Ok(Self { shutdown, reload_wake, reload_all })
+    }
+
+    /// The signal that requested shutdown, if any.
+    pub fn shutdown_signal(&self) -> Option<i32> {
+        match self.shutdown.load(Ordering::SeqCst) {
+            0 => None,
+            sig => Some(sig as i32),
+        }
+    }
+
+    /// True once per SIGUSR1.
+    pub fn take_reload_wake(&self) -> bool {
+        self.reload_wake.swap(false, Ordering::SeqCst)
+    }
+
+    /// True once per SIGHUP.
+    pub fn take_reload_all(&self) -> bool {
+        self.reload_all.swap(false, Ordering::SeqCst)
+    }
+}
+
+pub fn signal_name(sig: i32) -> &'static str {
+    match sig {
+        SIGTERM => "SIGTERM",
+        SIGINT => "SIGINT",
+        SIGUSR1 => "SIGUSR1",
+        SIGHUP => "SIGHUP",
+        _ => "signal",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raised_signals_set_the_flags() {
+        let signals = Signals::install().unwrap();
+        assert_eq!(signals.shutdown_signal(), None);
+        assert!(!signals.take_reload_wake());
+
+        signal_hook::low_level::raise(SIGUSR1).unwrap();
+        assert!(signals.take_reload_wake());
+        assert!(!signals.take_reload_wake());
+        assert!(!signals.take_reload_all());
+
+        signal_hook::low_level::raise(SIGHUP).unwrap();
+        assert!(signals.take_reload_all());
+        assert!(!signals.take_reload_all());
+
+        // Our handler replaces the default action, so this only sets the flag.
+        signal_hook::low_level::raise(SIGINT).unwrap();
+        assert_eq!(signals.shutdown_signal(), Some(SIGINT));
+        assert_eq!(signal_name(SIGINT), "SIGINT");
+    }
+}
@@ -0,0 +1,306 @@
+use crate::manager::{EventReceiver, State, StateEvent, TranscriptEntry};
+use std::collections::VecDeque;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// Bumped whenever a field changes meaning or goes away; new fields may be
+/// added without a bump.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Answers are cut to this many characters.
+const ANSWER_CHARS: usize = 120;
+/// Most recent transcripts carried in the document.
+pub const RECENT_TRANSCRIPTS: usize = 5;
+
+/// Latest answer shown to the user, picked up by the publisher thread.
+static LAST_ANSWER: Mutex<Option<String>> = Mutex::new(None);
+/// Latest partial transcript of the utterance still being spoken.
+static LAST_PARTIAL: Mutex<Option<String>> = Mutex::new(None);
+
+/// `$XDG_RUNTIME_DIR/btwd`, where the status file and FIFO live.
+pub fn status_dir() -> PathBuf {
+    let runtime_dir = std::env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".to_string());
+    PathBuf::from(runtime_dir).join("btwd")
+}
+
+/// Note an answer for the status document (the ui layer calls this for every
+/// answer it shows).
+pub fn record_answer(text: &str) {
+    *LAST_ANSWER.lock().unwrap_or_else(|p| p.into_inner()) = Some(text.to_string());
+}
+
+fn take_answer() -> Option<String> {
+    LAST_ANSWER.lock().unwrap_or_else(|p| p.into_inner()).take()
+}
+
+/// Note a live partial transcript; it shows as `partial` while listening.
+pub fn record_partial(text: &str) {
+    *LAST_PARTIAL.lock().unwrap_or_else(|p| p.into_inner()) = Some(text.to_string());
+}
+
+fn take_partial() -> Option<String> {
+    LAST_PARTIAL.lock().unwrap_or_else(|p| p.into_inner()).take()
+}
+
+fn snippet(text: &str) -> String {
+    let text = text.trim();
+    match text.char_indices().nth(ANSWER_CHARS) {
+        Some((i, _)) => format!("{}…", &text[..i]),
+        None => text.to_string(),
+    }
+}
+
+/// Mirrors [`Manager`](crate::manager::Manager) state into a one-line JSON
+/// document for status bars:
+///
+/// `{"version":1,"state":"confirming","pending":{"request_id":"...","preview":"..."},"partial":null,"answer":null,"transcripts":[...],"updated_at":1700000000}`
+///
+/// written atomically to the status file and, when a reader has it open,
+/// as a line on the FIFO.
+pub struct StatusPublisher {
+    file: Option<PathBuf>,
+    fifo: Option<PathBuf>,
+    state: State,
+    /// `(request_id, preview)` while Confirming.
+    pending: Option<(String, String)>,
+    /// What has been heard so far, only while Listening.
+    partial: Option<String>,
+    answer: Option<String>,
+    /// Oldest first, at most [`RECENT_TRANSCRIPTS`].
+    transcripts: VecDeque<TranscriptEntry>,
+}
+
+impl StatusPublisher {
+    pub fn new(file: Option<PathBuf>, fifo: Option<PathBuf>) -> Self {
+        Self { file, fifo, state: State::Idle, pending: None, partial: None, answer: None, transcripts: VecDeque::new() }
+    }
+
+    /// Fold one Manager event in; true if the document changed.
+    pub fn apply(&mut self, ev: &StateEvent) -> bool {
+        match ev {
+            StateEvent::StateChanged { to, .. } => {
+                self.state = *to;
+                self.partial = None;
+                if *to != State::Confirming {
+                    self.pending = None;
+                }
+                true
+            }
+            StateEvent::ConfirmationRequested { request_id, preview } => {
+                self.pending = Some((request_id.clone(), preview.clone()));
+                true
+            }
+            StateEvent::ConfirmationResolved { .. } => self.pending.take().is_some(),
+            StateEvent::TranscriptIgnored => false,
+            StateEvent::TranscriptRecorded(entry) => {
+                if self.transcripts.len() == RECENT_TRANSCRIPTS {
+                    self.transcripts.pop_front();
+                }
+                self.transcripts.push_back(entry.clone());
+                true
+            }
+        }
+    }
+
+    /// True if the document changed; a partial that arrives after the
+    /// utterance ended is dropped.
+    pub fn set_partial(&mut self, partial: &str) -> bool {
+        if self.state != State::Listening {
+            return false;
+        }
+        self.partial = Some(partial.trim().to_string());
+        true
+    }
+
+    pub fn set_answer(&mut self, answer: &str) {
+        self.answer = Some(snippet(answer));
+    }
+
+    pub fn document(&self) -> serde_json::Value {
+        let updated_at = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        serde_json::json!({
+            "version": SCHEMA_VERSION,
+            "state": format!("{:?}", self.state).to_ascii_lowercase(),
+            "pending": self.pending.as_ref().map(|(request_id, preview)| serde_json::json!({"request_id": request_id, "preview": preview})),
+            "partial": self.partial,
+            "answer": self.answer,
+            "transcripts": self.transcripts.iter().map(TranscriptEntry::to_json).collect::<Vec<_>>(),
+            "updated_at": updated_at,
+        })
+    }
+
+    /// Write the current document to the file and FIFO.
+    pub fn publish(&self) -> std::io::Result<()> {
+        let line = self.document().to_string();
+        if let Some(fifo) = &self.fifo {
+            write_fifo(fifo, &line);
+        }
+        match &self.file {
+            Some(path) => write_atomic(path, &line),
+            None => Ok(()),
+        }
+    }
+
+    /// Publish now, then after every event from `events`, every new answer
+    /// and every new partial transcript.
+    pub fn spawn(mut self, events: EventReceiver) {
+        if let Some(fifo) = &self.fifo {
+            if let Err(e) = make_fifo(fifo) {
+                log::warn!("status: cannot create FIFO {}: {}", fifo.display(), e);
+                self.fifo = None;
+            }
+        }
+        std::thread::spawn(move || {
+            let mut dirty = true;
+            loop {
+                if let Some(answer) = take_answer() {
+                    self.set_answer(&answer);
+                    dirty = true;
+                }
+                if let Some(partial) = take_partial() {
+                    dirty |= self.set_partial(&partial);
+                }
+                if dirty {
+                    if let Err(e) = self.publish() {
+                        log::warn!("status: write failed: {}", e);
+                    }
+                }
+                dirty = false;
+                if let Some(ev) = events.recv_timeout(Duration::from_millis(250)) {
+                    dirty = self.apply(&ev);
+                    for ev in events.drain() {
+                        dirty |= self.apply(&ev);
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Temp file + rename, so a bar never reads half a document.
+fn write_atomic(path: &Path, line: &str) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, format!("{}\n", line))?;
+    std::fs::rename(&tmp, path)
+}
+
+fn make_fifo(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::FileTypeExt;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    if let Ok(meta) = std::fs::metadata(path) {
+        if meta.file_type().is_fifo() {
+            return Ok(());
+        }
+        std::fs::remove_file(path)?;
+    }
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes()).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    // SAFETY: `c_path` is a valid NUL-terminated string for the duration of the call.
+    if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// One line per update, without ever blocking: with no reader (ENXIO) or a
+/// full pipe (EAGAIN) the update is dropped; the next one carries the full state.
+fn write_fifo(path: &Path, line: &str) {
+    use std::os::unix::fs::OpenOptionsExt;
+    if let Ok(mut f) = OpenOptions::new().write(true).custom_flags(libc::O_NONBLOCK).open(path) {
+        let _ = f.write_all(format!("{}\n", line).as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decision::{DecisionConfig, DecisionManager};
+    use crate::intent::IntentResult;
+    use crate::manager::Manager;
+    use serde_json::Value;
+
+    /// Fold the queued events in, publish, and parse what landed on disk.
+    fn sync(events: &EventReceiver, publisher: &mut StatusPublisher, path: &Path) -> Value {
+        for ev in events.drain() {
+            publisher.apply(&ev);
+        }
+        publisher.publish().unwrap();
+        let s = std::fs::read_to_string(path).unwrap();
+        assert_eq!(s.lines().count(), 1);
+        serde_json::from_str(&s).unwrap()
+    }
+
+    #[test]
+    fn status_file_follows_a_full_interaction() {
+        let dir = std::env::temp_dir().join(format!("btwd-status-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("status.json");
+        let mut publisher = StatusPublisher::new(Some(path.clone()), None);
+        let mut mgr = Manager::new(DecisionManager::new(DecisionConfig::with_threshold(0.75)).unwrap());
+        let events = mgr.events();
+
+        mgr.on_wake();
+        let doc = sync(&events, &mut publisher, &path);
+        assert_eq!((doc["version"].as_u64(), doc["state"].as_str()), (Some(1), Some("listening")));
+        assert!(doc["pending"].is_null());
+
+        assert!(publisher.set_partial(" lock my "));
+        let doc = sync(&events, &mut publisher, &path);
+        assert_eq!(doc["partial"], "lock my");
+
+        mgr.enter_deciding();
+        let intent = IntentResult {
+            intent_type: "command".into(),
+            command_id: Some("lock_screen".into()),
+            parameters: crate::params::Params::new(),
+            deterministic_score: Some(0.99),
+            embedding_score: None,
+            dangerous: true,
+            requires_confirmation: false,
+        };
+        let _ = mgr.on_transcript("lock my laptop", intent);
+        let doc = sync(&events, &mut publisher, &path);
+        assert_eq!(doc["state"], "confirming");
+        assert!(doc["partial"].is_null());
+        assert!(!publisher.set_partial("late"));
+        assert_eq!(doc["transcripts"][0]["raw_text"], "lock my laptop");
+        assert_eq!(doc["transcripts"][0]["decision_type"], "confirmation");
+        assert_eq!(doc["pending"]["request_id"], mgr.pending_request_id().unwrap());
+        assert!(doc["pending"]["preview"].as_str().is_some_and(|p| !p.is_empty()));
+
+        let token = mgr.confirmation_token().unwrap();
+        mgr.confirm(&token).unwrap();
+        let doc = sync(&events, &mut publisher, &path);
+        assert_eq!(doc["state"], "responding");
+        assert!(doc["pending"].is_null());
+
+        publisher.set_answer(&"x".repeat(200));
+        mgr.reset_to_idle();
+        let doc = sync(&events, &mut publisher, &path);
+        assert_eq!(doc["state"], "idle");
+        assert_eq!(doc["answer"].as_str().unwrap().chars().count(), ANSWER_CHARS + 1);
+        assert!(!path.with_extension("json.tmp").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn fifo_writes_never_block_without_a_reader() {
+        let dir = std::env::temp_dir().join(format!("btwd-status-fifo-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let fifo = dir.join("status.fifo");
+        make_fifo(&fifo).unwrap();
+        make_fifo(&fifo).unwrap();
+        let started = std::time::Instant::now();
+        StatusPublisher::new(None, Some(fifo.clone())).publish().unwrap();
+        assert!(started.elapsed() < Duration::from_secs(1));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
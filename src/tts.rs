@@ -1,26 +1,434 @@
 use crate::config::SpeechOutputCfg;
 use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::time::Duration;
 
-pub fn speak_async(text: String, cfg: SpeechOutputCfg) {
-    if !cfg.enabled || cfg.provider.to_lowercase() != "groq" { return; }
-    std::thread::spawn(move || {
-        if let Err(e) = speak_blocking(&text, &cfg) {
-            eprintln!("TTS error: {}", e);
+/// Stops one `speak_async` utterance: playback is killed mid-stream.
+#[derive(Debug, Clone, Default)]
+pub struct TtsCancelToken {
+    flag: Arc<AtomicBool>,
+}
+
+impl TtsCancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.flag.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_canceled(&self) -> bool {
+        self.flag.load(Ordering::SeqCst)
+    }
+}
+
+/// Utterances allowed to wait behind the one playing; more are dropped.
+const QUEUE_LIMIT: usize = 4;
+
+type SpeakFn = dyn Fn(&str, &SpeechOutputCfg, &TtsCancelToken) -> Result<(), String> + Send + Sync;
+
+/// Where an utterance goes in the queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// Answers and search results: played in order.
+    Normal,
+    /// Confirmation prompts: played next, ahead of queued answers, but
+    /// never cutting off the one already playing.
+    Urgent,
+}
+
+struct Job {
+    text: String,
+    cfg: SpeechOutputCfg,
+    token: TtsCancelToken,
+    priority: Priority,
+}
+
+/// Plays utterances one at a time on a single thread, so two quick answers
+/// never talk over each other.
+pub struct TtsPlayer {
+    queue: Arc<(Mutex<VecDeque<Job>>, Condvar)>,
+    /// Queued and playing utterances, oldest first.
+    pending: Arc<Mutex<Vec<TtsCancelToken>>>,
+    speaking: Arc<AtomicBool>,
+}
+
+impl TtsPlayer {
+    fn new(speak: Box<SpeakFn>) -> Self {
+        let queue: Arc<(Mutex<VecDeque<Job>>, Condvar)> = Arc::new((Mutex::new(VecDeque::new()), Condvar::new()));
+        let pending: Arc<Mutex<Vec<TtsCancelToken>>> = Arc::new(Mutex::new(Vec::new()));
+        let speaking = Arc::new(AtomicBool::new(false));
+        let (jobs, list, flag) = (queue.clone(), pending.clone(), speaking.clone());
+        std::thread::spawn(move || loop {
+            let job = {
+                let (lock, ready) = &*jobs;
+                let mut q = lock.lock().unwrap_or_else(|p| p.into_inner());
+                loop {
+                    match q.pop_front() {
+                        Some(job) => break job,
+                        None => q = ready.wait(q).unwrap_or_else(|p| p.into_inner()),
+                    }
+                }
+            };
+            // Stopped while queued: skip without making a sound.
+            if !job.token.is_canceled() {
+                flag.store(true, Ordering::SeqCst);
+                if let Err(e) = speak(&job.text, &job.cfg, &job.token) {
+                    log::error!("TTS error: {}", e);
+                }
+                flag.store(false, Ordering::SeqCst);
+            }
+            list.lock().unwrap_or_else(|p| p.into_inner()).retain(|t| !Arc::ptr_eq(&t.flag, &job.token.flag));
+        });
+        Self { queue, pending, speaking }
+    }
+
+    /// Queue `text` behind anything already playing. The token cancels just
+    /// this utterance.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn speak(&self, text: String, cfg: SpeechOutputCfg) -> TtsCancelToken {
+        self.speak_with(text, cfg, Priority::Normal)
+    }
+
+    /// Queue `text` after every queued utterance of at least `priority`.
+    pub fn speak_with(&self, text: String, cfg: SpeechOutputCfg, priority: Priority) -> TtsCancelToken {
+        let token = TtsCancelToken::new();
+        let (lock, ready) = &*self.queue;
+        let mut q = lock.lock().unwrap_or_else(|p| p.into_inner());
+        if q.len() >= QUEUE_LIMIT {
+            // A prompt displaces the newest answer rather than being lost.
+            match q.iter().rposition(|j| j.priority < priority) {
+                Some(i) => {
+                    let dropped = q.remove(i).expect("index from rposition");
+                    log::warn!("tts: queue full; dropping: {}", dropped.text);
+                    dropped.token.cancel();
+                }
+                None => {
+                    log::warn!("tts: {} utterance(s) already queued; dropping: {}", QUEUE_LIMIT, text);
+                    token.cancel();
+                    return token;
+                }
+            }
         }
-    });
+        // Registered before queueing so a stop_all racing with this call sees it.
+        self.pending.lock().unwrap_or_else(|p| p.into_inner()).push(token.clone());
+        let at = q.iter().position(|j| j.priority < priority).unwrap_or(q.len());
+        q.insert(at, Job { text, cfg, token: token.clone(), priority });
+        ready.notify_one();
+        token
+    }
+
+    /// Kill the utterance playing now and drop everything queued.
+    pub fn stop_all(&self) {
+        for token in self.pending.lock().unwrap_or_else(|p| p.into_inner()).iter() {
+            token.cancel();
+        }
+    }
+
+    /// Whether an utterance is playing right now.
+    pub fn is_speaking(&self) -> bool {
+        self.speaking.load(Ordering::SeqCst)
+    }
+}
+
+/// Speech can start from background threads (search answers), so there is
+/// one shared player rather than one per caller.
+fn player() -> &'static TtsPlayer {
+    static PLAYER: OnceLock<TtsPlayer> = OnceLock::new();
+    PLAYER.get_or_init(|| TtsPlayer::new(Box::new(speak_blocking)))
+}
+
+/// Barge in: stop the current utterance and everything queued behind it.
+/// Wake words, aborts and `btwctl stop` all end up here.
+pub fn stop_speaking() {
+    player().stop_all();
+}
+
+/// Whether btwd is talking right now.
+pub fn is_speaking() -> bool {
+    player().is_speaking()
+}
+
+pub fn speak_async(text: String, cfg: SpeechOutputCfg) -> TtsCancelToken {
+    speak_async_with(text, cfg, Priority::Normal)
+}
+
+/// [`speak_async`] with an explicit queue position, e.g. [`Priority::Urgent`]
+/// for a confirmation prompt that must not wait behind a long answer.
+pub fn speak_async_with(text: String, cfg: SpeechOutputCfg, priority: Priority) -> TtsCancelToken {
+    if !cfg.enabled { return TtsCancelToken::new(); }
+    if provider_for(&cfg.provider).is_none() {
+        log::warn!("tts: unknown speech_output.provider '{}'; not speaking", cfg.provider);
+        return TtsCancelToken::new();
+    }
+    player().speak_with(text, cfg, priority)
+}
+
+/// One way of turning text into audio on the speakers.
+trait TtsProvider: Sync {
+    /// Synthesize `text` and play it, stopping early if `cancel` fires.
+    fn speak(&self, text: &str, cfg: &SpeechOutputCfg, cancel: &TtsCancelToken) -> Result<(), String>;
+}
+
+/// Groq's OpenAI-compatible speech endpoint (needs GROQ_API_KEY).
+struct Groq;
+/// Local espeak-ng; robotic but always available offline.
+struct Espeak;
+/// Local piper with a `.onnx` voice model.
+struct Piper;
+
+impl TtsProvider for Groq {
+    fn speak(&self, text: &str, cfg: &SpeechOutputCfg, cancel: &TtsCancelToken) -> Result<(), String> {
+        speak_groq(text, cfg, cancel)
+    }
+}
+
+impl TtsProvider for Espeak {
+    fn speak(&self, text: &str, cfg: &SpeechOutputCfg, cancel: &TtsCancelToken) -> Result<(), String> {
+        speak_espeak(text, cfg, cancel)
+    }
+}
+
+impl TtsProvider for Piper {
+    fn speak(&self, text: &str, cfg: &SpeechOutputCfg, cancel: &TtsCancelToken) -> Result<(), String> {
+        speak_piper(text, cfg, cancel)
+    }
+}
+
+/// The provider named by `speech_output.provider` (case-insensitive).
+fn provider_for(name: &str) -> Option<&'static dyn TtsProvider> {
+    match name.to_lowercase().as_str() {
+        "groq" => Some(&Groq),
+        "espeak" => Some(&Espeak),
+        "piper" => Some(&Piper),
+        _ => None,
+    }
+}
+
+fn speak_blocking(text: &str, cfg: &SpeechOutputCfg, cancel: &TtsCancelToken) -> Result<(), String> {
+    let provider = provider_for(&cfg.provider).ok_or_else(|| format!("unknown TTS provider '{}'", cfg.provider))?;
+    provider.speak(text, cfg, cancel)
+}
+
+/// espeak-ng's default speaking rate; `cfg.rate` scales it.
+const ESPEAK_BASE_WPM: f32 = 175.0;
+
+fn espeak_wpm(rate: f32) -> u32 {
+    let rate = if rate > 0.0 { rate } else { 1.0 };
+    (ESPEAK_BASE_WPM * rate).round().clamp(80.0, 450.0) as u32
+}
+
+/// Piper expresses speed as phoneme length; 2.0x speed is length_scale 0.5.
+fn piper_length_scale(rate: f32) -> f32 {
+    if rate > 0.0 { 1.0 / rate } else { 1.0 }
+}
+
+fn speak_espeak(text: &str, cfg: &SpeechOutputCfg, cancel: &TtsCancelToken) -> Result<(), String> {
+    let wpm = espeak_wpm(cfg.rate).to_string();
+    let mut args: Vec<&str> = vec!["--stdout", "-s", wpm.as_str()];
+    // "default" is the shared config default; let espeak-ng pick its own voice.
+    if !cfg.voice.is_empty() && cfg.voice != "default" {
+        args.push("-v");
+        args.push(cfg.voice.as_str());
+    }
+    log::debug!("tts: request (provider=espeak voice={} wpm={} input_len={})", cfg.voice, wpm, text.len());
+    let wav = run_synth("espeak-ng", &args, text)?;
+    play_bytes(&wav, "wav", cfg, cancel)
+}
+
+fn speak_piper(text: &str, cfg: &SpeechOutputCfg, cancel: &TtsCancelToken) -> Result<(), String> {
+    let model = cfg
+        .local_model_path
+        .as_deref()
+        .filter(|p| !p.trim().is_empty())
+        .ok_or_else(|| "piper requires speech_output.local_model_path".to_string())?;
+    if !Path::new(model).is_file() {
+        return Err(format!("piper model not found: {}", model));
+    }
+    let length_scale = piper_length_scale(cfg.rate).to_string();
+    let sample_rate = piper_sample_rate(model);
+    log::debug!(
+        "tts: request (provider=piper model={} length_scale={} sample_rate={} input_len={})",
+        model,
+        length_scale,
+        sample_rate,
+        text.len()
+    );
+    let mut args = vec!["--model", model, "--length_scale", length_scale.as_str(), "--output-raw"];
+    // Multi-speaker piper models take a numeric speaker id as the voice.
+    if cfg.voice.parse::<u32>().is_ok() {
+        args.push("--speaker");
+        args.push(cfg.voice.as_str());
+    }
+    let pcm = run_synth("piper", &args, text)?;
+    play_raw(&pcm, sample_rate, cfg, cancel)
+}
+
+/// Piper voices ship a `<model>.json` next to the .onnx with the output rate.
+fn piper_sample_rate(model: &str) -> u32 {
+    std::fs::read_to_string(format!("{}.json", model))
+        .ok()
+        .and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok())
+        .and_then(|v| v["audio"]["sample_rate"].as_u64())
+        .map(|r| r as u32)
+        .unwrap_or(22050)
+}
+
+/// Run a local synthesizer that reads text on stdin and writes audio to stdout.
+fn run_synth(program: &str, args: &[&str], text: &str) -> Result<Vec<u8>, String> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => format!("{} is not installed (not found in PATH)", program),
+            _ => format!("failed to start {}: {}", program, e),
+        })?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes()).map_err(|e| e.to_string())?;
+        // Dropping stdin closes it so the synthesizer sees EOF.
+    }
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("{} exit: {} stderr={}", program, output.status, stderr.trim()));
+    }
+    if output.stdout.is_empty() {
+        return Err(format!("{} produced no audio", program));
+    }
+    Ok(output.stdout)
+}
+
+/// Cache key for synthesized audio: sha256 of provider, model, voice,
+/// format, rate (2dp) and text. Stable across releases, so existing cache
+/// entries stay valid.
+fn cache_key(provider: &str, model: &str, voice: &str, format: &str, rate: f32, text: &str) -> String {
+    use sha2::{Digest, Sha256};
+    let material = format!("{}:{}:{}:{}:{:.2}:{}", provider, model, voice, format, rate, text);
+    Sha256::digest(material.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Synthesized audio on disk, `<key>.<format>` per utterance, bounded by
+/// `max_bytes` with least-recently-used files evicted first.
+struct TtsCache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl TtsCache {
+    /// The shared cache, or None when `cache_max_mb = 0` turns it off.
+    fn open(cfg: &SpeechOutputCfg) -> Option<Self> {
+        if cfg.cache_max_mb == 0 {
+            return None;
+        }
+        Some(Self { dir: cache_dir()?, max_bytes: cfg.cache_max_mb.saturating_mul(1024 * 1024) })
+    }
+
+    fn path(&self, key: &str, format: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", key, format))
+    }
+
+    /// Cached bytes for `key`. A hit refreshes the file's mtime, which is
+    /// what eviction orders by.
+    fn get(&self, key: &str, format: &str) -> Option<Vec<u8>> {
+        let path = self.path(key, format);
+        let bytes = std::fs::read(&path).ok()?;
+        if let Ok(f) = std::fs::File::options().append(true).open(&path) {
+            let _ = f.set_modified(std::time::SystemTime::now());
+        }
+        Some(bytes)
+    }
+
+    /// Store `bytes` under `key`. Written to a temp file and renamed into
+    /// place, so a crash never leaves a truncated file to be replayed.
+    fn put(&self, key: &str, format: &str, bytes: &[u8]) -> std::io::Result<()> {
+        let path = self.path(key, format);
+        let tmp = self.dir.join(format!(".{}.{}.tmp", key, std::process::id()));
+        if let Err(e) = std::fs::write(&tmp, bytes).and_then(|_| std::fs::rename(&tmp, &path)) {
+            let _ = std::fs::remove_file(&tmp);
+            return Err(e);
+        }
+        evict_cache(&self.dir, self.max_bytes);
+        Ok(())
+    }
+}
+
+/// `$XDG_CACHE_HOME/btwd/tts`, created on demand. None disables caching.
+fn cache_dir() -> Option<PathBuf> {
+    xdg::BaseDirectories::with_prefix("btwd").ok()?.create_cache_directory("tts").ok()
 }
 
-fn speak_blocking(text: &str, cfg: &SpeechOutputCfg) -> Result<(), String> {
+/// Delete the oldest cached files until the directory fits in `max_bytes`.
+fn evict_cache(dir: &Path, max_bytes: u64) {
+    let Ok(rd) = std::fs::read_dir(dir) else { return };
+    let mut files: Vec<(std::time::SystemTime, u64, PathBuf)> = rd
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let meta = e.metadata().ok()?;
+            if !meta.is_file() {
+                return None;
+            }
+            let mtime = meta.modified().unwrap_or(std::time::UNIX_EPOCH);
+            Some((mtime, meta.len(), e.path()))
+        })
+        .collect();
+    let mut total: u64 = files.iter().map(|f| f.1).sum();
+    if total <= max_bytes {
+        return;
+    }
+    files.sort_by_key(|f| f.0);
+    let mut evicted = 0;
+    for (_, len, path) in files {
+        if total <= max_bytes {
+            break;
+        }
+        if std::fs::remove_file(&path).is_ok() {
+            total = total.saturating_sub(len);
+            evicted += 1;
+        }
+    }
+    log::debug!("tts: cache evicted {} file(s), {} bytes remain", evicted, total);
+}
+
+fn speak_groq(text: &str, cfg: &SpeechOutputCfg, cancel: &TtsCancelToken) -> Result<(), String> {
+    let response_format = cfg.format.to_lowercase();
+
+    // Best-effort cache: any IO problem just falls through to the API.
+    let cache = TtsCache::open(cfg);
+    let key = cache_key("groq", &groq_model(), &cfg.voice, &response_format, cfg.rate, text);
+    if let Some(bytes) = cache.as_ref().and_then(|c| c.get(&key, &response_format)) {
+        log::debug!("tts: cache hit ({})", key);
+        return play_bytes(&bytes, &response_format, cfg, cancel);
+    }
+
+    let bytes = fetch_groq(text, cfg)?;
+    if let Some(cache) = &cache {
+        if let Err(e) = cache.put(&key, &response_format, &bytes) {
+            log::warn!("tts: cache write failed: {}", e);
+        }
+    }
+    play_bytes(&bytes, &response_format, cfg, cancel)
+}
+
+fn groq_model() -> String {
+    crate::config_env::var::<String>("BTWD_TTS_MODEL").unwrap_or_else(|| "canopylabs/orpheus-v1-english".to_string())
+}
+
+fn fetch_groq(text: &str, cfg: &SpeechOutputCfg) -> Result<Vec<u8>, String> {
     let api_key = std::env::var("GROQ_API_KEY").map_err(|_| "missing GROQ_API_KEY".to_string())?;
     let url = "https://api.groq.com/openai/v1/audio/speech"; // Groq OpenAI-compatible endpoint
-    let primary_model = std::env::var("BTWD_TTS_MODEL")
-        .unwrap_or_else(|_| "canopylabs/orpheus-v1-english".to_string());
+    let primary_model = groq_model();
     let response_format = cfg.format.to_lowercase();
     // OpenAI-style TTS uses `response_format` (not `format`).
     // Groq returns 400 with "unknown field `format`" otherwise.
-    let fallback_models = std::env::var("BTWD_TTS_FALLBACK_MODELS")
-        .ok()
+    let fallback_models = crate::config_env::var::<String>("BTWD_TTS_FALLBACK_MODELS")
         .and_then(|s| {
             let items: Vec<String> = s
                 .split(',')
@@ -63,7 +471,7 @@ fn speak_blocking(text: &str, cfg: &SpeechOutputCfg) -> Result<(), String> {
             }
         }
 
-        eprintln!(
+        log::debug!(
             "tts: request (provider=groq model={} voice={} response_format={} speed={} input_len={})",
             req_body["model"].as_str().unwrap_or("?"),
             cfg.voice,
@@ -81,7 +489,7 @@ fn speak_blocking(text: &str, cfg: &SpeechOutputCfg) -> Result<(), String> {
 
         if resp.status().is_success() {
             let bytes = resp.bytes().map_err(|e| format!("read body: {}", e))?.to_vec();
-            return play_bytes(&bytes, &response_format);
+            return Ok(bytes);
         }
 
         let status = resp.status();
@@ -105,15 +513,108 @@ fn speak_blocking(text: &str, cfg: &SpeechOutputCfg) -> Result<(), String> {
     ))
 }
 
-fn play_bytes(bytes: &[u8], _format: &str) -> Result<(), String> {
-    // Try pw-play, aplay, then ffplay
-    if try_player("pw-play", &["-"], bytes).is_ok() { return Ok(()); }
-    if try_player("aplay", &["-"], bytes).is_ok() { return Ok(()); }
-    if try_player("ffplay", &["-nodisp", "-autoexit", "-loglevel", "quiet", "-"], bytes).is_ok() { return Ok(()); }
+/// Try each player in turn; a cancel between or during attempts ends playback quietly.
+fn play_with(players: &[(&str, &[&str])], bytes: &[u8], cancel: &TtsCancelToken) -> Result<(), String> {
+    for (cmd, args) in players {
+        if cancel.is_canceled() { return Ok(()); }
+        let res = try_player(cmd, args, bytes, cancel);
+        if cancel.is_canceled() {
+            log::info!("tts: playback interrupted");
+            return Ok(());
+        }
+        if res.is_ok() { return Ok(()); }
+    }
     Err("no suitable audio player found (pw-play/aplay/ffplay)".into())
 }
 
-fn try_player(cmd: &str, args: &[&str], bytes: &[u8]) -> Result<(), String> {
+/// Decode `bytes` as `format` ("wav", or "mp3" with the `mp3` feature).
+fn decode(bytes: &[u8], format: &str) -> Result<rodio::Decoder<std::io::Cursor<Vec<u8>>>, String> {
+    let cursor = std::io::Cursor::new(bytes.to_vec());
+    match format.to_ascii_lowercase().as_str() {
+        "wav" => rodio::Decoder::new_wav(cursor),
+        #[cfg(feature = "mp3")]
+        "mp3" => rodio::Decoder::new_mp3(cursor),
+        #[cfg(not(feature = "mp3"))]
+        "mp3" => return Err("rodio: mp3 support not compiled in (feature \"mp3\")".into()),
+        other => return Err(format!("rodio: unsupported format '{}'", other)),
+    }
+    .map_err(|e| format!("rodio decode: {}", e))
+}
+
+/// Play `source` on the default output device in-process. The stream and
+/// sink are opened per call, so the device is released between utterances.
+fn play_native<S>(source: S, cancel: &TtsCancelToken) -> Result<(), String>
+where
+    S: rodio::Source + Send + 'static,
+    S::Item: rodio::Sample + Send,
+    f32: rodio::cpal::FromSample<S::Item>,
+{
+    let (_stream, handle) = rodio::OutputStream::try_default().map_err(|e| format!("rodio output: {}", e))?;
+    let sink = rodio::Sink::try_new(&handle).map_err(|e| format!("rodio sink: {}", e))?;
+    sink.append(source);
+    while !sink.empty() {
+        if cancel.is_canceled() {
+            sink.stop();
+            log::info!("tts: playback interrupted");
+            return Ok(());
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    Ok(())
+}
+
+fn native_playback(cfg: &SpeechOutputCfg) -> bool {
+    !cfg.playback.trim().eq_ignore_ascii_case("external")
+}
+
+/// External players able to take `format` on stdin. aplay only understands
+/// wav, so it is left out for anything else.
+fn external_players(format: &str) -> Vec<(&'static str, &'static [&'static str])> {
+    let mut players: Vec<(&'static str, &'static [&'static str])> = vec![("pw-play", &["-"])];
+    if format.eq_ignore_ascii_case("wav") {
+        players.push(("aplay", &["-"]));
+    }
+    players.push(("ffplay", &["-nodisp", "-autoexit", "-loglevel", "quiet", "-"]));
+    players
+}
+
+fn play_bytes(bytes: &[u8], format: &str, cfg: &SpeechOutputCfg, cancel: &TtsCancelToken) -> Result<(), String> {
+    if native_playback(cfg) {
+        // An undecodable format goes straight to the external players; a
+        // device that won't open is worth a warning, since native was asked for.
+        match decode(bytes, format) {
+            Ok(source) => match play_native(source, cancel) {
+                Ok(()) => return Ok(()),
+                Err(e) => log::warn!("tts: {}; falling back to external players", e),
+            },
+            Err(e) => log::debug!("tts: {}; trying external players", e),
+        }
+    }
+    play_with(&external_players(format), bytes, cancel)
+}
+
+/// Play headerless mono s16le PCM (piper's `--output-raw`).
+fn play_raw(pcm: &[u8], sample_rate: u32, cfg: &SpeechOutputCfg, cancel: &TtsCancelToken) -> Result<(), String> {
+    if native_playback(cfg) {
+        let samples: Vec<i16> = pcm.chunks_exact(2).map(|b| i16::from_le_bytes([b[0], b[1]])).collect();
+        match play_native(rodio::buffer::SamplesBuffer::new(1, sample_rate, samples), cancel) {
+            Ok(()) => return Ok(()),
+            Err(e) => log::warn!("tts: {}; falling back to external players", e),
+        }
+    }
+    let rate = sample_rate.to_string();
+    play_with(
+        &[
+            ("pw-play", &["--format", "s16", "--rate", rate.as_str(), "--channels", "1", "-"]),
+            ("aplay", &["-t", "raw", "-f", "S16_LE", "-r", rate.as_str(), "-c", "1", "-"]),
+            ("ffplay", &["-nodisp", "-autoexit", "-loglevel", "quiet", "-f", "s16le", "-ar", rate.as_str(), "-ac", "1", "-"]),
+        ],
+        pcm,
+        cancel,
+    )
+}
+
+fn try_player(cmd: &str, args: &[&str], bytes: &[u8], cancel: &TtsCancelToken) -> Result<(), String> {
     let mut child = Command::new(cmd)
         .args(args)
         .stdin(Stdio::piped())
@@ -121,9 +622,283 @@ fn try_player(cmd: &str, args: &[&str], bytes: &[u8]) -> Result<(), String> {
         .stderr(Stdio::null())
         .spawn()
         .map_err(|e| e.to_string())?;
-    if let Some(mut stdin) = child.stdin.take() {
-        stdin.write_all(bytes).map_err(|e| e.to_string())?;
-    }
-    let status = child.wait().map_err(|e| e.to_string())?;
+    // Feed stdin from a helper thread: the pipe fills long before playback
+    // ends, and a blocked write would keep us from noticing a cancel.
+    let writer = child.stdin.take().map(|mut stdin| {
+        let bytes = bytes.to_vec();
+        std::thread::spawn(move || {
+            let _ = stdin.write_all(&bytes);
+        })
+    });
+    let status = loop {
+        if cancel.is_canceled() {
+            let _ = child.kill();
+            let _ = child.wait();
+            if let Some(w) = writer { let _ = w.join(); }
+            return Err(format!("player {} canceled", cmd));
+        }
+        match child.try_wait().map_err(|e| e.to_string())? {
+            Some(status) => break status,
+            None => std::thread::sleep(Duration::from_millis(20)),
+        }
+    };
+    if let Some(w) = writer { let _ = w.join(); }
     if status.success() { Ok(()) } else { Err(format!("player {} exit: {}", cmd, status)) }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_maps_to_provider_units() {
+        assert_eq!(espeak_wpm(1.0), 175);
+        assert_eq!(espeak_wpm(1.5), 263);
+        assert_eq!(espeak_wpm(0.0), 175);
+        assert_eq!(espeak_wpm(10.0), 450);
+        assert!((piper_length_scale(2.0) - 0.5).abs() < 1e-6);
+        assert!((piper_length_scale(0.0) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn providers_are_selected_by_name() {
+        assert!(provider_for("groq").is_some());
+        assert!(provider_for("Piper").is_some());
+        assert!(provider_for("espeak").is_some());
+        assert!(provider_for("festival").is_none());
+        let cfg = SpeechOutputCfg { provider: "festival".into(), ..SpeechOutputCfg::default() };
+        assert!(speak_blocking("hi", &cfg, &TtsCancelToken::new()).unwrap_err().contains("unknown TTS provider"));
+    }
+
+    #[test]
+    fn local_providers_fail_cleanly_without_their_files() {
+        let cancel = TtsCancelToken::new();
+        let cfg = SpeechOutputCfg { provider: "piper".into(), local_model_path: Some("/nonexistent/voice.onnx".into()), ..SpeechOutputCfg::default() };
+        assert_eq!(speak_blocking("hi", &cfg, &cancel).unwrap_err(), "piper model not found: /nonexistent/voice.onnx");
+        let cfg = SpeechOutputCfg { local_model_path: None, ..cfg };
+        assert!(speak_blocking("hi", &cfg, &cancel).unwrap_err().contains("local_model_path"));
+        assert_eq!(run_synth("btwd-no-such-synth", &[], "hi").unwrap_err(), "btwd-no-such-synth is not installed (not found in PATH)");
+    }
+
+    #[test]
+    fn cache_key_covers_every_synthesis_input() {
+        let model = "canopylabs/orpheus-v1-english";
+        let k = cache_key("groq", model, "alloy", "wav", 1.0, "Got it");
+        // Pinned: changing the key format would orphan every cached file.
+        assert_eq!(k, "375fe153c0819f7670bc6702d402ace28ac9c89589eb10e247948a44cea61535");
+        assert_eq!(k, cache_key("groq", model, "alloy", "wav", 1.001, "Got it"));
+        assert_ne!(k, cache_key("groq", model, "alloy", "wav", 1.25, "Got it"));
+        assert_ne!(k, cache_key("groq", model, "nova", "wav", 1.0, "Got it"));
+        assert_ne!(k, cache_key("groq", model, "alloy", "mp3", 1.0, "Got it"));
+        assert_ne!(k, cache_key("groq", model, "alloy", "wav", 1.0, "Got it!"));
+        assert_ne!(k, cache_key("groq", "other-model", "alloy", "wav", 1.0, "Got it"));
+        assert_ne!(k, cache_key("piper", model, "alloy", "wav", 1.0, "Got it"));
+    }
+
+    #[test]
+    fn cache_misses_then_hits_and_writes_atomically() {
+        let dir = std::env::temp_dir().join(format!("btwd-tts-hit-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let cache = TtsCache { dir: dir.clone(), max_bytes: 1024 };
+        assert_eq!(cache.get("abc", "wav"), None);
+        cache.put("abc", "wav", b"RIFFdata").unwrap();
+        assert_eq!(cache.get("abc", "wav").as_deref(), Some(&b"RIFFdata"[..]));
+        assert_eq!(cache.get("abc", "mp3"), None);
+        let names: Vec<String> = std::fs::read_dir(&dir).unwrap().map(|e| e.unwrap().file_name().into_string().unwrap()).collect();
+        assert_eq!(names, vec!["abc.wav"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn cache_hit_protects_an_entry_from_eviction() {
+        let dir = std::env::temp_dir().join(format!("btwd-tts-lru-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let cache = TtsCache { dir: dir.clone(), max_bytes: 250 };
+        for key in ["a", "b"] {
+            cache.put(key, "wav", &[0u8; 100]).unwrap();
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert!(cache.get("a", "wav").is_some());
+        std::thread::sleep(Duration::from_millis(20));
+        cache.put("c", "wav", &[0u8; 100]).unwrap();
+        assert!(cache.path("a", "wav").exists());
+        assert!(!cache.path("b", "wav").exists());
+        assert!(cache.path("c", "wav").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// 16-bit mono wav of `samples` zeros.
+    fn silent_wav(samples: u32) -> Vec<u8> {
+        let data = samples * 2;
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&[16, 0, 0, 0, 1, 0, 1, 0]);
+        wav.extend_from_slice(&16000u32.to_le_bytes());
+        wav.extend_from_slice(&32000u32.to_le_bytes());
+        wav.extend_from_slice(&[2, 0, 16, 0]);
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data.to_le_bytes());
+        wav.resize(wav.len() + data as usize, 0);
+        wav
+    }
+
+    #[test]
+    fn decoder_is_selected_by_format() {
+        use rodio::Source;
+        let source = decode(&silent_wav(1600), "WAV").unwrap();
+        assert_eq!((source.channels(), source.sample_rate()), (1, 16000));
+        assert!(decode(b"RIFF", "wav").err().unwrap().starts_with("rodio decode"));
+        assert!(decode(&[], "opus").err().unwrap().contains("unsupported format"));
+        #[cfg(feature = "mp3")]
+        assert!(decode(b"not mp3", "mp3").err().unwrap().starts_with("rodio decode"));
+    }
+
+    #[test]
+    fn external_players_match_the_format() {
+        let names = |f: &str| external_players(f).into_iter().map(|(cmd, _)| cmd).collect::<Vec<_>>();
+        assert_eq!(names("wav"), vec!["pw-play", "aplay", "ffplay"]);
+        assert_eq!(names("mp3"), vec!["pw-play", "ffplay"]);
+        assert!(native_playback(&SpeechOutputCfg::default()));
+        assert!(!native_playback(&SpeechOutputCfg { playback: "External".into(), ..SpeechOutputCfg::default() }));
+    }
+
+    #[test]
+    fn native_playback_of_silence() {
+        if rodio::OutputStream::try_default().is_err() {
+            eprintln!("skipping: no audio output device");
+            return;
+        }
+        let started = std::time::Instant::now();
+        play_native(decode(&silent_wav(1600), "wav").unwrap(), &TtsCancelToken::new()).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn cancel_kills_the_player() {
+        let cancel = TtsCancelToken::new();
+        let c = cancel.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            c.cancel();
+        });
+        let started = std::time::Instant::now();
+        // `sleep` stands in for a player that would run for 10s.
+        let res = try_player("sleep", &["10"], &[0u8; 1024], &cancel);
+        assert!(res.is_err());
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    /// Player whose utterances are `sleep <text>` runs of the fake "player".
+    fn sleeping_player(log: Arc<Mutex<Vec<String>>>) -> TtsPlayer {
+        TtsPlayer::new(Box::new(move |text: &str, _: &SpeechOutputCfg, cancel: &TtsCancelToken| {
+            log.lock().unwrap().push(format!("start {}", text));
+            let res = try_player("sleep", &[text], &[], cancel);
+            log.lock().unwrap().push(format!("end {}", text));
+            res
+        }))
+    }
+
+    fn wait_until(what: &str, mut cond: impl FnMut() -> bool) {
+        let started = std::time::Instant::now();
+        while !cond() {
+            assert!(started.elapsed() < Duration::from_secs(5), "timed out waiting for {}", what);
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn player_speaks_one_utterance_at_a_time() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let player = sleeping_player(log.clone());
+        player.speak("0.2".into(), SpeechOutputCfg::default());
+        player.speak("0.1".into(), SpeechOutputCfg::default());
+        wait_until("both utterances", || log.lock().unwrap().len() == 4);
+        assert_eq!(*log.lock().unwrap(), vec!["start 0.2", "end 0.2", "start 0.1", "end 0.1"]);
+        assert!(!player.is_speaking());
+    }
+
+    #[test]
+    fn stop_all_interrupts_and_drains_the_queue() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let player = sleeping_player(log.clone());
+        let first = player.speak("10".into(), SpeechOutputCfg::default());
+        player.speak("10".into(), SpeechOutputCfg::default());
+        wait_until("playback to start", || player.is_speaking());
+
+        let started = std::time::Instant::now();
+        player.stop_all();
+        wait_until("the queue to drain", || player.pending.lock().unwrap().is_empty());
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert!(first.is_canceled());
+        assert!(!player.is_speaking());
+        // The queued utterance never started.
+        assert_eq!(*log.lock().unwrap(), vec!["start 10", "end 10"]);
+    }
+
+    #[test]
+    fn full_queue_drops_new_utterances() {
+        let player = sleeping_player(Arc::new(Mutex::new(Vec::new())));
+        player.speak("10".into(), SpeechOutputCfg::default());
+        wait_until("playback to start", || player.is_speaking());
+        let queued: Vec<TtsCancelToken> = (0..QUEUE_LIMIT).map(|_| player.speak("10".into(), SpeechOutputCfg::default())).collect();
+        assert!(queued.iter().all(|t| !t.is_canceled()));
+        assert!(player.speak("10".into(), SpeechOutputCfg::default()).is_canceled());
+        player.stop_all();
+    }
+
+    #[test]
+    fn urgent_utterances_jump_the_queue() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let player = sleeping_player(log.clone());
+        player.speak("0.2".into(), SpeechOutputCfg::default());
+        wait_until("playback to start", || player.is_speaking());
+        player.speak("0.01".into(), SpeechOutputCfg::default());
+        player.speak_with("0.02".into(), SpeechOutputCfg::default(), Priority::Urgent);
+        wait_until("all utterances", || log.lock().unwrap().len() == 6);
+        // The prompt waits for the utterance playing, then goes ahead of the queued answer.
+        assert_eq!(*log.lock().unwrap(), vec!["start 0.2", "end 0.2", "start 0.02", "end 0.02", "start 0.01", "end 0.01"]);
+    }
+
+    #[test]
+    fn urgent_utterance_displaces_a_queued_answer_when_full() {
+        let player = sleeping_player(Arc::new(Mutex::new(Vec::new())));
+        player.speak("10".into(), SpeechOutputCfg::default());
+        wait_until("playback to start", || player.is_speaking());
+        let queued: Vec<TtsCancelToken> = (0..QUEUE_LIMIT).map(|_| player.speak("10".into(), SpeechOutputCfg::default())).collect();
+        let prompt = player.speak_with("10".into(), SpeechOutputCfg::default(), Priority::Urgent);
+        assert!(!prompt.is_canceled());
+        assert!(queued.last().unwrap().is_canceled());
+        assert!(queued[..QUEUE_LIMIT - 1].iter().all(|t| !t.is_canceled()));
+        player.stop_all();
+    }
+
+    #[test]
+    fn canceled_token_skips_remaining_players() {
+        let cancel = TtsCancelToken::new();
+        cancel.cancel();
+        let started = std::time::Instant::now();
+        assert!(play_with(&[("sleep", &["10"]), ("sleep", &["10"])], &[], &cancel).is_ok());
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn eviction_removes_oldest_first() {
+        let dir = std::env::temp_dir().join(format!("btwd-tts-cache-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["old", "mid", "new"] {
+            std::fs::write(dir.join(name), vec![0u8; 100]).unwrap();
+            // Distinct mtimes even on coarse-grained filesystems.
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        evict_cache(&dir, 250);
+        assert!(!dir.join("old").exists());
+        assert!(dir.join("mid").exists());
+        assert!(dir.join("new").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
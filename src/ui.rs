@@ -1,8 +1,175 @@
+use std::path::PathBuf;
 use std::process::{Command, Stdio, Child};
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use crate::cancel::CancelToken;
+use crate::config::UiCfg;
+use crate::notifications::{Notification, Notifier, UiAction, ZbusTransport};
 
 static OVERLAY_CHILD: Mutex<Option<Child>> = Mutex::new(None);
 
+/// The D-Bus notification client, when `ui.notifier = "dbus"` and the
+/// session bus is reachable. Unset means notify-send/dunstify subprocesses.
+static NOTIFIER: OnceLock<Notifier> = OnceLock::new();
+
+/// Connect the notification client. Actions picked on confirmation and
+/// answer notifications arrive on the returned receiver; with the subprocess
+/// notifier nothing is ever sent on it.
+pub fn init_notifier(cfg: &UiCfg) -> Receiver<UiAction> {
+    if cfg.notifier.trim().eq_ignore_ascii_case("subprocess") {
+        log::info!("ui: notifications via notify-send (ui.notifier = \"subprocess\")");
+        return channel().1;
+    }
+    let (transport, events) = match ZbusTransport::connect() {
+        Ok(t) => t,
+        Err(e) => {
+            log::warn!("ui: D-Bus notifications unavailable ({}); falling back to notify-send", e);
+            return channel().1;
+        }
+    };
+    let (notifier, actions) = Notifier::new(Box::new(transport));
+    if NOTIFIER.set(notifier).is_err() {
+        return channel().1;
+    }
+    std::thread::spawn(move || {
+        for event in events {
+            if let Some(n) = NOTIFIER.get() {
+                n.handle_event(event);
+            }
+        }
+    });
+    log::debug!("ui: notifications via D-Bus");
+    actions
+}
+
+/// Open `url` in the default browser.
+pub fn open_url(url: &str) {
+    if let Err(e) = Command::new("xdg-open")
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+    {
+        log::warn!("xdg-open error: {}", e);
+    }
+}
+
+/// How user-facing output is delivered for the current interaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    /// Regular notifications.
+    Notify,
+    /// DND is on but `ignore_dnd` is set: notify with critical urgency.
+    NotifyCritical,
+    /// DND is on: notifications would be swallowed, so speak instead.
+    TtsOnly,
+}
+
+static DELIVERY: Mutex<Delivery> = Mutex::new(Delivery::Notify);
+
+pub fn delivery_for(dnd_active: bool, ignore_dnd: bool) -> Delivery {
+    match (dnd_active, ignore_dnd) {
+        (false, _) => Delivery::Notify,
+        (true, true) => Delivery::NotifyCritical,
+        (true, false) => Delivery::TtsOnly,
+    }
+}
+
+/// Set the delivery mode; called once per interaction (on wake).
+pub fn set_delivery(d: Delivery) {
+    let mut guard = DELIVERY.lock().unwrap_or_else(|p| p.into_inner());
+    if *guard != d {
+        match d {
+            Delivery::Notify => log::info!("ui: DND off; notifications restored"),
+            Delivery::NotifyCritical => log::info!("ui: DND on but ignore_dnd=true; using critical notifications"),
+            Delivery::TtsOnly => log::info!("ui: DND on; answers go to TTS only, confirmations use TTS prompt"),
+        }
+    }
+    *guard = d;
+}
+
+pub fn delivery() -> Delivery {
+    *DELIVERY.lock().unwrap_or_else(|p| p.into_inner())
+}
+
+fn urgency(default: &'static str) -> &'static str {
+    if delivery() == Delivery::NotifyCritical { "critical" } else { default }
+}
+
+/// Where the notification daemon's Do-Not-Disturb state comes from.
+pub trait DndSource: Send {
+    /// `Some(true)` if notifications are being suppressed; `None` if unknown.
+    fn query(&self) -> Option<bool>;
+}
+
+/// Probes swaync, then dunst, then the generic `Inhibited` D-Bus property.
+pub struct SystemDnd;
+
+impl DndSource for SystemDnd {
+    fn query(&self) -> Option<bool> {
+        run_bool("swaync-client", &["--get-dnd"])
+            .or_else(|| run_bool("dunstctl", &["is-paused"]))
+            .or_else(|| {
+                run_bool(
+                    "gdbus",
+                    &[
+                        "call", "--session",
+                        "--dest", "org.freedesktop.Notifications",
+                        "--object-path", "/org/freedesktop/Notifications",
+                        "--method", "org.freedesktop.DBus.Properties.Get",
+                        "org.freedesktop.Notifications", "Inhibited",
+                    ],
+                )
+            })
+    }
+}
+
+fn run_bool(cmd: &str, args: &[&str]) -> Option<bool> {
+    let out = Command::new(cmd).args(args).stdin(Stdio::null()).stderr(Stdio::null()).output().ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    // swaync/dunst print "true"/"false"; gdbus prints "(<true>,)".
+    let text = String::from_utf8_lossy(&out.stdout).to_ascii_lowercase();
+    if text.contains("true") {
+        Some(true)
+    } else if text.contains("false") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// DND state with a short cache so a burst of notifications costs one probe.
+pub struct DndMonitor {
+    source: Box<dyn DndSource>,
+    ttl: Duration,
+    cached: Option<(Instant, bool)>,
+}
+
+impl DndMonitor {
+    pub fn new(source: Box<dyn DndSource>, ttl: Duration) -> Self {
+        Self { source, ttl, cached: None }
+    }
+
+    /// Unknown state counts as "off" so a missing daemon never hides output.
+    pub fn is_active(&mut self) -> bool {
+        let now = Instant::now();
+        if let Some((at, v)) = self.cached {
+            if now.duration_since(at) < self.ttl {
+                return v;
+            }
+        }
+        let v = self.source.query().unwrap_or(false);
+        self.cached = Some((now, v));
+        v
+    }
+}
+
 fn overlay_enable() {
     let mut guard = OVERLAY_CHILD.lock().unwrap();
 
@@ -44,33 +211,69 @@ fn sanitize_passive_body(body: &str) -> String {
     }
     out
 }
-pub fn notify_listening(enabled: bool, timeout_ms: u64) {
+pub fn notify_listening(enabled: bool, timeout_ms: u64, cancel: &CancelToken) {
     if !enabled { return; }
 
     // 🔵 START OVERLAY
     overlay_enable();
 
+    // Under DND the toast would be swallowed; the overlay still shows.
+    if delivery() == Delivery::TtsOnly { return; }
+
+    let cancel = cancel.clone();
+    let urgency = urgency("normal");
+    if let Some(n) = NOTIFIER.get() {
+        let mut note = Notification::new("btwd", "Listening…", urgency, timeout_ms);
+        note.synchronous = Some("btwd-listening");
+        std::thread::spawn(move || n.listening(&cancel, note));
+        return;
+    }
     std::thread::spawn(move || {
-        let _ = Command::new("notify-send")
+        // With an action attached notify-send waits and prints the chosen key.
+        // Daemons without action support just show a plain toast.
+        let output = Command::new("notify-send")
             .arg("btwd")
             .arg("Listening…")
+            .arg("--action").arg("cancel=Cancel")
+            .arg("-u").arg(urgency)
+            .arg("-h").arg("string:x-canonical-private-synchronous:btwd-listening")
             .arg("-t").arg(timeout_ms.to_string())
             .stdin(Stdio::null())
-            .stdout(Stdio::null())
             .stderr(Stdio::null())
-            .status();
+            .output();
+        if let Ok(o) = output {
+            if String::from_utf8_lossy(&o.stdout).trim() == "cancel" {
+                log::info!("ui: cancel via listening notification");
+                cancel.cancel();
+            }
+        }
     });
 }
 
+/// Tear down the listening overlay without showing anything (abort path).
+pub fn dismiss_listening() {
+    overlay_disable();
+}
+
 pub fn notify_text(enabled: bool, timeout_ms: u64, title: &str, body: &str) {
-    if !enabled { return; }
+    if !enabled || delivery() == Delivery::TtsOnly { return; }
     let title = title.to_string();
     let body = sanitize_passive_body(body);
+    let urgency = urgency("low");
+    if let Some(n) = NOTIFIER.get() {
+        let mut note = Notification::new(&title, &body, urgency, timeout_ms);
+        note.synchronous = Some("btwd-info");
+        note.category = Some("im.received");
+        note.transient = true;
+        std::thread::spawn(move || n.show(&note));
+        return;
+    }
     std::thread::spawn(move || {
         let _ = Command::new("notify-send")
             .arg(title)
             .arg(body)
             // Passive/info-only notification: no actions.
+            .arg("-u").arg(urgency)
             .arg("-h").arg("string:x-canonical-private-synchronous:btwd-info")
             .arg("-h").arg("string:category:im.received")
             .arg("-h").arg("int:transient:1")
@@ -81,19 +284,54 @@ pub fn notify_text(enabled: bool, timeout_ms: u64, title: &str, body: &str) {
             .status();
     });
 }
+/// What has been heard so far, while the user is still speaking: replaces the
+/// previous partial in the overlay and the status document.
+pub fn notify_partial(enabled: bool, timeout_ms: u64, text: &str) {
+    let text = text.trim();
+    if text.is_empty() { return; }
+    crate::status::record_partial(text);
+    notify_text(enabled, timeout_ms, "You", text);
+}
+
+/// Under DND the answer is spoken by the caller; keep a history entry so it
+/// can still be read later.
+fn answer_suppressed(body: &str) -> bool {
+    if delivery() != Delivery::TtsOnly {
+        return false;
+    }
+    log::info!("ui: DND active; answer delivered via TTS only");
+    crate::history::record("answer", body, "tts");
+    true
+}
+
+/// A confirmation went unanswered and the command was dropped.
+pub fn notify_confirmation_expired(enabled: bool, timeout_ms: u64, what: &str) {
+    notify_text(enabled, timeout_ms, "btwd", &format!("Confirmation timed out: {}", what));
+}
+
 pub fn notify_answer(enabled: bool, timeout_ms: u64, title: &str, body: &str) {
+    crate::status::record_answer(body);
     if !enabled { return; }
 
     // 🔴 STOP OVERLAY
     overlay_disable();
 
+    if answer_suppressed(body) { return; }
+
     let title = title.to_string();
     let body = sanitize_passive_body(body);
+    let urgency = urgency("normal");
+    if let Some(n) = NOTIFIER.get() {
+        let note = Notification::new(&title, &body, urgency, timeout_ms);
+        std::thread::spawn(move || n.show(&note));
+        return;
+    }
 
     std::thread::spawn(move || {
         let _ = Command::new("notify-send")
             .arg(title)
             .arg(body)
+            .arg("-u").arg(urgency)
             .arg("-t").arg(timeout_ms.to_string())
             .stdin(Stdio::null())
             .stdout(Stdio::null())
@@ -108,18 +346,31 @@ pub fn notify_answer_with_open_in_browser(
     timeout_ms: u64,
     title: &str,
     body: &str,
-    google_query_url: &str,
+    query_url: &str,
 ) {
+    crate::status::record_answer(body);
     if !enabled {
         return;
     }
 
     let title = title.to_string();
     let body = sanitize_passive_body(body);
-    let google_query_url = google_query_url.to_string();
+    let query_url = query_url.to_string();
 
         overlay_disable();
 
+    if answer_suppressed(&body) { return; }
+
+    let urgency = urgency("normal");
+    if let Some(n) = NOTIFIER.get() {
+        let mut note = Notification::new(&title, &body, urgency, timeout_ms);
+        note.synchronous = Some("btwd-answer");
+        note.category = Some("im.received");
+        note.transient = true;
+        std::thread::spawn(move || n.answer_with_open(&query_url, note));
+        return;
+    }
+
     std::thread::spawn(move || {
         let status = Command::new("notify-send")
             .arg(title)
@@ -127,7 +378,7 @@ pub fn notify_answer_with_open_in_browser(
             .arg("--action")
             .arg("open=Open in browser")
             .arg("-u")
-            .arg("normal")
+            .arg(urgency)
             .arg("-h")
             .arg("string:x-canonical-private-synchronous:btwd-answer")
             .arg("-h")
@@ -143,47 +394,294 @@ pub fn notify_answer_with_open_in_browser(
         let output = match status {
             Ok(o) => o,
             Err(e) => {
-                eprintln!("notify-send error: {}", e);
+                log::warn!("notify-send error: {}", e);
                 return;
             }
         };
 
         if !output.status.success() {
-            eprintln!("notify-send failed: status={:?}", output.status.code());
+            log::warn!("notify-send failed: status={:?}", output.status.code());
             return;
         }
 
         let selection = String::from_utf8_lossy(&output.stdout);
         if selection.trim() == "open" {
-            if let Err(e) = Command::new("xdg-open")
-                .arg(&google_query_url)
-                .stdin(Stdio::null())
-                .stdout(Stdio::null())
-                .stderr(Stdio::null())
-                .status()
-            {
-                eprintln!("xdg-open error: {}", e);
-            }
+            open_url(&query_url);
         }
     });
 }
 
+/// Whether `dunstify` was found on `$PATH` by [`detect_notifiers`].
+static HAS_DUNSTIFY: AtomicBool = AtomicBool::new(false);
+
+/// Helper for notification daemons without a blocking action API, found
+/// through [`crate::paths`] by [`detect_notifiers`].
+static CONFIRM_HELPER: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// Probe for notification tools once at startup; the result is cached for
+/// [`notify_confirm_actions`].
+pub fn detect_notifiers() {
+    let dunstify = crate::config::binary_in_path("dunstify");
+    HAS_DUNSTIFY.store(dunstify, Ordering::Relaxed);
+    log::debug!("ui: dunstify {}", if dunstify { "found; confirmations use it directly" } else { "not found" });
+    let helper = crate::paths::Paths::from_env().and_then(|p| p.data_file(crate::paths::CONFIRM_HELPER, None));
+    if let Err(e) = &helper {
+        log::debug!("ui: {}", e);
+    }
+    let _ = CONFIRM_HELPER.set(helper.ok());
+}
+
+/// Spool file the confirmation answer ("yes"/"no") is written to.
+pub fn confirm_path(request_id: &str) -> PathBuf {
+    let runtime_dir = std::env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".to_string());
+    PathBuf::from(runtime_dir).join(format!("btwd-confirm-{}", request_id))
+}
+
+/// dunstify prints the chosen action key, or "1"/"2" for timeout/dismissal.
+fn parse_confirm_action(stdout: &str) -> Option<&'static str> {
+    match stdout.trim() {
+        "yes" => Some("yes"),
+        "no" => Some("no"),
+        _ => None,
+    }
+}
+
+/// Block on a dunstify Yes/No prompt and spool the answer.
+fn confirm_with_dunstify(request_id: &str, title: &str, body: &str) -> std::io::Result<()> {
+    let output = Command::new("dunstify")
+        .arg("-a").arg("btwd")
+        .arg("-u").arg("critical")
+        .arg("-t").arg("0")
+        .arg("-A").arg("yes,Yes")
+        .arg("-A").arg("no,No")
+        .arg(title)
+        .arg(body)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()?;
+    if let Some(action) = parse_confirm_action(&String::from_utf8_lossy(&output.stdout)) {
+        std::fs::write(confirm_path(request_id), action)?;
+    }
+    Ok(())
+}
+
+/// Ask for a Yes/No answer. Over D-Bus the answer comes back as a
+/// [`UiAction`]; otherwise dunstify directly when available, else
+/// notify-send through the helper script (both write the confirm spool),
+/// else only a warning (the control spool's confirm/deny still works).
 pub fn notify_confirm_actions(enabled: bool, request_id: &str, title: &str, body: &str) {
     if !enabled { return; }
     let request_id = request_id.to_string();
     let title = title.to_string();
     let body = body.to_string();
-        overlay_disable();
+    overlay_disable();
+    if let Some(n) = NOTIFIER.get() {
+        let note = Notification::new(&title, &body, "critical", 0);
+        std::thread::spawn(move || n.confirm(&request_id, note));
+        return;
+    }
     std::thread::spawn(move || {
-        // Use a small helper that can use dunstify actions when available.
-        let helper = "./scripts/btwd-notify-confirm.sh";
-        let _ = Command::new(helper)
-            .arg(&request_id)
-            .arg(&title)
-            .arg(&body)
-            .stdin(Stdio::null())
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .status();
+        if HAS_DUNSTIFY.load(Ordering::Relaxed) {
+            match confirm_with_dunstify(&request_id, &title, &body) {
+                Ok(()) => return,
+                Err(e) => log::warn!("ui: dunstify confirmation failed: {}", e),
+            }
+        }
+        let helper = CONFIRM_HELPER.get().and_then(Option::as_ref);
+        if let Some(helper) = helper.filter(|_| crate::config::binary_in_path("notify-send")) {
+            let _ = Command::new(helper)
+                .arg(&request_id)
+                .arg(&title)
+                .arg(&body)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status();
+            return;
+        }
+        log::warn!("ui: cannot show a confirmation prompt (install dunstify); answer with confirm/deny on the control spool");
     });
 }
+
+/// Keeps the confirmation prompt showing the seconds left until `deadline`
+/// (the Executor's own, so display and expiry agree). Over D-Bus the Yes/No
+/// prompt itself is updated in place; with subprocess notifications the
+/// prompt is posted once and a passive toast counts down beside it. The
+/// expiry notice comes from the executor's timeout path. Dropping the handle
+/// (confirmed, canceled, replaced) stops the countdown at once.
+pub struct ConfirmCountdown {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ConfirmCountdown {
+    pub fn start(enabled: bool, request_id: &str, preview: &str, deadline: Instant) -> Self {
+        let prompt = format!("Confirm {}?", preview);
+        let body = {
+            let prompt = prompt.clone();
+            move |secs: u64| format!("{} {}s left", prompt, secs)
+        };
+        if !enabled {
+            return Self::spawn(deadline, |_| {});
+        }
+        overlay_disable();
+        if let Some(n) = NOTIFIER.get() {
+            let request_id = request_id.to_string();
+            let mut id = 0;
+            return Self::spawn(deadline, move |tick| match tick {
+                Some((secs, remaining)) => {
+                    // Expires with the deadline; no stale "0s left" prompt.
+                    let mut note = Notification::new("btwd", &body(secs), "critical", remaining.as_millis() as u64);
+                    note.replaces_id = id;
+                    id = n.confirm(&request_id, note).unwrap_or(id);
+                }
+                None if id != 0 => n.close(id),
+                None => {}
+            });
+        }
+        notify_confirm_actions(enabled, request_id, "btwd", &prompt);
+        Self::spawn(deadline, move |tick| {
+            let Some((secs, _)) = tick else { return };
+            let _ = Command::new("notify-send")
+                .arg("btwd")
+                .arg(body(secs))
+                .arg("-u").arg("critical")
+                .arg("-h").arg("string:x-canonical-private-synchronous:btwd-countdown")
+                .arg("-h").arg("int:transient:1")
+                .arg("-t").arg("1100")
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status();
+        })
+    }
+
+    /// Call `tick(Some((seconds_left, remaining)))` now and then once a
+    /// second until `deadline`; a [`stop`](Self::stop) before that ends with
+    /// `tick(None)`.
+    fn spawn(deadline: Instant, mut tick: impl FnMut(Option<(u64, Duration)>) + Send + 'static) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = stop.clone();
+        let thread = std::thread::spawn(move || loop {
+            if flag.load(Ordering::SeqCst) {
+                tick(None);
+                return;
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return;
+            }
+            tick(Some((remaining.as_secs_f64().ceil() as u64, remaining)));
+            // Sleep to the next whole second; stop() unparks us early.
+            let step = Duration::from_nanos((remaining.as_nanos() % 1_000_000_000) as u64);
+            std::thread::park_timeout(if step.is_zero() { Duration::from_secs(1) } else { step });
+        });
+        Self { stop, thread: Some(thread) }
+    }
+
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(t) = self.thread.take() {
+            t.thread().unpark();
+            let _ = t.join();
+        }
+    }
+}
+
+impl Drop for ConfirmCountdown {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    struct MockDnd {
+        state: Arc<Mutex<Option<bool>>>,
+        queries: Arc<AtomicUsize>,
+    }
+
+    impl DndSource for MockDnd {
+        fn query(&self) -> Option<bool> {
+            self.queries.fetch_add(1, Ordering::SeqCst);
+            *self.state.lock().unwrap()
+        }
+    }
+
+    fn mock(initial: Option<bool>, ttl: Duration) -> (DndMonitor, Arc<Mutex<Option<bool>>>, Arc<AtomicUsize>) {
+        let state = Arc::new(Mutex::new(initial));
+        let queries = Arc::new(AtomicUsize::new(0));
+        let src = MockDnd { state: state.clone(), queries: queries.clone() };
+        (DndMonitor::new(Box::new(src), ttl), state, queries)
+    }
+
+    #[test]
+    fn countdown_ticks_down_and_stops_promptly() {
+        let ticks = Arc::new(Mutex::new(Vec::new()));
+        let log = ticks.clone();
+        let mut countdown = ConfirmCountdown::spawn(Instant::now() + Duration::from_secs(60), move |t| {
+            log.lock().unwrap().push(t.map(|(secs, _)| secs));
+        });
+        std::thread::sleep(Duration::from_millis(50));
+        let started = Instant::now();
+        countdown.stop();
+        assert!(started.elapsed() < Duration::from_millis(200), "stop took {:?}", started.elapsed());
+        assert_eq!(*ticks.lock().unwrap(), vec![Some(60), None]);
+
+        // Reaching the deadline ends the loop on its own, without the stop call.
+        let ticks = Arc::new(Mutex::new(Vec::new()));
+        let log = ticks.clone();
+        let countdown = ConfirmCountdown::spawn(Instant::now() + Duration::from_millis(100), move |t| {
+            log.lock().unwrap().push(t.map(|(secs, _)| secs));
+        });
+        std::thread::sleep(Duration::from_millis(300));
+        assert!(countdown.thread.as_ref().unwrap().is_finished());
+        assert_eq!(*ticks.lock().unwrap(), vec![Some(1)]);
+    }
+
+    #[test]
+    fn confirm_answers_are_parsed_from_dunstify() {
+        assert_eq!(parse_confirm_action("yes\n"), Some("yes"));
+        assert_eq!(parse_confirm_action("no"), Some("no"));
+        // Timed out / dismissed.
+        assert_eq!(parse_confirm_action("1\n"), None);
+        assert_eq!(parse_confirm_action("2"), None);
+        assert_eq!(parse_confirm_action(""), None);
+        assert!(confirm_path("abc").ends_with("btwd-confirm-abc"));
+    }
+
+    #[test]
+    fn dnd_state_is_cached_briefly() {
+        let (mut mon, state, queries) = mock(Some(true), Duration::from_secs(60));
+        assert!(mon.is_active());
+        *state.lock().unwrap() = Some(false);
+        assert!(mon.is_active(), "cached value within ttl");
+        assert_eq!(queries.load(Ordering::SeqCst), 1);
+
+        let (mut mon, state, queries) = mock(Some(true), Duration::ZERO);
+        assert!(mon.is_active());
+        *state.lock().unwrap() = Some(false);
+        assert!(!mon.is_active(), "re-queried after ttl");
+        assert_eq!(queries.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn unknown_dnd_state_keeps_notifications() {
+        let (mut mon, _, _) = mock(None, Duration::ZERO);
+        assert_eq!(delivery_for(mon.is_active(), false), Delivery::Notify);
+    }
+
+    #[test]
+    fn dnd_moves_answers_and_confirmations_off_notifications() {
+        let (mut mon, state, _) = mock(Some(true), Duration::ZERO);
+        // Answer and confirmation flows both key off the per-interaction delivery mode.
+        assert_eq!(delivery_for(mon.is_active(), false), Delivery::TtsOnly);
+        assert_eq!(delivery_for(mon.is_active(), true), Delivery::NotifyCritical);
+        *state.lock().unwrap() = Some(false);
+        assert_eq!(delivery_for(mon.is_active(), false), Delivery::Notify);
+    }
+}
@@ -1,34 +1,436 @@
-use crate::error::Result;
+use crate::error::{BtwError, Result};
+use std::collections::VecDeque;
+
+/// Numeric mode (0..=3) to VadMode variants
+fn vad_mode(mode: i32) -> webrtc_vad::VadMode {
+    match mode {
+        0 => webrtc_vad::VadMode::LowBitrate,
+        1 => webrtc_vad::VadMode::Aggressive,
+        2 => webrtc_vad::VadMode::VeryAggressive,
+        3 => webrtc_vad::VadMode::VeryAggressive,
+        _ => webrtc_vad::VadMode::VeryAggressive,
+    }
+}
+
+/// Most aggressive numeric mode, used while the room is noisy.
+const NOISY_MODE: i32 = 3;
+
+fn vad_rate(sample_rate: u32) -> Option<webrtc_vad::SampleRate> {
+    match sample_rate {
+        8000 => Some(webrtc_vad::SampleRate::Rate8kHz),
+        16000 => Some(webrtc_vad::SampleRate::Rate16kHz),
+        32000 => Some(webrtc_vad::SampleRate::Rate32kHz),
+        48000 => Some(webrtc_vad::SampleRate::Rate48kHz),
+        _ => None,
+    }
+}
+
+/// Speech if at least `ratio` of the classified windows are speech; `None`
+/// when no window could be classified.
+fn vote(results: &[Option<bool>], ratio: f32) -> Option<bool> {
+    let classified = results.iter().flatten().count();
+    if classified == 0 {
+        return None;
+    }
+    let speech = results.iter().flatten().filter(|&&s| s).count();
+    Some(speech as f32 >= ratio * classified as f32)
+}
 
 /// Simple wrapper over WebRTC VAD
+///
+/// Accepts frames of any length: samples are re-chunked into windows of
+/// `window_ms` and whatever does not fill a window is kept for the next call.
 pub struct Vad {
     inner: webrtc_vad::Vad,
-    sample_rate: i32,
-    window_ms: i32,
+    sample_rate: u32,
+    window_ms: u32,
+    vote_ratio: f32,
+    carry: Vec<i16>,
+    warned: bool,
 }
 
 impl Vad {
+    /// 16 kHz, 30 ms windows, majority vote.
+    #[cfg_attr(not(test), allow(dead_code))]
     pub fn new(mode: i32) -> Result<Self> {
-        let mut inner = webrtc_vad::Vad::new();
-        // Map numeric mode (0..=3) to VadMode variants
-        let vm = match mode {
-            0 => webrtc_vad::VadMode::LowBitrate,
-            1 => webrtc_vad::VadMode::Aggressive,
-            2 => webrtc_vad::VadMode::VeryAggressive,
-            3 => webrtc_vad::VadMode::VeryAggressive,
-            _ => webrtc_vad::VadMode::VeryAggressive,
-        };
-        inner.set_mode(vm);
-        Ok(Vad { inner, sample_rate: 16000, window_ms: 30 })
-    }
-
-    /// Determine speech presence for a 30ms (480 samples) frame at 16kHz mono.
+        Self::with_rate(mode, 16000, 30, 0.5)
+    }
+
+    /// `sample_rate` must be 8, 16, 32 or 48 kHz and `window_ms` 10, 20 or 30,
+    /// the combinations webrtc_vad accepts.
+    pub fn with_rate(mode: i32, sample_rate: u32, window_ms: u32, vote_ratio: f32) -> Result<Self> {
+        let rate = vad_rate(sample_rate).ok_or_else(|| BtwError::InvalidSetting {
+            key: "sample rate",
+            message: format!("the VAD does not support {} Hz (8000, 16000, 32000 or 48000)", sample_rate),
+        })?;
+        if ![10, 20, 30].contains(&window_ms) {
+            return Err(BtwError::InvalidSetting {
+                key: "speech.vad_window_ms",
+                message: format!("unsupported VAD window {} ms (10, 20 or 30)", window_ms),
+            });
+        }
+        let inner = webrtc_vad::Vad::new_with_rate_and_mode(rate, vad_mode(mode));
+        Ok(Vad { inner, sample_rate, window_ms, vote_ratio: vote_ratio.clamp(0.0, 1.0), carry: Vec::new(), warned: false })
+    }
+
+    pub fn set_mode(&mut self, mode: i32) {
+        self.inner.set_mode(vad_mode(mode));
+    }
+
+    fn window_len(&self) -> usize {
+        (self.sample_rate * self.window_ms / 1000) as usize
+    }
+
+    /// Drop carried-over samples, e.g. before a new utterance.
+    pub fn reset(&mut self) {
+        self.carry.clear();
+    }
+
+    /// Aggregate speech decision for a frame of any length; `None` when no window
+    /// could be classified (not enough samples yet, or webrtc_vad returned an error).
+    pub fn classify(&mut self, frame: &[i16]) -> Option<bool> {
+        let results = self.windows(frame);
+        vote(&results, self.vote_ratio)
+    }
+
+    /// Per-window results for every window completed by `frame`, oldest first.
+    pub fn windows(&mut self, frame: &[i16]) -> Vec<Option<bool>> {
+        let len = self.window_len();
+        self.carry.extend_from_slice(frame);
+        let complete = self.carry.len() / len * len;
+        let mut results = Vec::with_capacity(complete / len);
+        for window in self.carry[..complete].chunks_exact(len) {
+            results.push(match self.inner.is_voice_segment(window) {
+                Ok(speech) => Some(speech),
+                Err(_) => {
+                    if !self.warned {
+                        log::warn!("vad: classification failed; falling back to the energy threshold");
+                        self.warned = true;
+                    }
+                    None
+                }
+            });
+        }
+        self.carry.drain(..complete);
+        results
+    }
+}
+
+/// Normalized (0.0..1.0) RMS of a frame.
+pub fn frame_rms(frame: &[i16]) -> f32 {
+    if frame.is_empty() {
+        return 0.0;
+    }
+    let sum_sq: f64 = frame.iter().map(|&s| (s as f64).powi(2)).sum();
+    ((sum_sq / frame.len() as f64).sqrt() / i16::MAX as f64) as f32
+}
+
+/// Rolling RMS over the last `capacity` non-speech frames.
+struct NoiseFloor {
+    frames: VecDeque<f32>,
+    capacity: usize,
+    sum_sq: f64,
+}
+
+impl NoiseFloor {
+    fn new(capacity: usize) -> Self {
+        Self { frames: VecDeque::with_capacity(capacity), capacity: capacity.max(1), sum_sq: 0.0 }
+    }
+
+    fn push(&mut self, rms: f32) {
+        if self.frames.len() == self.capacity {
+            if let Some(old) = self.frames.pop_front() {
+                self.sum_sq -= (old as f64).powi(2);
+            }
+        }
+        self.frames.push_back(rms);
+        self.sum_sq += (rms as f64).powi(2);
+    }
+
+    fn rms(&self) -> f32 {
+        if self.frames.is_empty() {
+            return 0.0;
+        }
+        (self.sum_sq.max(0.0) / self.frames.len() as f64).sqrt() as f32
+    }
+}
+
+/// Whether the VAD should be in its noisy mode. The gap between the two
+/// thresholds keeps it from flapping around a single level.
+fn noisy(was_noisy: bool, background: f32, threshold: f32) -> bool {
+    if was_noisy {
+        background >= 0.5 * threshold
+    } else {
+        background > threshold
+    }
+}
+
+/// [`Vad`] that tracks background noise and switches to the most aggressive
+/// mode while it is high. With `adaptive` off the estimate is still kept (for
+/// `/health`) but the configured mode never changes.
+pub struct AdaptiveVad {
+    inner: Vad,
+    mode: i32,
+    noise_threshold: f32,
+    adaptive: bool,
+    floor: NoiseFloor,
+    noisy: bool,
+}
+
+impl AdaptiveVad {
+    /// `frame_ms` is the duration of the frames passed to [`AdaptiveVad::classify`].
+    pub fn new(inner: Vad, mode: i32, noise_threshold: f32, adaptive: bool, frame_ms: f64) -> Self {
+        // 5 s of frames.
+        let capacity = (5000.0 / frame_ms.max(1.0)) as usize;
+        Self { inner, mode, noise_threshold, adaptive, floor: NoiseFloor::new(capacity), noisy: false }
+    }
+
     pub fn is_speech(&mut self, frame: &[i16]) -> bool {
-        if frame.len() < 480 {
-            return false;
+        self.classify(frame).unwrap_or(false)
+    }
+
+    pub fn reset(&mut self) {
+        self.inner.reset();
+    }
+
+    /// See [`Vad::classify`]. Frames classified as non-speech feed the noise floor.
+    pub fn classify(&mut self, frame: &[i16]) -> Option<bool> {
+        let speech = self.inner.classify(frame);
+        if speech == Some(false) {
+            self.floor.push(frame_rms(frame));
+            self.adjust();
+        }
+        speech
+    }
+
+    fn adjust(&mut self) {
+        if !self.adaptive {
+            return;
+        }
+        let background = self.floor.rms();
+        let noisy = noisy(self.noisy, background, self.noise_threshold);
+        if noisy == self.noisy {
+            return;
+        }
+        self.noisy = noisy;
+        if noisy {
+            log::info!("vad: background rms {:.4} above {:.4}; switching to mode {}", background, self.noise_threshold, NOISY_MODE);
+            self.inner.set_mode(NOISY_MODE);
+        } else {
+            log::info!("vad: background rms {:.4} back down; restoring mode {}", background, self.mode);
+            self.inner.set_mode(self.mode);
+        }
+    }
+
+    /// Current numeric VAD mode.
+    pub fn mode(&self) -> i32 {
+        if self.noisy { NOISY_MODE } else { self.mode }
+    }
+
+    /// Rolling RMS of the last 5 s of non-speech frames.
+    pub fn background_rms(&self) -> f32 {
+        self.floor.rms()
+    }
+}
+
+/// Knobs for [`EndpointDetector`], all in milliseconds except the threshold.
+#[derive(Debug, Clone, Copy)]
+pub struct EndpointCfg {
+    pub frame_ms: f64,
+    /// Continuous silence needed to end the utterance.
+    pub hangover_ms: u32,
+    /// Speech needed before silence ends it as an utterance rather than noise.
+    pub min_speech_ms: u32,
+    pub max_utterance_ms: u32,
+    /// RMS at or above which a frame counts as speech regardless of the VAD.
+    pub silence_threshold: f32,
+}
+
+/// Why [`EndpointDetector::push`] ended (or did not end) the recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endpoint {
+    Continue,
+    /// Speech followed by `hangover_ms` of silence.
+    Silence,
+    /// Fell silent before reaching `min_speech_ms`; the audio is not worth transcribing.
+    TooShort,
+    /// Hit `max_utterance_ms`.
+    MaxLength,
+}
+
+/// Decides when an utterance is over, one frame at a time.
+///
+/// A frame is speech when the VAD says so or its energy reaches
+/// `silence_threshold`; when the VAD could not classify it, energy alone decides.
+pub struct EndpointDetector {
+    cfg: EndpointCfg,
+    speech_ms: f64,
+    silence_ms: f64,
+    elapsed_ms: f64,
+}
+
+impl EndpointDetector {
+    pub fn new(cfg: EndpointCfg) -> Self {
+        Self { cfg, speech_ms: 0.0, silence_ms: 0.0, elapsed_ms: 0.0 }
+    }
+
+    pub fn reset(&mut self) {
+        self.speech_ms = 0.0;
+        self.silence_ms = 0.0;
+        self.elapsed_ms = 0.0;
+    }
+
+    /// Feed one frame and the VAD's verdict on it (`None` if it failed).
+    pub fn push(&mut self, frame: &[i16], vad: Option<bool>) -> Endpoint {
+        let loud = frame_rms(frame) >= self.cfg.silence_threshold;
+        let speech = vad.unwrap_or(false) || loud;
+        self.elapsed_ms += self.cfg.frame_ms;
+        if speech {
+            self.speech_ms += self.cfg.frame_ms;
+            self.silence_ms = 0.0;
+        } else {
+            self.silence_ms += self.cfg.frame_ms;
+        }
+
+        if self.elapsed_ms >= self.cfg.max_utterance_ms as f64 {
+            Endpoint::MaxLength
+        } else if self.silence_ms >= self.cfg.hangover_ms as f64 {
+            if self.speech_ms >= self.cfg.min_speech_ms as f64 { Endpoint::Silence } else { Endpoint::TooShort }
+        } else {
+            Endpoint::Continue
+        }
+    }
+
+    pub fn silence_ms(&self) -> f64 {
+        self.silence_ms
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn elapsed_ms(&self) -> f64 {
+        self.elapsed_ms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn noise_floor_is_rolling_rms() {
+        let mut f = NoiseFloor::new(3);
+        assert_eq!(f.rms(), 0.0);
+        f.push(0.03);
+        f.push(0.04);
+        assert!((f.rms() - (0.00125f32).sqrt()).abs() < 1e-6);
+        // Oldest frames fall out of the window.
+        for _ in 0..3 {
+            f.push(0.01);
+        }
+        assert!((f.rms() - 0.01).abs() < 1e-6);
+    }
+
+    #[test]
+    fn noisy_mode_has_hysteresis() {
+        assert!(!noisy(false, 0.02, 0.02));
+        assert!(noisy(false, 0.021, 0.02));
+        assert!(noisy(true, 0.015, 0.02));
+        assert!(noisy(true, 0.01, 0.02));
+        assert!(!noisy(true, 0.009, 0.02));
+    }
+
+    fn endpoint(hangover_ms: u32, min_speech_ms: u32) -> EndpointDetector {
+        EndpointDetector::new(EndpointCfg { frame_ms: 30.0, hangover_ms, min_speech_ms, max_utterance_ms: 3000, silence_threshold: 0.01 })
+    }
+
+    /// Feed `pattern` ('s' speech, '.' silence, '?' VAD failure with quiet
+    /// audio, 'L' VAD failure with loud audio) and return the first non-Continue
+    /// result with its frame index.
+    fn run(det: &mut EndpointDetector, pattern: &str) -> Option<(usize, Endpoint)> {
+        let quiet = [0i16; 480];
+        let loud = [8000i16; 480];
+        pattern.chars().enumerate().find_map(|(i, c)| {
+            let end = match c {
+                's' => det.push(&quiet, Some(true)),
+                '.' => det.push(&quiet, Some(false)),
+                '?' => det.push(&quiet, None),
+                'L' => det.push(&loud, None),
+                _ => unreachable!(),
+            };
+            (end != Endpoint::Continue).then_some((i, end))
+        })
+    }
+
+    #[test]
+    fn mid_sentence_pause_does_not_end_utterance() {
+        // 300 ms hangover = 10 frames; a 9-frame pause survives.
+        let mut det = endpoint(300, 0);
+        assert_eq!(run(&mut det, "ssssss.........ssss"), None);
+        assert_eq!(run(&mut det, ".........."), Some((9, Endpoint::Silence)));
+    }
+
+    #[test]
+    fn immediate_silence_is_too_short() {
+        let mut det = endpoint(300, 150);
+        assert_eq!(run(&mut det, "s.........."), Some((10, Endpoint::TooShort)));
+        det.reset();
+        assert_eq!(run(&mut det, "sssss.........."), Some((14, Endpoint::Silence)));
+    }
+
+    #[test]
+    fn energy_decides_when_vad_fails() {
+        let mut det = endpoint(90, 0);
+        assert_eq!(run(&mut det, "LLLLL"), None);
+        assert_eq!(run(&mut det, "???"), Some((2, Endpoint::Silence)));
+    }
+
+    #[test]
+    fn max_length_is_enforced() {
+        let mut det = endpoint(300, 0);
+        assert_eq!(run(&mut det, &"s".repeat(200)), Some((99, Endpoint::MaxLength)));
+        assert_eq!(det.elapsed_ms(), 3000.0);
+    }
+
+    #[test]
+    fn porcupine_frames_are_rechunked_without_loss() {
+        let mut vad = Vad::new(2).unwrap();
+        let mut windows = 0;
+        for i in 1..=20 {
+            windows += vad.windows(&[0i16; 512]).len();
+            assert_eq!(windows * 480 + vad.carry.len(), i * 512);
+        }
+        // 10240 samples: 21 full windows and 160 carried over.
+        assert_eq!((windows, vad.carry.len()), (21, 160));
+        assert_eq!(vad.classify(&[0i16; 100]), None);
+        assert_eq!(vad.carry.len(), 260);
+    }
+
+    #[test]
+    fn other_rates_and_windows() {
+        let mut vad = Vad::with_rate(2, 8000, 10, 0.5).unwrap();
+        assert_eq!(vad.windows(&[0i16; 512]).len(), 6);
+        assert_eq!(vad.carry.len(), 32);
+        let mut vad = Vad::with_rate(2, 48000, 20, 0.5).unwrap();
+        assert_eq!(vad.windows(&[0i16; 1920]).len(), 2);
+        assert!(Vad::with_rate(2, 44100, 30, 0.5).is_err());
+        assert!(Vad::with_rate(2, 16000, 25, 0.5).is_err());
+    }
+
+    #[test]
+    fn vote_uses_ratio_of_classified_windows() {
+        assert_eq!(vote(&[], 0.5), None);
+        assert_eq!(vote(&[None, None], 0.5), None);
+        assert_eq!(vote(&[Some(true), Some(false)], 0.5), Some(true));
+        assert_eq!(vote(&[Some(true), Some(false), Some(false)], 0.5), Some(false));
+        assert_eq!(vote(&[Some(true), None], 1.0), Some(true));
+    }
+
+    #[test]
+    fn silence_keeps_configured_mode() {
+        let mut vad = AdaptiveVad::new(Vad::new(1).unwrap(), 1, 0.02, true, 30.0);
+        for _ in 0..10 {
+            assert!(!vad.is_speech(&[0i16; 480]));
         }
-        let slice = &frame[..480];
-        // Use crate's voice segment API which expects 30ms @ 16kHz
-        self.inner.is_voice_segment(slice).unwrap_or(false)
+        assert_eq!(vad.background_rms(), 0.0);
+        assert_eq!(vad.mode(), 1);
     }
 }
@@ -0,0 +1,326 @@
+use crate::error::{BtwError, Result};
+
+/// A positive detection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WakeEvent {
+    /// Index into the configured keyword list (all language models, in order).
+    pub keyword_index: usize,
+    /// Language model that fired; empty for a single-model detector.
+    pub language: String,
+}
+
+/// What a keyword does when it fires (`wake_word.keywords[].action`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WakeAction {
+    /// Start listening for a request.
+    #[default]
+    Listen,
+    /// Abort whatever is in progress and stay idle.
+    Cancel,
+}
+
+impl WakeAction {
+    /// Unknown values fall back to `Listen` (`Config::warnings` reports them).
+    pub fn from_config(value: &str) -> Self {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "cancel" => WakeAction::Cancel,
+            _ => WakeAction::Listen,
+        }
+    }
+}
+
+/// A wake word engine fed one fixed-size frame at a time.
+///
+/// `Porcupine` is the production implementation; tests drive the pipeline
+/// with `MockWakeWordDetector` and synthetic frames instead of a licensed model.
+pub trait WakeWordDetector: Send {
+    /// Returns the zero-based index of the keyword that fired, if any.
+    fn process(&mut self, pcm: &[i16]) -> Result<Option<usize>>;
+    fn frame_length(&self) -> usize;
+    fn sample_rate(&self) -> u32;
+    /// Swap in new per-keyword sensitivities without restarting the daemon.
+    /// On error the detector keeps its previous settings.
+    fn reinit(&mut self, sensitivities: &[f32]) -> Result<()>;
+    /// Like `reinit` with the same sensitivity for every keyword.
+    fn reinitialize_sensitivity(&mut self, sensitivity: f32) -> Result<()>;
+    /// `process`, reported as a [`WakeEvent`].
+    fn detect(&mut self, pcm: &[i16]) -> Result<Option<WakeEvent>> {
+        Ok(self.process(pcm)?.map(|keyword_index| WakeEvent { keyword_index, language: String::new() }))
+    }
+}
+
+/// One per-language detector inside a [`MultiLanguageDetector`].
+pub struct LanguageDetector {
+    pub language: String,
+    pub detector: Box<dyn WakeWordDetector>,
+    /// How many keywords `detector` was built with.
+    pub keywords: usize,
+}
+
+/// Several detectors (one Porcupine instance per language model) fed the
+/// same frames. Keyword indices are global: the first detector's keywords
+/// come first, then the second's, and so on.
+pub struct MultiLanguageDetector {
+    instances: Vec<LanguageDetector>,
+    /// Current sensitivities in global keyword order, to roll back a partial `reinit`.
+    sensitivities: Vec<f32>,
+}
+
+impl MultiLanguageDetector {
+    /// All instances must agree on sample rate and frame length so the
+    /// single audio stream fits each of them (true for one Porcupine build).
+    pub fn new(instances: Vec<LanguageDetector>, sensitivities: Vec<f32>) -> Result<Self> {
+        let Some(first) = instances.first() else {
+            return Err(BtwError::Porcupine { message: "no language models configured".into() });
+        };
+        let (rate, frame) = (first.detector.sample_rate(), first.detector.frame_length());
+        if let Some(odd) = instances.iter().find(|i| i.detector.sample_rate() != rate || i.detector.frame_length() != frame) {
+            return Err(BtwError::Porcupine {
+                message: format!(
+                    "language model '{}' needs {} Hz / {} samples per frame, '{}' needs {} Hz / {}",
+                    odd.language,
+                    odd.detector.sample_rate(),
+                    odd.detector.frame_length(),
+                    first.language,
+                    rate,
+                    frame
+                ),
+            });
+        }
+        let total: usize = instances.iter().map(|i| i.keywords).sum();
+        if sensitivities.len() != total {
+            return Err(BtwError::Porcupine { message: format!("{} sensitivities for {} keywords", sensitivities.len(), total) });
+        }
+        Ok(Self { instances, sensitivities })
+    }
+}
+
+/// `reinit` each instance with its slice of `sensitivities`, counting the
+/// instances that succeeded in `switched`.
+fn apply_split(instances: &mut [LanguageDetector], sensitivities: &[f32], switched: &mut usize) -> Result<()> {
+    let mut offset = 0;
+    for instance in instances {
+        let end = offset + instance.keywords;
+        instance.detector.reinit(&sensitivities[offset..end])?;
+        offset = end;
+        *switched += 1;
+    }
+    Ok(())
+}
+
+impl WakeWordDetector for MultiLanguageDetector {
+    fn process(&mut self, pcm: &[i16]) -> Result<Option<usize>> {
+        Ok(self.detect(pcm)?.map(|ev| ev.keyword_index))
+    }
+
+    /// Every instance sees every frame (Porcupine is stateful across frames);
+    /// the first one that fires wins.
+    fn detect(&mut self, pcm: &[i16]) -> Result<Option<WakeEvent>> {
+        let mut hit = None;
+        let mut offset = 0;
+        for instance in &mut self.instances {
+            if let Some(kw) = instance.detector.process(pcm)? {
+                hit = hit.or(Some(WakeEvent { keyword_index: offset + kw, language: instance.language.clone() }));
+            }
+            offset += instance.keywords;
+        }
+        Ok(hit)
+    }
+
+    fn frame_length(&self) -> usize {
+        self.instances[0].detector.frame_length()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.instances[0].detector.sample_rate()
+    }
+
+    fn reinit(&mut self, sensitivities: &[f32]) -> Result<()> {
+        if sensitivities.len() != self.sensitivities.len() {
+            return Err(BtwError::Porcupine {
+                message: format!(
+                    "keyword count changed ({} -> {}); restart to change wake words",
+                    self.sensitivities.len(),
+                    sensitivities.len()
+                ),
+            });
+        }
+        let mut switched = 0;
+        let result = apply_split(&mut self.instances, sensitivities, &mut switched);
+        if result.is_err() {
+            // Put the instances already switched back, so all keep one setting.
+            let previous = self.sensitivities.clone();
+            let mut rolled_back = 0;
+            if let Err(e) = apply_split(&mut self.instances[..switched], &previous, &mut rolled_back) {
+                log::warn!("wake: rollback after failed reinit failed: {}", e);
+            }
+        } else {
+            self.sensitivities = sensitivities.to_vec();
+        }
+        result
+    }
+
+    fn reinitialize_sensitivity(&mut self, sensitivity: f32) -> Result<()> {
+        let all = vec![sensitivity; self.sensitivities.len()];
+        self.reinit(&all)
+    }
+}
+
+/// `wake_word.push_to_talk`: stands in for Porcupine and never fires, so
+/// listening only starts on request. Frames are what Porcupine would take
+/// (512 samples at 16 kHz), so capture and VAD work unchanged.
+pub struct PushToTalk;
+
+impl WakeWordDetector for PushToTalk {
+    fn process(&mut self, _pcm: &[i16]) -> Result<Option<usize>> {
+        Ok(None)
+    }
+
+    fn frame_length(&self) -> usize {
+        512
+    }
+
+    fn sample_rate(&self) -> u32 {
+        16000
+    }
+
+    fn reinit(&mut self, _sensitivities: &[f32]) -> Result<()> {
+        Ok(())
+    }
+
+    fn reinitialize_sensitivity(&mut self, _sensitivity: f32) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Fires keyword 0 on every `frames_until_detect`-th frame.
+#[cfg(test)]
+pub struct MockWakeWordDetector {
+    pub frames_until_detect: usize,
+    pub sensitivities: Vec<f32>,
+    count: usize,
+}
+
+#[cfg(test)]
+impl MockWakeWordDetector {
+    pub fn new(frames_until_detect: usize) -> Self {
+        Self { frames_until_detect, sensitivities: vec![0.5], count: 0 }
+    }
+}
+
+#[cfg(test)]
+impl WakeWordDetector for MockWakeWordDetector {
+    fn process(&mut self, pcm: &[i16]) -> Result<Option<usize>> {
+        if pcm.len() != self.frame_length() {
+            return Err(BtwError::Porcupine {
+                message: format!("invalid frame length: expected {} got {}", self.frame_length(), pcm.len()),
+            });
+        }
+        self.count += 1;
+        if self.frames_until_detect > 0 && self.count.is_multiple_of(self.frames_until_detect) {
+            Ok(Some(0))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn frame_length(&self) -> usize {
+        512
+    }
+
+    fn sample_rate(&self) -> u32 {
+        16000
+    }
+
+    fn reinit(&mut self, sensitivities: &[f32]) -> Result<()> {
+        if sensitivities.len() != self.sensitivities.len() {
+            return Err(BtwError::Porcupine { message: "keyword count changed".into() });
+        }
+        self.sensitivities = sensitivities.to_vec();
+        self.count = 0;
+        Ok(())
+    }
+    fn reinitialize_sensitivity(&mut self, sensitivity: f32) -> Result<()> {
+        let all = vec![sensitivity; self.sensitivities.len()];
+        self.reinit(&all)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{mpsc, Arc, Mutex};
+
+    #[test]
+    fn mock_detects_through_shared_trait_object() {
+        let detector: Arc<Mutex<dyn WakeWordDetector>> = Arc::new(Mutex::new(MockWakeWordDetector::new(3)));
+        let frame_length = detector.lock().unwrap().frame_length();
+
+        // Stand-in for the capture thread: synthetic frames over the same channel type.
+        let (tx, rx) = mpsc::sync_channel::<Vec<i16>>(8);
+        std::thread::spawn(move || {
+            for i in 0..7 {
+                let _ = tx.send(vec![i as i16; frame_length]);
+            }
+        });
+
+        let hits: Vec<usize> = rx
+            .iter()
+            .enumerate()
+            .filter_map(|(i, frame)| detector.lock().unwrap().process(&frame).unwrap().map(|_| i))
+            .collect();
+        assert_eq!(hits, vec![2, 5]);
+    }
+
+    #[test]
+    fn reinit_between_frames_through_the_shared_lock() {
+        let detector: Arc<Mutex<dyn WakeWordDetector>> = Arc::new(Mutex::new(MockWakeWordDetector::new(2)));
+        let feeder = {
+            let detector = detector.clone();
+            std::thread::spawn(move || {
+                for _ in 0..200 {
+                    detector.lock().unwrap().process(&[0; 512]).unwrap();
+                }
+            })
+        };
+        for _ in 0..50 {
+            detector.lock().unwrap().reinit(&[0.7]).unwrap();
+        }
+        feeder.join().unwrap();
+        assert!(detector.lock().unwrap().reinit(&[0.7, 0.3]).is_err());
+    }
+
+    #[test]
+    fn every_language_sees_every_frame_and_the_first_hit_wins() {
+        let instance = |language: &str, every: usize| LanguageDetector {
+            language: language.into(),
+            detector: Box::new(MockWakeWordDetector::new(every)),
+            keywords: 1,
+        };
+        let mut multi = MultiLanguageDetector::new(vec![instance("en", 3), instance("de", 2)], vec![0.5, 0.5]).unwrap();
+        let events: Vec<Option<WakeEvent>> = (0..6).map(|_| multi.detect(&[0; 512]).unwrap()).collect();
+        let de = Some(WakeEvent { keyword_index: 1, language: "de".into() });
+        let en = Some(WakeEvent { keyword_index: 0, language: "en".into() });
+        // Frame 6 fires both; "de" only keeps its cadence because it saw frame 3 too.
+        assert_eq!(events, vec![None, de.clone(), en.clone(), de, None, en]);
+
+        assert!(multi.reinit(&[0.7, 0.3]).is_ok());
+        assert!(multi.reinit(&[0.7]).is_err());
+        assert!(multi.reinitialize_sensitivity(0.4).is_ok());
+        assert!(MultiLanguageDetector::new(vec![instance("en", 3)], vec![0.5, 0.5]).is_err());
+        assert!(MultiLanguageDetector::new(Vec::new(), Vec::new()).is_err());
+    }
+
+    #[test]
+    fn push_to_talk_never_fires() {
+        let mut d = PushToTalk;
+        assert!((0..100).all(|_| d.detect(&[i16::MAX; 512]).unwrap().is_none()));
+        assert!(d.reinit(&[]).is_ok());
+    }
+
+    #[test]
+    fn mock_rejects_wrong_frame_length() {
+        let mut d = MockWakeWordDetector::new(1);
+        assert!(d.process(&[0; 10]).is_err());
+    }
+}